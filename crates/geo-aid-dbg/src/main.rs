@@ -7,7 +7,8 @@ use geo_aid_internal::script::math;
 use geo_aid_internal::script::math::Flags;
 use geo_aid_math::Func;
 use macroquad::prelude::*;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -130,7 +131,11 @@ impl Debugger {
                                 let total_error =
                                     total_error_power.pow(&context.constant(mean_exponent.recip()));
 
-                                let mut rng = rand::thread_rng();
+                                // The script's seed makes the starting state reproducible.
+                                let mut rng = match file.flags.seed {
+                                    Some(seed) => StdRng::seed_from_u64(seed),
+                                    None => StdRng::from_entropy(),
+                                };
 
                                 let flags = Arc::new(file.flags.clone());
 
@@ -174,7 +179,7 @@ fn window_conf() -> Conf {
 fn draw_label(label: &Option<Label>, color: Color) {
     if let Some(label) = label {
         draw_text(
-            &label.content.to_string(),
+            label.content.to_string(),
             label.position.x as f32,
             label.position.y as f32,
             18.0,
@@ -221,6 +226,33 @@ fn draw_figure(figure: &projector::figure::Figure, color: Color) {
                 );
                 draw_label(&circle.label, color);
             }
+            // Angle markers don't help with debugging.
+            Item::Angle(_) => (),
+            Item::Arc(arc) => {
+                // Approximate the arc with short segments.
+                let steps = 32;
+                let step = (arc.end_angle - arc.start_angle) / f64::from(steps);
+
+                for i in 0..steps {
+                    let from = arc.point_at(arc.start_angle + step * f64::from(i));
+                    let to = arc.point_at(arc.start_angle + step * f64::from(i + 1));
+                    draw_points(&(from, to), color);
+                }
+                draw_label(&arc.label, color);
+            }
+            Item::Polygon(polygon) => {
+                for (i, &from) in polygon.points.iter().enumerate() {
+                    let to = polygon.points[(i + 1) % polygon.points.len()];
+                    draw_points(&(from, to), color);
+                }
+            }
+            Item::Annotation(annotation) => draw_label(
+                &Some(Label {
+                    position: annotation.position,
+                    content: annotation.content.clone(),
+                }),
+                color,
+            ),
         }
     }
 }
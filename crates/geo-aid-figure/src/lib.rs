@@ -9,7 +9,8 @@
 
 use crate::math_string::MathString;
 use num_rational::Rational64;
-use serde::{Deserialize, Serialize};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt::{Display, Formatter};
 use std::num::NonZeroI64;
 use std::ops::{Add, Deref, DerefMut, Mul};
@@ -151,6 +152,72 @@ pub enum Style {
     Bold,
 }
 
+/// An RGB color. Black by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub struct Color {
+    /// The red component
+    pub r: u8,
+    /// The green component
+    pub g: u8,
+    /// The blue component
+    pub b: u8,
+}
+
+impl Color {
+    /// Black, the default color.
+    pub const BLACK: Self = Self::rgb(0, 0, 0);
+
+    /// Named colors recognized by [`Color::parse`].
+    pub const NAMED: [(&'static str, Self); 13] = [
+        ("black", Self::BLACK),
+        ("white", Self::rgb(255, 255, 255)),
+        ("gray", Self::rgb(128, 128, 128)),
+        ("grey", Self::rgb(128, 128, 128)),
+        ("red", Self::rgb(255, 0, 0)),
+        ("green", Self::rgb(0, 128, 0)),
+        ("blue", Self::rgb(0, 0, 255)),
+        ("yellow", Self::rgb(255, 255, 0)),
+        ("cyan", Self::rgb(0, 255, 255)),
+        ("magenta", Self::rgb(255, 0, 255)),
+        ("orange", Self::rgb(255, 165, 0)),
+        ("purple", Self::rgb(128, 0, 128)),
+        ("brown", Self::rgb(165, 42, 42)),
+    ];
+
+    /// Create a color from its components.
+    #[must_use]
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Parse a color given either as a `#rrggbb` hex code or by its name (case-insensitive).
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 || !hex.is_ascii() {
+                return None;
+            }
+
+            let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+            Some(Self::rgb(component(0)?, component(2)?, component(4)?))
+        } else {
+            let s = s.to_lowercase();
+
+            Self::NAMED
+                .iter()
+                .find(|(name, _)| *name == s)
+                .map(|(_, color)| *color)
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
 /// Label-related information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Label {
@@ -191,9 +258,61 @@ impl Add for Position {
     }
 }
 
+/// The current version of the figure format. Bumped every time the layout of the format changes.
+///
+/// * Version 1 - the original format, without a `version` field.
+/// * Version 2 - items got a `color`.
+/// * Version 3 - added `angle` items.
+/// * Version 4 - added `arc`, `polygon` and `annotation` items, `free-circle` and `point-on-segment`
+///   entities, `three-point-area` expressions, script spans, segment ticks and item layers.
+pub const CURRENT_VERSION: u32 = 4;
+
+/// The version assumed for documents without a `version` field.
+const fn legacy_version() -> u32 {
+    1
+}
+
+/// Deserializes a format version, rejecting versions newer than [`CURRENT_VERSION`].
+fn deserialize_version<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let version = u32::deserialize(deserializer)?;
+
+    if version > CURRENT_VERSION {
+        Err(D::Error::custom(format!(
+            "unsupported figure format version {version} (the newest supported version is {CURRENT_VERSION})"
+        )))
+    } else {
+        Ok(version)
+    }
+}
+
+/// A position in the script a figure was generated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourcePosition {
+    /// The line number, starting at 1
+    pub line: usize,
+    /// The column (character index), starting at 1
+    pub column: usize,
+}
+
+/// A span of the script a figure was generated from.
+/// Lets tools map parts of the figure back to the code that created them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    /// Starting position (included)
+    pub start: SourcePosition,
+    /// Ending position (excluded)
+    pub end: SourcePosition,
+}
+
 /// A figure generated by Geo-AID
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Figure {
+    /// The version of the format this figure is in. See [`CURRENT_VERSION`].
+    #[serde(default = "legacy_version", deserialize_with = "deserialize_version")]
+    pub version: u32,
     /// The width of the image
     pub width: f64,
     /// The height of the image
@@ -206,6 +325,42 @@ pub struct Figure {
     pub items: Vec<Item>,
 }
 
+impl Figure {
+    /// The items in the order they should be drawn in: sorted by their layers
+    /// and in the original order within a layer.
+    #[must_use]
+    pub fn items_in_drawing_order(&self) -> Vec<&Item> {
+        let mut items: Vec<_> = self.items.iter().collect();
+        items.sort_by_key(|item| item.layer());
+        items
+    }
+
+    /// Upgrade a figure loaded from an older document to [`CURRENT_VERSION`].
+    ///
+    /// Fields missing in older versions are filled with their defaults while deserializing,
+    /// this only brings the rest of the figure up to date.
+    #[must_use]
+    pub fn migrate(mut self) -> Self {
+        if self.version < 2 {
+            // Version 1 had no colors. They were defaulted to black, which is how
+            // version 1 figures were always drawn.
+            self.version = 2;
+        }
+
+        if self.version < 3 {
+            // Version 3 only added a new item kind, older figures are valid as they are.
+            self.version = 3;
+        }
+
+        if self.version < 4 {
+            // Version 4 only added new kinds and optional fields, older figures are valid as they are.
+            self.version = 4;
+        }
+
+        self
+    }
+}
+
 /// A single expression
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Expression {
@@ -213,6 +368,9 @@ pub struct Expression {
     pub hint: Value,
     /// The kind of an expression this is
     pub kind: ExpressionKind,
+    /// Where in the script the expression was defined, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
 }
 
 /// The kind of an expression
@@ -231,6 +389,26 @@ pub enum ExpressionKind {
         /// Line 2
         l: VarIndex,
     },
+    /// One of the intersections of a line and a circle,
+    /// ordered along the line's direction.
+    LineCircleIntersection {
+        /// The line
+        line: VarIndex,
+        /// The circle
+        circle: VarIndex,
+        /// Which of the two points (0 or 1)
+        index: u8,
+    },
+    /// One of the intersections of two circles, ordered along
+    /// the direction of `c1 -> c2` rotated counterclockwise by a right angle.
+    CircleCircleIntersection {
+        /// Circle 1
+        c1: VarIndex,
+        /// Circle 2
+        c2: VarIndex,
+        /// Which of the two points (0 or 1)
+        index: u8,
+    },
     /// The arithmetic average of points as complex numbers
     AveragePoint {
         /// The elements of the average
@@ -304,6 +482,15 @@ pub enum ExpressionKind {
         /// Arm 2
         c: VarIndex,
     },
+    /// Signed area of the triangle `abc`, positive for counterclockwise orientation
+    ThreePointArea {
+        /// Vertex 1
+        a: VarIndex,
+        /// Vertex 2
+        b: VarIndex,
+        /// Vertex 3
+        c: VarIndex,
+    },
     /// Angle between `k` and `l`
     TwoLineAngle {
         /// Line 1
@@ -336,6 +523,25 @@ pub enum ExpressionKind {
         /// The number to query.
         number: VarIndex,
     },
+    /// Absolute value of a real number
+    Abs {
+        /// The number to take the absolute value of
+        value: VarIndex,
+    },
+    /// The smaller of two real numbers
+    Min {
+        /// The first number
+        a: VarIndex,
+        /// The second number
+        b: VarIndex,
+    },
+    /// The larger of two real numbers
+    Max {
+        /// The first number
+        a: VarIndex,
+        /// The second number
+        b: VarIndex,
+    },
     /// Natural logarithm (base e)
     Log {
         /// The number to take the logarithm of
@@ -346,6 +552,11 @@ pub enum ExpressionKind {
         /// The exponent
         number: VarIndex,
     },
+    /// The radius of a circle
+    CircleRadius {
+        /// Circle to query
+        circle: VarIndex,
+    },
     /// Sine of an angle
     Sin {
         /// The angle to take sine of.
@@ -406,6 +617,15 @@ pub enum ExpressionKind {
         /// Arm 2
         r: VarIndex,
     },
+    /// Bisector of the acute angle between lines `k` and `l`
+    LineLineBisector {
+        /// Line 1
+        k: VarIndex,
+        /// Line 2
+        l: VarIndex,
+        /// Whether the obtuse angle is bisected instead
+        external: bool,
+    },
     /// Perpendicular line going through `point`
     PerpendicularThrough {
         /// The guiding point
@@ -420,6 +640,13 @@ pub enum ExpressionKind {
         /// The reference line
         line: VarIndex,
     },
+    /// The radical axis of two circles
+    RadicalAxis {
+        /// Circle 1
+        c1: VarIndex,
+        /// Circle 2
+        c2: VarIndex,
+    },
     /// A circle with center and radius
     ConstructCircle {
         /// The circle's center
@@ -436,6 +663,9 @@ pub struct Entity {
     pub hint: Value,
     /// The kind of an entity this is
     pub kind: EntityKind,
+    /// Where in the script the entity was defined, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
 }
 
 /// The kind of an entity
@@ -449,6 +679,13 @@ pub enum EntityKind {
         /// The reference line
         line: VarIndex,
     },
+    /// Point on a segment
+    PointOnSegment {
+        /// One end of the segment
+        p: VarIndex,
+        /// The other end of the segment
+        q: VarIndex,
+    },
     /// Point on a circle
     PointOnCircle {
         /// The reference circle
@@ -458,6 +695,8 @@ pub enum EntityKind {
     FreeReal,
     /// A distance unit
     DistanceUnit,
+    /// A free circle
+    FreeCircle,
 }
 
 /// An item drawn on the image
@@ -475,6 +714,14 @@ pub enum Item {
     Segment(TwoPointItem),
     /// A circle
     Circle(CircleItem),
+    /// An angle marker
+    Angle(AngleItem),
+    /// An arc of a circle
+    Arc(ArcItem),
+    /// A polygon
+    Polygon(PolygonItem),
+    /// A free-standing text annotation
+    Annotation(AnnotationItem),
 }
 
 impl Item {
@@ -486,6 +733,22 @@ impl Item {
             _ => None,
         }
     }
+
+    /// The layer the item is drawn on. Items without one are on layer 0.
+    #[must_use]
+    pub fn layer(&self) -> i32 {
+        match self {
+            Self::Point(v) => v.layer,
+            Self::Line(v) => v.layer,
+            Self::Ray(v) | Self::Segment(v) => v.layer,
+            Self::Circle(v) => v.layer,
+            Self::Angle(v) => v.layer,
+            Self::Arc(v) => v.layer,
+            Self::Polygon(v) => v.layer,
+            Self::Annotation(v) => v.layer,
+        }
+        .unwrap_or_default()
+    }
 }
 
 /// A point item. Usually depicted by a dot.
@@ -498,9 +761,21 @@ pub struct PointItem {
     /// Whether to display the dot (circle)
     #[serde(default)]
     pub display_dot: bool,
+    /// The point's color
+    #[serde(default)]
+    pub color: Color,
+    /// The point's opacity, between 0 and 1. Fully opaque if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
     /// The point's label
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub label: Option<Label>,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
 }
 
 /// A line item. Usually depicted by a line.
@@ -513,9 +788,21 @@ pub struct LineItem {
     /// How the line should be drawn
     #[serde(default)]
     pub style: Style,
+    /// The color of the line
+    #[serde(default)]
+    pub color: Color,
+    /// The opacity of the line, between 0 and 1. Fully opaque if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
     /// The line's label
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub label: Option<Label>,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
 }
 
 /// A segment or a ray. Usually depicted by a line.
@@ -530,9 +817,24 @@ pub struct TwoPointItem {
     /// How the line should be drawn
     #[serde(default)]
     pub style: Style,
+    /// The color of the line
+    #[serde(default)]
+    pub color: Color,
+    /// The opacity of the line, between 0 and 1. Fully opaque if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
     /// The item's label
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub label: Option<Label>,
+    /// How many ticks should mark the middle of a segment, 0 for none.
+    #[serde(default)]
+    pub ticks: u8,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
 }
 
 /// A circle item. Usually depicted by a circle.
@@ -547,7 +849,289 @@ pub struct CircleItem {
     /// How the line should be drawn
     #[serde(default)]
     pub style: Style,
+    /// The color of the circle
+    #[serde(default)]
+    pub color: Color,
+    /// The opacity of the circle, between 0 and 1. Fully opaque if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
     /// The circle's label
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub label: Option<Label>,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
+}
+
+/// An angle marker. Usually depicted by arcs drawn between the angle's arms.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AngleItem {
+    /// The first arm's point, the vertex and the second arm's point.
+    pub points: (Position, Position, Position),
+    /// The first arm point's expression index
+    pub p_id: VarIndex,
+    /// The vertex's expression index
+    pub q_id: VarIndex,
+    /// The second arm point's expression index
+    pub r_id: VarIndex,
+    /// How many arcs should mark the angle
+    #[serde(default = "default_arcs")]
+    pub arcs: u8,
+    /// How the arcs should be drawn
+    #[serde(default)]
+    pub style: Style,
+    /// The color of the arcs
+    #[serde(default)]
+    pub color: Color,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
+}
+
+/// An arc of a circle. Usually depicted by a curve.
+///
+/// The arc goes counterclockwise from `start_angle` to `end_angle`. Both are given in radians,
+/// `end_angle` is never smaller than `start_angle` and never greater than `start_angle + 2π`,
+/// so arcs going through the direction of the positive X axis don't need special handling.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArcItem {
+    /// The center of the arc's circle
+    pub center: Position,
+    /// The radius of the arc's circle
+    pub radius: f64,
+    /// The direction from the center to the arc's start
+    pub start_angle: f64,
+    /// The direction from the center to the arc's end
+    pub end_angle: f64,
+    /// The arc's circle's expression index
+    pub id: VarIndex,
+    /// How the arc should be drawn
+    #[serde(default)]
+    pub style: Style,
+    /// The color of the arc
+    #[serde(default)]
+    pub color: Color,
+    /// The arc's label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<Label>,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
+}
+
+impl ArcItem {
+    /// Get the point of the arc in the given direction from its center.
+    #[must_use]
+    pub fn point_at(&self, angle: f64) -> Position {
+        Position {
+            x: self.center.x + self.radius * angle.cos(),
+            y: self.center.y + self.radius * angle.sin(),
+        }
+    }
+}
+
+/// A polygon. Usually depicted by its outline, optionally filled.
+///
+/// The polygon may be self-intersecting. Its inside is then decided by the even-odd rule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolygonItem {
+    /// The polygon's vertices, in order
+    pub points: Vec<Position>,
+    /// The vertices' expression indices
+    pub ids: Vec<VarIndex>,
+    /// How the outline should be drawn
+    #[serde(default)]
+    pub style: Style,
+    /// The color of the polygon
+    #[serde(default)]
+    pub color: Color,
+    /// The opacity of the polygon, between 0 and 1. Fully opaque if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
+    /// The color the polygon should be filled with. Not filled if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<Color>,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
+}
+
+/// How free-standing text should be drawn.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TextStyle {
+    /// The color of the text
+    #[serde(default)]
+    pub color: Color,
+    /// The opacity of the text, between 0 and 1. Fully opaque if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f64>,
+}
+
+/// A text annotation, drawn centered on the position of a point.
+/// Unlike labels, it doesn't belong to any other item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotationItem {
+    /// Where the text should be drawn
+    pub position: Position,
+    /// The anchor point's expression index
+    pub id: VarIndex,
+    /// The annotation's text
+    pub content: MathString,
+    /// How the text should be drawn
+    #[serde(default)]
+    pub style: TextStyle,
+    /// The script span of the item's definition, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// The layer the item is drawn on. Items on higher layers are drawn over the lower ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
+}
+
+/// Angles are marked with a single arc by default.
+const fn default_arcs() -> u8 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Figure, Item, CURRENT_VERSION};
+
+    /// A version 1 document: no `version` field and no item colors.
+    const V1: &str = r#"{
+        "width": 500.0,
+        "height": 500.0,
+        "expressions": [
+            { "hint": { "type": "complex", "real": 1.0, "imaginary": 2.0 }, "kind": { "type": "entity", "id": 0 } }
+        ],
+        "entities": [
+            { "hint": { "type": "complex", "real": 1.0, "imaginary": 2.0 }, "kind": { "type": "free-point" } }
+        ],
+        "items": [
+            { "type": "point", "position": { "x": 10.0, "y": 20.0 }, "id": 0, "display_dot": true }
+        ]
+    }"#;
+
+    fn v2() -> String {
+        V1.replacen('{', r#"{ "version": 2,"#, 1).replace(
+            r#""display_dot": true"#,
+            r#""display_dot": true, "color": { "r": 255, "g": 0, "b": 0 }"#,
+        )
+    }
+
+    fn round_trip(figure: &Figure) -> Figure {
+        serde_json::from_str(&serde_json::to_string(figure).unwrap()).unwrap()
+    }
+
+    fn point_color(figure: &Figure) -> Color {
+        match &figure.items[0] {
+            Item::Point(point) => point.color,
+            _ => panic!("expected a point item"),
+        }
+    }
+
+    #[test]
+    fn test_v1_round_trip() {
+        let figure: Figure = serde_json::from_str(V1).unwrap();
+        assert_eq!(figure.version, 1);
+        assert_eq!(point_color(&figure), Color::BLACK);
+
+        let figure = round_trip(&figure);
+        assert_eq!(figure.version, 1);
+        assert_eq!(point_color(&figure), Color::BLACK);
+
+        let figure = round_trip(&figure.migrate());
+        assert_eq!(figure.version, CURRENT_VERSION);
+        assert_eq!(point_color(&figure), Color::BLACK);
+    }
+
+    #[test]
+    fn test_v2_round_trip() {
+        let figure: Figure = serde_json::from_str(&v2()).unwrap();
+        assert_eq!(figure.version, 2);
+        assert_eq!(point_color(&figure), Color::rgb(255, 0, 0));
+
+        let figure = round_trip(&figure.migrate());
+        assert_eq!(figure.version, CURRENT_VERSION);
+        assert_eq!(point_color(&figure), Color::rgb(255, 0, 0));
+        assert_eq!(figure.expressions.len(), 1);
+        assert_eq!(figure.entities.len(), 1);
+    }
+
+    #[test]
+    fn test_v3_angle_items() {
+        let doc = v2()
+            .replacen(r#""version": 2"#, r#""version": 3"#, 1)
+            .replace(
+                r#""color": { "r": 255, "g": 0, "b": 0 } }"#,
+                r#""color": { "r": 255, "g": 0, "b": 0 } },
+            {
+                "type": "angle",
+                "points": [{ "x": 0.0, "y": 0.0 }, { "x": 1.0, "y": 0.0 }, { "x": 1.0, "y": 1.0 }],
+                "p_id": 0, "q_id": 0, "r_id": 0
+            }"#,
+            );
+        let figure: Figure = serde_json::from_str(&doc).unwrap();
+        assert_eq!(figure.version, 3);
+
+        match &round_trip(&figure).items[1] {
+            Item::Angle(angle) => assert_eq!(angle.arcs, 1),
+            _ => panic!("expected an angle item"),
+        }
+    }
+
+    #[test]
+    fn test_v4_optional_fields() {
+        let segment = r#"{
+                "type": "segment",
+                "points": [{ "x": 0.0, "y": 0.0 }, { "x": 1.0, "y": 0.0 }],
+                "p_id": 0, "q_id": 0
+            }"#;
+        let doc = v2()
+            .replacen(r#""version": 2"#, r#""version": 3"#, 1)
+            .replace(
+                r#""color": { "r": 255, "g": 0, "b": 0 } }"#,
+                &format!(r#""color": {{ "r": 255, "g": 0, "b": 0 }} }}, {segment}"#),
+            );
+        let figure: Figure = serde_json::from_str(&doc).unwrap();
+        assert_eq!(figure.version, 3);
+
+        let figure = round_trip(&figure.migrate());
+        assert_eq!(figure.version, CURRENT_VERSION);
+
+        match &figure.items[1] {
+            Item::Segment(segment) => {
+                assert_eq!(segment.ticks, 0);
+                assert!(segment.span.is_none());
+                assert!(segment.layer.is_none());
+            }
+            _ => panic!("expected a segment item"),
+        }
+    }
+
+    #[test]
+    fn test_newer_version_rejected() {
+        let doc = V1.replacen(
+            '{',
+            &format!(r#"{{ "version": {},"#, CURRENT_VERSION + 1),
+            1,
+        );
+        let err = serde_json::from_str::<Figure>(&doc).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("unsupported figure format version"));
+    }
 }
@@ -11,7 +11,7 @@ use std::str::FromStr;
 pub const SPECIAL_MATH: [&str; 49] = [
     "alpha", "Alpha", "beta", "Beta", "gamma", "Gamma", "delta", "Delta", "epsilon", "Epsilon",
     "zeta", "Zeta", "eta", "Eta", "theta", "Theta", "iota", "Iota", "kappa", "Kappa", "lambda",
-    "Lambda", "mu", "Mu", "nu", "Nu", "xi", "Xi", "omicron", "Omicron", "phi", "Phi", "rho", "Rho",
+    "Lambda", "mu", "Mu", "nu", "Nu", "xi", "Xi", "omicron", "Omicron", "pi", "Pi", "rho", "Rho",
     "sigma", "Sigma", "tau", "Tau", "upsilon", "Upsilon", "phi", "Phi", "chi", "Chi", "psi", "Psi",
     "omega", "Omega", "quote",
 ];
@@ -57,6 +57,10 @@ pub enum ParseErrorKind {
     NestedIndex,
     /// A special tag (`[`) was left unclosed
     UnclosedSpecialTag(String),
+    /// A delimited index (`_{`) was left unclosed
+    UnclosedIndex,
+    /// A brace that neither opens nor closes a delimited index
+    UnmatchedBrace,
 }
 
 impl Display for ParseErrorKind {
@@ -67,6 +71,8 @@ impl Display for ParseErrorKind {
             Self::UnclosedSpecialTag(special) => {
                 write!(f, "unclosed special tag. Parsed '{special}'")
             }
+            Self::UnclosedIndex => write!(f, "unclosed index"),
+            Self::UnmatchedBrace => write!(f, "unmatched brace"),
         }
     }
 }
@@ -303,6 +309,7 @@ impl FromStr for MathString {
         let mut index_delimited = false;
 
         let mut special_start = 0;
+        let mut index_start = 0;
 
         for (char_count, c) in content.chars().enumerate() {
             if collect_special {
@@ -310,17 +317,18 @@ impl FromStr for MathString {
                     special.push(c);
                     ignore_next = false;
                 } else if c == ']' {
-                    math_string.push(MathChar::Special(MathSpecial::parse(&special).ok_or_else(
+                    let code = mem::take(&mut special);
+
+                    math_string.push(MathChar::Special(MathSpecial::parse(&code).ok_or_else(
                         || ParseError {
                             span: StringSpan {
                                 start: special_start + 1,
                                 end: char_count,
                             },
-                            kind: ParseErrorKind::SpecialNotRecognised(mem::take(&mut special)),
+                            kind: ParseErrorKind::SpecialNotRecognised(code.clone()),
                         },
                     )?));
 
-                    // special.clear();
                     collect_special = false;
                 } else {
                     ignore_next = true;
@@ -343,6 +351,7 @@ impl FromStr for MathString {
 
                 math_string.push(MathChar::SetIndex(MathIndex::Lower));
                 indexed = true;
+                index_start = char_count;
             } else if c == '[' {
                 special_start = char_count;
                 collect_special = true;
@@ -361,6 +370,15 @@ impl FromStr for MathString {
                 indexed = false;
                 index_delimited = false;
                 math_string.push(MathChar::SetIndex(MathIndex::Normal));
+            } else if c == '{' || c == '}' {
+                // Braces only delimit indices. To use them literally, they have to be escaped.
+                return Err(ParseError {
+                    span: StringSpan {
+                        start: char_count,
+                        end: char_count + 1,
+                    },
+                    kind: ParseErrorKind::UnmatchedBrace,
+                });
             } else if c == '\'' {
                 math_string.push(MathChar::Prime);
             } else {
@@ -368,10 +386,6 @@ impl FromStr for MathString {
             }
         }
 
-        if indexed {
-            math_string.push(MathChar::SetIndex(MathIndex::Normal));
-        }
-
         if collect_special {
             // Special tag was not closed
             return Err(ParseError {
@@ -383,6 +397,21 @@ impl FromStr for MathString {
             });
         }
 
+        if index_delimited {
+            // Delimited index was not closed
+            return Err(ParseError {
+                span: StringSpan {
+                    start: index_start,
+                    end: content.chars().count(),
+                },
+                kind: ParseErrorKind::UnclosedIndex,
+            });
+        }
+
+        if indexed {
+            math_string.push(MathChar::SetIndex(MathIndex::Normal));
+        }
+
         Ok(Self(math_string))
     }
 }
@@ -396,10 +425,13 @@ impl Serialize for MathString {
 
         for c in &self.0 {
             match c {
-                MathChar::Prime => s.push('c'),
+                MathChar::Prime => s.push('\''),
                 MathChar::SetIndex(MathIndex::Normal) => s.push('}'),
                 MathChar::SetIndex(MathIndex::Lower) => s += "_{",
-                MathChar::Ascii(c) => s.extend(['\\', *c]),
+                MathChar::Ascii(c @ ('\\' | '_' | '[' | ']' | '{' | '}' | '\'')) => {
+                    s.extend(['\\', *c]);
+                }
+                MathChar::Ascii(c) => s.push(*c),
                 MathChar::Special(c) => {
                     s.push('[');
                     s += SPECIAL_MATH[*c as usize];
@@ -408,7 +440,7 @@ impl Serialize for MathString {
             }
         }
 
-        serializer.serialize_str(&self.to_string())
+        serializer.serialize_str(&s)
     }
 }
 
@@ -437,3 +469,71 @@ impl Visitor<'_> for MathStringVisitor {
         MathString::from_str(v).map_err(|err| E::custom(err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{MathChar, MathIndex, MathString, ParseErrorKind};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_delimited_index() {
+        let string = MathString::from_str("M_{BC}").unwrap();
+
+        assert_eq!(
+            *string,
+            [
+                MathChar::Ascii('M'),
+                MathChar::SetIndex(MathIndex::Lower),
+                MathChar::Ascii('B'),
+                MathChar::Ascii('C'),
+                MathChar::SetIndex(MathIndex::Normal),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unbalanced_braces() {
+        let err = MathString::from_str("M_{BC").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::UnclosedIndex));
+        assert_eq!((err.span.start, err.span.end), (1, 5));
+
+        let err = MathString::from_str("M}").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::UnmatchedBrace));
+        assert_eq!((err.span.start, err.span.end), (1, 2));
+
+        assert!(MathString::from_str("\\{M\\}").is_ok());
+    }
+
+    #[test]
+    fn test_escapes_and_raw() {
+        let string = MathString::from_str("A\\_1").unwrap();
+        assert_eq!(
+            *string,
+            [
+                MathChar::Ascii('A'),
+                MathChar::Ascii('_'),
+                MathChar::Ascii('1'),
+            ]
+        );
+
+        let raw = MathString::raw("{}_'");
+        assert_eq!(
+            *raw,
+            [
+                MathChar::Ascii('{'),
+                MathChar::Ascii('}'),
+                MathChar::Ascii('_'),
+                MathChar::Ascii('\''),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let string = MathString::from_str("[alpha]'_{1 \\}} [pi]").unwrap();
+        let json = serde_json::to_string(&string).unwrap();
+        let parsed: MathString = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(*string, *parsed);
+    }
+}
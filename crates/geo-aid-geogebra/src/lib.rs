@@ -6,14 +6,14 @@ use geo_aid_figure::{
     CircleItem, EntityIndex, EntityKind, Figure, Item, Label, LineItem, PointItem, Style,
     TwoPointItem, Value,
 };
-use geo_aid_figure::{Expression, ExpressionKind};
+use geo_aid_figure::{Expression, ExpressionKind, VarIndex};
 
 use geogebra_types::{prelude::*, LineAccess, Var};
 use geogebra_types::{Geogebra as Ggb, LineStyle, LineType};
 
 use num_traits::ToPrimitive;
 
-/// The SVG format writer.
+/// The GeoGebra format writer.
 pub struct Geogebra<'f> {
     /// Geogebra workspace
     workspace: Ggb,
@@ -25,6 +25,8 @@ pub struct Geogebra<'f> {
     entities: Vec<Option<VarKind>>,
     /// Wether the entity should be drawn.
     draw_entities: Vec<Option<PointItem>>,
+    /// Centers and radii of free circle entities.
+    free_circles: Vec<Option<(Var<Point>, Var<Numeric>)>>,
 }
 
 enum VarKind {
@@ -97,7 +99,8 @@ impl VarKind {
 }
 
 impl<'f> Geogebra<'f> {
-    /// Get the figure in SVG format.
+    /// Write the figure as a GeoGebra workspace (a zip archive containing `geogebra.xml`).
+    /// Only the generated objects are exported, the rules are not.
     pub fn draw(figure: &'f Figure, writer: impl Write + Seek) -> Result<(), std::io::Error> {
         let mut ggb = Self {
             workspace: Ggb::default(),
@@ -105,10 +108,12 @@ impl<'f> Geogebra<'f> {
             variables: Vec::new(),
             entities: Vec::new(),
             draw_entities: Vec::new(),
+            free_circles: Vec::new(),
         };
 
         ggb.draw_entities.resize(figure.entities.len(), None);
         ggb.entities.resize_with(figure.entities.len(), || None);
+        ggb.free_circles.resize_with(figure.entities.len(), || None);
 
         // First, check what entities should be drawn by analyzing the items.
         for item in &figure.items {
@@ -132,6 +137,10 @@ impl<'f> Geogebra<'f> {
                 Item::Ray(ray) => ggb.draw_ray(ray),
                 Item::Segment(segment) => ggb.draw_segment(segment),
                 Item::Circle(circle) => ggb.draw_circle(circle),
+                // Angles can't be added as visible objects to the construction.
+                Item::Angle(_) => (),
+                // Arcs, polygons and annotations aren't exported yet.
+                Item::Arc(_) | Item::Polygon(_) | Item::Annotation(_) => (),
             }
         }
 
@@ -183,6 +192,47 @@ impl Geogebra<'_> {
                 let l = self.variables[l.0].as_line().unwrap();
                 self.workspace.var(Point::intersect(k, l)).into()
             }
+            ExpressionKind::LineCircleIntersection {
+                line,
+                circle,
+                index,
+            } => {
+                // Both points lie symmetrically around the projection of the center onto the line.
+                let line = self.variables[line.0].as_line().unwrap();
+                let (center, radius) = self.circle_parts(circle);
+                let foot = Point::intersect(line, Line::perpendicular(line, center)).complex();
+                let dst = Numeric::distance(center, line);
+                let half_chord = (Numeric::from(radius).pow(2.0) - dst.pow(2.0)).pow(0.5);
+                let offset = line.direction().normalize() * half_chord;
+                let point = if *index == 0 {
+                    foot - offset
+                } else {
+                    foot + offset
+                };
+
+                self.workspace.var(point.point()).into()
+            }
+            ExpressionKind::CircleCircleIntersection { c1, c2, index } => {
+                // Both points lie symmetrically around the radical axis' intersection with `c1c2`.
+                let (p, r1) = self.circle_parts(c1);
+                let (q, r2) = self.circle_parts(c2);
+                let dst = Numeric::distance(p, q);
+                let (p, q) = (p.complex(), q.complex());
+                let (r1, r2) = (Numeric::from(r1), Numeric::from(r2));
+                let unit = (q - p.clone()) / dst.clone();
+                let along =
+                    (dst.clone().pow(2.0) + r1.clone().pow(2.0) - r2.pow(2.0)) / (dst * 2.0);
+                let half_chord = (r1.pow(2.0) - along.clone().pow(2.0)).pow(0.5);
+                let middle = p + unit.clone() * along;
+                let offset = unit * Numeric::complex(0.0, 1.0) * half_chord;
+                let point = if *index == 0 {
+                    middle - offset
+                } else {
+                    middle + offset
+                };
+
+                self.workspace.var(point.point()).into()
+            }
             ExpressionKind::AveragePoint { items } => {
                 let list: List<Point> = items
                     .iter()
@@ -198,6 +248,10 @@ impl Geogebra<'_> {
                 let circle = self.variables[circle.0].as_circle().unwrap();
                 self.workspace.var(circle.center()).into()
             }
+            ExpressionKind::CircleRadius { circle } => {
+                let (_, radius) = self.circle_parts(circle);
+                self.workspace.var(Numeric::from(radius)).into()
+            }
             ExpressionKind::ComplexToPoint { number } => {
                 let number = self.variables[number.0].as_number().unwrap();
                 self.workspace.var(number.point()).into()
@@ -284,6 +338,15 @@ impl Geogebra<'_> {
 
                 self.workspace.var(div.arg()).into()
             }
+            ExpressionKind::ThreePointArea { a, b, c } => {
+                let a = self.variables[a.0].as_point().unwrap();
+                let b = self.variables[b.0].as_point().unwrap();
+                let c = self.variables[c.0].as_point().unwrap();
+
+                let cross = (b.x() - a.x()) * (c.y() - a.y()) - (b.y() - a.y()) * (c.x() - a.x());
+
+                self.workspace.var(cross / 2.0).into()
+            }
             ExpressionKind::TwoLineAngle { k, l } => {
                 let k = self.variables[k.0].as_line().unwrap();
                 let l = self.variables[l.0].as_line().unwrap();
@@ -310,6 +373,26 @@ impl Geogebra<'_> {
                 let number = self.variables[number.0].as_number().unwrap();
                 self.workspace.var(number.imaginary()).into()
             }
+            ExpressionKind::Abs { value } => {
+                // |x| = (x^2)^(1/2)
+                let value = self.variables[value.0].as_number().unwrap();
+                let two = self.workspace.var(2.0.real());
+                let half = self.workspace.var(0.5.real());
+                self.workspace.var(value.real().pow(&two).pow(&half)).into()
+            }
+            ExpressionKind::Min { a, b } | ExpressionKind::Max { a, b } => {
+                // min(a, b) = (a + b - |a - b|) / 2, max(a, b) = (a + b + |a - b|) / 2
+                let a = self.variables[a.0].as_number().unwrap().real();
+                let b = self.variables[b.0].as_number().unwrap().real();
+                let diff = (a.clone() - b.clone()).pow(2.0).pow(0.5);
+                let diff = if matches!(expr.kind, ExpressionKind::Min { .. }) {
+                    -diff
+                } else {
+                    diff
+                };
+
+                self.workspace.var((a + b + diff) / 2.0).into()
+            }
             ExpressionKind::Log { number } => {
                 let number = self.variables[number.0].as_number().unwrap();
                 self.workspace.var(number.ln()).into()
@@ -366,6 +449,30 @@ impl Geogebra<'_> {
 
                 self.workspace.var(Line::angle_bisector(p, q, r)).into()
             }
+            ExpressionKind::LineLineBisector { k, l, external } => {
+                // Which of the two bisectors is the internal one depends on the directions
+                // the lines had in the generated figure.
+                let aligned = match (
+                    &self.figure.expressions[k.0].hint,
+                    &self.figure.expressions[l.0].hint,
+                ) {
+                    (Value::Line(k), Value::Line(l)) => {
+                        k.direction.real * l.direction.real
+                            + k.direction.imaginary * l.direction.imaginary
+                            > 0.0
+                    }
+                    _ => panic!("Invalid hint type"),
+                };
+                let k = self.variables[k.0].as_line().unwrap();
+                let l = self.variables[l.0].as_line().unwrap();
+                let u = k.direction().normalize();
+                let v = l.direction().normalize();
+                let direction = if aligned == *external { u - v } else { u + v };
+
+                self.workspace
+                    .var(Line::point_vector(Point::intersect(k, l), direction))
+                    .into()
+            }
             ExpressionKind::PerpendicularThrough { point, line } => {
                 let point = self.variables[point.0].as_point().unwrap();
                 let line = self.variables[line.0].as_line().unwrap();
@@ -378,6 +485,39 @@ impl Geogebra<'_> {
 
                 self.workspace.var(Line::parallel(line, point)).into()
             }
+            ExpressionKind::RadicalAxis { c1, c2 } => {
+                let (o1, r1) = self.circle_parts(c1);
+                let (o2, r2) = self.circle_parts(c2);
+
+                // `PointExpr::y` on references returns the x coordinate, go through `Point` instead.
+                let (o1, o2) = (Point::from(o1), Point::from(o2));
+                let dx = o2.clone().x() - o1.clone().x();
+                let dy = o2.y() - o1.clone().y();
+                let power_diff = Numeric::from(r1) * r1 - Numeric::from(r2) * r2;
+                let o1_x = o1.clone().x();
+                let o1_y = o1.y();
+
+                let dx = self.workspace.var(dx);
+                let dy = self.workspace.var(dy);
+                let d_squared = self
+                    .workspace
+                    .var(Numeric::from(&dx) * &dx + Numeric::from(&dy) * &dy);
+                let t = self.workspace.var(
+                    (Numeric::from(&d_squared) + power_diff) / (Numeric::from(&d_squared) * 2.0),
+                );
+
+                let origin = Point::from((
+                    o1_x + Numeric::from(&dx) * &t,
+                    o1_y + Numeric::from(&dy) * &t,
+                ));
+
+                self.workspace
+                    .var(Line::point_vector(
+                        origin,
+                        Numeric::complex(-Numeric::from(&dy), &dx),
+                    ))
+                    .into()
+            }
             ExpressionKind::ConstructCircle { center, radius } => {
                 let center = self.variables[center.0].as_point().unwrap();
                 let radius = self.variables[radius.0].as_number().unwrap();
@@ -389,6 +529,22 @@ impl Geogebra<'_> {
         self.variables.push(var);
     }
 
+    /// Get the center and the radius of a circle. Circles are always either constructed
+    /// from them or free circle entities.
+    fn circle_parts(&self, circle: &VarIndex) -> (&Var<Point>, &Var<Numeric>) {
+        match &self.figure.expressions[circle.0].kind {
+            ExpressionKind::ConstructCircle { center, radius } => (
+                self.variables[center.0].as_point().unwrap(),
+                self.variables[radius.0].as_number().unwrap(),
+            ),
+            ExpressionKind::Entity { id } => {
+                let (center, radius) = self.free_circles[id.0].as_ref().unwrap();
+                (center, radius)
+            }
+            _ => panic!("circle not constructed from a center and a radius"),
+        }
+    }
+
     fn load_entity(&mut self, id: EntityIndex) {
         let point = self.draw_entities[id.0].clone();
 
@@ -404,13 +560,8 @@ impl Geogebra<'_> {
 
                 if let Some(item) = point {
                     let mut point = Point::from((pos.real, pos.imaginary));
-                    point.set_display_label(item.label.is_some());
-                    self.workspace.add(
-                        point,
-                        item.label
-                            .map(|x| format!("${}$", Self::math_to_latex(&x.content)))
-                            .unwrap_or_default(),
-                    )
+                    Self::style_point(&mut point, &item);
+                    self.workspace.add(point, Self::get_caption(&item.label))
                 } else {
                     self.workspace.var((pos.real, pos.imaginary))
                 }
@@ -425,12 +576,34 @@ impl Geogebra<'_> {
 
                 if let Some(item) = point {
                     let mut point = Point::on(self.variables[line.0].as_line().unwrap());
-                    point.set_display_label(item.label.is_some());
+                    Self::style_point(&mut point, &item);
+                    self.workspace.add_point(
+                        point,
+                        Self::get_caption(&item.label),
+                        (pos.real, pos.imaginary),
+                    )
+                } else {
+                    self.workspace.var((pos.real, pos.imaginary))
+                }
+                .into()
+            }
+            EntityKind::PointOnSegment { p, q } => {
+                let pos = if let Value::Complex(v) = ent.hint {
+                    v
+                } else {
+                    panic!("Invalid hint type")
+                };
+
+                if let Some(item) = point {
+                    let segment = Segment::new(
+                        self.variables[p.0].as_point().unwrap(),
+                        self.variables[q.0].as_point().unwrap(),
+                    );
+                    let mut point = Point::on(segment);
+                    Self::style_point(&mut point, &item);
                     self.workspace.add_point(
                         point,
-                        item.label
-                            .map(|x| format!("${}$", Self::math_to_latex(&x.content)))
-                            .unwrap_or_default(),
+                        Self::get_caption(&item.label),
                         (pos.real, pos.imaginary),
                     )
                 } else {
@@ -447,12 +620,10 @@ impl Geogebra<'_> {
 
                 if let Some(item) = point {
                     let mut point = Point::on(self.variables[circle.0].as_circle().unwrap());
-                    point.set_display_label(item.label.is_some());
+                    Self::style_point(&mut point, &item);
                     self.workspace.add_point(
                         point,
-                        item.label
-                            .map(|x| format!("${}$", Self::math_to_latex(&x.content)))
-                            .unwrap_or_default(),
+                        Self::get_caption(&item.label),
                         (pos.real, pos.imaginary),
                     )
                 } else {
@@ -471,6 +642,22 @@ impl Geogebra<'_> {
                     .var(Numeric::complex(num.real, num.imaginary))
                     .into()
             }
+            EntityKind::FreeCircle => {
+                let circle = if let Value::Circle(v) = ent.hint {
+                    v
+                } else {
+                    panic!("Invalid hint type")
+                };
+
+                let center = self
+                    .workspace
+                    .var((circle.center.real, circle.center.imaginary));
+                let radius = self.workspace.var(Numeric::from(circle.radius));
+                let var = self.workspace.var(Conic::circle(&center, &radius));
+
+                self.free_circles[id.0] = Some((center, radius));
+                var.into()
+            }
         };
 
         self.entities[id.0] = Some(var);
@@ -495,6 +682,12 @@ impl Geogebra<'_> {
         }
     }
 
+    /// Apply the item's color and label visibility to a point.
+    fn style_point(point: &mut Point, item: &PointItem) {
+        point.set_color(item.color.r, item.color.g, item.color.b);
+        point.set_display_label(item.label.is_some());
+    }
+
     fn get_caption(label: &Option<Label>) -> String {
         label
             .as_ref()
@@ -508,21 +701,16 @@ impl Geogebra<'_> {
             return;
         }
 
-        let v = self.variables[point.id.0].as_point().unwrap();
+        let mut v = Point::from(self.variables[point.id.0].as_point().unwrap());
+        Self::style_point(&mut v, point);
 
-        self.workspace.add(
-            v,
-            point
-                .label
-                .as_ref()
-                .map(|x| format!("${}$", Self::math_to_latex(&x.content)))
-                .unwrap_or_default(),
-        );
+        self.workspace.add(v, Self::get_caption(&point.label));
     }
 
     fn draw_line(&mut self, line: &LineItem) {
         let mut v = Line::from(self.variables[line.id.0].as_line().unwrap());
         v.set_style(Self::get_style(line.style));
+        v.set_color(line.color.r, line.color.g, line.color.b);
         v.set_display_label(line.label.is_some());
 
         self.workspace.add(v, Self::get_caption(&line.label));
@@ -534,6 +722,7 @@ impl Geogebra<'_> {
 
         let mut v = Ray::new(origin, through);
         v.set_style(Self::get_style(ray.style));
+        v.set_color(ray.color.r, ray.color.g, ray.color.b);
         v.set_display_label(ray.label.is_some());
 
         self.workspace.add(v, Self::get_caption(&ray.label));
@@ -545,6 +734,7 @@ impl Geogebra<'_> {
 
         let mut v = Segment::new(a, b);
         v.set_style(Self::get_style(segment.style));
+        v.set_color(segment.color.r, segment.color.g, segment.color.b);
         v.set_display_label(segment.label.is_some());
 
         self.workspace.add(v, Self::get_caption(&segment.label));
@@ -575,6 +765,7 @@ impl Geogebra<'_> {
     fn draw_circle(&mut self, circle: &CircleItem) {
         let mut v = Conic::from(self.variables[circle.id.0].as_circle().unwrap());
         v.set_style(Self::get_style(circle.style));
+        v.set_color(circle.color.r, circle.color.g, circle.color.b);
         v.set_display_label(circle.label.is_some());
 
         self.workspace.add(v, Self::get_caption(&circle.label));
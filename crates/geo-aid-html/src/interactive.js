@@ -0,0 +1,616 @@
+// Draws a Geo-AID figure and lets the user drag its points around.
+//
+// The figure's expressions are evaluated here, the same way Geo-AID evaluates them during
+// generation. Entities keep whatever doesn't change when other points move: free points their
+// positions, points on lines, segments and circles their positions along them. Everything else
+// is recalculated from them every time a point is dragged.
+"use strict";
+
+(() => {
+    const SVG_NS = "http://www.w3.org/2000/svg";
+
+    const data = JSON.parse(document.getElementById("figure-data").textContent);
+    const figure = data.figure;
+    const svg = document.getElementById("figure");
+
+    // Complex numbers. Points are complex numbers too.
+    const complex = (re, im = 0) => ({ re, im });
+    const add = (a, b) => complex(a.re + b.re, a.im + b.im);
+    const sub = (a, b) => complex(a.re - b.re, a.im - b.im);
+    const mul = (a, b) => complex(a.re * b.re - a.im * b.im, a.re * b.im + a.im * b.re);
+    const norm = (a) => a.re * a.re + a.im * a.im;
+    const div = (a, b) =>
+        complex((a.re * b.re + a.im * b.im) / norm(b), (a.im * b.re - a.re * b.im) / norm(b));
+    const scale = (a, k) => complex(a.re * k, a.im * k);
+    const mulI = (a) => complex(-a.im, a.re);
+    const abs = (a) => Math.hypot(a.re, a.im);
+    const arg = (a) => Math.atan2(a.im, a.re);
+    const normalize = (a) => scale(a, 1 / abs(a));
+    const polar = (r, theta) => complex(r * Math.cos(theta), r * Math.sin(theta));
+    const log = (a) => complex(Math.log(abs(a)), arg(a));
+    const exp = (a) => polar(Math.exp(a.re), a.im);
+    const pow = (a, e) => (abs(a) === 0 ? complex(0) : exp(scale(log(a), e)));
+    const sin = (a) => complex(Math.sin(a.re) * Math.cosh(a.im), Math.cos(a.re) * Math.sinh(a.im));
+    const cos = (a) => complex(Math.cos(a.re) * Math.cosh(a.im), -Math.sin(a.re) * Math.sinh(a.im));
+    const cross = (a, b) => a.re * b.im - a.im * b.re;
+    const clamp = (x, min, max) => Math.min(Math.max(x, min), max);
+
+    // With an arm of zero length, an angle is undefined. Same as in the generator.
+    const ANGLE_EPSILON = 1e-12;
+
+    const fromComplex = (v) => complex(v.real ?? 0, v.imaginary ?? 0);
+    const fromPosition = (p) => complex(p.x, p.y);
+
+    const fromHint = (hint) => {
+        switch (hint.type) {
+            case "line":
+                return { origin: fromComplex(hint.origin), direction: fromComplex(hint.direction) };
+            case "circle":
+                return { center: fromComplex(hint.center), radius: hint.radius };
+            default:
+                return fromComplex(hint);
+        }
+    };
+
+    const hintOf = (index) => fromHint(figure.expressions[index].hint);
+
+    // The state of every entity, based on the values calculated by the generator.
+    const entities = figure.entities.map((entity) => {
+        const kind = entity.kind;
+        const hint = fromHint(entity.hint);
+
+        switch (kind.type) {
+            case "point-on-line": {
+                const line = hintOf(kind.line);
+                return { kind, t: div(sub(hint, line.origin), line.direction).re };
+            }
+            case "point-on-segment": {
+                const p = hintOf(kind.p);
+                return { kind, t: div(sub(hint, p), sub(hintOf(kind.q), p)).re };
+            }
+            case "point-on-circle":
+                return { kind, theta: arg(sub(hint, hintOf(kind.circle).center)) };
+            default:
+                return { kind, value: hint };
+        }
+    });
+
+    const entityValue = (entity, value) => {
+        const kind = entity.kind;
+
+        switch (kind.type) {
+            case "point-on-line": {
+                const line = value(kind.line);
+                return add(line.origin, scale(line.direction, entity.t));
+            }
+            case "point-on-segment": {
+                const p = value(kind.p);
+                return add(p, scale(sub(value(kind.q), p), entity.t));
+            }
+            case "point-on-circle": {
+                const circle = value(kind.circle);
+                return add(circle.center, polar(circle.radius, entity.theta));
+            }
+            default:
+                return entity.value;
+        }
+    };
+
+    // Half of the length of a chord at distance `sqrt(dstSquared)` from the center.
+    // Zero if the chord doesn't exist.
+    const halfChord = (radius, dstSquared) => Math.sqrt(Math.max(radius * radius - dstSquared, 0));
+
+    const sum = (indices, value) => indices.map(value).reduce(add, complex(0));
+    const product = (indices, value) => indices.map(value).reduce(mul, complex(1));
+
+    // Calculate the value of an expression, given a function getting values of other expressions.
+    const compute = (kind, value) => {
+        switch (kind.type) {
+            case "entity":
+                return entityValue(entities[kind.id], value);
+            case "line-line-intersection": {
+                const k = value(kind.k);
+                const l = value(kind.l);
+                const quotient =
+                    div(sub(k.origin, l.origin), l.direction).im / div(k.direction, l.direction).im;
+                return sub(k.origin, scale(k.direction, quotient));
+            }
+            case "line-circle-intersection": {
+                // The points lie symmetrically around the projection of the center onto the line.
+                const line = value(kind.line);
+                const circle = value(kind.circle);
+                const unit = normalize(line.direction);
+                const t = div(sub(circle.center, line.origin), unit).re;
+                const foot = add(line.origin, scale(unit, t));
+                const offset = scale(unit, halfChord(circle.radius, norm(sub(circle.center, foot))));
+                return kind.index === 0 ? sub(foot, offset) : add(foot, offset);
+            }
+            case "circle-circle-intersection": {
+                const c1 = value(kind.c1);
+                const c2 = value(kind.c2);
+                const d = sub(c2.center, c1.center);
+                const dAbs = abs(d);
+                const unit = scale(d, 1 / dAbs);
+                const a = (dAbs * dAbs + c1.radius * c1.radius - c2.radius * c2.radius) / (2 * dAbs);
+                const middle = add(c1.center, scale(unit, a));
+                const offset = scale(mulI(unit), halfChord(c1.radius, a * a));
+                return kind.index === 0 ? sub(middle, offset) : add(middle, offset);
+            }
+            case "average-point":
+                return scale(sum(kind.items, value), 1 / kind.items.length);
+            case "circle-center":
+                return value(kind.circle).center;
+            case "circle-radius":
+                return complex(value(kind.circle).radius);
+            case "complex-to-point":
+                return value(kind.number);
+            case "point-to-complex":
+                return value(kind.point);
+            case "sum":
+                return sub(sum(kind.plus, value), sum(kind.minus, value));
+            case "product":
+                return div(product(kind.times, value), product(kind.by, value));
+            case "const":
+                return fromComplex(kind.value);
+            case "power":
+                return pow(value(kind.value), kind.exponent.num / (kind.exponent.denom ?? 1));
+            case "point-point-distance":
+                return complex(abs(sub(value(kind.p), value(kind.q))));
+            case "point-line-distance": {
+                const line = value(kind.line);
+                return complex(Math.abs(div(sub(value(kind.point), line.origin), line.direction).im));
+            }
+            case "three-point-angle": {
+                const arm1 = sub(value(kind.a), value(kind.b));
+                const arm2 = sub(value(kind.c), value(kind.b));
+                const dot = arm1.re * arm2.re + arm1.im * arm2.im;
+                const magnitudes = Math.max(abs(arm1) * abs(arm2), ANGLE_EPSILON);
+                return complex(Math.acos(clamp(dot / magnitudes, -1, 1)));
+            }
+            case "three-point-angle-dir": {
+                const b = value(kind.b);
+                return complex(arg(div(sub(value(kind.c), b), sub(value(kind.a), b))));
+            }
+            case "three-point-area": {
+                const a = value(kind.a);
+                return complex(cross(sub(value(kind.b), a), sub(value(kind.c), a)) / 2);
+            }
+            case "two-line-angle":
+                return complex(Math.abs(arg(div(value(kind.k).direction, value(kind.l).direction))));
+            case "point-x":
+                return complex(value(kind.point).re);
+            case "point-y":
+                return complex(value(kind.point).im);
+            case "real":
+                return complex(value(kind.number).re);
+            case "imaginary":
+                return complex(value(kind.number).im);
+            case "abs":
+                return complex(Math.abs(value(kind.value).re));
+            case "min":
+                return complex(Math.min(value(kind.a).re, value(kind.b).re));
+            case "max":
+                return complex(Math.max(value(kind.a).re, value(kind.b).re));
+            case "log":
+                return log(value(kind.number));
+            case "exp":
+                return exp(value(kind.number));
+            case "sin":
+                return sin(value(kind.angle));
+            case "cos":
+                return cos(value(kind.angle));
+            case "asin":
+                return complex(Math.asin(value(kind.value).re));
+            case "acos":
+                return complex(Math.acos(value(kind.value).re));
+            case "atan":
+                return complex(Math.atan(value(kind.value).re));
+            case "atan2":
+                return complex(Math.atan2(value(kind.y).re, value(kind.x).re));
+            case "direction-vector":
+                return value(kind.line).direction;
+            case "point-point-line": {
+                const p = value(kind.p);
+                return { origin: p, direction: normalize(sub(value(kind.q), p)) };
+            }
+            case "point-vector-line":
+                return { origin: value(kind.point), direction: normalize(value(kind.vector)) };
+            case "angle-bisector": {
+                // The direction is the square root of the product of the arms, normalized.
+                const origin = value(kind.q);
+                const ab = mul(sub(value(kind.p), origin), sub(value(kind.r), origin));
+                const root = (x) => normalize(add(x, complex(abs(x))));
+                const direction = ab.re > 0 ? root(ab) : mulI(root(scale(ab, -1)));
+                return { origin, direction };
+            }
+            case "line-line-bisector": {
+                // See the generator's compiler for the derivation.
+                const k = value(kind.k);
+                const l = value(kind.l);
+                const u = normalize(k.direction);
+                let v = normalize(l.direction);
+                const aligned = u.re * v.re + u.im * v.im > 0;
+
+                if (kind.external) {
+                    v = aligned ? scale(v, -1) : v;
+                    v = norm(add(u, v)) > 1e-12 ? v : scale(v, -1);
+                } else {
+                    v = aligned ? v : scale(v, -1);
+                }
+
+                const offset = cross(u, k.origin) + cross(v, l.origin);
+                const direction = add(u, v);
+                return {
+                    origin: scale(mulI(direction), offset / norm(direction)),
+                    direction: normalize(direction),
+                };
+            }
+            case "perpendicular-through":
+                return { origin: value(kind.point), direction: mulI(value(kind.line).direction) };
+            case "parallel-through":
+                return { origin: value(kind.point), direction: value(kind.line).direction };
+            case "radical-axis": {
+                const c1 = value(kind.c1);
+                const c2 = value(kind.c2);
+                const d = sub(c2.center, c1.center);
+                const dSquared = norm(d);
+                const t = (dSquared + c1.radius * c1.radius - c2.radius * c2.radius) / (2 * dSquared);
+                return { origin: add(c1.center, scale(d, t)), direction: mulI(normalize(d)) };
+            }
+            case "construct-circle":
+                return { center: value(kind.center), radius: value(kind.radius).re };
+            default:
+                throw new Error(`unknown expression kind: ${kind.type}`);
+        }
+    };
+
+    // Calculate the values of all expressions. Each one is calculated once, when first needed.
+    const evaluate = () => {
+        const values = new Array(figure.expressions.length);
+        const value = (index) => {
+            if (values[index] === undefined) {
+                values[index] = compute(figure.expressions[index].kind, value);
+            }
+
+            return values[index];
+        };
+
+        figure.expressions.forEach((_, i) => value(i));
+        return values;
+    };
+
+    const valid = (...points) => points.every((p) => Number.isFinite(p.re) && Number.isFinite(p.im));
+
+    // The part of a line inside the canvas. Only the part after `tMin` is taken (rays).
+    const clip = (origin, direction, tMin = -Infinity) => {
+        let low = tMin;
+        let high = Infinity;
+
+        for (const [o, d, size] of [
+            [origin.re, direction.re, figure.width],
+            [origin.im, direction.im, figure.height],
+        ]) {
+            if (d === 0) {
+                if (o < 0 || o > size) {
+                    return null;
+                }
+            } else {
+                const t1 = -o / d;
+                const t2 = (size - o) / d;
+                low = Math.max(low, Math.min(t1, t2));
+                high = Math.min(high, Math.max(t1, t2));
+            }
+        }
+
+        if (!(low <= high)) {
+            return null;
+        }
+
+        return [add(origin, scale(direction, low)), add(origin, scale(direction, high))];
+    };
+
+    const initial = evaluate();
+
+    // Labels stay where they were placed relative to their items. Each item gets an anchor
+    // following it, given the label's starting position, and the label keeps its offset from it.
+    const circleAnchor = (item, start) => {
+        const theta = arg(sub(start, initial[item.id].center));
+        return (values) => add(values[item.id].center, polar(values[item.id].radius, theta));
+    };
+
+    const anchors = {
+        point: (item) => (values) => values[item.id],
+        annotation: (item) => (values) => values[item.id],
+        segment: (item) => (values) => scale(add(values[item.p_id], values[item.q_id]), 0.5),
+        ray: (item) => (values) => values[item.p_id],
+        line: (item, start) => {
+            const line = initial[item.id];
+            const t = div(sub(start, line.origin), line.direction).re;
+            return (values) => add(values[item.id].origin, scale(values[item.id].direction, t));
+        },
+        circle: circleAnchor,
+        arc: circleAnchor,
+    };
+
+    // Functions giving the current label positions of the items.
+    const labelPositions = figure.items.map((item) => {
+        const position = item.type === "annotation" ? item.position : item.label?.position;
+
+        if (position === undefined || anchors[item.type] === undefined) {
+            return null;
+        }
+
+        const start = fromPosition(position);
+        const anchor = anchors[item.type](item, start);
+        const offset = sub(start, anchor(initial));
+        return (values) => add(anchor(values), offset);
+    });
+
+    const color = (c) => `rgb(${c.r},${c.g},${c.b})`;
+
+    const styleWidth = (style) => ({ dashed: "0.5", dotted: "0.5", bold: "2" })[style] ?? "1";
+    const styleDashing = (style) => ({ dotted: "0.8,1", dashed: "2,2" })[style] ?? "1,0";
+
+    const root = document.createElementNS(SVG_NS, "g");
+    root.setAttribute("transform", `translate(0,${figure.height}) scale(1,-1)`);
+    svg.appendChild(root);
+
+    const element = (name, attributes) => {
+        const el = document.createElementNS(SVG_NS, name);
+
+        for (const [key, value] of Object.entries(attributes)) {
+            if (value !== undefined && value !== null) {
+                el.setAttribute(key, value);
+            }
+        }
+
+        root.appendChild(el);
+        return el;
+    };
+
+    const stroke = (item) => ({
+        stroke: color(item.color),
+        opacity: item.opacity,
+        "stroke-width": styleWidth(item.style),
+        "stroke-dasharray": styleDashing(item.style),
+        fill: "transparent",
+    });
+
+    const segment = (p, q, item) => {
+        if (p !== null && valid(p, q)) {
+            element("line", { x1: p.re, y1: p.im, x2: q.re, y2: q.im, ...stroke(item) });
+        }
+    };
+
+    const ticks = (p, q, item) => {
+        const unit = normalize(sub(q, p));
+        const middle = scale(add(p, q), 0.5);
+        const normal = scale(mulI(unit), 4);
+
+        for (let i = 0; i < item.ticks; i++) {
+            // Ticks are 3 units apart, centered on the middle.
+            const center = add(middle, scale(unit, 3 * (i - (item.ticks - 1) / 2)));
+            segment(add(center, normal), sub(center, normal), { ...item, style: "solid" });
+        }
+    };
+
+    const text = (markup, position, c, opacity) => {
+        if (markup === null || !valid(position)) {
+            return;
+        }
+
+        const el = element("text", {
+            transform: "scale(1,-1)",
+            "text-anchor": "middle",
+            "dominant-baseline": "middle",
+            style: "font-family: 'Computer Modern'",
+            "font-size": "10px",
+            fill: color(c),
+            opacity,
+            "stroke-width": "0",
+            x: position.re,
+            y: -position.im,
+        });
+        el.innerHTML = markup;
+    };
+
+    const draw = {
+        point: (item, values) => {
+            const position = values[item.id];
+
+            if (item.display_dot && valid(position)) {
+                element("circle", {
+                    cx: position.re,
+                    cy: position.im,
+                    r: 1,
+                    fill: color(item.color),
+                    opacity: item.opacity,
+                });
+            }
+        },
+        line: (item, values) => {
+            const line = values[item.id];
+            const ends = clip(line.origin, line.direction);
+
+            if (ends !== null) {
+                segment(ends[0], ends[1], item);
+            }
+        },
+        ray: (item, values) => {
+            const p = values[item.p_id];
+            const ends = clip(p, sub(values[item.q_id], p), 0);
+
+            if (ends !== null) {
+                segment(ends[0], ends[1], item);
+            }
+        },
+        segment: (item, values) => {
+            const p = values[item.p_id];
+            const q = values[item.q_id];
+
+            if (valid(p, q)) {
+                segment(p, q, item);
+                ticks(p, q, item);
+            }
+        },
+        circle: (item, values) => {
+            const circle = values[item.id];
+
+            if (valid(circle.center) && Number.isFinite(circle.radius)) {
+                element("circle", {
+                    cx: circle.center.re,
+                    cy: circle.center.im,
+                    r: Math.abs(circle.radius),
+                    ...stroke(item),
+                });
+            }
+        },
+        angle: (item, values) => {
+            const origin = values[item.q_id];
+            const arm1 = normalize(sub(values[item.p_id], origin));
+            const arm2 = normalize(sub(values[item.r_id], origin));
+
+            if (!valid(origin, arm1, arm2)) {
+                return;
+            }
+
+            // Always mark the smaller angle.
+            const sweep = cross(arm1, arm2) > 0 ? 1 : 0;
+
+            for (let i = 0; i < item.arcs; i++) {
+                const radius = 12 + 3 * i;
+                const start = add(origin, scale(arm1, radius));
+                const end = add(origin, scale(arm2, radius));
+
+                element("path", {
+                    d: `M ${start.re} ${start.im} A ${radius} ${radius} 0 0 ${sweep} ${end.re} ${end.im}`,
+                    ...stroke(item),
+                });
+            }
+        },
+        arc: (item, values) => {
+            // The figure format doesn't say which points the arc's ends come from,
+            // so the arc keeps its angles and only follows its circle.
+            const circle = values[item.id];
+            const r = Math.abs(circle.radius);
+            const at = (angle) => add(circle.center, polar(r, angle));
+            const start = at(item.start_angle);
+            const mid = at((item.start_angle + item.end_angle) / 2);
+            const end = at(item.end_angle);
+
+            if (valid(start, mid, end)) {
+                element("path", {
+                    d: `M ${start.re} ${start.im} A ${r} ${r} 0 0 1 ${mid.re} ${mid.im} A ${r} ${r} 0 0 1 ${end.re} ${end.im}`,
+                    ...stroke(item),
+                });
+            }
+        },
+        polygon: (item, values) => {
+            const points = item.ids.map((id) => values[id]);
+
+            if (valid(...points)) {
+                element("polygon", {
+                    points: points.map((p) => `${p.re},${p.im}`).join(" "),
+                    ...stroke(item),
+                    fill: item.fill ? color(item.color) : "transparent",
+                    "fill-rule": "evenodd",
+                });
+            }
+        },
+        annotation: () => {},
+    };
+
+    // Items are drawn by layers, in the original order within a layer.
+    const order = figure.items
+        .map((item, index) => ({ item, index }))
+        .sort((a, b) => (a.item.layer ?? 0) - (b.item.layer ?? 0));
+
+    const draggable = ["free-point", "point-on-line", "point-on-segment", "point-on-circle"];
+
+    // Point items defined directly by a draggable entity.
+    const handles = figure.items
+        .filter((item) => item.type === "point")
+        .map((item) => ({ item, kind: figure.expressions[item.id].kind }))
+        .filter(({ kind }) => kind.type === "entity" && draggable.includes(entities[kind.id].kind.type))
+        .map(({ item, kind }) => ({ id: item.id, entity: entities[kind.id] }));
+
+    let values = initial;
+    let dragged = null;
+
+    const redraw = () => {
+        root.replaceChildren();
+
+        for (const { item, index } of order) {
+            draw[item.type](item, values);
+
+            const label = labelPositions[index];
+            if (label !== null) {
+                const style = item.type === "annotation" ? item.style : item;
+                text(data.labels[index], label(values), style.color ?? { r: 0, g: 0, b: 0 }, style.opacity);
+            }
+        }
+
+        for (const handle of handles) {
+            const position = values[handle.id];
+
+            if (valid(position)) {
+                const el = element("circle", {
+                    cx: position.re,
+                    cy: position.im,
+                    r: 6,
+                    class: "handle",
+                });
+                el.addEventListener("pointerdown", (event) => {
+                    dragged = handle.entity;
+                    svg.setPointerCapture(event.pointerId);
+                    event.preventDefault();
+                });
+            }
+        }
+    };
+
+    // Move an entity as close to the given position as it can get.
+    const move = (entity, position) => {
+        const kind = entity.kind;
+
+        switch (kind.type) {
+            case "free-point":
+                entity.value = position;
+                break;
+            case "point-on-line": {
+                const line = values[kind.line];
+                entity.t = div(sub(position, line.origin), line.direction).re;
+                break;
+            }
+            case "point-on-segment": {
+                const p = values[kind.p];
+                entity.t = clamp(div(sub(position, p), sub(values[kind.q], p)).re, 0, 1);
+                break;
+            }
+            case "point-on-circle":
+                entity.theta = arg(sub(position, values[kind.circle].center));
+                break;
+        }
+    };
+
+    svg.addEventListener("pointermove", (event) => {
+        if (dragged === null) {
+            return;
+        }
+
+        const point = new DOMPoint(event.clientX, event.clientY).matrixTransform(
+            root.getScreenCTM().inverse(),
+        );
+        move(dragged, complex(point.x, point.y));
+        values = evaluate();
+        redraw();
+    });
+
+    const release = () => {
+        dragged = null;
+    };
+    svg.addEventListener("pointerup", release);
+    svg.addEventListener("pointercancel", release);
+
+    redraw();
+})();
@@ -0,0 +1,158 @@
+//! Geo-AID is capable of outputting figures as interactive HTML pages. The page is self-contained:
+//! it carries the figure in the JSON format along with a script that evaluates its expressions.
+//! Free points (and points on lines, segments and circles) can be dragged around and the rest
+//! of the figure follows them.
+
+use std::io::{self, Seek, Write};
+
+use geo_aid_figure::{Figure, Item};
+use geo_aid_svg::math_to_svg;
+use serde_json::json;
+
+/// The script drawing the figure and handling dragging.
+const SCRIPT: &str = include_str!("interactive.js");
+
+/// The interactive HTML format writer.
+#[derive(Debug, Default)]
+pub struct Html;
+
+impl Html {
+    /// Output the figure as an interactive HTML page.
+    ///
+    /// # Errors
+    /// Returns an error if writing fails.
+    pub fn draw(figure: &Figure, mut writer: impl Write + Seek) -> io::Result<()> {
+        // Labels are converted to SVG text content here, so that the script doesn't have to
+        // understand math strings. They're kept in the same order as the items.
+        let labels: Vec<_> = figure.items.iter().map(Self::label).collect();
+
+        // `<` can only appear inside strings, where it can be escaped. This way the data
+        // never closes the script tag it's in.
+        let data = json!({
+            "figure": figure,
+            "labels": labels,
+        })
+        .to_string()
+        .replace('<', r"\u003c");
+
+        write!(
+            writer,
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Geo-AID figure</title>
+<style>
+    .handle {{ fill: transparent; cursor: grab; }}
+    .handle:hover {{ fill: rgba(0, 0, 255, 0.2); }}
+    svg {{ touch-action: none; }}
+</style>
+</head>
+<body>
+<svg id="figure" width="{}" height="{}" xmlns="http://www.w3.org/2000/svg"></svg>
+<script type="application/json" id="figure-data">{data}</script>
+<script>
+{SCRIPT}
+</script>
+</body>
+</html>
+"#,
+            figure.width, figure.height,
+        )
+    }
+
+    /// Get an item's label as SVG text content, if it has one.
+    fn label(item: &Item) -> Option<String> {
+        let content = match item {
+            Item::Point(point) => &point.label.as_ref()?.content,
+            Item::Line(line) => &line.label.as_ref()?.content,
+            Item::Ray(item) | Item::Segment(item) => &item.label.as_ref()?.content,
+            Item::Circle(circle) => &circle.label.as_ref()?.content,
+            Item::Arc(arc) => &arc.label.as_ref()?.content,
+            Item::Annotation(annotation) => &annotation.content,
+            Item::Angle(_) | Item::Polygon(_) => return None,
+        };
+
+        Some(math_to_svg(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Html;
+    use geo_aid_figure::math_string::MathString;
+    use geo_aid_figure::{
+        Color, Complex, Entity, EntityIndex, EntityKind, Expression, ExpressionKind, Figure, Item,
+        Label, PointItem, Position, Value, VarIndex, CURRENT_VERSION,
+    };
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    fn figure(label: &str) -> Figure {
+        let position = Complex {
+            real: 1.0,
+            imaginary: 2.0,
+        };
+
+        Figure {
+            version: CURRENT_VERSION,
+            width: 500.0,
+            height: 500.0,
+            expressions: vec![Expression {
+                hint: Value::Complex(position),
+                kind: ExpressionKind::Entity { id: EntityIndex(0) },
+                span: None,
+            }],
+            entities: vec![Entity {
+                hint: Value::Complex(position),
+                kind: EntityKind::FreePoint,
+                span: None,
+            }],
+            items: vec![Item::Point(PointItem {
+                position: Position { x: 1.0, y: 2.0 },
+                id: VarIndex(0),
+                display_dot: true,
+                color: Color::default(),
+                opacity: None,
+                label: Some(Label {
+                    position: Position { x: 1.0, y: 12.0 },
+                    content: MathString::from_str(label).unwrap(),
+                }),
+                span: None,
+                layer: None,
+            })],
+        }
+    }
+
+    fn draw(figure: &Figure) -> String {
+        let mut output = Cursor::new(Vec::new());
+        Html::draw(figure, &mut output).unwrap();
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    /// The JSON embedded in the page, as written.
+    fn data(page: &str) -> &str {
+        let start = page.find(r#"id="figure-data">"#).unwrap() + r#"id="figure-data">"#.len();
+        let end = start + page[start..].find("</script>").unwrap();
+        &page[start..end]
+    }
+
+    #[test]
+    fn page_carries_the_figure_and_labels() {
+        let page = draw(&figure("A_1"));
+        let data: serde_json::Value = serde_json::from_str(data(&page)).unwrap();
+
+        assert_eq!(data["figure"]["entities"][0]["kind"]["type"], "free-point");
+        assert_eq!(
+            data["labels"][0],
+            r#"A<tspan baseline-shift="sub" font-size="7px">1</tspan>"#
+        );
+    }
+
+    #[test]
+    fn data_cannot_close_its_script() {
+        // The label's markup has a closing tag, which must be escaped in the page.
+        let page = draw(&figure("A_1"));
+        assert!(!data(&page).contains('<'));
+    }
+}
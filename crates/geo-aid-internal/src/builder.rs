@@ -0,0 +1,296 @@
+//! A programmatic way of building figures, without going through `GeoScript`.
+//!
+//! [`FigureBuilder`] constructs the same [`Intermediate`] that the script compiler produces,
+//! so the result can be passed directly to an engine:
+//!
+//! ```
+//! use geo_aid_internal::builder::{FigureBuilder, RuleOperator};
+//! use geo_aid_internal::projector::figure::Style;
+//!
+//! let mut builder = FigureBuilder::new();
+//! let a = builder.free_point();
+//! let b = builder.free_point();
+//! let c = builder.free_point();
+//!
+//! let ab = builder.distance(a.clone(), b.clone());
+//! let ac = builder.distance(a.clone(), c.clone());
+//! builder.add_rule(ab, RuleOperator::Eq, ac);
+//!
+//! let bc = builder.line(b, c);
+//! builder.add_line_item(bc, Style::Dashed);
+//!
+//! let intermediate = builder.build();
+//! assert_eq!(intermediate.adjusted.rules.len(), 1);
+//! ```
+
+use geo_aid_figure::{math_string::MathString, Color, EntityIndex, Style, VarIndex};
+use num_traits::One;
+
+use crate::script::figure::{CircleItem, Figure, Item, LineItem, PointItem, RayItem, SegmentItem};
+use crate::script::math::{
+    Adjusted, Entity, EntityKind, Expr, ExprKind, ExprType, Flags, Intermediate, Rule, RuleKind,
+};
+use crate::script::token::number::ProcNum;
+
+/// An operator of a rule added with [`FigureBuilder::add_rule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleOperator {
+    /// `lhs = rhs`. Works for both points and numbers.
+    Eq,
+    /// `lhs < rhs`
+    Lt,
+    /// `lhs <= rhs`
+    Lteq,
+    /// `lhs > rhs`
+    Gt,
+    /// `lhs >= rhs`
+    Gteq,
+}
+
+/// Builds an [`Intermediate`] directly, bypassing parsing and unrolling.
+/// No optimizations are performed on the built figure.
+#[derive(Debug, Default)]
+pub struct FigureBuilder {
+    /// Entities of the figure.
+    entities: Vec<Entity<()>>,
+    /// Expressions of the figure.
+    variables: Vec<Expr<()>>,
+    /// Rules of the figure.
+    rules: Vec<Rule>,
+    /// Drawn items.
+    items: Vec<Item>,
+    /// Flags passed on to the engine.
+    flags: Flags,
+}
+
+impl FigureBuilder {
+    /// Create a new, empty builder.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the flags of the figure.
+    pub fn flags_mut(&mut self) -> &mut Flags {
+        &mut self.flags
+    }
+
+    /// Add an entity and return the expression referencing it.
+    fn entity(&mut self, kind: EntityKind) -> VarIndex {
+        let id = EntityIndex(self.entities.len());
+        self.entities.push(Entity { kind, meta: () });
+        self.expr(ExprKind::Entity { id })
+    }
+
+    /// Add an expression and return its index.
+    ///
+    /// # Panics
+    /// If the expression references expressions or entities not added to this builder.
+    pub fn expr(&mut self, kind: ExprKind) -> VarIndex {
+        let ty = kind.get_type(&self.variables, &self.entities);
+        self.variables.push(Expr::new(kind, ty));
+        VarIndex(self.variables.len() - 1)
+    }
+
+    /// Get the type of an added expression.
+    #[must_use]
+    pub fn get_type(&self, expr: &VarIndex) -> ExprType {
+        self.variables[expr.0].ty
+    }
+
+    /// A free point with two degrees of freedom.
+    pub fn free_point(&mut self) -> VarIndex {
+        self.entity(EntityKind::FreePoint)
+    }
+
+    /// A point lying on the given line.
+    pub fn point_on_line(&mut self, line: VarIndex) -> VarIndex {
+        self.entity(EntityKind::PointOnLine { line })
+    }
+
+    /// A point lying on the segment `pq`.
+    pub fn point_on_segment(&mut self, p: VarIndex, q: VarIndex) -> VarIndex {
+        self.entity(EntityKind::PointOnSegment { p, q })
+    }
+
+    /// A point lying on the given circle.
+    pub fn point_on_circle(&mut self, circle: VarIndex) -> VarIndex {
+        self.entity(EntityKind::PointOnCircle { circle })
+    }
+
+    /// A free real number.
+    pub fn free_real(&mut self) -> VarIndex {
+        self.entity(EntityKind::FreeReal)
+    }
+
+    /// A free circle with three degrees of freedom.
+    pub fn free_circle(&mut self) -> VarIndex {
+        self.entity(EntityKind::FreeCircle)
+    }
+
+    /// A constant number.
+    pub fn constant(&mut self, value: ProcNum) -> VarIndex {
+        self.expr(ExprKind::Const { value })
+    }
+
+    /// A line through two points.
+    pub fn line(&mut self, p: VarIndex, q: VarIndex) -> VarIndex {
+        self.expr(ExprKind::PointPoint { p, q })
+    }
+
+    /// A circle with the given center and radius.
+    pub fn circle(&mut self, center: VarIndex, radius: VarIndex) -> VarIndex {
+        self.expr(ExprKind::ConstructCircle { center, radius })
+    }
+
+    /// The distance between two points.
+    pub fn distance(&mut self, p: VarIndex, q: VarIndex) -> VarIndex {
+        self.expr(ExprKind::PointPointDistance { p, q })
+    }
+
+    /// Add a rule with the default weight of 1.
+    ///
+    /// # Panics
+    /// If `op` is not [`RuleOperator::Eq`] and the operands are not numbers.
+    pub fn add_rule(&mut self, lhs: VarIndex, op: RuleOperator, rhs: VarIndex) {
+        self.add_rule_weighted(lhs, op, rhs, ProcNum::one());
+    }
+
+    /// Add a rule with the given weight.
+    ///
+    /// # Panics
+    /// If `op` is not [`RuleOperator::Eq`] and the operands are not numbers.
+    pub fn add_rule_weighted(
+        &mut self,
+        lhs: VarIndex,
+        op: RuleOperator,
+        rhs: VarIndex,
+        weight: ProcNum,
+    ) {
+        let lhs_type = self.get_type(&lhs);
+
+        assert!(
+            op == RuleOperator::Eq || lhs_type == ExprType::Number,
+            "only numbers can be compared"
+        );
+
+        let kind = match op {
+            RuleOperator::Eq => {
+                if lhs_type == ExprType::Point {
+                    RuleKind::PointEq(lhs, rhs)
+                } else {
+                    RuleKind::NumberEq(lhs, rhs)
+                }
+            }
+            RuleOperator::Gt => RuleKind::Gt(lhs, rhs),
+            RuleOperator::Lt => RuleKind::Gt(rhs, lhs),
+            RuleOperator::Lteq => RuleKind::Invert(Box::new(RuleKind::Gt(lhs, rhs))),
+            RuleOperator::Gteq => RuleKind::Invert(Box::new(RuleKind::Gt(rhs, lhs))),
+        };
+
+        self.add_rule_kind(kind, weight);
+    }
+
+    /// Add a rule of any kind with the given weight.
+    pub fn add_rule_kind(&mut self, kind: RuleKind, weight: ProcNum) {
+        self.rules.push(Rule {
+            kind,
+            weight,
+            entities: Vec::new(),
+        });
+    }
+
+    /// Add any item to be drawn.
+    pub fn add_item(&mut self, item: impl Into<Item>) {
+        self.items.push(item.into());
+    }
+
+    /// Draw a point with a label.
+    pub fn add_point_item(&mut self, point: VarIndex, label: MathString) {
+        self.add_item(PointItem {
+            id: point,
+            label,
+            display_dot: true,
+            color: Color::default(),
+            opacity: None,
+            label_position: None,
+            label_offset: None,
+        });
+    }
+
+    /// Draw a line.
+    pub fn add_line_item(&mut self, line: VarIndex, style: Style) {
+        self.add_item(LineItem {
+            id: line,
+            label: MathString::new(),
+            style,
+            color: Color::default(),
+            opacity: None,
+        });
+    }
+
+    /// Draw a circle.
+    pub fn add_circle_item(&mut self, circle: VarIndex, style: Style) {
+        self.add_item(CircleItem {
+            id: circle,
+            label: MathString::new(),
+            style,
+            color: Color::default(),
+            opacity: None,
+        });
+    }
+
+    /// Draw a segment between two points.
+    pub fn add_segment_item(&mut self, p: VarIndex, q: VarIndex, style: Style) {
+        self.add_item(SegmentItem {
+            p_id: p,
+            q_id: q,
+            label: MathString::new(),
+            style,
+            color: Color::default(),
+            opacity: None,
+            ticks: 0,
+        });
+    }
+
+    /// Draw a ray from `p` going through `q`.
+    pub fn add_ray_item(&mut self, p: VarIndex, q: VarIndex, style: Style) {
+        self.add_item(RayItem {
+            p_id: p,
+            q_id: q,
+            label: MathString::new(),
+            style,
+            color: Color::default(),
+            opacity: None,
+        });
+    }
+
+    /// Finish building the figure.
+    #[must_use]
+    pub fn build(self) -> Intermediate {
+        let entities: Vec<_> = self.entities.into_iter().map(|ent| ent.kind).collect();
+
+        let mut adjusted = Adjusted {
+            variables: self.variables.clone(),
+            rules: self.rules,
+            entities: entities.clone(),
+        };
+        adjusted.find_rule_entities();
+
+        // Figures built in code have no script to point to.
+        Intermediate {
+            figure: Figure {
+                item_spans: vec![None; self.items.len()],
+                item_layers: vec![None; self.items.len()],
+                entity_spans: vec![None; entities.len()],
+                variable_spans: vec![None; self.variables.len()],
+                entities,
+                variables: self.variables,
+                items: self.items,
+            },
+            adjusted,
+            flags: self.flags,
+            warnings: Vec::new(),
+        }
+    }
+}
@@ -4,12 +4,47 @@
 //! but generic, simplified descriptions are also provided here.
 
 use std::collections::VecDeque;
+use std::time::Duration;
 
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::script::figure::Generated;
+
+pub mod annealing;
 pub mod compiler;
 pub mod glide;
 pub mod rage;
 mod thread_pool;
 
+/// A common interface of engines generating the figure in a single call.
+pub trait Engine {
+    /// Performs the generation. Executes `callback` every time progress is made.
+    /// Returns how long the whole process took.
+    fn generate(&mut self, callback: impl FnMut()) -> Duration;
+
+    /// Get the figure based on the current best state.
+    #[must_use]
+    fn get_figure(&self) -> Generated;
+
+    /// Get the quality of the current best state.
+    #[must_use]
+    fn get_total_quality(&self) -> f64;
+}
+
+/// Creates the random number generator used for the sample with the given index.
+///
+/// With a seed, the generator depends only on the seed and the index, so that
+/// the order in which worker threads pick up samples doesn't change the results.
+/// Without one, the generator is seeded from system entropy.
+#[must_use]
+pub(crate) fn sample_rng(seed: Option<u64>, index: u64) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed ^ index.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+        None => StdRng::from_entropy(),
+    }
+}
+
 /// Helper struct for managing mean quality of last `n` qualities.
 ///
 /// Detects if the arithmetic mean of last `entry_count` quality deltas exceeds `limit`.
@@ -0,0 +1,283 @@
+//! # Simulated annealing
+//!
+//! Randomly adjusts the inputs, just like Rage does, but occasionally accepts
+//! worse states. The probability of accepting a worse state decreases along with
+//! the temperature, which lets the engine escape local minima early on and
+//! settle down later. The minimized energy is the figure error, computed the same
+//! way Glide computes it.
+
+use crate::engine::compiler::{Compiled, FigureFn};
+use crate::engine::rage::generator::{magic_box, AdjustableTemplate, State};
+use crate::engine::{sample_rng, Engine};
+use crate::script::figure::Generated;
+use crate::script::math::Intermediate;
+use geo_aid_math::Func;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// How the temperature decreases after each cooling step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoolingSchedule {
+    /// The temperature is multiplied by the cooling rate.
+    #[default]
+    Exponential,
+    /// The cooling rate (as a fraction of the initial temperature) is subtracted from the temperature.
+    Linear,
+}
+
+/// Parameters modifying the behavior of simulated annealing.
+#[derive(Debug, Clone, Copy)]
+pub struct SaParams {
+    /// How strictly the rules are applied.
+    pub strictness: f64,
+    /// The starting temperature.
+    pub initial_temperature: f64,
+    /// The final temperature. Generation stops when the temperature goes below it.
+    pub min_temperature: f64,
+    /// How fast the temperature decreases. Interpreted according to `schedule`.
+    pub cooling_rate: f64,
+    /// The temperature schedule.
+    pub schedule: CoolingSchedule,
+    /// How many adjustments to try out before cooling down.
+    pub steps_per_temperature: usize,
+    /// The maximal adjustment of an input. The actual adjustment shrinks along with the temperature.
+    pub max_adjustment: f64,
+    /// Seed for all random sampling. If set, generation is deterministic.
+    pub seed: Option<u64>,
+}
+
+impl Default for SaParams {
+    fn default() -> Self {
+        Self {
+            strictness: 2.0,
+            initial_temperature: 1.0,
+            min_temperature: 1e-4,
+            cooling_rate: 0.99,
+            schedule: CoolingSchedule::Exponential,
+            steps_per_temperature: 64,
+            max_adjustment: 0.5,
+            seed: None,
+        }
+    }
+}
+
+impl SaParams {
+    /// Checks whether annealing with these parameters ever finishes.
+    ///
+    /// # Errors
+    /// Returns an error describing the first invalid parameter.
+    pub fn validate(&self) -> Result<(), InvalidSaParams> {
+        if !self.initial_temperature.is_finite() || self.initial_temperature <= 0.0 {
+            return Err(InvalidSaParams::InitialTemperature(
+                self.initial_temperature,
+            ));
+        }
+
+        if self.min_temperature.is_nan() || self.min_temperature <= 0.0 {
+            return Err(InvalidSaParams::MinTemperature(self.min_temperature));
+        }
+
+        let cooling_rate_ok = match self.schedule {
+            CoolingSchedule::Exponential => self.cooling_rate > 0.0 && self.cooling_rate < 1.0,
+            CoolingSchedule::Linear => self.cooling_rate > 0.0 && self.cooling_rate.is_finite(),
+        };
+
+        if cooling_rate_ok {
+            Ok(())
+        } else {
+            Err(InvalidSaParams::CoolingRate(
+                self.schedule,
+                self.cooling_rate,
+            ))
+        }
+    }
+}
+
+/// A parameter that would keep simulated annealing from ever finishing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidSaParams {
+    /// The initial temperature is not positive or not finite.
+    InitialTemperature(f64),
+    /// The minimal temperature is not positive.
+    MinTemperature(f64),
+    /// The cooling rate never lowers the temperature with the given schedule.
+    CoolingRate(CoolingSchedule, f64),
+}
+
+impl Display for InvalidSaParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InitialTemperature(value) => {
+                write!(
+                    f,
+                    "initial temperature must be positive and finite, got {value}"
+                )
+            }
+            Self::MinTemperature(value) => {
+                write!(f, "minimal temperature must be positive, got {value}")
+            }
+            Self::CoolingRate(CoolingSchedule::Exponential, value) => write!(
+                f,
+                "cooling rate must be between 0 and 1 with an exponential schedule, got {value}"
+            ),
+            Self::CoolingRate(CoolingSchedule::Linear, value) => write!(
+                f,
+                "cooling rate must be positive with a linear schedule, got {value}"
+            ),
+        }
+    }
+}
+
+/// Simulated annealing runtime.
+pub struct SimulatedAnnealing {
+    /// Generation params
+    params: SaParams,
+    /// Function computing entity errors
+    error_fn: Func,
+    /// Figure function
+    figure_fn: FigureFn,
+    /// What adjustables are being adjusted.
+    adjustables: Vec<AdjustableTemplate>,
+    /// Current best state
+    best: State,
+    /// The source of randomness.
+    rng: StdRng,
+}
+
+impl SimulatedAnnealing {
+    /// Creates a new runtime based on parameters and Math IR
+    ///
+    /// # Panics
+    /// If the parameters are invalid. See [`SaParams::validate`].
+    #[must_use]
+    pub fn new(params: SaParams, intermediate: &Intermediate) -> Self {
+        if let Err(err) = params.validate() {
+            panic!("invalid simulated annealing parameters: {err}");
+        }
+
+        let Compiled {
+            context,
+            errors,
+            figure_fn,
+            input_count,
+            ..
+        } = super::compiler::compile(intermediate);
+
+        let error_fn = context.exec(|ctx| ctx.compute(errors.iter().map(|v| v.expr)));
+        let adjustables: Vec<_> = intermediate
+            .adjusted
+            .entities
+            .iter()
+            .map(AdjustableTemplate::from)
+            .collect();
+
+        let mut rng = sample_rng(params.seed, 0);
+        let mut best = State {
+            inputs: (0..input_count)
+                .map(|_| rng.gen::<f64>() * 10.0 - 5.0)
+                .collect(),
+            qualities: vec![0.0; adjustables.len()],
+            total_quality: 0.0,
+        };
+        evaluate(
+            error_fn,
+            params.strictness,
+            &mut best,
+            &mut vec![0.0; adjustables.len()],
+        );
+
+        Self {
+            params,
+            error_fn,
+            figure_fn,
+            adjustables,
+            best,
+            rng,
+        }
+    }
+
+    /// Get the temperature following the given one.
+    fn cool_down(&self, temperature: f64) -> f64 {
+        match self.params.schedule {
+            CoolingSchedule::Exponential => temperature * self.params.cooling_rate,
+            CoolingSchedule::Linear => {
+                temperature - self.params.cooling_rate * self.params.initial_temperature
+            }
+        }
+    }
+}
+
+impl Engine for SimulatedAnnealing {
+    /// Anneals the figure until the temperature drops below the minimum.
+    /// Executes `callback` after every cooling step.
+    fn generate(&mut self, mut callback: impl FnMut()) -> Duration {
+        let start = Instant::now();
+
+        let mut current = self.best.clone();
+        let mut candidate = current.clone();
+        let mut errors = vec![0.0; self.adjustables.len()];
+        let mut current_energy = -current.total_quality.ln();
+        let mut temperature = self.params.initial_temperature;
+
+        while temperature > self.params.min_temperature {
+            let magnitude =
+                self.params.max_adjustment * temperature / self.params.initial_temperature;
+
+            for _ in 0..self.params.steps_per_temperature {
+                candidate.clone_from(&current);
+                magic_box::adjust(&mut candidate, magnitude, &self.adjustables, &mut self.rng);
+                let candidate_energy = evaluate(
+                    self.error_fn,
+                    self.params.strictness,
+                    &mut candidate,
+                    &mut errors,
+                );
+
+                // Better states are always accepted, worse ones with a probability
+                // decreasing with the temperature.
+                let delta = candidate_energy - current_energy;
+                if delta <= 0.0 || self.rng.gen::<f64>() < (-delta / temperature).exp() {
+                    std::mem::swap(&mut current, &mut candidate);
+                    current_energy = candidate_energy;
+
+                    if current.total_quality > self.best.total_quality {
+                        self.best.clone_from(&current);
+                    }
+                }
+            }
+
+            temperature = self.cool_down(temperature);
+            callback();
+        }
+
+        start.elapsed()
+    }
+
+    fn get_figure(&self) -> Generated {
+        (self.figure_fn)(&self.best.inputs)
+    }
+
+    fn get_total_quality(&self) -> f64 {
+        self.best.total_quality
+    }
+}
+
+/// Computes the entity errors and qualities of the given state.
+/// Returns the total figure error (the energy).
+fn evaluate(error_fn: Func, strictness: f64, state: &mut State, errors: &mut [f64]) -> f64 {
+    error_fn.call(&state.inputs, errors);
+
+    for (quality, error) in state.qualities.iter_mut().zip(errors.iter()) {
+        *quality = (-error).exp();
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let errors_len = errors.len() as f64;
+    let total_error = (errors.iter().map(|x| x.powf(strictness)).sum::<f64>() / errors_len)
+        .powf(strictness.recip());
+
+    state.total_quality = (-total_error).exp();
+    total_error
+}
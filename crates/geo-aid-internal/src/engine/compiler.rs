@@ -17,6 +17,16 @@ use geo_aid_math::{
 use num_traits::ToPrimitive;
 use std::f64::consts::PI;
 
+/// If the sum of two distances compared in a rule is lower than this, the rule's error
+/// is relative to the sum instead of absolute.
+const RELATIVE_DISTANCE_SCALE: f64 = 2.0;
+
+/// The lowest product of arm lengths used when computing an angle.
+const ANGLE_EPSILON: f64 = 1e-12;
+
+/// The highest error of an inverted rule. Also used when the inverted rule can't be evaluated.
+const MAX_INVERTED_ERROR: f64 = 100.0;
+
 /// A function that takes in values for all inputs and returns
 /// a generated figure
 pub type FigureFn = Box<dyn for<'a> Fn(&'a [f64]) -> Generated>;
@@ -31,8 +41,7 @@ pub struct Compiled {
     pub context: Shared,
     /// The number of inputs of this figure.
     pub input_count: usize,
-    /// Errors of each rule (for debugging purposes)
-    #[allow(unused)]
+    /// Weighted errors of each rule, in the order of the Math IR's rules.
     pub rule_errors: Vec<RealExpr>,
 }
 
@@ -44,8 +53,10 @@ pub fn compile(intermediate: &Intermediate) -> Compiled {
         .entities
         .iter()
         .map(|ent| match ent {
+            EntityKind::FreeCircle => 3,
             EntityKind::FreePoint => 2,
             EntityKind::PointOnLine { .. }
+            | EntityKind::PointOnSegment { .. }
             | EntityKind::PointOnCircle { .. }
             | EntityKind::FreeReal
             | EntityKind::DistanceUnit => 1,
@@ -76,8 +87,10 @@ pub fn compile(intermediate: &Intermediate) -> Compiled {
             ty: match ent {
                 EntityKind::FreePoint
                 | EntityKind::PointOnLine { line: _ }
+                | EntityKind::PointOnSegment { p: _, q: _ }
                 | EntityKind::PointOnCircle { circle: _ } => ExprType::Point,
                 EntityKind::FreeReal | EntityKind::DistanceUnit => ExprType::Number,
+                EntityKind::FreeCircle => ExprType::Circle,
                 EntityKind::Bind(_) => unreachable!(),
             },
         };
@@ -183,7 +196,19 @@ impl<'r> Compiler<'r> {
                     }));
                     index += 2;
                 }
+                EntityKind::FreeCircle => {
+                    // The radius is kept positive by taking the absolute value of the input.
+                    adjustables.push(ValueExpr::Circle(CircleExpr {
+                        center: ComplexExpr {
+                            real: context.input(index),
+                            imaginary: context.input(index + 1),
+                        },
+                        radius: context.input(index + 2).abs(),
+                    }));
+                    index += 3;
+                }
                 EntityKind::PointOnLine { .. }
+                | EntityKind::PointOnSegment { .. }
                 | EntityKind::PointOnCircle { .. }
                 | EntityKind::FreeReal
                 | EntityKind::DistanceUnit => {
@@ -241,6 +266,20 @@ impl<'r> Compiler<'r> {
                 let five = self.context.constant(5.0);
                 (a - &b).norm() * &five
             }
+            RuleKind::DistanceEq(a, b) => {
+                // Same as for other numbers, but for small distances the error is relative,
+                // so that shrinking the figure doesn't improve its quality.
+                let a = self.variables[a.0].to_complex().real;
+                let b = self.variables[b.0].to_complex().real;
+                let scale = self.context.constant(RELATIVE_DISTANCE_SCALE);
+                let epsilon = self.context.constant(1e-8);
+                let five = self.context.constant(5.0);
+                let relative_to = ((a.abs() + &b.abs()) / &scale).min(&self.context.real_one());
+                let difference = a - &b;
+                (difference.clone() * &difference)
+                    / &(relative_to.clone() * &relative_to + &epsilon)
+                    * &five
+            }
             RuleKind::Gt(a, b) => self.gt(a, b),
             RuleKind::Alternative(rules) => {
                 // necessary because borrowing
@@ -252,8 +291,35 @@ impl<'r> Compiler<'r> {
                 qualities.into_iter().reduce(|a, b| a.min(&b)).unwrap()
             }
             RuleKind::Invert(q) => {
-                self.context.real_one()
-                    / &(self.context.constant(10.0) * &self.compile_rule_kind(q))
+                // `1 / 10q`, capped so that a fully violated rule doesn't give an infinite error.
+                let q = self.compile_rule_kind(q);
+                let max_error = self.context.constant(MAX_INVERTED_ERROR);
+                let min_q = self.context.constant((10.0 * MAX_INVERTED_ERROR).recip());
+                let infinity = self.context.constant(f64::INFINITY);
+                let inverted = self.context.real_one() / &(self.context.constant(10.0) * &q);
+
+                let capped = self.context.ternary(
+                    Condition::Comparison(Comparison {
+                        a: q.expr,
+                        b: min_q.expr,
+                        kind: ComparisonKind::Gt,
+                    }),
+                    inverted,
+                    max_error.clone(),
+                );
+
+                // If the rule being inverted couldn't be evaluated (NaN or infinity, e.g. for
+                // an intersection of parallel lines), it mustn't count as satisfied.
+                // Comparisons with NaN are false, so it gets the highest error, too.
+                self.context.ternary(
+                    Condition::Comparison(Comparison {
+                        a: infinity.expr,
+                        b: q.expr,
+                        kind: ComparisonKind::Gt,
+                    }),
+                    capped,
+                    max_error,
+                )
             }
             RuleKind::Bias => self.context.real_zero(), // Bias in this approach doesn't really do anything
         }
@@ -284,6 +350,21 @@ impl<'r> Compiler<'r> {
             .unwrap_or(self.context.complex_one())
     }
 
+    /// Compile half of the length of a chord at distance `sqrt(dst_squared)` from the center
+    /// of a circle. Clamped to zero if the chord doesn't exist.
+    fn compile_half_chord(&mut self, radius: &RealExpr, dst_squared: &RealExpr) -> RealExpr {
+        let h_squared = radius.clone() * radius - dst_squared;
+        let positive = Condition::Comparison(Comparison {
+            a: h_squared.expr,
+            b: Context::zero(),
+            kind: ComparisonKind::Gt,
+        });
+
+        self.context
+            .ternary(positive, h_squared, self.context.real_zero())
+            .sqrt()
+    }
+
     /// Compile the specific expression.
     /// Assume all expressions it may depend on are already compiled.
     /// This assumption is true given that expressions are processed
@@ -294,13 +375,27 @@ impl<'r> Compiler<'r> {
             ExprKind::Entity { id } => {
                 let kind = self.entities[id.0].clone();
                 match kind {
-                    EntityKind::FreePoint => self.adjustables[id.0].clone(),
+                    EntityKind::FreePoint | EntityKind::FreeCircle => {
+                        self.adjustables[id.0].clone()
+                    }
                     EntityKind::PointOnLine { line } => {
                         let line = self.variables[line.0].to_line();
                         let offset = self.adjustables[id.0].to_single();
 
                         (line.origin + &(offset * &line.direction)).into()
                     }
+                    EntityKind::PointOnSegment { p, q } => {
+                        // The offset is mapped onto `[0, 1]` periodically, so that the point
+                        // never leaves the segment, whatever the input.
+                        let p = self.variables[p.0].to_complex();
+                        let q = self.variables[q.0].to_complex();
+                        let offset = self.adjustables[id.0].to_single();
+                        let pi = self.context.constant(PI);
+                        let half = self.context.constant(0.5);
+                        let t = (self.context.real_one() - &(offset * &pi).cos()) * &half;
+
+                        (p.clone() + &((q - &p) * &t)).into()
+                    }
                     EntityKind::PointOnCircle { circle } => {
                         let circle = self.variables[circle.0].to_circle();
                         let theta = self.adjustables[id.0].to_single();
@@ -349,6 +444,51 @@ impl<'r> Compiler<'r> {
 
                 (k.origin - &b_times_quotient).into()
             }
+            ExprKind::LineCircleIntersection {
+                line,
+                circle,
+                index,
+            } => {
+                // The points lie symmetrically around the projection of the center onto the line.
+                let line = self.variables[line.0].to_line();
+                let circle = self.variables[circle.0].to_circle();
+
+                let unit = line.direction.clone() / &line.direction.abs();
+                let t = ((circle.center.clone() - &line.origin) / &unit).real;
+                let foot = line.origin + &(unit.clone() * &t);
+                let dst_squared = (circle.center - &foot).norm();
+                let offset = unit * &self.compile_half_chord(&circle.radius, &dst_squared);
+
+                if *index == 0 {
+                    (foot - &offset).into()
+                } else {
+                    (foot + &offset).into()
+                }
+            }
+            ExprKind::CircleCircleIntersection { c1, c2, index } => {
+                // The points lie symmetrically around the radical axis' intersection with `o1o2`.
+                // See `RadicalAxis` for the derivation.
+                let c1 = self.variables[c1.0].to_circle();
+                let c2 = self.variables[c2.0].to_circle();
+
+                let d = c2.center - &c1.center;
+                let d_abs = d.abs();
+                let unit = d / &d_abs;
+                let r1_squared = c1.radius.clone() * &c1.radius;
+                let r2_squared = c2.radius.clone() * &c2.radius;
+                let two = self.context.constant(2.0);
+
+                let a = (d_abs.clone() * &d_abs + &r1_squared - &r2_squared) / &(d_abs * &two);
+                let middle = c1.center + &(unit.clone() * &a);
+                let a_squared = a.clone() * &a;
+                let offset = unit.mul_i() * &self.compile_half_chord(&c1.radius, &a_squared);
+
+                if *index == 0 {
+                    (middle - &offset).into()
+                } else {
+                    (middle + &offset).into()
+                }
+            }
             ExprKind::AveragePoint { items } => {
                 let sum = self.compile_sum(items);
 
@@ -357,6 +497,9 @@ impl<'r> Compiler<'r> {
                 (sum / &len).into()
             }
             ExprKind::CircleCenter { circle } => self.variables[circle.0].to_circle().center.into(),
+            ExprKind::CircleRadius { circle } => {
+                ComplexExpr::real(self.variables[circle.0].to_circle().radius).into()
+            }
             ExprKind::ComplexToPoint { number } => self.variables[number.0].clone(),
             ExprKind::Sum { plus, minus } => {
                 (self.compile_sum(plus) - &self.compile_sum(minus)).into()
@@ -374,9 +517,26 @@ impl<'r> Compiler<'r> {
             }
             ExprKind::Exponentiation { value, exponent } => {
                 let value = self.variables[value.0].to_complex();
-                let exp = self.context.constant(exponent.to_f64().unwrap());
 
-                value.pow(&ComplexExpr::real(exp)).into()
+                if exponent.is_integer() {
+                    // `exp(log(v) * n)` isn't finite for `v = 0`, so integer powers
+                    // are computed by multiplying instead.
+                    let power = exponent.to_integer();
+                    let mut result = self.context.complex_one();
+                    for _ in 0..power.unsigned_abs() {
+                        result = result * &value;
+                    }
+
+                    if power < 0 {
+                        (self.context.complex_one() / &result).into()
+                    } else {
+                        result.into()
+                    }
+                } else {
+                    let exp = self.context.constant(exponent.to_f64().unwrap());
+
+                    value.pow(&ComplexExpr::real(exp)).into()
+                }
             }
             ExprKind::PointPointDistance { p, q } => {
                 let p = self.variables[p.0].to_complex();
@@ -408,11 +568,16 @@ impl<'r> Compiler<'r> {
                 let arm1_vec = p - &q;
                 let arm2_vec = r - &q;
 
-                let mag_product = arm1_vec.abs() * &arm2_vec.abs();
+                // With an arm of zero length the angle is undefined. The product is kept
+                // positive and the cosine in range, so that a NaN doesn't spread into
+                // the gradient and the errors of the rules using this angle.
+                let epsilon = self.context.constant(ANGLE_EPSILON);
+                let mag_product = (arm1_vec.abs() * &arm2_vec.abs()).max(&epsilon);
                 let dot_product_alpha = arm1_vec.real * &arm2_vec.real;
                 let dot_product_beta = arm1_vec.imaginary * &arm2_vec.imaginary;
                 let dot_product = dot_product_alpha + &dot_product_beta;
-                let quotient = dot_product / &mag_product;
+                let quotient = (dot_product / &mag_product)
+                    .clamp(&self.context.constant(-1.0), &self.context.real_one());
                 ComplexExpr::real(quotient.acos()).into()
             }
             ExprKind::ThreePointAngleDir { p, q, r } => {
@@ -436,6 +601,19 @@ impl<'r> Compiler<'r> {
                 let rotated = arm2_vec / &arm1_vec;
                 ComplexExpr::real(rotated.arg()).into()
             }
+            ExprKind::ThreePointArea { p, q, r } => {
+                // ((q - p) x (r - p)) / 2
+                let p = self.variables[p.0].to_complex();
+                let q = self.variables[q.0].to_complex();
+                let r = self.variables[r.0].to_complex();
+
+                let side1 = q - &p;
+                let side2 = r - &p;
+
+                let cross = side1.real * &side2.imaginary - &(side1.imaginary * &side2.real);
+                let half = self.context.constant(0.5);
+                ComplexExpr::real(cross * &half).into()
+            }
             ExprKind::TwoLineAngle { k, l } => {
                 // (k.direction / l.direction).arg().abs()
                 let k = self.variables[k.0].to_line();
@@ -458,6 +636,27 @@ impl<'r> Compiler<'r> {
             ExprKind::Imaginary { number } => {
                 ComplexExpr::real(self.variables[number.0].to_complex().imaginary).into()
             }
+            ExprKind::Abs { value } => {
+                // Numbers with an absolute value are never expected to be complex.
+                let value = self.variables[value.0].to_complex().real;
+                ComplexExpr::real(value.abs()).into()
+            }
+            ExprKind::Min { a, b } | ExprKind::Max { a, b } => {
+                // Like with absolute values, only real numbers are expected here.
+                let a = self.variables[a.0].to_complex().real;
+                let b = self.variables[b.0].to_complex().real;
+                let a_greater = Condition::Comparison(Comparison {
+                    a: a.expr,
+                    b: b.expr,
+                    kind: ComparisonKind::Gt,
+                });
+
+                if matches!(value.kind, ExprKind::Min { .. }) {
+                    ComplexExpr::real(self.context.ternary(a_greater, b, a)).into()
+                } else {
+                    ComplexExpr::real(self.context.ternary(a_greater, a, b)).into()
+                }
+            }
             ExprKind::Log { number } => self.variables[number.0].to_complex().log().into(),
             ExprKind::Exp { number } => self.variables[number.0].to_complex().exp().into(),
             ExprKind::Sin { angle } => {
@@ -544,6 +743,49 @@ impl<'r> Compiler<'r> {
 
                 LineExpr { origin, direction }.into()
             }
+            ExprKind::LineLineBisector { k, l, external } => {
+                // With `s_k(p) = Im(conj(u) * (p - a))` being the signed distance from `k`,
+                // the bisector is the locus of `s_k + s_l = 0`, which is the line
+                // `Im(conj(u + v) * p) = Im(conj(u) * a + conj(v) * b)`. `v` is first flipped
+                // so that `u + v` bisects the acute angle (or the obtuse one, if external).
+                // For parallel lines that leaves `u + v = 0` in the external case, so `v` is
+                // flipped back to get the mid-parallel.
+                let k = self.variables[k.0].to_line();
+                let l = self.variables[l.0].to_line();
+
+                let u = k.direction.clone() / &k.direction.abs();
+                let v = l.direction.clone() / &l.direction.abs();
+
+                let dot = u.real.clone() * &v.real + &(u.imaginary.clone() * &v.imaginary);
+                let aligned = Condition::Comparison(Comparison {
+                    a: dot.expr,
+                    b: Context::zero(),
+                    kind: ComparisonKind::Gt,
+                });
+                let v = if *external {
+                    let v = self.context.complex_ternary(aligned, -v.clone(), v);
+                    let degenerate = Condition::Comparison(Comparison {
+                        a: (u.clone() + &v).norm().expr,
+                        b: self.context.constant(1e-12).expr,
+                        kind: ComparisonKind::Gt,
+                    });
+                    self.context.complex_ternary(degenerate, v.clone(), -v)
+                } else {
+                    self.context.complex_ternary(aligned, v.clone(), -v)
+                };
+
+                let cross = |x: &ComplexExpr, y: &ComplexExpr| {
+                    x.real.clone() * &y.imaginary - &(x.imaginary.clone() * &y.real)
+                };
+                let offset = cross(&u, &k.origin) + &cross(&v, &l.origin);
+                let sum = u + &v;
+
+                LineExpr {
+                    origin: sum.mul_i() * &(offset / &sum.norm()),
+                    direction: sum.clone() / &sum.abs(),
+                }
+                .into()
+            }
             ExprKind::ParallelThrough { point, line } => {
                 let point = self.variables[point.0].to_complex();
                 let mut line = self.variables[line.0].to_line();
@@ -561,6 +803,27 @@ impl<'r> Compiler<'r> {
                 }
                 .into()
             }
+            ExprKind::RadicalAxis { c1, c2 } => {
+                // The axis is perpendicular to `o1o2` and goes through `o1 + (o2 - o1) * t`,
+                // where `t = (d^2 + r1^2 - r2^2) / 2d^2` and `d = |o2 - o1|`.
+                let c1 = self.variables[c1.0].to_circle();
+                let c2 = self.variables[c2.0].to_circle();
+
+                let d = c2.center - &c1.center;
+                let d_abs = d.abs();
+                let d_squared = d_abs.clone() * &d_abs;
+                let r1_squared = c1.radius.clone() * &c1.radius;
+                let r2_squared = c2.radius.clone() * &c2.radius;
+                let two = self.context.constant(2.0);
+
+                let t = (d_squared.clone() + &r1_squared - &r2_squared) / &(d_squared * &two);
+
+                LineExpr {
+                    origin: c1.center + &(d.clone() * &t),
+                    direction: (d / &d_abs).mul_i(),
+                }
+                .into()
+            }
             ExprKind::ConstructCircle { center, radius } => {
                 let center = self.variables[center.0].to_complex();
                 let radius = self.variables[radius.0].to_complex();
@@ -868,10 +1131,15 @@ fn get_figure(figure: &crate::script::figure::Figure, values: &[f64]) -> Generat
         let v = match ent {
             EntityKind::PointOnCircle { .. }
             | EntityKind::PointOnLine { .. }
+            | EntityKind::PointOnSegment { .. }
             | EntityKind::FreePoint => ValueEnum::Complex(get_complex(&mut value)),
             EntityKind::DistanceUnit | EntityKind::FreeReal => {
                 ValueEnum::Complex(Complex::real(get_complex(&mut value).real))
             }
+            EntityKind::FreeCircle => ValueEnum::Circle(Circle {
+                center: get_complex(&mut value),
+                radius: value.next().unwrap().abs(),
+            }),
             EntityKind::Bind(_) => unreachable!(),
         };
         entities.push(Entity {
@@ -884,5 +1152,9 @@ fn get_figure(figure: &crate::script::figure::Figure, values: &[f64]) -> Generat
         variables,
         entities,
         items: figure.items.clone(),
+        item_spans: figure.item_spans.clone(),
+        item_layers: figure.item_layers.clone(),
+        entity_spans: figure.entity_spans.clone(),
+        variable_spans: figure.variable_spans.clone(),
     }
 }
@@ -5,11 +5,14 @@
 
 use crate::engine::compiler::{Compiled, FigureFn};
 use crate::engine::thread_pool::ThreadPool;
-use crate::engine::QualityRecord;
+use crate::engine::{sample_rng, Engine, QualityRecord};
 use crate::script::figure::Generated;
 use crate::script::math::Intermediate;
 use geo_aid_math::Func;
 use rand::Rng;
+use serde::Serialize;
+use std::cell::Cell;
+use std::cmp::{Ordering, Reverse};
 use std::time::{Duration, Instant};
 
 /// Glide runtime.
@@ -22,8 +25,125 @@ pub struct Glide {
     gradient_fn: Func,
     /// Figure function
     figure_fn: FigureFn,
+    /// Function computing the error of each rule
+    rule_error_fn: Func,
+    /// Descriptions and weights of the rules, in the order of `rule_error_fn`'s outputs.
+    rules: Vec<(String, f64)>,
     /// Current best state
     inputs: Vec<f64>,
+    /// How many samples the last generation completed.
+    samples_completed: usize,
+    /// How long the last generation took.
+    time: Duration,
+    /// For each rule, in how many samples of the last generation its error wasn't finite.
+    anomalies: Vec<usize>,
+}
+
+/// The method used to descend towards a local minimum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptimizerKind {
+    /// Vanilla gradient descent with speed decaying on oscillation.
+    #[default]
+    GradientDescent,
+    /// Adam (Adaptive Moment Estimation).
+    Adam,
+}
+
+/// Hyperparameters of the Adam optimizer.
+#[derive(Debug, Clone, Copy)]
+pub struct AdamParams {
+    /// Step size.
+    pub learning_rate: f64,
+    /// Decay rate of the gradient moving average (`β1`).
+    pub beta1: f64,
+    /// Decay rate of the squared gradient moving average (`β2`).
+    pub beta2: f64,
+    /// Small constant preventing division by zero (`ε`).
+    pub epsilon: f64,
+}
+
+impl Default for AdamParams {
+    fn default() -> Self {
+        Self {
+            learning_rate: 0.1,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+}
+
+/// Information about a completed sample, passed to the callback of [`Glide::generate`].
+#[derive(Debug, Clone, Copy)]
+pub struct SampleResult {
+    /// Quality achieved by this sample.
+    pub quality: f64,
+    /// Index of this sample in the order of completion, starting at 0.
+    pub sample_index: usize,
+    /// Whether this sample is better than all previous ones.
+    pub is_new_best: bool,
+    /// Time elapsed since the generation started.
+    pub elapsed: Duration,
+}
+
+/// How well a single rule is satisfied by the generated figure.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleQuality {
+    /// Index of the rule in the Math IR.
+    pub rule_index: usize,
+    /// Human-readable form of the rule.
+    pub rule_description: String,
+    /// The rule's error, already multiplied by its weight. Zero means the rule is fully satisfied.
+    pub error: f64,
+    /// The rule's weight.
+    pub weight: f64,
+}
+
+/// Per-rule breakdown of the figure's quality.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QualityReport {
+    /// Qualities of all rules, in the order of the Math IR.
+    pub rules: Vec<RuleQuality>,
+}
+
+impl QualityReport {
+    /// Get the rule with the highest error, if there are any rules.
+    #[must_use]
+    pub fn worst(&self) -> Option<&RuleQuality> {
+        self.rules.iter().max_by(|a, b| a.error.total_cmp(&b.error))
+    }
+}
+
+/// A rule whose error evaluated to NaN or infinity during generation.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleAnomaly {
+    /// Index of the rule in the Math IR.
+    pub rule_index: usize,
+    /// Human-readable form of the rule.
+    pub rule_description: String,
+    /// In how many samples the rule's error wasn't finite.
+    pub count: usize,
+}
+
+/// How many rules with anomalies are included in a [`GenerationReport`].
+const REPORTED_ANOMALIES: usize = 10;
+
+/// A machine-readable summary of a generation, e.g. for regression-testing scripts.
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationReport {
+    /// Total quality of the figure.
+    pub total_quality: f64,
+    /// How long the generation took, in seconds.
+    pub seconds: f64,
+    /// How many samples were completed, including retries.
+    pub samples: usize,
+    /// How many times, in total, a rule's error wasn't finite at the end of a sample.
+    /// These usually come from degenerate constructions, like intersections of parallel lines.
+    pub anomaly_count: usize,
+    /// The rules with the most anomalies, most frequent first.
+    pub anomalies: Vec<RuleAnomaly>,
+    /// Qualities of all rules, in the order of the Math IR.
+    pub rules: Vec<RuleQuality>,
 }
 
 /// Parameters modifying the behavior of Glide
@@ -40,6 +160,38 @@ pub struct Params {
     /// If the arithmetic mean of the last `mean_count` deltas ever goes below
     /// this number, the generation process stops.
     pub max_mean_delta: f64,
+    /// The optimizer used for descending.
+    pub optimizer: OptimizerKind,
+    /// Hyperparameters used if `optimizer` is [`OptimizerKind::Adam`].
+    pub adam: AdamParams,
+    /// Seed for all random sampling. If set, generation is deterministic.
+    pub seed: Option<u64>,
+    /// If the total quality after all samples is below this value, another batch
+    /// of `samples` new samples is generated, up to `max_retries` times.
+    pub min_quality: Option<f64>,
+    /// How many additional batches of samples can be generated at most.
+    pub max_retries: usize,
+    /// If a sample reaches this quality, no more samples are launched.
+    /// Samples already running are still completed.
+    pub quality_threshold: Option<f64>,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            strictness: 2.0,
+            samples: 512,
+            worker_count: 32,
+            mean_count: 128,
+            max_mean_delta: 0.0001,
+            optimizer: OptimizerKind::default(),
+            adam: AdamParams::default(),
+            seed: None,
+            min_quality: None,
+            max_retries: 0,
+            quality_threshold: None,
+        }
+    }
 }
 
 impl Glide {
@@ -51,7 +203,7 @@ impl Glide {
             errors,
             figure_fn,
             input_count,
-            ..
+            rule_errors,
         } = super::compiler::compile(intermediate);
 
         // let rule1 = rule_errors[0];
@@ -71,30 +223,89 @@ impl Glide {
         });
         let total_error = total_error_power.pow(&context.constant(mean_exponent.recip()));
 
-        let mut rng = rand::thread_rng();
+        // The initial state uses index 0, samples are numbered from 1.
+        let mut rng = sample_rng(params.seed, 0);
         let inputs = (0..input_count).map(|_| rng.gen::<f64>() * 10.0 - 5.0);
 
+        let rules: Vec<_> = intermediate
+            .adjusted
+            .rules
+            .iter()
+            .map(|rule| (rule.to_string(), rule.weight.to_complex().real))
+            .collect();
+
         Self {
             params,
             error_fn: context.exec(|ctx| ctx.compute([total_error.expr])),
             gradient_fn: context.exec(|ctx| ctx.compute_gradient(total_error.expr)),
             figure_fn,
+            rule_error_fn: context.exec(|ctx| ctx.compute(rule_errors.into_iter().map(|v| v.expr))),
+            anomalies: vec![0; rules.len()],
+            rules,
             inputs: inputs.collect(),
+            samples_completed: 0,
+            time: Duration::ZERO,
         }
     }
 
-    /// Performs a generation over the previously specified sample count.
+    /// Performs a generation over the previously specified sample count. If the
+    /// minimum quality isn't reached, retries with new samples, keeping the best one overall.
     /// Executes `sample_complete` every time a sample is completed.
-    /// Returns how long the whole process took.
-    pub fn generate(&mut self, mut sample_complete: impl FnMut()) -> Duration {
+    /// Returns how long the whole process took, including retries.
+    pub fn generate(&mut self, sample_complete: impl FnMut(SampleResult)) -> Duration {
+        self.generate_recording(|_| (), sample_complete)
+    }
+
+    /// Performs a generation like [`Glide::generate`], additionally recording
+    /// the figure every time a new best state is found. At most `max_snapshots`
+    /// figures are kept: when the limit is reached, every second snapshot is dropped
+    /// and later ones are recorded half as often, so that the history always spans
+    /// the whole generation. The last snapshot is always the final figure.
+    ///
+    /// Returns how long the whole process took and the recorded figures, oldest first.
+    pub fn generate_with_history(
+        &mut self,
+        max_snapshots: usize,
+        sample_complete: impl FnMut(SampleResult),
+    ) -> (Duration, Vec<Generated>) {
+        let mut history = History::new(max_snapshots);
+        let time = self.generate_recording(|inputs| history.record(inputs), sample_complete);
+
+        let mut snapshots = history.snapshots;
+        if max_snapshots > 0 && snapshots.last() != Some(&self.inputs) {
+            if snapshots.len() >= max_snapshots {
+                snapshots.pop();
+            }
+            snapshots.push(self.inputs.clone());
+        }
+
+        let figures = snapshots
+            .iter()
+            .map(|inputs| (self.figure_fn)(inputs))
+            .collect();
+
+        (time, figures)
+    }
+
+    /// Performs the generation, executing `new_best` with the inputs every time
+    /// a new best state is found and `sample_complete` every time a sample is completed.
+    fn generate_recording(
+        &mut self,
+        mut new_best: impl FnMut(&[f64]),
+        mut sample_complete: impl FnMut(SampleResult),
+    ) -> Duration {
         let start = Instant::now();
         let input_count = self.inputs.len();
         let params = self.params;
         let error_fn = self.error_fn;
         let gradient_fn = self.gradient_fn;
+        let rule_error_fn = self.rule_error_fn;
+        let mut rule_errors = vec![0.0; self.rules.len()];
+        let mut anomalies = vec![0; self.rules.len()];
         let mut pool = ThreadPool::new(
             self.params.worker_count,
             move |_: Option<()>| GenerateContext {
+                sample_index: 0,
                 sample: vec![0.0; input_count],
                 error_fn,
                 gradient_fn,
@@ -104,42 +315,98 @@ impl Glide {
                 quality_record: QualityRecord::new(params.mean_count, params.max_mean_delta),
             },
             None,
-            descend,
+            move |ctx: &mut GenerateContext| match params.optimizer {
+                OptimizerKind::GradientDescent => GradientDescender.descend(ctx),
+                OptimizerKind::Adam => AdamDescender::new(params.adam, input_count).descend(ctx),
+            },
         );
 
-        let mut rng = rand::thread_rng();
-
         let mut error = [0.0];
         self.error_fn.call(&self.inputs, &mut error);
         let mut current_quality = (-error[0]).exp();
+        let mut best_index = None;
 
         let mut samples_launched = 0;
+        let mut samples_completed = 0;
+        let mut sample_limit = self.params.samples;
+        let mut retries = 0;
+        let threshold_reached = Cell::new(
+            params
+                .quality_threshold
+                .is_some_and(|threshold| current_quality >= threshold),
+        );
 
-        pool.execute(
-            |ctx| {
-                if samples_launched >= self.params.samples {
-                    return false;
-                }
+        loop {
+            pool.execute(
+                |ctx| {
+                    if samples_launched >= sample_limit || threshold_reached.get() {
+                        return false;
+                    }
 
-                for input in &mut ctx.sample {
-                    *input = rng.gen::<f64>() * 10.0 - 5.0;
-                }
+                    samples_launched += 1;
+                    ctx.sample_index = samples_launched;
 
-                samples_launched += 1;
-                true
-            },
-            |ctx| {
-                let q = ctx.quality_record.get_quality();
-                if ctx.quality_record.get_quality() > current_quality {
-                    self.inputs.clone_from(&ctx.sample);
-                    current_quality = q;
-                }
+                    let mut rng = sample_rng(params.seed, samples_launched as u64);
+                    for input in &mut ctx.sample {
+                        *input = rng.gen::<f64>() * 10.0 - 5.0;
+                    }
 
-                sample_complete();
-            },
-        );
+                    true
+                },
+                |ctx| {
+                    rule_error_fn.call(&ctx.sample, &mut rule_errors);
+                    for (count, error) in anomalies.iter_mut().zip(&rule_errors) {
+                        if !error.is_finite() {
+                            *count += 1;
+                        }
+                    }
+
+                    let q = ctx.quality_record.get_quality();
+                    // Ties are resolved by sample index, so that the result doesn't depend on
+                    // the order in which samples complete.
+                    let is_new_best = match q.total_cmp(&current_quality) {
+                        Ordering::Greater => true,
+                        Ordering::Equal => best_index.is_some_and(|i| ctx.sample_index < i),
+                        Ordering::Less => false,
+                    };
+                    if is_new_best {
+                        self.inputs.clone_from(&ctx.sample);
+                        current_quality = q;
+                        best_index = Some(ctx.sample_index);
+                        new_best(&self.inputs);
+
+                        if params
+                            .quality_threshold
+                            .is_some_and(|threshold| q >= threshold)
+                        {
+                            threshold_reached.set(true);
+                        }
+                    }
 
-        start.elapsed()
+                    sample_complete(SampleResult {
+                        quality: q,
+                        sample_index: samples_completed,
+                        is_new_best,
+                        elapsed: start.elapsed(),
+                    });
+                    samples_completed += 1;
+                },
+            );
+
+            let below_minimum = params.min_quality.is_some_and(|min| current_quality < min);
+            if !below_minimum || threshold_reached.get() || retries >= params.max_retries {
+                break;
+            }
+
+            // Each sample is seeded by its index, so the next batch uses new samples.
+            retries += 1;
+            sample_limit += params.samples;
+        }
+
+        self.samples_completed = samples_completed;
+        self.anomalies = anomalies;
+        self.time = start.elapsed();
+        self.time
     }
 
     #[must_use]
@@ -153,10 +420,130 @@ impl Glide {
         self.error_fn.call(&self.inputs, &mut q);
         (-q[0]).exp()
     }
+
+    /// Get the errors of each rule in the current best state.
+    #[must_use]
+    pub fn get_quality_report(&self) -> QualityReport {
+        let mut errors = vec![0.0; self.rules.len()];
+        self.rule_error_fn.call(&self.inputs, &mut errors);
+
+        QualityReport {
+            rules: self
+                .rules
+                .iter()
+                .zip(errors)
+                .enumerate()
+                .map(|(rule_index, ((description, weight), error))| RuleQuality {
+                    rule_index,
+                    rule_description: description.clone(),
+                    error,
+                    weight: *weight,
+                })
+                .collect(),
+        }
+    }
+
+    /// Get a summary of the last generation: its quality, time, sample count
+    /// and the rules that couldn't be evaluated.
+    #[must_use]
+    pub fn get_generation_report(&self) -> GenerationReport {
+        let rules = self.get_quality_report().rules;
+
+        let mut anomalies: Vec<_> = rules
+            .iter()
+            .zip(self.anomalies.iter().copied())
+            .filter(|(_, count)| *count > 0)
+            .map(|(rule, count)| RuleAnomaly {
+                rule_index: rule.rule_index,
+                rule_description: rule.rule_description.clone(),
+                count,
+            })
+            .collect();
+        let anomaly_count = anomalies.iter().map(|anomaly| anomaly.count).sum();
+        // The sort is stable, so ties stay in rule order.
+        anomalies.sort_by_key(|anomaly| Reverse(anomaly.count));
+        anomalies.truncate(REPORTED_ANOMALIES);
+
+        GenerationReport {
+            total_quality: self.get_total_quality(),
+            seconds: self.time.as_secs_f64(),
+            samples: self.samples_completed,
+            anomaly_count,
+            anomalies,
+            rules,
+        }
+    }
+}
+
+impl Engine for Glide {
+    fn generate(&mut self, mut callback: impl FnMut()) -> Duration {
+        Glide::generate(self, |_| callback())
+    }
+
+    fn get_figure(&self) -> Generated {
+        Glide::get_figure(self)
+    }
+
+    fn get_total_quality(&self) -> f64 {
+        Glide::get_total_quality(self)
+    }
+}
+
+/// A bounded record of the best states found during generation.
+struct History {
+    /// Maximum number of snapshots kept.
+    max_snapshots: usize,
+    /// Only every `stride`-th state is recorded.
+    stride: usize,
+    /// How many states were offered since the last recorded one.
+    skipped: usize,
+    /// The recorded inputs, oldest first.
+    snapshots: Vec<Vec<f64>>,
+}
+
+impl History {
+    /// Creates an empty history keeping at most `max_snapshots` snapshots.
+    fn new(max_snapshots: usize) -> Self {
+        Self {
+            max_snapshots,
+            stride: 1,
+            skipped: 0,
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Offers a new best state to the history.
+    fn record(&mut self, inputs: &[f64]) {
+        if self.max_snapshots == 0 {
+            return;
+        }
+
+        self.skipped += 1;
+        if self.skipped < self.stride {
+            return;
+        }
+        self.skipped = 0;
+
+        if self.max_snapshots == 1 {
+            self.snapshots.clear();
+        } else if self.snapshots.len() >= self.max_snapshots {
+            // Thin out the history, keeping it evenly spread.
+            let mut index = 0;
+            self.snapshots.retain(|_| {
+                index += 1;
+                index % 2 == 1
+            });
+            self.stride *= 2;
+        }
+
+        self.snapshots.push(inputs.to_vec());
+    }
 }
 
 /// Context for task executors to work on.
 struct GenerateContext {
+    /// Index of the sample being processed
+    sample_index: usize,
     /// Input sample
     sample: Vec<f64>,
     /// Program computing figure error.
@@ -180,60 +567,157 @@ const INITIAL_SPEED: f64 = 1.0;
 const DOT_THRESHOLD: f64 = 0.0;
 /// If the speed ever goes below this value, generation stops.
 const SPEED_LIMIT: f64 = 1e-6;
+/// Maximum number of steps Adam can take in a single descent.
+const ADAM_MAX_ITERATIONS: usize = 100_000;
 
-/// Finds the nearest local minimum based on the provided sample (and the remainder of context)
-fn descend(ctx: &mut GenerateContext) {
-    let mut speed = INITIAL_SPEED;
-    let mut error = [0.0];
-    let mut candidate_error = [0.0];
-    ctx.error_fn.call(&ctx.sample, &mut error);
-    ctx.gradient_fn.call(&ctx.sample, &mut ctx.gradient);
-    ctx.previous_gradient.clone_from(&ctx.gradient);
-    ctx.quality_record.clear();
-    ctx.candidate.clone_from(&ctx.sample);
-
-    loop {
-        while speed > SPEED_LIMIT {
-            for (delta, target) in ctx
-                .gradient
-                .iter()
-                .copied()
-                .map(|v| v * -speed)
-                .zip(&mut ctx.candidate)
-            {
-                *target += delta;
-            }
+/// A method of finding the nearest local minimum of the error function.
+trait GlideOptimizer {
+    /// Finds the nearest local minimum based on the provided sample (and the remainder of context)
+    fn descend(&mut self, ctx: &mut GenerateContext);
+}
+
+/// Vanilla gradient descent. The speed grows after every successful step
+/// and decays when the descent starts to oscillate.
+struct GradientDescender;
+
+impl GlideOptimizer for GradientDescender {
+    fn descend(&mut self, ctx: &mut GenerateContext) {
+        let mut speed = INITIAL_SPEED;
+        let mut error = [0.0];
+        let mut candidate_error = [0.0];
+        ctx.error_fn.call(&ctx.sample, &mut error);
+        ctx.gradient_fn.call(&ctx.sample, &mut ctx.gradient);
+        ctx.previous_gradient.clone_from(&ctx.gradient);
+        ctx.quality_record.clear();
+        ctx.candidate.clone_from(&ctx.sample);
+
+        loop {
+            while speed > SPEED_LIMIT {
+                for (delta, target) in ctx
+                    .gradient
+                    .iter()
+                    .copied()
+                    .map(|v| v * -speed)
+                    .zip(&mut ctx.candidate)
+                {
+                    *target += delta;
+                }
+
+                ctx.error_fn.call(&ctx.candidate, &mut candidate_error);
 
-            ctx.error_fn.call(&ctx.candidate, &mut candidate_error);
+                if candidate_error[0] < error[0] {
+                    // There's a possibility that we're "circling" around the minimum.
+                    // We can detect if this is the case by comparing the current gradient
+                    // to the previous one using dot product.
+                    if dot(&ctx.gradient, &ctx.previous_gradient) < DOT_THRESHOLD {
+                        // println!("Slowing down because angle");
+                        speed /= 1.5;
+                    }
 
-            if candidate_error[0] < error[0] {
-                // There's a possibility that we're "circling" around the minimum.
-                // We can detect if this is the case by comparing the current gradient
-                // to the previous one using dot product.
-                if dot(&ctx.gradient, &ctx.previous_gradient) < DOT_THRESHOLD {
-                    // println!("Slowing down because angle");
-                    speed /= 1.5;
+                    ctx.sample.clone_from_slice(&ctx.candidate);
+                    error = candidate_error;
+                    speed *= 1.1;
+                    break;
                 }
 
-                ctx.sample.clone_from_slice(&ctx.candidate);
-                error = candidate_error;
-                speed *= 1.1;
+                ctx.candidate.clone_from_slice(&ctx.sample);
+                speed /= 1.5;
+            }
+
+            if ctx.quality_record.record((-error[0]).exp()) || speed < SPEED_LIMIT {
+                // println!("Sample: {ctx.sample:?}");
+                // println!("Gradient: {gradient:?}");
+                // println!("Speed: {speed:?}");
                 break;
             }
 
-            ctx.candidate.clone_from_slice(&ctx.sample);
-            speed /= 1.5;
+            ctx.previous_gradient.clone_from_slice(&ctx.gradient);
+            ctx.gradient_fn.call(&ctx.sample, &mut ctx.gradient);
         }
+    }
+}
 
-        if ctx.quality_record.record((-error[0]).exp()) || speed < SPEED_LIMIT {
-            // println!("Sample: {ctx.sample:?}");
-            // println!("Gradient: {gradient:?}");
-            // println!("Speed: {speed:?}");
-            break;
+/// Adam (Adaptive Moment Estimation) optimizer. Keeps exponential moving averages
+/// of the gradient and the squared gradient and scales each step per input.
+struct AdamDescender {
+    /// Hyperparameters
+    params: AdamParams,
+    /// Moving average of the gradient (first moment)
+    m: Vec<f64>,
+    /// Moving average of the squared gradient (second moment)
+    v: Vec<f64>,
+}
+
+impl AdamDescender {
+    /// Creates a new descender for the given number of inputs.
+    fn new(params: AdamParams, input_count: usize) -> Self {
+        Self {
+            params,
+            m: vec![0.0; input_count],
+            v: vec![0.0; input_count],
         }
+    }
+}
 
-        ctx.previous_gradient.clone_from_slice(&ctx.gradient);
+impl GlideOptimizer for AdamDescender {
+    fn descend(&mut self, ctx: &mut GenerateContext) {
+        let AdamParams {
+            learning_rate,
+            beta1,
+            beta2,
+            epsilon,
+        } = self.params;
+        let mut error = [0.0];
+        // Bias correction terms: `β1^t` and `β2^t`.
+        let mut beta1_t = 1.0;
+        let mut beta2_t = 1.0;
         ctx.gradient_fn.call(&ctx.sample, &mut ctx.gradient);
+        ctx.quality_record.clear();
+        // The last sample with a finite error, restored if the descent diverges.
+        ctx.candidate.clone_from(&ctx.sample);
+
+        for _ in 0..ADAM_MAX_ITERATIONS {
+            if ctx.gradient.iter().any(|g| !g.is_finite()) {
+                break;
+            }
+
+            beta1_t *= beta1;
+            beta2_t *= beta2;
+            let mut max_step = 0.0_f64;
+
+            for ((target, g), (m, v)) in ctx
+                .sample
+                .iter_mut()
+                .zip(&ctx.gradient)
+                .zip(self.m.iter_mut().zip(&mut self.v))
+            {
+                *m = beta1 * *m + (1.0 - beta1) * g;
+                *v = beta2 * *v + (1.0 - beta2) * g * g;
+
+                let m_hat = *m / (1.0 - beta1_t);
+                let v_hat = *v / (1.0 - beta2_t);
+
+                let step = learning_rate * m_hat / (v_hat.sqrt() + epsilon);
+                *target -= step;
+                max_step = max_step.max(step.abs());
+            }
+
+            ctx.error_fn.call(&ctx.sample, &mut error);
+
+            if !error[0].is_finite() {
+                break;
+            }
+
+            ctx.candidate.clone_from_slice(&ctx.sample);
+
+            if ctx.quality_record.record((-error[0]).exp()) || max_step < SPEED_LIMIT {
+                break;
+            }
+
+            ctx.gradient_fn.call(&ctx.sample, &mut ctx.gradient);
+        }
+
+        ctx.sample.clone_from_slice(&ctx.candidate);
     }
 }
 
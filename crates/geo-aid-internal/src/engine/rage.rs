@@ -12,7 +12,7 @@ use crate::script::math::Intermediate;
 use geo_aid_math::Func;
 use std::time::Duration;
 
-mod generator;
+pub(crate) mod generator;
 
 /// The Random Adjustment Generation Enginei runtime.
 pub struct Rage {
@@ -34,6 +34,8 @@ pub struct Params {
     pub samples: usize,
     /// How many threads to use
     pub worker_count: usize,
+    /// Seed for all random sampling. If set, generation is deterministic.
+    pub seed: Option<u64>,
 }
 
 impl Rage {
@@ -2,17 +2,21 @@
 //! of the engine.
 
 use std::{
+    cmp::Ordering,
     collections::VecDeque,
     sync::Arc,
     time::{Duration, Instant},
 };
 
+use crate::engine::sample_rng;
 use crate::engine::thread_pool::ThreadPool;
 use crate::geometry::Complex;
 use crate::script::math::EntityKind;
 use geo_aid_math::Func;
+use rand::rngs::StdRng;
+use rand::Rng;
 
-mod magic_box;
+pub(crate) mod magic_box;
 
 /// The generation context
 struct GenerateContext {
@@ -26,6 +30,10 @@ struct GenerateContext {
     error_fn: Func,
     /// The exponent used for a generic mean for computing figure quality.
     mean_exponent: f64,
+    /// Index of the current adjustment attempt.
+    sample_index: u64,
+    /// The source of randomness.
+    rng: StdRng,
 }
 
 /// An attempt at adjusting the state.
@@ -34,6 +42,7 @@ fn adjust_and_check(ctx: &mut GenerateContext) {
         &mut ctx.current_state,
         ctx.adjustment_magnitude,
         &ctx.adjustable_template,
+        &mut ctx.rng,
     );
 
     #[allow(clippy::cast_precision_loss)]
@@ -78,6 +87,10 @@ pub struct Generator {
     delta: f64,
     /// Input count
     input_count: usize,
+    /// Seed for all random sampling.
+    seed: Option<u64>,
+    /// How many adjustment attempts have been launched so far.
+    samples_launched: u64,
 }
 
 /// The kind of an adjustable (entity)
@@ -85,10 +98,16 @@ pub struct Generator {
 pub enum AdjustableTemplate {
     /// A free point
     Point,
-    /// A point described with one real value.
-    Clip1d,
+    /// A point on a line, described with its signed distance from the line's origin.
+    PointOnLine,
+    /// A point on a circle, described with a periodic real value (a full turn is `1`).
+    PointOnCircle,
+    /// A point on a segment, described with one real value in `[0, 1]`.
+    PointOnSegment,
     /// A free real
     Real,
+    /// A free circle
+    Circle,
 }
 
 impl AdjustableTemplate {
@@ -97,7 +116,10 @@ impl AdjustableTemplate {
     /// [`Point`]: AdjustableTemplate::Point
     #[must_use]
     pub fn is_point(self) -> bool {
-        matches!(self, Self::Point | Self::Clip1d)
+        matches!(
+            self,
+            Self::Point | Self::PointOnLine | Self::PointOnCircle | Self::PointOnSegment
+        )
     }
 }
 
@@ -105,10 +127,11 @@ impl From<&EntityKind> for AdjustableTemplate {
     fn from(value: &EntityKind) -> Self {
         match value {
             EntityKind::FreePoint => AdjustableTemplate::Point,
-            EntityKind::PointOnLine { .. } | EntityKind::PointOnCircle { .. } => {
-                AdjustableTemplate::Clip1d
-            }
+            EntityKind::PointOnLine { .. } => AdjustableTemplate::PointOnLine,
+            EntityKind::PointOnCircle { .. } => AdjustableTemplate::PointOnCircle,
+            EntityKind::PointOnSegment { .. } => AdjustableTemplate::PointOnSegment,
             EntityKind::FreeReal | EntityKind::DistanceUnit => AdjustableTemplate::Real,
+            EntityKind::FreeCircle => AdjustableTemplate::Circle,
             EntityKind::Bind(_) => unreachable!(),
         }
     }
@@ -127,11 +150,12 @@ impl Generator {
         adjustables: &Arc<[AdjustableTemplate]>,
     ) -> Self {
         // Randomize the first state
+        let mut rng = sample_rng(params.seed, 0);
         let current_state = State {
             inputs: {
                 let mut v = Vec::new();
                 v.reserve_exact(input_count);
-                v.resize_with(input_count, || rand::random::<f64>() * 10.0 - 5.0);
+                v.resize_with(input_count, || rng.gen::<f64>() * 10.0 - 5.0);
                 v
             },
             qualities: {
@@ -154,6 +178,8 @@ impl Generator {
                     adjustable_template: temp,
                     error_fn,
                     mean_exponent: -params.strictness,
+                    sample_index: 0,
+                    rng: sample_rng(None, 0),
                 }
             },
             std::iter::from_fn(|| Some((Arc::clone(adjustables), current_state.clone()))),
@@ -165,6 +191,8 @@ impl Generator {
             pool,
             delta: 0.0,
             input_count,
+            seed: params.seed,
+            samples_launched: 0,
         }
     }
 
@@ -181,9 +209,19 @@ impl Generator {
         let current_state = self.current_state.clone();
 
         let mut mags = magnitudes.iter().copied();
+        let seed = self.seed;
+        let samples_launched = &mut self.samples_launched;
+        let best_state = &mut self.current_state;
+        let mut best_index = None;
         self.pool.execute(
             |ctx| {
                 if let Some(mag) = mags.next() {
+                    *samples_launched += 1;
+                    ctx.sample_index = *samples_launched;
+                    if seed.is_some() {
+                        ctx.rng = sample_rng(seed, ctx.sample_index);
+                    }
+
                     ctx.adjustment_magnitude = mag;
                     ctx.current_state.clone_from(&current_state);
                     true
@@ -192,8 +230,21 @@ impl Generator {
                 }
             },
             |ctx| {
-                if ctx.current_state.total_quality > self.current_state.total_quality {
-                    self.current_state.clone_from(&ctx.current_state);
+                // Ties are resolved by sample index, so that the result doesn't depend on
+                // the order in which the attempts complete.
+                let is_new_best = match ctx
+                    .current_state
+                    .total_quality
+                    .total_cmp(&best_state.total_quality)
+                {
+                    Ordering::Greater => true,
+                    Ordering::Equal => best_index.is_some_and(|i| ctx.sample_index < i),
+                    Ordering::Less => false,
+                };
+
+                if is_new_best {
+                    best_state.clone_from(&ctx.current_state);
+                    best_index = Some(ctx.sample_index);
                 }
             },
         );
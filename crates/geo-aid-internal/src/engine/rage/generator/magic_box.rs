@@ -2,10 +2,15 @@
 //!
 //! Different entity kinds have different adjustments. Points are moved in a random direction.
 //! Clips have their values moved similarly to points, just with only two possible directions.
-//! Free reals are modified proportionally to their current value.
+//! Clips on lines are moved relative to the line's drawn length, clips on circles wrap around
+//! a full turn and clips on segments are kept within `[0, 1]`.
+//! Free reals are scaled up or down by a factor, so that they keep their sign and don't drift.
+//! Circles have their centers moved like points and their radii scaled like free reals.
 
 use std::f64::consts::PI;
 
+use rand::Rng;
+
 use super::{AdjustableTemplate, Complex, State};
 
 /// Performs an adjustment of all entities in a random direction.
@@ -14,21 +19,24 @@ use super::{AdjustableTemplate, Complex, State};
 /// * `current_state` - current values and errors of all inputs
 /// * `matrix` - adjustment results are written to this thing.
 /// * `adjustment_magnitude` - the magnitude to apply to the adjustment (how much of a jump to allow). Eta in the formula.
+/// * `rng` - the source of randomness.
 pub fn adjust(
     current_state: &mut State,
     adjustment_magnitude: f64,
     template: &[AdjustableTemplate],
+    rng: &mut impl Rng,
 ) {
     let it = template
         .iter()
         .zip(current_state.qualities.iter().copied().map(|x| 1.0 - x));
 
+    let line_length = drawn_length(&current_state.inputs, template);
     let mut index = 0;
 
     for (template, error) in it {
         match template {
             AdjustableTemplate::Point => {
-                let direction = 2.0 * rand::random::<f64>() * PI;
+                let direction = 2.0 * rng.gen::<f64>() * PI;
 
                 let unit = Complex::new(direction.cos(), direction.sin());
                 let offset = unit * adjustment_magnitude * error;
@@ -38,29 +46,97 @@ pub fn adjust(
                 index += 2;
             }
             AdjustableTemplate::Real => {
-                let direction = if rand::random::<u8>() & 1 == 0 {
-                    1.0
-                } else {
-                    -1.0
-                };
+                let direction = if rng.gen::<u8>() & 1 == 0 { 1.0 } else { -1.0 };
 
                 // Adjust by a RELATIVE value based on quality and randomly chosen direction (+/-)
+                current_state.inputs[index] *= scale(direction, adjustment_magnitude * error);
+                index += 1;
+            }
+            AdjustableTemplate::PointOnLine => {
+                let direction = if rng.gen::<u8>() & 1 == 0 { 1.0 } else { -1.0 };
+
+                // Adjust by a value RELATIVE to the line's drawn length, so that the point
+                // moves similarly along short and long lines.
                 let val = current_state.inputs[index];
-                current_state.inputs[index] += direction * adjustment_magnitude * val * error;
+                current_state.inputs[index] =
+                    val + direction * adjustment_magnitude * line_length * error;
                 index += 1;
             }
-            AdjustableTemplate::Clip1d => {
-                let direction = if rand::random::<u8>() & 1 == 0 {
-                    1.0
-                } else {
-                    -1.0
-                };
+            AdjustableTemplate::PointOnCircle => {
+                let direction = if rng.gen::<u8>() & 1 == 0 { 1.0 } else { -1.0 };
+
+                // Adjust by an ABSOLUTE value, wrapped to a single turn so that it never drifts.
+                let val = current_state.inputs[index];
+                current_state.inputs[index] =
+                    (val + direction * adjustment_magnitude * error).rem_euclid(1.0);
+                index += 1;
+            }
+            AdjustableTemplate::PointOnSegment => {
+                let direction = if rng.gen::<u8>() & 1 == 0 { 1.0 } else { -1.0 };
 
-                // Adjust by an ABSOLUTE value based on quality and randomly chosen direction (+/-)
+                // Same as above, but the value is kept within `[0, 1]` by reflecting it off
+                // the ends. The point is mapped symmetrically around both, so it doesn't move.
                 let val = current_state.inputs[index];
-                current_state.inputs[index] = val + direction * adjustment_magnitude * error;
+                let val = (val + direction * adjustment_magnitude * error).rem_euclid(2.0);
+                current_state.inputs[index] = if val > 1.0 { 2.0 - val } else { val };
                 index += 1;
             }
+            AdjustableTemplate::Circle => {
+                let direction = 2.0 * rng.gen::<f64>() * PI;
+
+                let unit = Complex::new(direction.cos(), direction.sin());
+                let offset = unit * adjustment_magnitude * error;
+
+                current_state.inputs[index] += offset.real;
+                current_state.inputs[index + 1] += offset.imaginary;
+
+                let direction = if rng.gen::<u8>() & 1 == 0 { 1.0 } else { -1.0 };
+
+                current_state.inputs[index + 2] *= scale(direction, adjustment_magnitude * error);
+                index += 3;
+            }
         }
     }
 }
+
+/// The factor a relative adjustment scales a value by. Growing by `1 + step` and shrinking by
+/// the inverse makes both directions cancel out, so the value doesn't drift towards zero.
+fn scale(direction: f64, step: f64) -> f64 {
+    (1.0 + step).powf(direction)
+}
+
+/// Approximates the length lines are drawn with: the size of the figure spanned by the free
+/// points and circle centers. Lines are drawn across the whole figure, so it's a good estimate.
+/// Falls back to `1.0` if the figure has no size.
+fn drawn_length(inputs: &[f64], template: &[AdjustableTemplate]) -> f64 {
+    let mut min = Complex::new(f64::INFINITY, f64::INFINITY);
+    let mut max = Complex::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+    let mut index = 0;
+
+    for template in template {
+        match template {
+            AdjustableTemplate::Point | AdjustableTemplate::Circle => {
+                let (x, y) = (inputs[index], inputs[index + 1]);
+                min = Complex::new(min.real.min(x), min.imaginary.min(y));
+                max = Complex::new(max.real.max(x), max.imaginary.max(y));
+                index += if *template == AdjustableTemplate::Point {
+                    2
+                } else {
+                    3
+                };
+            }
+            AdjustableTemplate::PointOnLine
+            | AdjustableTemplate::PointOnCircle
+            | AdjustableTemplate::PointOnSegment
+            | AdjustableTemplate::Real => index += 1,
+        }
+    }
+
+    let size = max - min;
+    let length = size.real.max(size.imaginary);
+    if length.is_finite() && length > 0.0 {
+        length
+    } else {
+        1.0
+    }
+}
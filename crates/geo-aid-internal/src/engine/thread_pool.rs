@@ -100,6 +100,11 @@ impl<T: Send + 'static> ThreadPool<T> {
                 .unwrap();
         }
 
+        // If no task was started, there's no result to wait for.
+        if tasks_sent == 0 {
+            return;
+        }
+
         loop {
             let mut worker = self.result_receiver.recv().unwrap();
 
@@ -1,6 +1,1190 @@
 #![warn(clippy::pedantic)]
 
+//! Internals of Geo-AID.
+//!
+//! The simplest way of generating a figure is [`generate`], which runs the whole
+//! pipeline in memory and returns a serializable [`Figure`]:
+//!
+//! ```
+//! use geo_aid_internal::{generate, GenerationParams};
+//!
+//! let script = "
+//!     let A, B, C = Point();
+//!     AB = AC;
+//!     BC = 3;
+//! ";
+//!
+//! let params = GenerationParams {
+//!     samples: 16,
+//!     ..GenerationParams::default()
+//! };
+//!
+//! let figure = generate(script, params).unwrap();
+//! let json = serde_json::to_string(&figure).unwrap();
+//! assert!(json.contains("\"items\""));
+//! ```
+
+use std::sync::Arc;
+
+use engine::{
+    annealing::{SaParams, SimulatedAnnealing},
+    glide::{self, Glide},
+    rage::{self, GenParams, Rage},
+    Engine,
+};
+use geo_aid_figure::Figure;
+use script::math::{self, EngineKind};
+
+pub mod builder;
 pub mod engine;
 pub mod projector;
 pub use geo_aid_script as script;
 pub use geo_aid_script::geometry;
+
+/// Parameters of [`generate`].
+#[derive(Debug, Clone, Copy)]
+pub struct GenerationParams {
+    /// How strictly the rules are applied. Must not be zero.
+    /// With Glide, the `glide.strictness` flag set in the script takes precedence.
+    pub strictness: f64,
+    /// How many samples to try out in search for a global minimum.
+    /// This bounds the time the generation takes.
+    pub samples: usize,
+    /// How many threads to use.
+    pub worker_count: usize,
+    /// How many last quality deltas to use in mean calculation.
+    pub mean_count: usize,
+    /// If the arithmetic mean of the last `mean_count` deltas ever goes below
+    /// this number, descending a sample stops.
+    pub max_mean_delta: f64,
+    /// The optimizer used for descending.
+    pub optimizer: glide::OptimizerKind,
+    /// Hyperparameters used if `optimizer` is [`glide::OptimizerKind::Adam`].
+    pub adam: glide::AdamParams,
+    /// Width of the canvas. The size set in the script takes precedence.
+    pub width: f64,
+    /// Height of the canvas. The size set in the script takes precedence.
+    /// Defaults to `width` if not given.
+    pub height: Option<f64>,
+    /// Seed for all random sampling. With the same seed, script and sample count,
+    /// the generated figure is always the same. The seed set in the script takes precedence.
+    pub seed: Option<u64>,
+    /// If the figure's total quality is below this value, generation is retried with new samples.
+    pub min_quality: Option<f64>,
+    /// How many times generation can be retried at most.
+    pub max_retries: usize,
+    /// The engine to generate the figure with. Overrides the `engine` flag set in the script.
+    /// If neither is given, Glide is used.
+    pub engine: Option<EngineKind>,
+    /// The maximal adjustment of figure inputs. Only used by RAGE and simulated annealing.
+    /// The `rage.max_adjustment` and `sa.max_adjustment` flags set in the script take precedence.
+    pub max_adjustment: f64,
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self {
+            strictness: 2.0,
+            samples: 512,
+            worker_count: 32,
+            mean_count: 128,
+            max_mean_delta: 0.0001,
+            optimizer: glide::OptimizerKind::default(),
+            adam: glide::AdamParams::default(),
+            width: 1.0,
+            height: None,
+            seed: None,
+            min_quality: None,
+            max_retries: 0,
+            engine: None,
+            max_adjustment: 0.5,
+        }
+    }
+}
+
+/// Compiles the script, generates the figure with the selected engine and projects it onto the canvas.
+/// Nothing is written to the filesystem.
+///
+/// # Errors
+/// Returns all errors found while compiling the script.
+pub fn generate(script: &str, params: GenerationParams) -> Result<Figure, Vec<script::Error>> {
+    let intermediate = math::load_script(script)?;
+    let seed = intermediate.flags.seed.or(params.seed);
+
+    let generated = match params
+        .engine
+        .or(intermediate.flags.engine)
+        .unwrap_or(EngineKind::Glide)
+    {
+        EngineKind::Glide => {
+            let mut glide = Glide::new(
+                glide::Params {
+                    strictness: intermediate
+                        .flags
+                        .glide
+                        .strictness
+                        .unwrap_or(params.strictness),
+                    samples: params.samples,
+                    worker_count: params.worker_count,
+                    mean_count: params.mean_count,
+                    max_mean_delta: params.max_mean_delta,
+                    optimizer: params.optimizer,
+                    adam: params.adam,
+                    seed,
+                    min_quality: params.min_quality,
+                    max_retries: params.max_retries,
+                    quality_threshold: intermediate.flags.quality_threshold,
+                },
+                &intermediate,
+            );
+            glide.generate(|_| ());
+            glide.get_figure()
+        }
+        EngineKind::Rage => {
+            let mut rage = Rage::new(
+                rage::Params {
+                    strictness: params.strictness,
+                    samples: params.samples,
+                    worker_count: params.worker_count,
+                    seed,
+                },
+                &intermediate,
+            );
+            rage.generate_mean_delta(GenParams {
+                max_adjustment: intermediate
+                    .flags
+                    .rage
+                    .max_adjustment
+                    .unwrap_or(params.max_adjustment),
+                mean_count: params.mean_count,
+                delta_max_mean: params.max_mean_delta,
+                progress_update: Box::new(|_| ()),
+            });
+            rage.get_figure()
+        }
+        EngineKind::Sa => {
+            let mut sa = SimulatedAnnealing::new(
+                SaParams {
+                    strictness: params.strictness,
+                    max_adjustment: intermediate
+                        .flags
+                        .sa
+                        .max_adjustment
+                        .unwrap_or(params.max_adjustment),
+                    seed,
+                    ..SaParams::default()
+                },
+                &intermediate,
+            );
+            sa.generate(|| ());
+            sa.get_figure()
+        }
+    };
+
+    let flags = Arc::new(intermediate.flags);
+    let width = flags.figure.width.unwrap_or(params.width);
+    let height = flags.figure.height.or(params.height).unwrap_or(width);
+
+    Ok(projector::project(generated, &flags, (width, height)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        engine::{
+            annealing::{CoolingSchedule, SaParams, SimulatedAnnealing},
+            compiler,
+            glide::{self, Glide},
+            rage::generator::{magic_box, AdjustableTemplate, State},
+            sample_rng, Engine,
+        },
+        generate,
+        geometry::Complex,
+        math,
+        projector::figure::Item,
+        GenerationParams,
+    };
+
+    #[test]
+    fn seeded_generation_is_deterministic() {
+        let script = "
+            let A, B, C = Point();
+            AB = AC;
+            BC = 3;
+            angle(ABC) = degrees(50);
+        ";
+
+        let params = GenerationParams {
+            samples: 64,
+            worker_count: 8,
+            seed: Some(42),
+            ..GenerationParams::default()
+        };
+
+        let first = serde_json::to_string(&generate(script, params).unwrap()).unwrap();
+        let second = serde_json::to_string(&generate(script, params).unwrap()).unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn quality_report_lists_every_rule() {
+        let script = "
+            let A, B, C = Point();
+            AB = 1;
+            AB = 3;
+            AC = BC;
+        ";
+        let intermediate = math::load_script(script).unwrap();
+
+        let mut glide = Glide::new(
+            glide::Params {
+                samples: 16,
+                worker_count: 4,
+                seed: Some(1),
+                ..glide::Params::default()
+            },
+            &intermediate,
+        );
+        glide.generate(|_| ());
+
+        let report = glide.get_quality_report();
+        assert_eq!(report.rules.len(), intermediate.adjusted.rules.len());
+        assert!(report
+            .rules
+            .iter()
+            .enumerate()
+            .all(|(i, rule)| rule.rule_index == i && rule.error >= 0.0));
+
+        // The contradicting rules can't both be satisfied.
+        let worst = report.worst().unwrap();
+        assert!(worst.error > 0.1);
+    }
+
+    #[test]
+    fn glide_history_is_bounded_and_ends_with_the_result() {
+        let script = "
+            let A, B, C = Point();
+            AB = AC;
+            BC = 3;
+        ";
+        let intermediate = math::load_script(script).unwrap();
+
+        let mut glide = Glide::new(
+            glide::Params {
+                samples: 64,
+                worker_count: 4,
+                seed: Some(5),
+                ..glide::Params::default()
+            },
+            &intermediate,
+        );
+
+        let (_, history) = glide.generate_with_history(4, |_| ());
+        assert!(!history.is_empty() && history.len() <= 4);
+
+        let last = format!("{:?}", history.last().unwrap().variables);
+        assert_eq!(last, format!("{:?}", glide.get_figure().variables));
+    }
+
+    #[test]
+    fn glide_retries_until_minimum_quality() {
+        // A figure hard enough for a single sample to usually end up in a local minimum.
+        let script = "
+            let A, B, C, D = Point();
+            ABCD lies_on Circle();
+
+            let X = intersection(bisector(BCD), bisector(CDA)) lies_on Segment(AB);
+            let omega = Circle(X, dst(X, DC));
+        ";
+        let intermediate = math::load_script(script).unwrap();
+
+        let run = |min_quality, max_retries| {
+            let mut glide = Glide::new(
+                glide::Params {
+                    samples: 1,
+                    worker_count: 1,
+                    seed: Some(3),
+                    min_quality,
+                    max_retries,
+                    ..glide::Params::default()
+                },
+                &intermediate,
+            );
+
+            let mut samples = 0;
+            glide.generate(|_| samples += 1);
+
+            (glide.get_total_quality(), samples)
+        };
+
+        let (single_quality, single_samples) = run(None, 0);
+        assert_eq!(single_samples, 1);
+        assert!(single_quality < 0.99);
+
+        let (quality, samples) = run(Some(0.99), 64);
+        assert!(quality >= 0.99);
+        assert!(samples > 1 && samples <= 65);
+    }
+
+    #[test]
+    fn glide_stops_at_quality_threshold() {
+        let script = "
+            @quality_threshold: 0.99;
+            let A, B, C = Point();
+            AB = BC;
+            angle(ABC) = degrees(40);
+        ";
+        let intermediate = math::load_script(script).unwrap();
+        assert_eq!(intermediate.flags.quality_threshold, Some(0.99));
+
+        let params = glide::Params {
+            samples: 64,
+            worker_count: 1,
+            seed: Some(3),
+            quality_threshold: intermediate.flags.quality_threshold,
+            ..glide::Params::default()
+        };
+        let mut glide = Glide::new(params, &intermediate);
+
+        let mut samples = 0;
+        glide.generate(|_| samples += 1);
+
+        assert!(glide.get_total_quality() >= 0.99);
+        assert!((1..64).contains(&samples));
+
+        // If the initial state is good enough already, no sample is launched at all.
+        let mut glide = Glide::new(
+            glide::Params {
+                quality_threshold: Some(0.0),
+                ..params
+            },
+            &intermediate,
+        );
+
+        let mut samples = 0;
+        glide.generate(|_| samples += 1);
+        assert_eq!(samples, 0);
+    }
+
+    #[test]
+    fn adam_converges() {
+        let script = "
+            let A, B, C = Point();
+            AB = BC;
+            angle(ABC) = degrees(40);
+        ";
+        let intermediate = math::load_script(script).unwrap();
+
+        let mut glide = Glide::new(
+            glide::Params {
+                samples: 8,
+                worker_count: 1,
+                optimizer: glide::OptimizerKind::Adam,
+                seed: Some(5),
+                ..glide::Params::default()
+            },
+            &intermediate,
+        );
+        glide.generate(|sample| assert!(sample.quality.is_finite()));
+
+        let quality = glide.get_total_quality();
+        assert!(quality > 0.99, "Adam only reached quality {quality}");
+    }
+
+    #[test]
+    fn equal_distances_dont_shrink_the_figure() {
+        // A square whose diagonal is also supposed to equal its side. The rules contradict
+        // each other, and with an absolute error the figure would shrink to a single point
+        // to make the contradiction smaller. Point inequalities are disabled, as they would
+        // keep the points apart on their own.
+        let script = "
+            @point_inequalities: false;
+            let A, B, C, D = Point();
+            AB = BC = CD = DA;
+            AC = BD;
+            AC = AB;
+        ";
+        let intermediate = math::load_script(script).unwrap();
+
+        let mut glide = Glide::new(
+            glide::Params {
+                samples: 16,
+                worker_count: 4,
+                seed: Some(7),
+                ..glide::Params::default()
+            },
+            &intermediate,
+        );
+        glide.generate(|_| ());
+
+        let figure = glide.get_figure();
+        let points: Vec<_> = figure
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                super::script::figure::Item::Point(point) => {
+                    figure.variables[point.id.0].meta.as_complex()
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(points.len(), 4);
+
+        // With an absolute error, the side ends up around 1e-15.
+        let side = (points[0] - points[1]).magnitude();
+        assert!(side > 0.1, "the square collapsed to side {side}");
+    }
+
+    #[test]
+    fn engine_flags_are_validated() {
+        let flags = math::load_script("@engine: rage; @rage.max_adjustment: 0.2; let A = Point();")
+            .unwrap()
+            .flags;
+        assert_eq!(flags.engine, Some(math::EngineKind::Rage));
+        assert_eq!(flags.rage.max_adjustment, Some(0.2));
+
+        let errors = math::load_script("@engine: genetic; let A = Point();").unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [super::script::Error::EnumInvalidValue { received_value, .. }] if received_value == "genetic"
+        ));
+
+        let errors =
+            math::load_script("@engine: glide; @rage.max_adjustment: 0.2; let A = Point();")
+                .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [super::script::Error::FlagForOtherEngine { flag_name, .. }] if flag_name == "rage.max_adjustment"
+        ));
+
+        let errors =
+            math::load_script("@engine: sa; @glide.strictness: 3; let A = Point();").unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [super::script::Error::FlagForOtherEngine { flag_name, .. }] if flag_name == "glide.strictness"
+        ));
+    }
+
+    #[test]
+    fn scripts_can_select_annealing() {
+        let script = "@engine: sa; @sa.max_adjustment: 0.25; let A, B = Point();";
+        let flags = math::load_script(script).unwrap().flags;
+        assert_eq!(flags.engine, Some(math::EngineKind::Sa));
+        assert_eq!(flags.sa.max_adjustment, Some(0.25));
+
+        let params = GenerationParams {
+            seed: Some(4),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        assert_eq!(figure.items.len(), 2);
+    }
+
+    #[test]
+    fn annealing_params_are_validated() {
+        let exponential = SaParams::default();
+        let linear = SaParams {
+            schedule: CoolingSchedule::Linear,
+            cooling_rate: 0.01,
+            ..exponential
+        };
+        assert_eq!(exponential.validate(), Ok(()));
+        assert_eq!(linear.validate(), Ok(()));
+
+        for cooling_rate in [1.0, 1.5, 0.0, f64::NAN] {
+            assert!(SaParams {
+                cooling_rate,
+                ..exponential
+            }
+            .validate()
+            .is_err());
+        }
+
+        for cooling_rate in [0.0, -0.1, f64::NAN] {
+            assert!(SaParams {
+                cooling_rate,
+                ..linear
+            }
+            .validate()
+            .is_err());
+        }
+
+        assert!(SaParams {
+            min_temperature: 0.0,
+            ..exponential
+        }
+        .validate()
+        .is_err());
+        assert!(SaParams {
+            initial_temperature: -1.0,
+            ..exponential
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn linear_annealing_finishes() {
+        let intermediate = math::load_script("let A, B, C = Point(); AB = BC;").unwrap();
+        let params = SaParams {
+            schedule: CoolingSchedule::Linear,
+            cooling_rate: 0.1,
+            steps_per_temperature: 8,
+            seed: Some(2),
+            ..SaParams::default()
+        };
+        let mut sa = SimulatedAnnealing::new(params, &intermediate);
+
+        let mut cooling_steps = 0;
+        sa.generate(|| cooling_steps += 1);
+
+        // The temperature goes from 1 down to 1e-4 in steps of 0.1.
+        assert_eq!(cooling_steps, 10);
+    }
+
+    #[should_panic(expected = "invalid simulated annealing parameters")]
+    #[test]
+    fn annealing_rejects_invalid_params() {
+        let intermediate = math::load_script("let A = Point();").unwrap();
+        let _ = SimulatedAnnealing::new(
+            SaParams {
+                cooling_rate: 1.0,
+                ..SaParams::default()
+            },
+            &intermediate,
+        );
+    }
+
+    #[test]
+    fn trigonometry_requires_angles() {
+        assert!(math::load_script("let A, B, C = Point(); sin(angle(ABC)) = 0.5;").is_ok());
+        assert!(math::load_script("let A, B, C = Point(); AB / BC = tan(radians(0.5));").is_ok());
+
+        let errors = math::load_script("let A, B, C = Point(); AB / BC = sin(0.5);").unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [super::script::Error::OverloadNotFound { function_name, .. }] if function_name == "sin"
+        ));
+    }
+
+    #[test]
+    fn point_labels_avoid_lines_unless_forced() {
+        let script = "
+            let A, B = Point();
+            let C [label_position = above] = Point();
+            let k = line(A, B);
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            width: 100.0,
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let line = figure
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Line(line) => Some(line),
+                _ => None,
+            })
+            .unwrap();
+        let direction = Complex::from(line.points.1) - Complex::from(line.points.0);
+
+        let points = figure.items.iter().filter_map(|item| match item {
+            Item::Point(point) => Some(point),
+            _ => None,
+        });
+
+        for (i, point) in points.enumerate() {
+            let offset = Complex::from(point.label.as_ref().unwrap().position)
+                - Complex::from(point.position);
+
+            if i == 2 {
+                // Forced above, which is towards negative y.
+                assert!(offset.real.abs() < 1e-9 && offset.imaginary < 0.0);
+            } else {
+                // The label shouldn't lie on the line.
+                let sin = (offset / direction).imaginary.abs() * direction.magnitude()
+                    / offset.magnitude();
+                assert!(sin > 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn label_offset_moves_label() {
+        let script = "let A [label_position = above; label_offset = (4, -1.5)] = Point();";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            width: 100.0,
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let point = figure
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Point(point) => Some(point),
+                _ => None,
+            })
+            .unwrap();
+
+        let offset =
+            Complex::from(point.label.as_ref().unwrap().position) - Complex::from(point.position);
+
+        // Above and then moved by the offset.
+        assert!((offset.real - 4.0).abs() < 1e-9);
+        assert!(offset.imaginary < -1.5);
+
+        assert!(matches!(
+            &math::load_script("let A [label_offset = 4] = Point();").unwrap_err()[..],
+            [super::script::Error::PairExpected { .. }]
+        ));
+    }
+
+    #[test]
+    fn arc_midpoint_is_opposite_the_given_point() {
+        let script = "
+            let A, B, C = Point();
+            let M = arc_mid(A, B, circumcircle(A, B, C), C);
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(5),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let position = |name: &str| {
+            figure
+                .items
+                .iter()
+                .find_map(|item| match item {
+                    Item::Point(point)
+                        if point.label.as_ref().unwrap().content.to_string() == name =>
+                    {
+                        Some(Complex::from(point.position))
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let [a, b, c, m] = ["A", "B", "C", "M"].map(position);
+
+        assert!(((m - a).magnitude() - (m - b).magnitude()).abs() < 1e-6);
+
+        // Opposite points of a cyclic quadrilateral see AB at supplementary angles.
+        let ratio = ((a - c) / (b - c)) / ((a - m) / (b - m));
+        assert!(ratio.imaginary.abs() < 1e-6 * ratio.magnitude());
+        assert!(ratio.real < 0.0);
+    }
+
+    #[test]
+    fn foot_segments_follow_display_properties() {
+        let script = "
+            let A, B, C = Point();
+            let D = foot(A, BC) [display_segment = true; color = red; opacity = 0.5; ticks = 2];
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let segment = figure
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Segment(segment) => Some(segment),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(segment.color, geo_aid_figure::Color::rgb(255, 0, 0));
+        assert_eq!(segment.opacity, Some(0.5));
+        assert_eq!(segment.ticks, 2);
+        assert!(matches!(segment.style, geo_aid_figure::Style::Dashed));
+
+        // The foot itself keeps the color and opacity.
+        assert!(figure.items.iter().any(|item| matches!(
+            item,
+            Item::Point(point)
+                if point.color == geo_aid_figure::Color::rgb(255, 0, 0) && point.opacity == Some(0.5)
+        )));
+    }
+
+    #[test]
+    fn segments_carry_tick_marks() {
+        let script = "
+            let A, B, C = Point();
+            AB [ticks = 2] = AC;
+            ?segment(B, C) [ticks = 3];
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let mut ticks: Vec<_> = figure
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Segment(segment) if segment.ticks > 0 => Some(segment.ticks),
+                _ => None,
+            })
+            .collect();
+        ticks.sort_unstable();
+
+        assert_eq!(ticks, [2, 3]);
+
+        assert!(matches!(
+            &math::load_script("let A, B = Point(); AB [ticks = 4] = 1;").unwrap_err()[..],
+            [super::script::Error::InvalidTickCount { .. }]
+        ));
+    }
+
+    #[test]
+    fn bisector_ticks_mark_both_halves() {
+        let script = "
+            let A, B = Point();
+            let k [display_ticks = true; tick_style = bold] = bisector(AB);
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let halves: Vec<_> = figure
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Segment(segment) => Some(segment),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(halves.len(), 2);
+        assert!(halves
+            .iter()
+            .all(|half| half.ticks == 1 && matches!(half.style, geo_aid_figure::Style::Bold)));
+    }
+
+    #[test]
+    fn polygons_are_only_filled_when_asked_to() {
+        let script = "
+            let A, B, C, D = Point();
+            let abc [fill = blue] = polygon(ABC);
+            let abd = polygon(ABD);
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let fills: Vec<_> = figure
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Polygon(polygon) => Some(polygon.fill),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(fills, [Some(geo_aid_figure::Color::rgb(0, 0, 255)), None]);
+    }
+
+    #[test]
+    fn items_are_drawn_by_layer() {
+        let script = "
+            let A, B, C = Point();
+            let omega [layer = -1] = circumcircle(A, B, C);
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+
+        // Items follow the script.
+        assert!(matches!(
+            &figure.items[..],
+            [
+                Item::Point(_),
+                Item::Point(_),
+                Item::Point(_),
+                Item::Circle(_)
+            ]
+        ));
+        assert_eq!(figure.items[0].layer(), 0);
+        assert_eq!(figure.items[3].layer(), -1);
+
+        // The circle is drawn beneath the points.
+        let order = figure.items_in_drawing_order();
+        assert!(matches!(order[0], Item::Circle(_)));
+        assert!(order[1..].iter().all(|item| matches!(item, Item::Point(_))));
+
+        assert!(matches!(
+            &math::load_script("let A [layer = 0.5] = Point();").unwrap_err()[..],
+            [super::script::Error::InvalidLayer { .. }]
+        ));
+    }
+
+    #[test]
+    fn items_carry_definition_spans() {
+        let script = "let A = Point();\nlet B = Point();\nAB = 1;\n";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(3),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let a = figure
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Point(point) if point.label.as_ref().unwrap().content.to_string() == "A" => {
+                    Some(point)
+                }
+                _ => None,
+            })
+            .unwrap();
+
+        // `Point()` in the first line.
+        let span = a.span.unwrap();
+        assert_eq!((span.start.line, span.start.column), (1, 9));
+        assert_eq!((span.end.line, span.end.column), (1, 16));
+        assert_eq!(figure.expressions[a.id.0].span, Some(span));
+
+        let json = serde_json::to_string(&figure).unwrap();
+        assert!(json.contains(r#""span":{"start":{"line":1,"column":9}"#));
+    }
+
+    #[test]
+    fn points_on_circles_stay_on_them() {
+        let script = "
+            let O = Point();
+            let omega = Circle(O, 3);
+            let P = Point();
+            P lies_on omega;
+        ";
+
+        let intermediate = math::load_script(script).unwrap();
+        let compiled = compiler::compile(&intermediate);
+        let template: Vec<_> = intermediate
+            .adjusted
+            .entities
+            .iter()
+            .map(AdjustableTemplate::from)
+            .collect();
+        assert!(template.contains(&AdjustableTemplate::PointOnCircle));
+
+        let mut state = State {
+            inputs: vec![0.5; compiled.input_count],
+            qualities: vec![0.0; template.len()],
+            total_quality: 0.0,
+        };
+        let mut rng = sample_rng(Some(11), 0);
+
+        for _ in 0..1000 {
+            magic_box::adjust(&mut state, 0.7, &template, &mut rng);
+
+            let figure = (compiled.figure_fn)(&state.inputs);
+            let circle = figure
+                .items
+                .iter()
+                .find_map(|item| match item {
+                    super::script::figure::Item::Circle(circle) => {
+                        figure.variables[circle.id.0].meta.as_circle()
+                    }
+                    _ => None,
+                })
+                .unwrap();
+            let on_circle = figure
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    super::script::figure::Item::Point(point) => {
+                        figure.variables[point.id.0].meta.as_complex()
+                    }
+                    _ => None,
+                })
+                .filter(|point| {
+                    (circle.radius - (*point - circle.center).magnitude()).abs()
+                        < 1e-9 * circle.radius
+                })
+                .count();
+            assert_eq!(on_circle, 1);
+        }
+
+        for (value, template) in state.inputs.iter().zip(&template) {
+            if *template == AdjustableTemplate::PointOnCircle {
+                assert!((0.0..1.0).contains(value));
+            }
+        }
+    }
+
+    #[test]
+    fn let_destructures_point_collections() {
+        let script = "
+            let A, B, O = Point();
+            let omega = Circle(O, 3);
+            let (X, Y_1 [label = Z]) = intersection(Line(A, B), omega);
+            dst(X, Y_1) = 4;
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let labels: Vec<_> = figure
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Point(point) => point.label.as_ref().map(|label| label.content.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert!(labels.iter().any(|label| label == "X"));
+        assert!(labels.iter().any(|label| label == "Z"));
+
+        // The lengths must match.
+        assert!(matches!(
+            &math::load_script("let (X, Y, Z) = intersection(Line(Point(), Point()), Circle());")
+                .unwrap_err()[..],
+            [super::script::Error::CannotUnpack { length: 3, .. }]
+        ));
+    }
+
+    #[test]
+    fn perpendicular_bisectors_meet_at_the_circumcenter() {
+        let script = "
+            let A, B, C = Point();
+            let P = intersection(perpendicular_bisector(A, B), perp_bisector(BC));
+        ";
+
+        let params = GenerationParams {
+            samples: 16,
+            worker_count: 4,
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        let figure = generate(script, params).unwrap();
+        let position = |name: &str| {
+            figure
+                .items
+                .iter()
+                .find_map(|item| match item {
+                    Item::Point(point)
+                        if point.label.as_ref().unwrap().content.to_string() == name =>
+                    {
+                        Some(Complex::from(point.position))
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let [a, b, c, p] = ["A", "B", "C", "P"].map(position);
+
+        assert!(((p - a).magnitude() - (p - b).magnitude()).abs() < 1e-6);
+        assert!(((p - b).magnitude() - (p - c).magnitude()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn free_circles_have_positive_radii() {
+        let intermediate = math::load_script("let c = Circle();").unwrap();
+        let compiled = compiler::compile(&intermediate);
+
+        let figure = (compiled.figure_fn)(&vec![-2.0; compiled.input_count]);
+        let radius = figure
+            .entities
+            .iter()
+            .find_map(|entity| entity.meta.as_circle())
+            .unwrap()
+            .radius;
+        assert!((radius - 2.0).abs() < 1e-9, "radius is {radius}");
+    }
+
+    #[test]
+    fn figure_flags_override_the_canvas_size() {
+        let params = GenerationParams {
+            samples: 8,
+            worker_count: 4,
+            seed: Some(1),
+            width: 50.0,
+            height: Some(40.0),
+            ..GenerationParams::default()
+        };
+
+        let script = "
+            @figure.width: 300
+            @figure.height: 200
+            let A, B = Point();
+        ";
+        let figure = generate(script, params).unwrap();
+        assert_eq!((figure.width, figure.height), (300.0, 200.0));
+
+        // Only the width is set, so the height follows it.
+        let script = "
+            @figure.width: 300
+            let A, B = Point();
+        ";
+        let params = GenerationParams {
+            height: None,
+            ..params
+        };
+        let figure = generate(script, params).unwrap();
+        assert_eq!((figure.width, figure.height), (300.0, 300.0));
+    }
+
+    #[test]
+    fn generation_report_counts_anomalies() {
+        let script = "
+            let A, B, C = Point();
+            AB = AC;
+            let X = intersection(AB, parallel_through(C, AB));
+            AX = 1;
+        ";
+        let intermediate = math::load_script(script).unwrap();
+
+        let mut glide = Glide::new(
+            glide::Params {
+                samples: 16,
+                worker_count: 4,
+                seed: Some(1),
+                ..glide::Params::default()
+            },
+            &intermediate,
+        );
+        glide.generate(|_| ());
+
+        let report = glide.get_generation_report();
+        assert_eq!(report.samples, 16);
+        assert_eq!(report.rules.len(), intermediate.adjusted.rules.len());
+        assert!(report.anomaly_count > 0);
+
+        // Parallel lines never intersect, so the rule using X fails in every sample.
+        let worst = &report.anomalies[0];
+        assert_eq!(worst.count, 16);
+        assert!(report
+            .anomalies
+            .iter()
+            .all(|anomaly| anomaly.count <= worst.count));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["samples"], 16);
+    }
+
+    #[test]
+    fn heavier_rules_dominate_contradictions() {
+        // The order of the rules mustn't matter. The first rule is a candidate for being
+        // turned into a point on a circle, which makes it exact.
+        for rules in [
+            "AB = 1 [weight = 10];\nAB = 3;",
+            "AB = 3;\nAB = 1 [weight = 10];",
+        ] {
+            let script = format!("let A, B = Point();\n{rules}");
+            let intermediate = math::load_script(&script).unwrap();
+
+            let mut glide = Glide::new(
+                glide::Params {
+                    samples: 16,
+                    worker_count: 4,
+                    seed: Some(1),
+                    ..glide::Params::default()
+                },
+                &intermediate,
+            );
+            glide.generate(|_| ());
+
+            // The lighter rule gives way: its unweighted error is the largest one. The heavier
+            // one is either made exact (and not listed) or almost satisfied.
+            let report = glide.get_quality_report();
+            let light = report.worst().unwrap();
+            assert!((light.weight - 1.0).abs() < 1e-9);
+            assert!(light.error > 1.0);
+            assert!(report
+                .rules
+                .iter()
+                .filter(|rule| (rule.weight - 10.0).abs() < 1e-9)
+                .all(|heavy| heavy.error / heavy.weight < light.error / light.weight));
+        }
+    }
+
+    #[test]
+    fn inverted_rules_fail_when_evaluation_fails() {
+        // AC and the line through B parallel to it never intersect.
+        let script = "
+            let A, B, C, E = Point();
+            E != intersection(AC, parallel_through(B, AC));
+        ";
+        let intermediate = math::load_script(script).unwrap();
+
+        let mut glide = Glide::new(
+            glide::Params {
+                samples: 8,
+                worker_count: 4,
+                seed: Some(1),
+                ..glide::Params::default()
+            },
+            &intermediate,
+        );
+        glide.generate(|_| ());
+
+        let report = glide.get_quality_report();
+        assert!(report.rules.iter().all(|rule| rule.error.is_finite()));
+
+        // The rule isn't reported as satisfied, but gets the highest possible error.
+        let worst = report.worst().unwrap();
+        assert!(worst.rule_description.starts_with("not"));
+        assert!(worst.error >= 99.0);
+    }
+
+    #[test]
+    fn degenerate_angles_have_finite_errors() {
+        let script = "
+            @point_inequalities: false;
+            let A, B, C = Point();
+            angle(ABC) = degrees(60);
+            clamp(AB, 1, 3) = 2;
+        ";
+        let intermediate = math::load_script(script).unwrap();
+        let compiled = compiler::compile(&intermediate);
+        let error_fn = compiled
+            .context
+            .exec(|ctx| ctx.compute(compiled.errors.iter().map(|error| error.expr)));
+
+        // All points in the same place - the angle is undefined. The distance unit is zero, too.
+        let inputs = vec![0.0; compiled.input_count];
+        let mut errors = vec![0.0; compiled.errors.len()];
+        error_fn.call(&inputs, &mut errors);
+
+        assert!(errors.iter().all(|error| error.is_finite()));
+    }
+}
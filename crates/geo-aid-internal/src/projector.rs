@@ -4,15 +4,17 @@ use crate::geometry;
 use crate::geometry::{Circle, Complex, Line, ValueEnum};
 pub use geo_aid_figure as figure;
 use geo_aid_figure::{
+    AngleItem as RenderedAngle, AnnotationItem as RenderedAnnotation, ArcItem as RenderedArc,
     CircleItem as RenderedCircle, Entity, Expression, Figure, Item as Rendered, Label,
-    LineItem as RenderedLine, PointItem as RenderedPoint, Position,
-    TwoPointItem as RenderedTwoPoint, VarIndex,
+    LineItem as RenderedLine, PointItem as RenderedPoint, PolygonItem as RenderedPolygon, Position,
+    TextStyle, TwoPointItem as RenderedTwoPoint, VarIndex, CURRENT_VERSION,
 };
-use std::f64::consts::PI;
+use std::f64::consts::{PI, TAU};
 use std::sync::Arc;
 
 use crate::script::figure::{
-    CircleItem, Generated, Item, LineItem, PointItem, RayItem, SegmentItem,
+    AngleItem, AnnotationItem, ArcItem, CircleItem, Generated, Item, LabelPosition, LineItem,
+    PointItem, PolygonItem, RayItem, SegmentItem,
 };
 use crate::script::math::{EntityKind, Expr, ExprType, Flags};
 
@@ -28,6 +30,10 @@ struct Projector {
     pub segments: Vec<(Complex, Complex)>,
     /// Circles visible on the picture.
     pub circles: Vec<Circle>,
+    /// Script span of the item being projected.
+    pub span: Option<figure::Span>,
+    /// Layer of the item being projected.
+    pub layer: Option<i32>,
 }
 
 impl Projector {
@@ -94,106 +100,90 @@ impl Projector {
         }
     }
 
-    /// Get the point's label position relative to the point.
-    fn get_label_position_rel(&self, point: Complex, min_size: f64) -> Position {
-        let mut vectors = Vec::new();
-
-        // Checking the lines for proximity.
-        for (a, b) in &self.segments {
-            // Identifying the "first" point by the real axis.
-            let (a, b) = if a.real < b.real { (*a, *b) } else { (*b, *a) };
-
-            let ln = geometry::get_line(a, b);
-            let distance = geometry::distance_pt_ln(point, ln);
-
-            // Check if the point lies on the line.
-            // If so, we have 4 cases:
-            // A - point lies close to point A. In that case, we only apply the AB vector.
-            // B - point lies close to point B. In that case, we only apply the BA vector.
-            // C - point lies on the segment AB. In that case, we apply both vectors.
-            // D - point lies far from the segment AB. We do nothing.
-
-            if distance < 1e-2 {
-                if geometry::distance_pt_pt(point, a) < 1e-2 {
-                    vectors.push((b - a).normalize());
-                } else if geometry::distance_pt_pt(point, b) < 1e-2 {
-                    vectors.push((a - b).normalize());
-                } else if a.real < point.real && point.real < b.real {
-                    let v = (a - b).normalize();
-                    vectors.extend([v, -v]);
+    /// Get the point's label position relative to the point. Candidates in all eight directions
+    /// are scored by how much they would overlap with drawn segments, circles and other labels.
+    /// The least crowded one is chosen.
+    fn get_label_position_rel(&self, point: Complex, labels: &[Complex], radius: f64) -> Complex {
+        LabelPosition::ALL
+            .into_iter()
+            .map(|position| position.direction() * radius)
+            .map(|offset| {
+                (
+                    offset,
+                    self.get_label_crowding(point + offset, labels, radius),
+                )
+            })
+            .reduce(|best, candidate| {
+                if candidate.1 < best.1 {
+                    candidate
+                } else {
+                    best
                 }
-            }
-        }
+            })
+            .unwrap()
+            .0
+    }
 
-        // Checking the circles for associated vectors.
-        for &Circle { center, radius } in &self.circles {
-            if (geometry::distance_pt_pt(center, point) - radius).abs() < 1e-4 {
-                let direction = (center - point).mul_i().normalize();
+    /// Estimate how much a label at the given position would overlap with other drawn items.
+    /// Labels are approximated with circles of the given radius.
+    fn get_label_crowding(&self, position: Complex, labels: &[Complex], radius: f64) -> f64 {
+        let overlap = |distance: f64, clearance: f64| (clearance - distance).max(0.0);
 
-                vectors.push(direction);
-                vectors.push(-direction);
-            }
-        }
+        let segments: f64 = self
+            .segments
+            .iter()
+            .map(|&(a, b)| overlap(distance_pt_segment(position, a, b), radius))
+            .sum();
 
-        // Sorting by the complex number argument.
-        vectors.sort_by(|a, b| a.arg().partial_cmp(&b.arg()).unwrap());
+        let circles: f64 = self
+            .circles
+            .iter()
+            .map(|circle| {
+                let distance = geometry::distance_pt_pt(position, circle.center);
+                overlap((distance - circle.radius).abs(), radius)
+            })
+            .sum();
 
-        let mut vec_iter = vectors.iter();
-        vec_iter.next();
+        let labels: f64 = labels
+            .iter()
+            .map(|&label| overlap(geometry::distance_pt_pt(position, label), 2.0 * radius))
+            .sum();
 
-        let radius = 15.0;
+        // Labels sticking out of the picture would get cut off.
+        let edge = position
+            .real
+            .min(self.width - position.real)
+            .min(position.imaginary)
+            .min(self.height - position.imaginary);
 
-        if vectors.is_empty() {
-            // No vectors associated with the given point.
-            Complex::polar(PI / 4.0, radius).into()
-        } else if vectors.len() == 1 {
-            // Only one vector which is associated with the given point.
-            (-radius * vectors.first().copied().unwrap()).into()
-        } else {
-            let mut flip = false;
-            let mut biggest_angle = 0.0;
-            // Vectors between which the label should be located.
-            let mut label_vectors = (Complex::default(), Complex::default());
-
-            // If there is more than one associated vector.
-            for vec in &vectors {
-                if let Some(vec_next) = vec_iter.next() {
-                    let angle = vec_next.arg() - vec.arg();
-                    if angle > biggest_angle {
-                        biggest_angle = angle;
-                        label_vectors = (*vec, *vec_next);
-                    }
-                } else {
-                    let first = vectors.first().unwrap();
-                    let last = vectors.last().unwrap();
-                    let angle = 2.0 * PI - (last.arg() - first.arg());
-                    if angle > biggest_angle {
-                        biggest_angle = angle;
-                        label_vectors = (*first, *last);
-                        flip = true; // The only case when the label offset will have to be flipped (scale -1)
-                    }
-                    break;
-                }
-            }
+        segments + circles + labels + overlap(edge, radius)
+    }
+}
 
-            // We get the bisector angle.
-            let bisector_angle = (label_vectors.1.arg() + label_vectors.0.arg()) / 2.0;
+/// Get the distance between a point and a segment.
+fn distance_pt_segment(point: Complex, a: Complex, b: Complex) -> f64 {
+    let ab = b - a;
 
-            // This is just the standard complex number formula.
-            let bisector_vec = Complex::polar(
-                bisector_angle,
-                (radius / (biggest_angle / 2.0).sin()).min(radius),
-            ) * min_size
-                / 500.0;
+    if ab.len_squared() < 1e-12 {
+        return geometry::distance_pt_pt(point, a);
+    }
 
-            // to do -> better scaling
-            if flip {
-                bisector_vec * -1.0
-            } else {
-                bisector_vec
-            }
-            .into()
+    // How far along the segment the projection of the point is, clamped to its ends.
+    let t = ((point - a) / ab).real.clamp(0.0, 1.0);
+    geometry::distance_pt_pt(point, a + ab * t)
+}
+
+/// Get the position of an already placed text of a rendered item, if it has one.
+fn get_text_position(item: &Rendered) -> Option<Position> {
+    match item {
+        Rendered::Line(line) => line.label.as_ref().map(|label| label.position),
+        Rendered::Ray(two_point) | Rendered::Segment(two_point) => {
+            two_point.label.as_ref().map(|label| label.position)
         }
+        Rendered::Circle(circle) => circle.label.as_ref().map(|label| label.position),
+        Rendered::Arc(arc) => arc.label.as_ref().map(|label| label.position),
+        Rendered::Annotation(annotation) => Some(annotation.position),
+        Rendered::Point(_) | Rendered::Angle(_) | Rendered::Polygon(_) => None,
     }
 }
 
@@ -239,6 +229,10 @@ impl Project<Item> for Projector {
             Item::Line(v) => Rendered::Line(self.project(v)),
             Item::Ray(v) => Rendered::Ray(self.project(v)),
             Item::Segment(v) => Rendered::Segment(self.project(v)),
+            Item::Angle(v) => Rendered::Angle(self.project(v)),
+            Item::Arc(v) => Rendered::Arc(self.project(v)),
+            Item::Polygon(v) => Rendered::Polygon(self.project(v)),
+            Item::Annotation(v) => Rendered::Annotation(self.project(v)),
         }
     }
 }
@@ -255,6 +249,8 @@ impl Project<PointItem> for Projector {
                 .into(),
             id: item.id,
             display_dot: item.display_dot,
+            color: item.color,
+            opacity: item.opacity,
             label: if item.label.is_empty() {
                 None
             } else {
@@ -263,6 +259,8 @@ impl Project<PointItem> for Projector {
                     position: Position { x: 0.0, y: 0.0 },
                 })
             },
+            span: self.span,
+            layer: self.layer,
         }
     }
 }
@@ -278,6 +276,8 @@ impl Project<LineItem> for Projector {
         RenderedLine {
             id: item.id,
             style: item.style,
+            color: item.color,
+            opacity: item.opacity,
             points: (points.0.into(), points.1.into()),
             label: if item.label.is_empty() {
                 None
@@ -287,6 +287,8 @@ impl Project<LineItem> for Projector {
                     position: Position { x: 0.0, y: 0.0 },
                 })
             },
+            span: self.span,
+            layer: self.layer,
         }
     }
 }
@@ -304,6 +306,8 @@ impl Project<SegmentItem> for Projector {
             p_id: item.p_id,
             q_id: item.q_id,
             style: item.style,
+            color: item.color,
+            opacity: item.opacity,
             label: if item.label.is_empty() {
                 None
             } else {
@@ -312,6 +316,9 @@ impl Project<SegmentItem> for Projector {
                     position: Position { x: 0.0, y: 0.0 },
                 })
             },
+            ticks: item.ticks,
+            span: self.span,
+            layer: self.layer,
         }
     }
 }
@@ -348,6 +355,8 @@ impl Project<RayItem> for Projector {
             p_id: item.p_id,
             q_id: item.q_id,
             style: item.style,
+            color: item.color,
+            opacity: item.opacity,
             label: if item.label.is_empty() {
                 None
             } else {
@@ -356,6 +365,9 @@ impl Project<RayItem> for Projector {
                     position: Position { x: 0.0, y: 0.0 },
                 })
             },
+            ticks: 0,
+            span: self.span,
+            layer: self.layer,
         }
     }
 }
@@ -374,6 +386,8 @@ impl Project<CircleItem> for Projector {
             center: center.into(),
             radius,
             style: item.style,
+            color: item.color,
+            opacity: item.opacity,
             label: if item.label.is_empty() {
                 None
             } else {
@@ -382,48 +396,147 @@ impl Project<CircleItem> for Projector {
                     position: Position { x: 0.0, y: 0.0 },
                 })
             },
+            span: self.span,
+            layer: self.layer,
         }
     }
 }
 
-// /// Function getting the points defining the angle from the Expression defining it.
-// ///
-// /// # Panics
-// /// If given invalid data.
-// fn get_angle_points(
-//     angle: &ExprKind,
-//     variables: &[Expr<ValueEnum>],
-// ) -> (Complex, Complex, Complex) {
-//     match angle {
-//         ExprKind::ThreePointAngle { p, q, r } => {
-//             let arm1 = variables[p.0].meta.as_complex().unwrap();
-//             let origin = variables[q.0].meta.as_complex().unwrap();
-//             let arm2 = variables[r.0].meta.as_complex().unwrap();
-//
-//             (arm1, origin, arm2)
-//         }
-//         ExprKind::ThreePointAngleDir { p, q, r } => {
-//             let arm1 = variables[p.0].meta.as_complex().unwrap();
-//             let origin = variables[q.0].meta.as_complex().unwrap();
-//             let arm2 = variables[r.0].meta.as_complex().unwrap();
-//
-//             (arm1, origin, arm2)
-//         }
-//         ExprKind::TwoLineAngle { k, l } => {
-//             let ev_ln1 = variables[k.0].meta.as_line().unwrap();
-//             let ev_ln2 = variables[l.0].meta.as_line().unwrap();
-//
-//             let origin = geometry::get_intersection(ev_ln1, ev_ln2);
-//
-//             (
-//                 ev_ln1.origin + ev_ln1.direction,
-//                 origin,
-//                 ev_ln2.origin + ev_ln2.direction,
-//             )
-//         }
-//         _ => unreachable!(),
-//     }
-// }
+impl Project<AngleItem> for Projector {
+    type Result = RenderedAngle;
+
+    fn project(&mut self, item: AngleItem) -> Self::Result {
+        let arm1: Complex = self.un_var(&item.p_id).unwrap();
+        let origin: Complex = self.un_var(&item.q_id).unwrap();
+        let arm2: Complex = self.un_var(&item.r_id).unwrap();
+
+        RenderedAngle {
+            points: (arm1.into(), origin.into(), arm2.into()),
+            p_id: item.p_id,
+            q_id: item.q_id,
+            r_id: item.r_id,
+            arcs: item.arcs,
+            style: item.style,
+            color: item.color,
+            span: self.span,
+            layer: self.layer,
+        }
+    }
+}
+
+impl Project<ArcItem> for Projector {
+    type Result = RenderedArc;
+
+    fn project(&mut self, item: ArcItem) -> Self::Result {
+        let circle: Circle = self.un_var(&item.circle_id).unwrap();
+        let (start_angle, end_angle) = arc_angles(
+            circle.center,
+            self.un_var(&item.start_id).unwrap(),
+            self.un_var(&item.end_id).unwrap(),
+            item.through_id.map(|id| self.un_var(&id).unwrap()),
+        );
+        let radius = circle.radius.abs();
+
+        // The label is put outside of the arc, next to its middle, unless moved manually.
+        let label_position = circle.center
+            + Complex::polar(f64::midpoint(start_angle, end_angle), radius + 15.0)
+            + item.label_offset.unwrap_or(Complex::zero());
+
+        RenderedArc {
+            center: circle.center.into(),
+            radius,
+            start_angle,
+            end_angle,
+            id: item.circle_id,
+            style: item.style,
+            color: item.color,
+            label: if item.label.is_empty() {
+                None
+            } else {
+                Some(Label {
+                    content: item.label,
+                    position: label_position.into(),
+                })
+            },
+            span: self.span,
+            layer: self.layer,
+        }
+    }
+}
+
+impl Project<PolygonItem> for Projector {
+    type Result = RenderedPolygon;
+
+    fn project(&mut self, item: PolygonItem) -> Self::Result {
+        let vertices: Vec<Complex> = item.ids.iter().map(|id| self.un_var(id).unwrap()).collect();
+
+        // Labels should avoid the polygon's sides.
+        for (i, &vertex) in vertices.iter().enumerate() {
+            self.segments
+                .push((vertex, vertices[(i + 1) % vertices.len()]));
+        }
+
+        RenderedPolygon {
+            points: vertices.into_iter().map(Into::into).collect(),
+            ids: item.ids,
+            style: item.style,
+            color: item.color,
+            opacity: item.opacity,
+            fill: item.fill,
+            span: self.span,
+            layer: self.layer,
+        }
+    }
+}
+
+impl Project<AnnotationItem> for Projector {
+    type Result = RenderedAnnotation;
+
+    fn project(&mut self, item: AnnotationItem) -> Self::Result {
+        RenderedAnnotation {
+            position: <Projector as UnVar<Complex>>::un_var(self, &item.id)
+                .unwrap()
+                .into(),
+            id: item.id,
+            content: item.content,
+            style: TextStyle {
+                color: item.color,
+                opacity: item.opacity,
+            },
+            span: self.span,
+            layer: self.layer,
+        }
+    }
+}
+
+/// Get the start and end angles of an arc of a circle with the given center.
+/// The arc goes counterclockwise from `start` to `end`, unless it has to go clockwise
+/// to pass through `through`. The returned end angle is always greater than the start angle,
+/// at most by a full turn.
+fn arc_angles(
+    center: Complex,
+    start: Complex,
+    end: Complex,
+    through: Option<Complex>,
+) -> (f64, f64) {
+    let start_angle = (start - center).arg();
+    let end_angle = (end - center).arg();
+
+    // Coinciding ends make a full circle.
+    let mut sweep = (end_angle - start_angle).rem_euclid(TAU);
+    if sweep == 0.0 {
+        sweep = TAU;
+    }
+
+    let clockwise = through
+        .is_some_and(|through| ((through - center).arg() - start_angle).rem_euclid(TAU) > sweep);
+
+    if clockwise {
+        (end_angle, end_angle + TAU - sweep)
+    } else {
+        (start_angle, start_angle + sweep)
+    }
+}
 
 /// Represents the transform used by the projector to fit all
 /// items on the canvas with a proper margin.
@@ -464,36 +577,6 @@ impl Transform {
     }
 }
 
-// /// Function that outputs the vector containing the angles.
-// ///
-// /// # Panics
-// /// It shouldn't panic.
-// fn angles(
-//     figure: &Figure,
-//     offset: Complex,
-//     scale: f64,
-//     size: Complex,
-//     args: &EvaluationArgs,
-// ) -> Vec<RenderedAngle> {
-//     let mut blueprint_angles = Vec::new();
-//     for ang in &figure.angles {
-//         let angle_points = get_angle_points(&ang.0, args);
-//         blueprint_angles.push(RenderedAngle {
-//             label: String::new(),
-//             points: (
-//                 transform(offset, scale, size, angle_points.0),
-//                 transform(offset, scale, size, angle_points.1),
-//                 transform(offset, scale, size, angle_points.2),
-//             ),
-//             no_arcs: ang.1,
-//             expr: Arc::clone(&ang.0),
-//             angle_value: ang.0.evaluate(args),
-//             style: ang.2,
-//         });
-//     }
-//     blueprint_angles
-// }
-
 /// Takes the figure and rendered adjustables and attempts to design a figure that can then be rendered in chosen format.
 ///
 /// # Panics
@@ -503,12 +586,17 @@ pub fn project(figure: Generated, _flags: &Arc<Flags>, canvas_size: (f64, f64))
     let mut entities: Vec<_> = figure.entities;
     let mut expressions: Vec<_> = figure.variables;
     let items = figure.items;
+    let item_spans = figure.item_spans;
+    let item_layers = figure.item_layers;
 
     let mut points = Vec::new();
 
     for item in &items {
         match item {
             Item::Point(pt) => points.push(expressions[pt.id.0].meta.as_complex().unwrap()),
+            Item::Annotation(annotation) => {
+                points.push(expressions[annotation.id.0].meta.as_complex().unwrap());
+            }
             Item::Circle(c) => {
                 let circle = expressions[c.id.0].meta.as_circle().unwrap();
 
@@ -519,6 +607,34 @@ pub fn project(figure: Generated, _flags: &Arc<Flags>, canvas_size: (f64, f64))
                     circle.center + circle.radius * Complex::i(),
                 ]);
             }
+            Item::Polygon(polygon) => points.extend(
+                polygon
+                    .ids
+                    .iter()
+                    .map(|id| expressions[id.0].meta.as_complex().unwrap()),
+            ),
+            Item::Arc(arc) => {
+                let circle = expressions[arc.circle_id.0].meta.as_circle().unwrap();
+                let point = |id: &VarIndex| expressions[id.0].meta.as_complex().unwrap();
+                let (start_angle, end_angle) = arc_angles(
+                    circle.center,
+                    point(&arc.start_id),
+                    point(&arc.end_id),
+                    arc.through_id.as_ref().map(point),
+                );
+
+                // The ends and the extremes the arc goes through.
+                points.extend(
+                    [start_angle, end_angle]
+                        .into_iter()
+                        .chain(
+                            (-2..=6)
+                                .map(|i| f64::from(i) * PI / 2.0)
+                                .filter(|angle| (start_angle..end_angle).contains(angle)),
+                        )
+                        .map(|angle| circle.center + Complex::polar(angle, circle.radius.abs())),
+                );
+            }
             _ => (),
         }
     }
@@ -622,33 +738,76 @@ pub fn project(figure: Generated, _flags: &Arc<Flags>, canvas_size: (f64, f64))
         height: size1.imaginary,
         segments: Vec::new(),
         circles: Vec::new(),
+        span: None,
+        layer: None,
     };
 
-    let mut rendered: Vec<_> = items.into_iter().map(|v| projector.project(v)).collect();
+    let forced_label_positions: Vec<_> = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Point(point) => Some((point.label_position, point.label_offset)),
+            _ => None,
+        })
+        .collect();
+
+    let mut rendered: Vec<_> = items
+        .into_iter()
+        .zip(item_spans)
+        .zip(item_layers)
+        .map(|((v, span), layer)| {
+            projector.span = span.map(Into::into);
+            projector.layer = layer;
+            projector.project(v)
+        })
+        .collect();
 
-    for point in rendered.iter_mut().filter_map(Rendered::as_point_mut) {
-        let pos = point.position;
+    // Point labels are placed one by one, avoiding the ones placed before.
+    let mut labels: Vec<Complex> = rendered
+        .iter()
+        .filter_map(get_text_position)
+        .map(Into::into)
+        .collect();
+    let label_radius = min_size * 0.03;
+
+    for (point, (forced, manual_offset)) in rendered
+        .iter_mut()
+        .filter_map(Rendered::as_point_mut)
+        .zip(forced_label_positions)
+    {
+        let pos: Complex = point.position.into();
         if let Some(label) = &mut point.label {
-            label.position = pos + projector.get_label_position_rel(pos.into(), min_size);
+            // The manual offset is applied on top of the chosen direction.
+            let offset = forced.map_or_else(
+                || projector.get_label_position_rel(pos, &labels, label_radius),
+                |forced| forced.direction() * label_radius,
+            ) + manual_offset.unwrap_or(Complex::zero());
+
+            labels.push(pos + offset);
+            label.position = (pos + offset).into();
         }
     }
 
     Figure {
+        version: CURRENT_VERSION,
         width: canvas_size.0,
         height: canvas_size.1,
         expressions: projector
             .variables
             .into_iter()
-            .map(|expr| Expression {
+            .zip(figure.variable_spans)
+            .map(|(expr, span)| Expression {
                 hint: expr.meta.into(),
                 kind: expr.kind.into(),
+                span: span.map(Into::into),
             })
             .collect(),
         entities: entities
             .into_iter()
-            .map(|ent| Entity {
+            .zip(figure.entity_spans)
+            .map(|(ent, span)| Entity {
                 hint: ent.meta.into(),
                 kind: ent.kind.into(),
+                span: span.map(Into::into),
             })
             .collect(),
         items: rendered,
@@ -2,33 +2,60 @@
 
 use geo_aid_figure::math_string::{MathChar, MathIndex, MathSpecial, MathString, SPECIAL_MATH};
 use geo_aid_figure::{
-    CircleItem, Figure, Item, LineItem, PointItem, Position, Style, TwoPointItem,
+    AngleItem, AnnotationItem, ArcItem, CircleItem, Color, Figure, Item, LineItem, PointItem,
+    PolygonItem, Position, Style, TwoPointItem,
 };
 use num_traits::ToPrimitive;
 use std::io::{self, Seek, Write};
 use std::string::String;
 
+/// Options of the LaTeX output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatexOptions {
+    /// Whether to output only the `tikzpicture` environment, without the document preamble.
+    /// The result can then be `\input` into an existing document, which has to load
+    /// `tikz`, `tkz-euclide` and the `angles`, `calc` and `quotes` tikz libraries itself.
+    pub fragment: bool,
+    /// The scale of the picture, if set. Otherwise, the canvas size decides it.
+    pub scale: Option<f64>,
+}
+
 /// The LaTeX format writer.
 #[derive(Debug)]
 pub struct Latex<W: Write + Seek> {
     /// Write stream
     writer: W,
+    /// Output options
+    options: LatexOptions,
 }
 
 impl<W: Write + Seek> Latex<W> {
-    /// Get the figure in LaTeX format.
+    /// Get the figure in LaTeX format, as a full document.
     pub fn draw(figure: &Figure, writer: W) -> Result<(), std::io::Error> {
-        let mut latex = Self { writer };
+        Self::draw_with(figure, writer, LatexOptions::default())
+    }
+
+    /// Get the figure in LaTeX format with the given options.
+    pub fn draw_with(
+        figure: &Figure,
+        writer: W,
+        options: LatexOptions,
+    ) -> Result<(), std::io::Error> {
+        let mut latex = Self { writer, options };
 
         latex.begin()?;
 
-        for item in &figure.items {
+        for item in figure.items_in_drawing_order() {
             match item {
                 Item::Point(point) => latex.draw_point(point)?,
                 Item::Line(line) => latex.draw_line(line)?,
                 Item::Ray(ray) => latex.draw_ray(ray)?,
                 Item::Segment(segment) => latex.draw_segment(segment)?,
                 Item::Circle(circle) => latex.draw_circle(circle)?,
+                Item::Angle(angle) => latex.draw_angle(angle)?,
+                Item::Arc(arc) => latex.draw_arc(arc)?,
+                Item::Polygon(polygon) => latex.draw_polygon(polygon)?,
+                Item::Annotation(annotation) => latex.draw_annotation(annotation)?,
             }
         }
 
@@ -37,12 +64,18 @@ impl<W: Write + Seek> Latex<W> {
         Ok(())
     }
 
-    /// Convert the given math string into a LaTeX string.
+    /// Convert the given math string into a LaTeX string. Characters that would break the
+    /// surrounding math mode are escaped, while backslashes and braces are kept as-is
+    /// so that raw strings can still inject LaTeX commands.
     fn math_to_latex(math: &MathString) -> String {
         let mut s = String::new();
 
         for c in math.iter().copied() {
             match c {
+                MathChar::Ascii(c @ ('#' | '%' | '&' | '$' | '_')) => {
+                    s.push('\\');
+                    s.push(c);
+                }
                 MathChar::Ascii(c) => s.push(c),
                 MathChar::Special(special) => match special {
                     MathSpecial::Quote => s += "\"",
@@ -71,11 +104,26 @@ impl<W: Write + Seek> Latex<W> {
         }
     }
 
+    /// Get the tikz definition of the given [`Color`]
+    fn get_color(color: Color) -> String {
+        format!(
+            "{{rgb,255:red,{};green,{};blue,{}}}",
+            color.r, color.g, color.b
+        )
+    }
+
+    /// Get the tikz opacity of an item. Items are fully opaque by default.
+    fn get_opacity(opacity: Option<f64>) -> f64 {
+        opacity.unwrap_or(1.0)
+    }
+
     /// Draw a styled segment delimited by two points.
     fn draw_simple_segment(
         &mut self,
         points: &(Position, Position),
         style: Style,
+        color: Color,
+        opacity: Option<f64>,
     ) -> io::Result<()> {
         write!(
             &mut self.writer,
@@ -83,29 +131,46 @@ impl<W: Write + Seek> Latex<W> {
                 \begin{{scope}}
                     \coordinate (A) at ({},{});
                     \coordinate (B) at ({},{});
-                    \tkzDrawSegment[{}](A,B)
+                    \tkzDrawSegment[{}, color={}, opacity={}](A,B)
                 \end{{scope}}
             "#,
             points.0.x,
             points.0.y,
             points.1.x,
             points.1.y,
-            Self::get_style_name(style)
+            Self::get_style_name(style),
+            Self::get_color(color),
+            Self::get_opacity(opacity)
         )
     }
 
     fn begin(&mut self) -> io::Result<()> {
-        write!(
-            &mut self.writer,
-            r"
+        if !self.options.fragment {
+            write!(
+                &mut self.writer,
+                r"
                 \documentclass{{article}}
                 \usepackage{{tikz}}
                 \usepackage{{tkz-euclide}}
                 \usetikzlibrary {{angles,calc,quotes}}
-                \begin{{document}}
+                \begin{{document}}"
+            )?;
+        }
+
+        match self.options.scale {
+            Some(scale) => write!(
+                &mut self.writer,
+                r"
+                \begin{{tikzpicture}}[scale={scale}]
+            "
+            ),
+            None => write!(
+                &mut self.writer,
+                r"
                 \begin{{tikzpicture}}
-            ",
-        )
+            "
+            ),
+        }
     }
 
     fn draw_point(&mut self, point: &PointItem) -> io::Result<()> {
@@ -115,9 +180,14 @@ impl<W: Write + Seek> Latex<W> {
         write!(
             &mut self.writer,
             r#"
-                \coordinate ({}) at ({}, {}); \fill[black] ({}) circle (1pt);
+                \coordinate ({}) at ({}, {}); \fill[color={}, opacity={}] ({}) circle (1pt);
             "#,
-            id, pos.x, pos.y, id
+            id,
+            pos.x,
+            pos.y,
+            Self::get_color(point.color),
+            Self::get_opacity(point.opacity),
+            id
         )?;
 
         if let Some(label) = &point.label {
@@ -126,8 +196,10 @@ impl<W: Write + Seek> Latex<W> {
             write!(
                 &mut self.writer,
                 r#"
-                \node at ({}, {}) {{${}$}};
+                \node[text={}, opacity={}] at ({}, {}) {{${}$}};
             "#,
+                Self::get_color(point.color),
+                Self::get_opacity(point.opacity),
                 label_pos.x,
                 label_pos.y,
                 Self::math_to_latex(&label.content)
@@ -138,102 +210,112 @@ impl<W: Write + Seek> Latex<W> {
     }
 
     fn draw_line(&mut self, line: &LineItem) -> io::Result<()> {
-        self.draw_simple_segment(&line.points, line.style)
+        self.draw_simple_segment(&line.points, line.style, line.color, line.opacity)
     }
 
     fn draw_ray(&mut self, ray: &TwoPointItem) -> io::Result<()> {
-        self.draw_simple_segment(&ray.points, ray.style)
+        self.draw_simple_segment(&ray.points, ray.style, ray.color, ray.opacity)
     }
 
     fn draw_segment(&mut self, segment: &TwoPointItem) -> io::Result<()> {
-        self.draw_simple_segment(&segment.points, segment.style)
+        self.draw_simple_segment(
+            &segment.points,
+            segment.style,
+            segment.color,
+            segment.opacity,
+        )?;
+
+        if segment.ticks == 0 {
+            return Ok(());
+        }
+
+        let mark = "|".repeat(usize::from(segment.ticks.min(3)));
+
+        write!(
+            &mut self.writer,
+            r#"
+                \begin{{scope}}
+                    \coordinate (A) at ({},{});
+                    \coordinate (B) at ({},{});
+                    \tkzMarkSegment[mark={mark}, color={}, opacity={}](A,B)
+                \end{{scope}}
+            "#,
+            segment.points.0.x,
+            segment.points.0.y,
+            segment.points.1.x,
+            segment.points.1.y,
+            Self::get_color(segment.color),
+            Self::get_opacity(segment.opacity)
+        )
+    }
+
+    fn draw_angle(&mut self, angle: &AngleItem) -> io::Result<()> {
+        let (mut arm1, origin, mut arm2) = angle.points;
+
+        // `\tkzMarkAngle` goes counterclockwise from the first arm to the second one.
+        // Make sure it's the smaller angle that gets marked.
+        let cross =
+            (arm1.x - origin.x) * (arm2.y - origin.y) - (arm1.y - origin.y) * (arm2.x - origin.x);
+        if cross < 0.0 {
+            std::mem::swap(&mut arm1, &mut arm2);
+        }
+
+        let arcs = "l".repeat(usize::from(angle.arcs.clamp(1, 3)));
+
+        write!(
+            &mut self.writer,
+            r#"
+                \begin{{scope}}
+                    \coordinate (A) at ({}, {});
+                    \coordinate (B) at ({}, {});
+                    \coordinate (C) at ({}, {});
+                    \tkzMarkAngle[size = 0.5,mark = none,arc={arcs},color={}, {}](A,B,C)
+                \end{{scope}}
+            "#,
+            arm1.x,
+            arm1.y,
+            origin.x,
+            origin.y,
+            arm2.x,
+            arm2.y,
+            Self::get_color(angle.color),
+            Self::get_style_name(angle.style)
+        )
     }
 
-    // fn draw_angle(&mut self, angle: &RenderedAngle) {
-    //     let no_arcs = String::from("l"); // Requires a change later! It has to be based on info from the script
-    //
-    //     self.content += &match &angle.expr.kind {
-    //         ScalarExpr::AnglePoint(AnglePoint { arm1, origin, arm2 }) => {
-    //             format!(
-    //                 r#"
-    //                 \begin{{scope}}
-    //                     \coordinate (A) at {};
-    //                     \coordinate (B) at {};
-    //                     \coordinate (C) at {};
-    //                         \tkzMarkAngle[size = 0.5,mark = none,arc={no_arcs},mkcolor = black, {}](A,B,C)
-    //                 \end{{scope}}
-    //                 "#,
-    //                 get_point_name(arm1, output, angle.points.0, self.scale),
-    //                 get_point_name(origin, output, angle.points.1, self.scale),
-    //                 get_point_name(arm2, output, angle.points.2, self.scale),
-    //                 styling(angle.style)
-    //             )
-    //         }
-    //         // There are hard coded values in \coordinate, it is intentional, every point has it's label marked by Rendered::Point sequence above
-    //         ScalarExpr::AngleLine(_) => {
-    //             format!(
-    //                 r#"
-    //                 \begin{{scope}}
-    //                     \coordinate (A) at ({}, {});
-    //                     \coordinate (B) at ({}, {});
-    //                     \coordinate (C) at ({}, {});
-    //                         \tkzMarkAngle[size = 2,mark = none,arc={no_arcs},mkcolor = black, {}](A,B,C)
-    //                 \end{{scope}}
-    //                 "#,
-    //                 angle.points.0.real,
-    //                 angle.points.0.imaginary,
-    //                 angle.points.1.real,
-    //                 angle.points.1.imaginary,
-    //                 angle.points.2.real,
-    //                 angle.points.2.imaginary,
-    //                 styling(angle.style)
-    //             )
-    //         }
-    //         _ => unreachable!(),
-    //     };
-    // }fn draw_angle(&mut self, angle: &RenderedAngle) {
-    //     let no_arcs = String::from("l"); // Requires a change later! It has to be based on info from the script
-    //
-    //     self.content += &match &angle.expr.kind {
-    //         ScalarExpr::AnglePoint(AnglePoint { arm1, origin, arm2 }) => {
-    //             format!(
-    //                 r#"
-    //                 \begin{{scope}}
-    //                     \coordinate (A) at {};
-    //                     \coordinate (B) at {};
-    //                     \coordinate (C) at {};
-    //                         \tkzMarkAngle[size = 0.5,mark = none,arc={no_arcs},mkcolor = black, {}](A,B,C)
-    //                 \end{{scope}}
-    //                 "#,
-    //                 get_point_name(arm1, output, angle.points.0, self.scale),
-    //                 get_point_name(origin, output, angle.points.1, self.scale),
-    //                 get_point_name(arm2, output, angle.points.2, self.scale),
-    //                 styling(angle.style)
-    //             )
-    //         }
-    //         // There are hard coded values in \coordinate, it is intentional, every point has it's label marked by Rendered::Point sequence above
-    //         ScalarExpr::AngleLine(_) => {
-    //             format!(
-    //                 r#"
-    //                 \begin{{scope}}
-    //                     \coordinate (A) at ({}, {});
-    //                     \coordinate (B) at ({}, {});
-    //                     \coordinate (C) at ({}, {});
-    //                         \tkzMarkAngle[size = 2,mark = none,arc={no_arcs},mkcolor = black, {}](A,B,C)
-    //                 \end{{scope}}
-    //                 "#,
-    //                 angle.points.0.real,
-    //                 angle.points.0.imaginary,
-    //                 angle.points.1.real,
-    //                 angle.points.1.imaginary,
-    //                 angle.points.2.real,
-    //                 angle.points.2.imaginary,
-    //                 styling(angle.style)
-    //             )
-    //         }
-    //         _ => unreachable!(),
-    //     };
-    // }
+    fn draw_arc(&mut self, arc: &ArcItem) -> io::Result<()> {
+        let start = arc.point_at(arc.start_angle);
+
+        // Tikz arcs go counterclockwise when the end angle is greater than the start angle.
+        write!(
+            &mut self.writer,
+            r#"
+                \draw[{}, color={}] ({}, {}) arc[start angle={}, end angle={}, radius={}];
+            "#,
+            Self::get_style_name(arc.style),
+            Self::get_color(arc.color),
+            start.x,
+            start.y,
+            arc.start_angle.to_degrees(),
+            arc.end_angle.to_degrees(),
+            arc.radius
+        )?;
+
+        if let Some(label) = &arc.label {
+            write!(
+                &mut self.writer,
+                r#"
+                \node[text={}] at ({}, {}) {{${}$}};
+            "#,
+                Self::get_color(arc.color),
+                label.position.x,
+                label.position.y,
+                Self::math_to_latex(&label.content)
+            )?;
+        }
+
+        Ok(())
+    }
 
     fn draw_circle(&mut self, circle: &CircleItem) -> io::Result<()> {
         let pos1 = circle.center;
@@ -249,18 +331,208 @@ impl<W: Write + Seek> Latex<W> {
             \begin{{scope}}
                 \coordinate (A) at ({}, {});
                 \coordinate (B) at ({}, {});
-                    \tkzDrawCircle[{}](A,B)
+                    \tkzDrawCircle[{}, color={}, opacity={}](A,B)
             \end{{scope}}
             "#,
             pos1.x,
             pos1.y,
             pos2.x,
             pos2.y,
-            Self::get_style_name(circle.style)
+            Self::get_style_name(circle.style),
+            Self::get_color(circle.color),
+            Self::get_opacity(circle.opacity)
+        )
+    }
+
+    fn draw_polygon(&mut self, polygon: &PolygonItem) -> io::Result<()> {
+        for (i, p) in polygon.points.iter().enumerate() {
+            write!(
+                &mut self.writer,
+                r"
+                \coordinate (P{i}) at ({}, {});
+            ",
+                p.x, p.y
+            )?;
+        }
+
+        let vertices = (0..polygon.points.len())
+            .map(|i| format!("P{i}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        let fill = polygon
+            .fill
+            .map(|color| format!(", fill={}", Self::get_color(color)))
+            .unwrap_or_default();
+
+        write!(
+            &mut self.writer,
+            r"
+                \tkzDrawPolygon[{}, color={}, opacity={}, even odd rule{}]({})
+            ",
+            Self::get_style_name(polygon.style),
+            Self::get_color(polygon.color),
+            Self::get_opacity(polygon.opacity),
+            fill,
+            vertices
+        )
+    }
+
+    fn draw_annotation(&mut self, annotation: &AnnotationItem) -> io::Result<()> {
+        write!(
+            &mut self.writer,
+            r#"
+                \node[text={}, opacity={}] at ({}, {}) {{${}$}};
+            "#,
+            Self::get_color(annotation.style.color),
+            Self::get_opacity(annotation.style.opacity),
+            annotation.position.x,
+            annotation.position.y,
+            Self::math_to_latex(&annotation.content)
         )
     }
 
     fn end(&mut self) -> io::Result<()> {
-        write!(&mut self.writer, "\\end{{tikzpicture}} \\end{{document}}")
+        write!(&mut self.writer, "\\end{{tikzpicture}}")?;
+
+        if !self.options.fragment {
+            write!(&mut self.writer, " \\end{{document}}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Latex, LatexOptions};
+    use geo_aid_figure::math_string::MathString;
+    use geo_aid_figure::{
+        Color, Figure, Item, PointItem, PolygonItem, Position, Style, TwoPointItem, VarIndex,
+        CURRENT_VERSION,
+    };
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    type Writer = Latex<Cursor<Vec<u8>>>;
+
+    fn figure() -> Figure {
+        let a = Position { x: 1.0, y: 2.0 };
+        let b = Position { x: 4.0, y: 6.0 };
+
+        Figure {
+            version: CURRENT_VERSION,
+            width: 10.0,
+            height: 10.0,
+            expressions: Vec::new(),
+            entities: Vec::new(),
+            items: vec![
+                Item::Point(PointItem {
+                    position: a,
+                    id: VarIndex(0),
+                    display_dot: true,
+                    color: Color::default(),
+                    opacity: None,
+                    label: None,
+                    span: None,
+                    layer: None,
+                }),
+                Item::Segment(TwoPointItem {
+                    points: (a, b),
+                    p_id: VarIndex(0),
+                    q_id: VarIndex(1),
+                    color: Color::default(),
+                    style: Style::Dashed,
+                    opacity: None,
+                    label: None,
+                    ticks: 0,
+                    span: None,
+                    layer: None,
+                }),
+            ],
+        }
+    }
+
+    fn draw(options: LatexOptions) -> String {
+        let mut output = Cursor::new(Vec::new());
+        Latex::draw_with(&figure(), &mut output, options).unwrap();
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn fragment_is_the_picture_of_the_document() {
+        let document = draw(LatexOptions::default());
+        let fragment = draw(LatexOptions {
+            fragment: true,
+            scale: None,
+        });
+
+        assert!(!fragment.contains("\\documentclass"));
+        assert!(!fragment.contains("document}"));
+        assert!(fragment.trim_start().starts_with("\\begin{tikzpicture}"));
+        assert!(fragment.ends_with("\\end{tikzpicture}"));
+
+        // Wrapped in a minimal document, the fragment gives the same picture.
+        let start = document.find("\\begin{tikzpicture}").unwrap();
+        let end = document.rfind(" \\end{document}").unwrap();
+        assert_eq!(&document[start..end], fragment.trim_start());
+        assert!(document[..start].contains("\\usepackage{tkz-euclide}"));
+    }
+
+    #[test]
+    fn scale_can_be_set_explicitly() {
+        let fragment = draw(LatexOptions {
+            fragment: true,
+            scale: Some(0.5),
+        });
+
+        assert!(fragment.contains("\\begin{tikzpicture}[scale=0.5]"));
+    }
+
+    #[test]
+    fn polygons_are_filled_with_their_fill_color() {
+        let mut figure = figure();
+        figure.items.push(Item::Polygon(PolygonItem {
+            points: vec![
+                Position { x: 0.0, y: 0.0 },
+                Position { x: 4.0, y: 0.0 },
+                Position { x: 0.0, y: 3.0 },
+            ],
+            ids: vec![VarIndex(0), VarIndex(1), VarIndex(2)],
+            style: Style::Solid,
+            color: Color::default(),
+            opacity: None,
+            fill: Some(Color::rgb(0, 0, 255)),
+            span: None,
+            layer: None,
+        }));
+
+        let mut output = Cursor::new(Vec::new());
+        Latex::draw_with(&figure, &mut output, LatexOptions::default()).unwrap();
+        let document = String::from_utf8(output.into_inner()).unwrap();
+
+        assert!(document.contains("\\coordinate (P2) at (0, 3);"));
+        assert!(document.contains("fill={rgb,255:red,0;green,0;blue,255}](P0,P1,P2)"));
+        assert!(document.contains("\\tkzDrawPolygon[thin, color={rgb,255:red,0;green,0;blue,0}"));
+    }
+
+    #[test]
+    fn escaped_underscore_is_literal() {
+        let string = MathString::from_str("A\\_1").unwrap();
+
+        assert_eq!(Writer::math_to_latex(&string), "A\\_1");
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        let string = MathString::raw("50% #1 & $");
+
+        assert_eq!(Writer::math_to_latex(&string), "50\\% \\#1 \\& \\$");
+    }
+
+    #[test]
+    fn raw_braces_pass_through() {
+        let string = MathString::raw("\\mathbb{X}^{}");
+
+        assert_eq!(Writer::math_to_latex(&string), "\\mathbb{X}^{}");
     }
 }
@@ -0,0 +1,229 @@
+//! Analysis of a single GeoScript document.
+
+use std::collections::HashMap;
+
+use geo_aid_script::{
+    cli::{AnnotationKind, DiagnosticData},
+    parser::Type,
+    token::{self, Span},
+    unroll,
+};
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, Position, Range, Uri,
+};
+
+/// A variable defined in the script.
+#[derive(Debug, Clone)]
+pub struct VariableInfo {
+    /// Where the variable is defined.
+    pub definition_span: Span,
+    /// The variable's type.
+    pub ty: Type,
+}
+
+/// An open document along with the results of its last analysis.
+#[derive(Debug, Default)]
+pub struct Document {
+    /// The document's contents.
+    pub text: String,
+    /// Variables defined in the document.
+    ///
+    /// Scripts with errors cannot be fully unrolled, so these are kept from the last
+    /// successful analysis.
+    pub variables: HashMap<String, VariableInfo>,
+}
+
+impl Document {
+    /// Replace the document's contents and analyse it again.
+    /// Returns the diagnostics for the new contents.
+    pub fn update(&mut self, uri: &Uri, text: String) -> Vec<Diagnostic> {
+        self.text = text;
+
+        match unroll::unroll(&self.text) {
+            Ok((context, _)) => {
+                self.variables = context
+                    .variables
+                    .iter()
+                    .map(|(name, var)| {
+                        (
+                            name.clone(),
+                            VariableInfo {
+                                definition_span: var
+                                    .try_get_variable_span()
+                                    .unwrap_or_else(|| var.get_span()),
+                                ty: var.get_type(),
+                            },
+                        )
+                    })
+                    .collect();
+
+                Vec::new()
+            }
+            Err(errors) => errors
+                .into_iter()
+                .map(|err| self.to_lsp_diagnostic(uri, err.diagnostic()))
+                .collect(),
+        }
+    }
+
+    /// Convert a diagnostic into its LSP representation.
+    fn to_lsp_diagnostic(&self, uri: &Uri, data: DiagnosticData) -> Diagnostic {
+        let range = data
+            .spans
+            .first()
+            .map_or_else(Range::default, |span| self.range(*span));
+
+        let mut message = data.message;
+
+        for (kind, note) in data.notes {
+            message += &format!("\n{}: {note}", annotation_name(kind));
+        }
+
+        for fix in data.fixes {
+            message += &format!("\nhelp: {}", fix.message);
+        }
+
+        let related_information = data
+            .annotations
+            .into_iter()
+            .map(|annotation| DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.clone(),
+                    range: self.range(annotation.at),
+                },
+                message: format!(
+                    "{}: {}",
+                    annotation_name(annotation.kind),
+                    annotation.message
+                ),
+            })
+            .collect::<Vec<_>>();
+
+        Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some(String::from("geo-aid")),
+            message,
+            related_information: (!related_information.is_empty()).then_some(related_information),
+            ..Default::default()
+        }
+    }
+
+    /// Convert a script span into an LSP range.
+    #[must_use]
+    pub fn range(&self, span: Span) -> Range {
+        Range {
+            start: self.position(span.start),
+            end: self.position(span.end),
+        }
+    }
+
+    /// Convert a script position (1-based line and character) into an LSP position
+    /// (0-based line and UTF-16 offset).
+    fn position(&self, position: token::Position) -> Position {
+        let line = position.line.saturating_sub(1);
+        let character = self.text.lines().nth(line).map_or(0, |text| {
+            text.chars()
+                .take(position.column.saturating_sub(1))
+                .map(char::len_utf16)
+                .sum()
+        });
+
+        Position {
+            line: u32::try_from(line).unwrap_or(u32::MAX),
+            character: u32::try_from(character).unwrap_or(u32::MAX),
+        }
+    }
+
+    /// Get the identifier at the given LSP position, along with its range.
+    #[must_use]
+    pub fn ident_at(&self, position: Position) -> Option<(&str, Range)> {
+        let text = self.text.lines().nth(position.line as usize)?;
+
+        // Find the byte index of the position's character.
+        let mut utf16 = 0;
+        let cursor = text
+            .char_indices()
+            .find(|(_, c)| {
+                utf16 += c.len_utf16();
+                utf16 > position.character as usize
+            })
+            .map_or(text.len(), |(i, _)| i);
+
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+        let start = text[..cursor]
+            .rfind(|c| !is_ident(c))
+            .map_or(0, |i| i + text[i..].chars().next().unwrap().len_utf8());
+        let end = text[cursor..]
+            .find(|c| !is_ident(c))
+            .map_or(text.len(), |i| cursor + i);
+
+        if start == end {
+            return None;
+        }
+
+        let to_utf16 = |i: usize| {
+            u32::try_from(text[..i].chars().map(char::len_utf16).sum::<usize>()).unwrap_or(u32::MAX)
+        };
+
+        Some((
+            &text[start..end],
+            Range {
+                start: Position {
+                    line: position.line,
+                    character: to_utf16(start),
+                },
+                end: Position {
+                    line: position.line,
+                    character: to_utf16(end),
+                },
+            },
+        ))
+    }
+
+    /// Get the variable at the given LSP position, if any.
+    #[must_use]
+    pub fn variable_at(&self, position: Position) -> Option<(&VariableInfo, Range)> {
+        let (ident, range) = self.ident_at(position)?;
+        self.variables.get(ident).map(|var| (var, range))
+    }
+}
+
+/// Get the name of an annotation kind, as displayed in the CLI.
+fn annotation_name(kind: AnnotationKind) -> &'static str {
+    match kind {
+        AnnotationKind::Note => "note",
+        AnnotationKind::Help => "help",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_are_found() {
+        let uri: Uri = "file:///test.geo".parse().unwrap();
+        let mut document = Document::default();
+        let diagnostics = document.update(&uri, String::from("let A, B = Point();\nAB = 3;\n"));
+
+        assert!(diagnostics.is_empty());
+
+        let (var, range) = document.variable_at(Position::new(0, 7)).unwrap();
+        assert_eq!(var.ty, Type::Point);
+        assert_eq!(range, Range::new(Position::new(0, 7), Position::new(0, 8)));
+        assert!(document.variable_at(Position::new(1, 0)).is_none());
+    }
+
+    #[test]
+    fn errors_keep_variables() {
+        let uri: Uri = "file:///test.geo".parse().unwrap();
+        let mut document = Document::default();
+        document.update(&uri, String::from("let A = Point();\n"));
+        let diagnostics = document.update(&uri, String::from("let A = Point();\nAX = 3;\n"));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 0));
+        assert!(document.variables.contains_key("A"));
+    }
+}
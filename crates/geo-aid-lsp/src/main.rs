@@ -0,0 +1,232 @@
+//! A language server for GeoScript. Communicates over stdio and provides error
+//! diagnostics, hovers with variable types, go-to-definition for variables
+//! and completion of builtin function names.
+
+use std::{collections::HashMap, error::Error};
+
+use geo_aid_script::unroll::library::Library;
+use lsp_server::{Connection, ExtractError, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Request as _},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkedString, OneOf, PublishDiagnosticsParams,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+
+use analysis::Document;
+
+mod analysis;
+
+type BoxedError = Box<dyn Error + Send + Sync>;
+
+/// The server's state.
+struct Server {
+    /// The client connection.
+    connection: Connection,
+    /// Currently open documents.
+    documents: HashMap<Uri, Document>,
+    /// Builtin function names, offered as completions.
+    function_names: Vec<&'static str>,
+}
+
+impl Server {
+    /// Create a new server on the given connection.
+    fn new(connection: Connection) -> Self {
+        let mut function_names: Vec<_> = Library::new().function_names().collect();
+        function_names.sort_unstable();
+
+        Self {
+            connection,
+            documents: HashMap::new(),
+            function_names,
+        }
+    }
+
+    /// Handle messages until the client requests a shutdown.
+    fn run(&mut self) -> Result<(), BoxedError> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+
+                    self.handle_request(request)?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a single request.
+    fn handle_request(&mut self, request: Request) -> Result<(), BoxedError> {
+        let response = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                let (id, params) = extract::<HoverRequest>(request)?;
+                Response::new_ok(id, self.hover(&params))
+            }
+            GotoDefinition::METHOD => {
+                let (id, params) = extract::<GotoDefinition>(request)?;
+                Response::new_ok(id, self.definition(&params))
+            }
+            Completion::METHOD => {
+                let (id, params) = extract::<Completion>(request)?;
+                Response::new_ok(id, self.completion(&params))
+            }
+            _ => Response::new_err(
+                request.id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {}", request.method),
+            ),
+        };
+
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+
+    /// Handle a single notification.
+    fn handle_notification(&mut self, notification: Notification) -> Result<(), BoxedError> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = extract_notification::<DidOpenTextDocument>(notification)?;
+                self.update(params.text_document.uri, params.text_document.text)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = extract_notification::<DidChangeTextDocument>(notification)?;
+
+                // Documents are always synchronized in full.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.update(params.text_document.uri, change.text)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = extract_notification::<DidCloseTextDocument>(notification)?;
+                self.documents.remove(&params.text_document.uri);
+                self.publish_diagnostics(params.text_document.uri, Vec::new())?;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Update a document's contents and publish its diagnostics.
+    fn update(&mut self, uri: Uri, text: String) -> Result<(), BoxedError> {
+        let diagnostics = self
+            .documents
+            .entry(uri.clone())
+            .or_default()
+            .update(&uri, text);
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    /// Send diagnostics for a document to the client.
+    fn publish_diagnostics(
+        &self,
+        uri: Uri,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Result<(), BoxedError> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                params,
+            )))?;
+        Ok(())
+    }
+
+    /// Show the type of the variable under the cursor.
+    fn hover(&self, params: &HoverParams) -> Option<Hover> {
+        let position = &params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let (var, range) = document.variable_at(position.position)?;
+
+        Some(Hover {
+            contents: HoverContents::Scalar(MarkedString::String(var.ty.to_string())),
+            range: Some(range),
+        })
+    }
+
+    /// Find where the variable under the cursor is defined.
+    fn definition(&self, params: &GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = &params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let (var, _) = document.variable_at(position.position)?;
+
+        Some(GotoDefinitionResponse::Scalar(Location {
+            uri: position.text_document.uri.clone(),
+            range: document.range(var.definition_span),
+        }))
+    }
+
+    /// Offer builtin function names starting with the identifier under the cursor.
+    fn completion(&self, params: &CompletionParams) -> Option<CompletionResponse> {
+        let position = &params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+
+        // Function names are normalized, so the typed prefix has to be as well.
+        let mut prefix = document
+            .ident_at(position.position)
+            .map_or_else(String::new, |(ident, _)| ident.to_lowercase());
+        prefix.retain(|c| c != '_');
+
+        let items = self
+            .function_names
+            .iter()
+            .filter(|name| name.starts_with(&prefix))
+            .map(|name| CompletionItem {
+                label: (*name).to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                ..Default::default()
+            })
+            .collect();
+
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+/// Extract a request's parameters.
+fn extract<R: lsp_types::request::Request>(
+    request: Request,
+) -> Result<(RequestId, R::Params), ExtractError<Request>> {
+    request.extract(R::METHOD)
+}
+
+/// Extract a notification's parameters.
+fn extract_notification<N: lsp_types::notification::Notification>(
+    notification: Notification,
+) -> Result<N::Params, ExtractError<Notification>> {
+    notification.extract(N::METHOD)
+}
+
+fn main() -> Result<(), BoxedError> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        ..Default::default()
+    })?;
+    connection.initialize(capabilities)?;
+
+    Server::new(connection).run()?;
+    io_threads.join()?;
+
+    Ok(())
+}
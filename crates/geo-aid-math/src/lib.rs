@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
 mod compiler;
 pub mod shared;
 
@@ -13,7 +17,7 @@ pub type Float = f32;
 pub struct Expr(usize);
 
 /// A comparison between two expressions.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Comparison {
     pub a: Expr,
     pub b: Expr,
@@ -21,7 +25,7 @@ pub struct Comparison {
 }
 
 /// The primitive kind of a comparison.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ComparisonKind {
     Eq,
     Neq,
@@ -30,7 +34,7 @@ pub enum ComparisonKind {
 }
 
 /// A condition for ternary operators
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Condition {
     Comparison(Comparison),
 }
@@ -68,6 +72,64 @@ enum ExprKind {
     Log(Expr),
 }
 
+// Constants are compared by their bits, so that structurally equal expressions always
+// compute the same value, including `-0.0` and `NaN`s.
+impl PartialEq for ExprKind {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Constant(a), Self::Constant(b)) => a.to_bits() == b.to_bits(),
+            (Self::Input(a), Self::Input(b)) => a == b,
+            (Self::Add(a1, b1), Self::Add(a2, b2))
+            | (Self::Sub(a1, b1), Self::Sub(a2, b2))
+            | (Self::Mul(a1, b1), Self::Mul(a2, b2))
+            | (Self::Div(a1, b1), Self::Div(a2, b2))
+            | (Self::Atan2(a1, b1), Self::Atan2(a2, b2)) => a1 == a2 && b1 == b2,
+            (Self::Sin(a), Self::Sin(b))
+            | (Self::Cos(a), Self::Cos(b))
+            | (Self::Neg(a), Self::Neg(b))
+            | (Self::Sqrt(a), Self::Sqrt(b))
+            | (Self::Exp(a), Self::Exp(b))
+            | (Self::Log(a), Self::Log(b)) => a == b,
+            (Self::Ternary(cond1, then1, else1), Self::Ternary(cond2, then2, else2)) => {
+                cond1 == cond2 && then1 == then2 && else1 == else2
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ExprKind {}
+
+impl Hash for ExprKind {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match self {
+            Self::Constant(v) => v.to_bits().hash(state),
+            Self::Input(i) => i.hash(state),
+            Self::Add(a, b)
+            | Self::Sub(a, b)
+            | Self::Mul(a, b)
+            | Self::Div(a, b)
+            | Self::Atan2(a, b) => {
+                a.hash(state);
+                b.hash(state);
+            }
+            Self::Sin(v)
+            | Self::Cos(v)
+            | Self::Neg(v)
+            | Self::Sqrt(v)
+            | Self::Exp(v)
+            | Self::Log(v) => v.hash(state),
+            Self::Ternary(cond, then, else_) => {
+                cond.hash(state);
+                then.hash(state);
+                else_.hash(state);
+            }
+        }
+    }
+}
+
 /// An entry in the expression record.
 #[derive(Debug, Clone, Copy)]
 struct Entry {
@@ -87,6 +149,8 @@ pub struct Context {
     /// Derivatives w.r.t. respective inputs. Every `inputs` next entries are a set
     /// of derivatives.
     derivatives: Vec<Expr>,
+    /// Expressions mapped to their indices. Structurally equal expressions are only stored once.
+    lookup: HashMap<ExprKind, Expr>,
 }
 
 impl Context {
@@ -118,10 +182,17 @@ impl Context {
             derivatives.extend((0..inputs).map(|j| if i == j { Expr(1) } else { Expr(0) }));
         }
 
+        let lookup = exprs
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.kind, Expr(i)))
+            .collect();
+
         Self {
             inputs,
             exprs,
             derivatives,
+            lookup,
         }
     }
 
@@ -181,25 +252,43 @@ impl Context {
         }
     }
 
-    /// Push an expression without derivatives.
+    /// Push an expression without derivatives. If a structurally equal one exists, it's reused.
     fn push_expr_nodiff(&mut self, kind: ExprKind) -> Expr {
+        if let Some(expr) = self.lookup.get(&kind) {
+            return *expr;
+        }
+
         let id = self.exprs.len();
         self.exprs.push(Entry {
             kind,
             derivatives: None,
         });
+        self.lookup.insert(kind, Expr(id));
         Expr(id)
     }
 
-    /// Push an expression with its derivatives.
+    /// Push an expression with its derivatives. If a structurally equal one exists, it's reused.
     fn push_expr(&mut self, kind: ExprKind, derivatives: Vec<Expr>) -> Expr {
         assert_eq!(self.inputs, derivatives.len());
+
+        if let Some(expr) = self.lookup.get(&kind).copied() {
+            // Expressions pushed without derivatives get them now.
+            let entry = &mut self.exprs[expr.0];
+            if entry.derivatives.is_none() {
+                entry.derivatives = Some(self.derivatives.len());
+                self.derivatives.extend(derivatives);
+            }
+
+            return expr;
+        }
+
         let id = self.exprs.len();
         self.exprs.push(Entry {
             kind,
             derivatives: Some(self.derivatives.len()),
         });
         self.derivatives.extend(derivatives);
+        self.lookup.insert(kind, Expr(id));
         Expr(id)
     }
 
@@ -224,11 +313,19 @@ impl Context {
     /// Creates a constant value.
     pub fn constant(&mut self, value: Float) -> Expr {
         let kind = ExprKind::Constant(value);
+
+        // The derivatives of a constant are all zeros, same as the ones of zero itself.
+        if let Some(expr) = self.lookup.get(&kind).copied() {
+            self.exprs[expr.0].derivatives.get_or_insert(0);
+            return expr;
+        }
+
         let id = self.exprs.len();
         self.exprs.push(Entry {
             kind,
             derivatives: Some(0),
         });
+        self.lookup.insert(kind, Expr(id));
         Expr(id)
     }
 
@@ -410,6 +507,19 @@ impl Context {
         )
     }
 
+    /// Gets the maximum value.
+    pub fn max(&mut self, a: Expr, b: Expr) -> Expr {
+        self.ternary(
+            Condition::Comparison(Comparison {
+                a,
+                b,
+                kind: ComparisonKind::Gt,
+            }),
+            a,
+            b,
+        )
+    }
+
     /// Takes the absolute value.
     pub fn abs(&mut self, v: Expr) -> Expr {
         let cond = Condition::Comparison(Comparison {
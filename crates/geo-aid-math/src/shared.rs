@@ -10,6 +10,7 @@ use std::{
 use crate::{Condition, Context, Expr, Float};
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
     use crate::shared::Complex;
 
@@ -53,6 +54,48 @@ mod tests {
         assert!((buff[0] - 4.12f64.sin()).abs() < 0.001);
         assert!((buff[1].abs()) < 0.001);
     }
+
+    #[test]
+    fn test_equal_expressions_are_shared() {
+        let ctx = Shared::new(2);
+
+        let first = (ctx.input(0) * &ctx.input(1)).sin();
+        let second = (ctx.input(0) * &ctx.input(1)).sin();
+        assert_eq!(first.expr, second.expr);
+
+        // `-0.0` must not be mistaken for `0.0`.
+        assert_ne!(ctx.constant(-0.0).expr, ctx.real_zero().expr);
+    }
+
+    #[test]
+    fn test_rule_subexpressions_are_shared() {
+        let ctx = Shared::new(4);
+
+        // Every rule is compiled on its own, so a distance used by two rules is built twice.
+        let distance = || {
+            let a = Complex {
+                real: ctx.input(0),
+                imaginary: ctx.input(1),
+            };
+            let b = Complex {
+                real: ctx.input(2),
+                imaginary: ctx.input(3),
+            };
+            (a - &b).abs()
+        };
+
+        let first = distance() - &ctx.real_one();
+        let count = ctx.exec(|ctx| ctx.exprs.len());
+
+        // The second copy, along with all its derivatives, adds no new expressions.
+        let second = distance() - &ctx.real_one();
+        assert_eq!(first.expr, second.expr);
+        assert_eq!(ctx.exec(|ctx| ctx.exprs.len()), count);
+        assert_eq!(
+            ctx.exec(|ctx| ctx.gradient(first.expr)),
+            ctx.exec(|ctx| ctx.gradient(second.expr))
+        );
+    }
 }
 
 /// Access to a shared context.
@@ -190,6 +233,18 @@ impl Real {
         self.exec(|ctx| ctx.min(self.expr, other.expr))
     }
 
+    /// Get the maximum
+    #[must_use]
+    pub fn max(&self, other: &Self) -> Self {
+        self.exec(|ctx| ctx.max(self.expr, other.expr))
+    }
+
+    /// Limit the value to the range from `min` to `max`.
+    #[must_use]
+    pub fn clamp(&self, min: &Self, max: &Self) -> Self {
+        self.min(max).max(min)
+    }
+
     /// Raise to a power
     #[must_use]
     pub fn pow(&self, exponent: &Self) -> Self {
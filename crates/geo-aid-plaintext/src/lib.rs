@@ -4,7 +4,8 @@
 use std::io::{self, Seek, Write};
 
 use geo_aid_figure::{
-    CircleItem, Figure, Item, Label, LineItem, PointItem, Position, Style, TwoPointItem,
+    AngleItem, AnnotationItem, ArcItem, CircleItem, Color, Figure, Item, Label, LineItem,
+    PointItem, PolygonItem, Position, Style, TwoPointItem,
 };
 
 /// The raw format writer
@@ -26,6 +27,10 @@ impl<W: Write + Seek> Plaintext<W> {
                 Item::Ray(ray) => plain.draw_ray(ray)?,
                 Item::Segment(segment) => plain.draw_segment(segment)?,
                 Item::Circle(circle) => plain.draw_circle(circle)?,
+                Item::Angle(angle) => plain.draw_angle(angle)?,
+                Item::Arc(arc) => plain.draw_arc(arc)?,
+                Item::Polygon(polygon) => plain.draw_polygon(polygon)?,
+                Item::Annotation(annotation) => plain.draw_annotation(annotation)?,
             }
         }
 
@@ -42,24 +47,35 @@ impl<W: Write + Seek> Plaintext<W> {
         }
     }
 
+    /// Describe a color, unless it's the default one.
+    fn get_color_suffix(color: Color) -> String {
+        if color == Color::default() {
+            String::new()
+        } else {
+            format!(" colored {color}")
+        }
+    }
+
     /// Draw a styled segment delimited by two points.
     fn draw_simple_segment(
         &mut self,
         (p1, p2): (Position, Position),
         style: Style,
+        color: Color,
         label: Option<&Label>,
     ) -> io::Result<()> {
         let label = label.map(|l| l.content.to_string()).unwrap_or_default();
 
         writeln!(
             &mut self.writer,
-            "{} line \"{}\" from ({:.3}, {:.3}) to ({:.3}, {:.3})",
+            "{} line \"{}\" from ({:.3}, {:.3}) to ({:.3}, {:.3}){}",
             Self::get_style_name(style),
             label,
             p1.x,
             p1.y,
             p2.x,
-            p2.y
+            p2.y,
+            Self::get_color_suffix(color)
         )
     }
 
@@ -72,31 +88,128 @@ impl<W: Write + Seek> Plaintext<W> {
 
         writeln!(
             &mut self.writer,
-            "point \"{label}\" at ({:.3}, {:.3})",
-            point.position.x, point.position.y
+            "point \"{label}\" at ({:.3}, {:.3}){}",
+            point.position.x,
+            point.position.y,
+            Self::get_color_suffix(point.color)
         )
     }
 
     fn draw_line(&mut self, line: &LineItem) -> io::Result<()> {
-        self.draw_simple_segment(line.points, line.style, line.label.as_ref())
+        self.draw_simple_segment(line.points, line.style, line.color, line.label.as_ref())
     }
 
     fn draw_ray(&mut self, ray: &TwoPointItem) -> io::Result<()> {
-        self.draw_simple_segment(ray.points, ray.style, ray.label.as_ref())
+        self.draw_simple_segment(ray.points, ray.style, ray.color, ray.label.as_ref())
     }
 
     fn draw_segment(&mut self, segment: &TwoPointItem) -> io::Result<()> {
-        self.draw_simple_segment(segment.points, segment.style, segment.label.as_ref())
+        self.draw_simple_segment(
+            segment.points,
+            segment.style,
+            segment.color,
+            segment.label.as_ref(),
+        )?;
+
+        if segment.ticks > 0 {
+            let (p1, p2) = segment.points;
+
+            writeln!(
+                &mut self.writer,
+                "{} tick(s) at ({:.3}, {:.3}){}",
+                segment.ticks,
+                (p1.x + p2.x) / 2.0,
+                (p1.y + p2.y) / 2.0,
+                Self::get_color_suffix(segment.color)
+            )?;
+        }
+
+        Ok(())
     }
 
     fn draw_circle(&mut self, circle: &CircleItem) -> io::Result<()> {
         writeln!(
             &mut self.writer,
-            "{} circle at ({:.3}, {:.3}) with radius {:.3}",
+            "{} circle at ({:.3}, {:.3}) with radius {:.3}{}",
             Self::get_style_name(circle.style),
             circle.center.x,
             circle.center.y,
             circle.radius,
+            Self::get_color_suffix(circle.color),
+        )
+    }
+    fn draw_angle(&mut self, angle: &AngleItem) -> io::Result<()> {
+        let (arm1, origin, arm2) = angle.points;
+
+        writeln!(
+            &mut self.writer,
+            "{} angle with {} arc(s) from ({:.3}, {:.3}) through ({:.3}, {:.3}) to ({:.3}, {:.3}){}",
+            Self::get_style_name(angle.style),
+            angle.arcs,
+            arm1.x,
+            arm1.y,
+            origin.x,
+            origin.y,
+            arm2.x,
+            arm2.y,
+            Self::get_color_suffix(angle.color),
+        )
+    }
+
+    fn draw_arc(&mut self, arc: &ArcItem) -> io::Result<()> {
+        let label = arc
+            .label
+            .as_ref()
+            .map(|l| l.content.to_string())
+            .unwrap_or_default();
+        let start = arc.point_at(arc.start_angle);
+        let end = arc.point_at(arc.end_angle);
+
+        writeln!(
+            &mut self.writer,
+            "{} arc \"{}\" of circle at ({:.3}, {:.3}) with radius {:.3} counterclockwise from ({:.3}, {:.3}) to ({:.3}, {:.3}){}",
+            Self::get_style_name(arc.style),
+            label,
+            arc.center.x,
+            arc.center.y,
+            arc.radius,
+            start.x,
+            start.y,
+            end.x,
+            end.y,
+            Self::get_color_suffix(arc.color),
+        )
+    }
+
+    fn draw_polygon(&mut self, polygon: &PolygonItem) -> io::Result<()> {
+        let vertices = polygon
+            .points
+            .iter()
+            .map(|p| format!("({:.3}, {:.3})", p.x, p.y))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        writeln!(
+            &mut self.writer,
+            "{} polygon with vertices {}{}{}",
+            Self::get_style_name(polygon.style),
+            vertices,
+            Self::get_color_suffix(polygon.color),
+            polygon
+                .fill
+                .map(|color| format!(", filled with {color}"))
+                .unwrap_or_default(),
+        )
+    }
+
+    fn draw_annotation(&mut self, annotation: &AnnotationItem) -> io::Result<()> {
+        writeln!(
+            &mut self.writer,
+            "annotation \"{}\" at ({:.3}, {:.3}){}",
+            annotation.content,
+            annotation.position.x,
+            annotation.position.y,
+            Self::get_color_suffix(annotation.style.color),
         )
     }
 }
@@ -11,6 +11,8 @@ use crate::token::{Position, Span};
 pub enum DiagnosticKind {
     /// A language error
     Error,
+    /// A possible mistake that does not stop compilation
+    Warning,
     /// Some information
     Note,
     /// A suggestion
@@ -23,8 +25,7 @@ pub fn get_color(kind: DiagnosticKind) -> Color {
     match kind {
         DiagnosticKind::Error => Color::Red,
         DiagnosticKind::Note => Color::Blue,
-        DiagnosticKind::Help => Color::Yellow,
-        // UnderscoreType::Warning => Color::Yellow,
+        DiagnosticKind::Help | DiagnosticKind::Warning => Color::Yellow,
         // UnderscoreType::Info => Color::Cyan,
     }
 }
@@ -402,6 +403,7 @@ impl Display for Diagnostic<'_> {
                 DiagnosticKind::Error => "error".red().bold(),
                 DiagnosticKind::Note => "note".blue().bold(),
                 DiagnosticKind::Help => "help".yellow().bold(),
+                DiagnosticKind::Warning => "warning".yellow().bold(),
             },
             ":".white().bold(),
             self.message.clone().white().bold()
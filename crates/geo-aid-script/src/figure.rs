@@ -3,12 +3,12 @@
 
 use std::{fmt::Display, str::FromStr};
 
-use crate::geometry::ValueEnum;
+use crate::geometry::{Complex, ValueEnum};
 use crate::math::{EntityKind, IndexMap, Reconstruct, ReconstructCtx, Reindex};
 use geo_aid_figure::math_string::{
     MathChar, MathIndex, MathSpecial, MathString, ParseErrorKind, SPECIAL_MATH,
 };
-use geo_aid_figure::{Style, VarIndex};
+use geo_aid_figure::{Color, Style, VarIndex};
 
 use crate::span;
 
@@ -16,7 +16,7 @@ use super::math::Entity;
 use super::{
     math,
     parser::{FromProperty, Parse, PropertyValue},
-    token::{Ident, PointCollectionItem, Span},
+    token::{self, Ident, PointCollectionItem, Span},
     unroll::most_similar,
     Error,
 };
@@ -30,6 +30,67 @@ pub struct PointItem {
     pub label: MathString,
     /// Whether to display a small circle in its place
     pub display_dot: bool,
+    /// Color of the point
+    pub color: Color,
+    /// Opacity of the point, if set
+    pub opacity: Option<f64>,
+    /// Forced direction of the label, if set
+    pub label_position: Option<LabelPosition>,
+    /// Offset of the label from its chosen position, if set
+    pub label_offset: Option<Complex>,
+}
+
+/// A direction in which a point's label can be placed.
+/// Directions are given in figure space, where the y axis points down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelPosition {
+    /// Towards negative y
+    Above,
+    /// Towards positive y
+    Below,
+    /// Towards negative x
+    Left,
+    /// Towards positive x
+    Right,
+    /// Diagonally up and left
+    AboveLeft,
+    /// Diagonally up and right
+    AboveRight,
+    /// Diagonally down and left
+    BelowLeft,
+    /// Diagonally down and right
+    BelowRight,
+}
+
+impl LabelPosition {
+    /// All directions, clockwise. The first one is preferred if no other is less crowded.
+    pub const ALL: [Self; 8] = [
+        Self::BelowRight,
+        Self::Below,
+        Self::BelowLeft,
+        Self::Left,
+        Self::AboveLeft,
+        Self::Above,
+        Self::AboveRight,
+        Self::Right,
+    ];
+
+    /// Get the unit vector pointing in this direction.
+    #[must_use]
+    pub fn direction(self) -> Complex {
+        let diagonal = std::f64::consts::FRAC_1_SQRT_2;
+
+        match self {
+            Self::Above => Complex::new(0.0, -1.0),
+            Self::Below => Complex::new(0.0, 1.0),
+            Self::Left => Complex::new(-1.0, 0.0),
+            Self::Right => Complex::new(1.0, 0.0),
+            Self::AboveLeft => Complex::new(-diagonal, -diagonal),
+            Self::AboveRight => Complex::new(diagonal, -diagonal),
+            Self::BelowLeft => Complex::new(-diagonal, diagonal),
+            Self::BelowRight => Complex::new(diagonal, diagonal),
+        }
+    }
 }
 
 impl Reindex for PointItem {
@@ -62,6 +123,10 @@ pub struct CircleItem {
     pub label: MathString,
     /// How to draw the circle (brush)
     pub style: Style,
+    /// Color of the circle
+    pub color: Color,
+    /// Opacity of the circle, if set
+    pub opacity: Option<f64>,
 }
 
 impl Reindex for CircleItem {
@@ -94,6 +159,10 @@ pub struct LineItem {
     pub label: MathString,
     /// How to draw the line (brush)
     pub style: Style,
+    /// Color of the line
+    pub color: Color,
+    /// Opacity of the line, if set
+    pub opacity: Option<f64>,
 }
 
 impl Reindex for LineItem {
@@ -128,6 +197,10 @@ pub struct RayItem {
     pub label: MathString,
     /// How to draw the ray (brush)
     pub style: Style,
+    /// Color of the ray
+    pub color: Color,
+    /// Opacity of the ray, if set
+    pub opacity: Option<f64>,
 }
 
 impl Reindex for RayItem {
@@ -164,6 +237,12 @@ pub struct SegmentItem {
     pub label: MathString,
     /// How to draw the segment (brush)
     pub style: Style,
+    /// Color of the segment
+    pub color: Color,
+    /// Opacity of the segment, if set
+    pub opacity: Option<f64>,
+    /// How many ticks mark the segment's middle, 0 for none
+    pub ticks: u8,
 }
 
 impl From<SegmentItem> for Item {
@@ -189,6 +268,167 @@ impl Reconstruct for SegmentItem {
     }
 }
 
+/// A drawn angle marker
+#[derive(Debug, Clone)]
+pub struct AngleItem {
+    /// Index of the expression defining the first arm's point
+    pub p_id: VarIndex,
+    /// Index of the expression defining the vertex
+    pub q_id: VarIndex,
+    /// Index of the expression defining the second arm's point
+    pub r_id: VarIndex,
+    /// How many arcs mark the angle
+    pub arcs: u8,
+    /// How to draw the arcs (brush)
+    pub style: Style,
+    /// Color of the arcs
+    pub color: Color,
+}
+
+impl From<AngleItem> for Item {
+    fn from(value: AngleItem) -> Self {
+        Self::Angle(value)
+    }
+}
+
+impl Reindex for AngleItem {
+    fn reindex(&mut self, map: &IndexMap) {
+        self.p_id.reindex(map);
+        self.q_id.reindex(map);
+        self.r_id.reindex(map);
+    }
+}
+
+impl Reconstruct for AngleItem {
+    fn reconstruct(self, ctx: &mut ReconstructCtx) -> Self {
+        Self {
+            p_id: self.p_id.reconstruct(ctx),
+            q_id: self.q_id.reconstruct(ctx),
+            r_id: self.r_id.reconstruct(ctx),
+            ..self
+        }
+    }
+}
+
+/// A drawn arc of a circle
+#[derive(Debug, Clone)]
+pub struct ArcItem {
+    /// Index of the expression defining the arc's circle
+    pub circle_id: VarIndex,
+    /// Index of the expression defining the point at the arc's start
+    pub start_id: VarIndex,
+    /// Index of the expression defining the point at the arc's end
+    pub end_id: VarIndex,
+    /// Index of the expression defining a point the arc goes through.
+    /// If not given, the arc goes counterclockwise from start to end.
+    pub through_id: Option<VarIndex>,
+    /// Label of this arc
+    pub label: MathString,
+    /// How to draw the arc (brush)
+    pub style: Style,
+    /// Color of the arc
+    pub color: Color,
+    /// Offset of the label from its default position, if set
+    pub label_offset: Option<Complex>,
+}
+
+impl From<ArcItem> for Item {
+    fn from(value: ArcItem) -> Self {
+        Self::Arc(value)
+    }
+}
+
+impl Reindex for ArcItem {
+    fn reindex(&mut self, map: &IndexMap) {
+        self.circle_id.reindex(map);
+        self.start_id.reindex(map);
+        self.end_id.reindex(map);
+        self.through_id.reindex(map);
+    }
+}
+
+impl Reconstruct for ArcItem {
+    fn reconstruct(self, ctx: &mut ReconstructCtx) -> Self {
+        Self {
+            circle_id: self.circle_id.reconstruct(ctx),
+            start_id: self.start_id.reconstruct(ctx),
+            end_id: self.end_id.reconstruct(ctx),
+            through_id: self.through_id.reconstruct(ctx),
+            ..self
+        }
+    }
+}
+
+/// A drawn polygon
+#[derive(Debug, Clone)]
+pub struct PolygonItem {
+    /// Indices of the expressions defining the vertices, in order
+    pub ids: Vec<VarIndex>,
+    /// How to draw the outline (brush)
+    pub style: Style,
+    /// Color of the polygon
+    pub color: Color,
+    /// Opacity of the polygon, if set
+    pub opacity: Option<f64>,
+    /// Color to fill the polygon with, if any
+    pub fill: Option<Color>,
+}
+
+impl From<PolygonItem> for Item {
+    fn from(value: PolygonItem) -> Self {
+        Self::Polygon(value)
+    }
+}
+
+impl Reindex for PolygonItem {
+    fn reindex(&mut self, map: &IndexMap) {
+        self.ids.reindex(map);
+    }
+}
+
+impl Reconstruct for PolygonItem {
+    fn reconstruct(self, ctx: &mut ReconstructCtx) -> Self {
+        Self {
+            ids: self.ids.reconstruct(ctx),
+            ..self
+        }
+    }
+}
+
+/// A drawn text annotation
+#[derive(Debug, Clone)]
+pub struct AnnotationItem {
+    /// Index of the expression defining the anchor point
+    pub id: VarIndex,
+    /// The annotation's text
+    pub content: MathString,
+    /// Color of the text
+    pub color: Color,
+    /// Opacity of the text, if set
+    pub opacity: Option<f64>,
+}
+
+impl From<AnnotationItem> for Item {
+    fn from(value: AnnotationItem) -> Self {
+        Self::Annotation(value)
+    }
+}
+
+impl Reindex for AnnotationItem {
+    fn reindex(&mut self, map: &IndexMap) {
+        self.id.reindex(map);
+    }
+}
+
+impl Reconstruct for AnnotationItem {
+    fn reconstruct(self, ctx: &mut ReconstructCtx) -> Self {
+        Self {
+            id: self.id.reconstruct(ctx),
+            ..self
+        }
+    }
+}
+
 /// A type-erased drawn item of the figure
 #[derive(Debug, Clone)]
 pub enum Item {
@@ -197,6 +437,10 @@ pub enum Item {
     Line(LineItem),
     Ray(RayItem),
     Segment(SegmentItem),
+    Angle(AngleItem),
+    Arc(ArcItem),
+    Polygon(PolygonItem),
+    Annotation(AnnotationItem),
 }
 
 impl Reindex for Item {
@@ -207,6 +451,10 @@ impl Reindex for Item {
             Self::Line(v) => v.reindex(map),
             Self::Ray(v) => v.reindex(map),
             Self::Segment(v) => v.reindex(map),
+            Self::Angle(v) => v.reindex(map),
+            Self::Arc(v) => v.reindex(map),
+            Self::Polygon(v) => v.reindex(map),
+            Self::Annotation(v) => v.reindex(map),
         }
     }
 }
@@ -219,6 +467,10 @@ impl Reconstruct for Item {
             Self::Line(v) => Self::Line(v.reconstruct(ctx)),
             Self::Ray(v) => Self::Ray(v.reconstruct(ctx)),
             Self::Segment(v) => Self::Segment(v.reconstruct(ctx)),
+            Self::Angle(v) => Self::Angle(v.reconstruct(ctx)),
+            Self::Arc(v) => Self::Arc(v.reconstruct(ctx)),
+            Self::Polygon(v) => Self::Polygon(v.reconstruct(ctx)),
+            Self::Annotation(v) => Self::Annotation(v.reconstruct(ctx)),
         }
     }
 }
@@ -232,6 +484,14 @@ pub struct Figure {
     pub variables: Vec<math::Expr<()>>,
     /// Drawn items
     pub items: Vec<Item>,
+    /// Spans of the nodes the items were built by, if known
+    pub item_spans: Vec<Option<Span>>,
+    /// Layers the items are drawn on, if set
+    pub item_layers: Vec<Option<i32>>,
+    /// Definition spans of the entities, if known
+    pub entity_spans: Vec<Option<Span>>,
+    /// Definition spans of the variables, if known
+    pub variable_spans: Vec<Option<Span>>,
 }
 
 /// Generated figure, created by the engine
@@ -243,6 +503,28 @@ pub struct Generated {
     pub variables: Vec<math::Expr<ValueEnum>>,
     /// Drawn items with meta
     pub items: Vec<Item>,
+    /// Spans of the nodes the items were built by, if known
+    pub item_spans: Vec<Option<Span>>,
+    /// Layers the items are drawn on, if set
+    pub item_layers: Vec<Option<i32>>,
+    /// Definition spans of the entities, if known
+    pub entity_spans: Vec<Option<Span>>,
+    /// Definition spans of the variables, if known
+    pub variable_spans: Vec<Option<Span>>,
+}
+
+impl From<Span> for geo_aid_figure::Span {
+    fn from(value: Span) -> Self {
+        let position = |pos: token::Position| geo_aid_figure::SourcePosition {
+            line: pos.line,
+            column: pos.column,
+        };
+
+        Self {
+            start: position(value.start),
+            end: position(value.end),
+        }
+    }
 }
 
 /// A [`MathString`] with a [`Span`].
@@ -331,8 +613,8 @@ impl SpannedMathString {
             let error_span = span!(
                 content_span.start.line,
                 content_span.start.column + err.span.start,
-                content_span.end.line,
-                content_span.end.column + err.span.end
+                content_span.start.line,
+                content_span.start.column + err.span.end
             );
 
             match err.kind {
@@ -350,6 +632,8 @@ impl SpannedMathString {
                     error_span,
                     parsed_special: special,
                 },
+                ParseErrorKind::UnclosedIndex => Error::UnclosedLabelIndex { error_span },
+                ParseErrorKind::UnmatchedBrace => Error::UnmatchedLabelBrace { error_span },
             }
         })?;
 
@@ -394,13 +678,17 @@ impl FromProperty for SpannedMathString {
                     s.span.start.line,
                     s.span.start.column + 1,
                     s.span.end.line,
-                    s.span.end.column - 1
+                    // Strings added by the compiler itself have empty spans.
+                    s.span.end.column.saturating_sub(1)
                 ),
             ),
             PropertyValue::RawString(s) => Ok(Self {
                 string: MathString::raw(&s.lit.content),
                 span: s.get_span(),
             }),
+            PropertyValue::Pair(p) => Err(Error::StringOrIdentExpected {
+                error_span: p.get_span(),
+            }),
         }
     }
 }
@@ -409,13 +697,14 @@ impl From<PointCollectionItem> for SpannedMathString {
     fn from(value: PointCollectionItem) -> Self {
         let mut string = MathString::new();
         string.push(MathChar::Ascii(value.letter));
+        string.extend([MathChar::Prime].repeat(value.primes.into()));
 
         if let Some(index) = value.index {
+            string.push(MathChar::SetIndex(MathIndex::Lower));
             string.extend(index.chars().map(MathChar::Ascii));
+            string.push(MathChar::SetIndex(MathIndex::Normal));
         }
 
-        string.extend([MathChar::Prime].repeat(value.primes.into()));
-
         Self {
             string,
             span: value.span,
@@ -8,7 +8,7 @@ use std::{
 };
 
 use crate::cli::{AnnotationKind, Change, DiagnosticData, Fix};
-use geo_aid_figure::math_string::SPECIAL_MATH;
+use geo_aid_figure::{math_string::SPECIAL_MATH, Color};
 use num_traits::{One, Zero};
 use serde::Serialize;
 
@@ -97,6 +97,16 @@ pub enum Error {
         /// Exactly where the error occurred.
         error_span: Box<Span>,
     },
+    /// Numbers of incompatible units added, subtracted or compared.
+    InconsistentUnits {
+        /// The type of the lhs operand
+        // boxes here to reduce size
+        lhs: (Type, Box<Span>),
+        /// The type of the rhs operand
+        rhs: (Type, Box<Span>),
+        /// The whole operation.
+        error_span: Box<Span>,
+    },
     /// A variable with the same name already exists
     RedefinedVariable {
         /// The first definition span
@@ -122,14 +132,39 @@ pub enum Error {
         /// Whether to suggest adding the complex numbers flag.
         suggest_complex: bool,
     },
+    /// A variable of a `let` statement was referenced before its definition in the same statement.
+    VariableDefinedLater {
+        /// The reference span
+        error_span: Span,
+        /// The variable name
+        variable_name: String,
+        /// Where the variable is defined
+        defined_at: Span,
+    },
     /// An undefined function was referenced.
     UndefinedFunction {
         /// The reference span
         error_span: Span,
         /// The function name
         function_name: String,
-        /// The potentially intended name.
-        suggested: Option<&'static str>,
+        /// The potentially intended names, the most similar first.
+        suggested: Vec<&'static str>,
+    },
+    /// A function with the same name has already been defined in the script.
+    RedefinedFunction {
+        /// The first definition span
+        defined_at: Span,
+        /// The second definition span
+        error_span: Span,
+        /// The function name
+        function_name: String,
+    },
+    /// A script-defined function calls itself, directly or not.
+    RecursiveFunction {
+        /// The recursive call span
+        error_span: Span,
+        /// The function name
+        function_name: String,
     },
     /// An undefined method was referenced.
     UndefinedMethod {
@@ -137,15 +172,21 @@ pub enum Error {
         error_span: Span,
         /// Teh method name
         function_name: String,
-        /// The potentially intended name.
-        suggested: Option<&'static str>,
+        /// The potentially intended names, the most similar first.
+        suggested: Vec<&'static str>,
         /// The type the method was searched on.
         on_type: Type,
     },
-    /// An attempt to access a field of a value has been made.
-    FieldAccess {
-        /// The access span
+    /// An undefined field of a value was accessed.
+    UndefinedField {
+        /// The field span
         error_span: Span,
+        /// The field name
+        field_name: String,
+        /// The type the field was searched on.
+        on_type: Type,
+        /// The potentially intended name.
+        suggested: Option<&'static str>,
     },
     /// An attempt to use an unsupported language feature was made.
     FeatureNotSupported {
@@ -181,6 +222,13 @@ pub enum Error {
         /// The desired collection length
         length: usize,
     },
+    /// A polygon was given too few vertices.
+    NotAPolygon {
+        /// The span of the vertices
+        error_span: Span,
+        /// The number of vertices given
+        length: usize,
+    },
     /// There's no implicit conversion between two types.
     ImplicitConversionDoesNotExist {
         /// The conversion span
@@ -248,6 +296,24 @@ pub enum Error {
     BooleanExpected { error_span: Span },
     /// A number value was expected
     NumberExpected { error_span: Span },
+    /// A positive number value was expected
+    PositiveNumberExpected { error_span: Span },
+    /// An angle can only be marked with 1, 2 or 3 arcs.
+    InvalidArcCount { error_span: Span },
+    /// A segment can only be marked with 1, 2 or 3 ticks.
+    InvalidTickCount { error_span: Span },
+    /// Opacity must be a number between 0 and 1.
+    InvalidOpacity { error_span: Span },
+    /// Layers are identified by integers.
+    InvalidLayer { error_span: Span },
+    /// A pair of numbers, like `(1, 2)`, was expected.
+    PairExpected { error_span: Span },
+    /// A rule was given a negative weight.
+    NegativeWeight { error_span: Span },
+    /// A rule can never be satisfied, regardless of the figure.
+    UnsatisfiableRule { error_span: Span },
+    /// An alternative in an `or` rule unrolled into more than one rule.
+    CompoundAlternative { error_span: Span },
     /// The provided identifier cannot be converted into a math string.
     InvalidIdentMathString { error_span: Span },
     /// A flag's value was set more than once
@@ -259,6 +325,17 @@ pub enum Error {
         /// Name of the flag
         flag_name: &'static str,
     },
+    /// An engine-specific flag was set for an engine other than the selected one.
+    FlagForOtherEngine {
+        /// The flag's span
+        error_span: Span,
+        /// The full name of the flag
+        flag_name: String,
+        /// The selected engine
+        engine: String,
+        /// Where the engine is selected
+        engine_span: Span,
+    },
     /// Invalid value for an enumeration
     EnumInvalidValue {
         /// The value span
@@ -268,6 +345,13 @@ pub enum Error {
         /// The received, invalid value.
         received_value: String,
     },
+    /// An invalid color was given as a property value.
+    InvalidColor {
+        /// The value span
+        error_span: Span,
+        /// The received, invalid value.
+        received_value: String,
+    },
     /// A flag that must be set was not set.
     RequiredFlagNotSet {
         /// The flag's name
@@ -303,8 +387,14 @@ pub enum Error {
     },
     /// There's an unclosed string
     UnclosedString { error_span: Span },
+    /// A block comment was never closed.
+    UnclosedComment { error_span: Span },
     /// An index was found inside another index, in a math string
     LabelIndexInsideIndex { error_span: Span },
+    /// A lower index delimited with `_{` was never closed.
+    UnclosedLabelIndex { error_span: Span },
+    /// A brace in a label doesn't open or close a lower index.
+    UnmatchedLabelBrace { error_span: Span },
     /// An unexpected property was found
     UnexpectedDisplayOption {
         error_span: Span,
@@ -328,6 +418,38 @@ pub enum Error {
     ZeroDenominator { error_span: Span },
     /// A function name was expected
     ExpectedFunction { error_span: Span },
+    /// Named and positional arguments were mixed in a function call.
+    MixedArguments { error_span: Span },
+    /// An exponent was not an integer or a fraction of integers.
+    NonLiteralExponent { error_span: Span },
+    /// A point collection was indexed out of its bounds.
+    IndexOutOfBounds {
+        /// The index span
+        error_span: Span,
+        /// The index used
+        index: String,
+        /// The length of the indexed collection
+        length: usize,
+    },
+    /// A value that isn't a point collection was indexed.
+    NotIndexable {
+        /// The indexed value's span
+        error_span: Span,
+        /// The value's type
+        on_type: Type,
+    },
+}
+
+/// Make the help message for the given suggested names, if there are any.
+fn suggestion_message(suggested: &[&str]) -> Option<String> {
+    match suggested {
+        [] => None,
+        [name] => Some(format!("did you mean: `{name}`?")),
+        [names @ .., last] => {
+            let names: Vec<_> = names.iter().map(|v| format!("`{v}`")).collect();
+            Some(format!("did you mean: {} or `{last}`?", names.join(", ")))
+        }
+    }
 }
 
 impl Error {
@@ -403,6 +525,22 @@ impl Error {
                     AnnotationKind::Note,
                     &format!("This expression is of type {}", got.0),
                 ),
+            Self::InconsistentUnits {
+                lhs,
+                rhs,
+                error_span,
+            } => DiagnosticData::new("inconsistent units")
+                .add_span(*error_span)
+                .add_annotation(
+                    *lhs.1,
+                    AnnotationKind::Note,
+                    &format!("This expression is of type {}", lhs.0),
+                )
+                .add_annotation(
+                    *rhs.1,
+                    AnnotationKind::Note,
+                    &format!("This expression is of type {}", rhs.0),
+                ),
             Self::RedefinedVariable {
                 defined_at,
                 error_span,
@@ -438,30 +576,60 @@ impl Error {
                     data
                 }
             }
+            Self::VariableDefinedLater {
+                error_span,
+                variable_name,
+                defined_at,
+            } => DiagnosticData::new(&format!("variable used before its definition: `{variable_name}`"))
+                .add_span(error_span)
+                .add_annotation(defined_at, AnnotationKind::Note, "Defined later in the same statement."),
             Self::UndefinedFunction {
                 error_span,
                 function_name,
                 suggested
             } => {
-                let message = suggested.map(|v| format!("did you mean: `{v}`?"));
+                let message = suggestion_message(&suggested);
                 DiagnosticData::new(&format!("function `{function_name}` not found"))
                     .add_span(error_span)
                     .add_annotation_opt_msg(error_span, AnnotationKind::Help, message.as_ref())
             }
+            Self::RedefinedFunction {
+                defined_at,
+                error_span,
+                function_name,
+            } => DiagnosticData::new(&format!("redefined function: `{function_name}`"))
+                .add_span(error_span)
+                .add_annotation(defined_at, AnnotationKind::Note, "First defined here."),
+            Self::RecursiveFunction {
+                error_span,
+                function_name,
+            } => DiagnosticData::new(&format!("function `{function_name}` is recursive"))
+                .add_span(error_span)
+                .add_annotation(error_span, AnnotationKind::Help, "Recursive functions are not supported."),
             Self::UndefinedMethod {
                 error_span,
                 function_name,
                 suggested,
                 on_type
             } => {
-                let message = suggested.map(|v| format!("did you mean: `{v}`?"));
+                let message = suggestion_message(&suggested);
                 DiagnosticData::new(&format!("method `{function_name}` not found on type {on_type}"))
                     .add_span(error_span)
                     .add_annotation_opt_msg(error_span, AnnotationKind::Help, message.as_ref())
             }
-            Self::FieldAccess { error_span } => {
-                DiagnosticData::new("GeoScript has no fields. Did you mean to call a method?")
-                .add_span(error_span)
+            Self::UndefinedField {
+                error_span,
+                field_name,
+                on_type,
+                suggested
+            } => {
+                let message = suggested.map_or_else(
+                    || String::from("did you mean to call a method?"),
+                    |v| format!("did you mean: `{v}`?")
+                );
+                DiagnosticData::new(&format!("field `{field_name}` not found on type {on_type}"))
+                    .add_span(error_span)
+                    .add_annotation(error_span, AnnotationKind::Help, &message)
             }
             Self::FeatureNotSupported {
                 error_span,
@@ -490,6 +658,10 @@ impl Error {
                 DiagnosticData::new(&format!("could not unpack `{ty}` onto a point collection of length {length}"))
                     .add_span(error_span)
             }
+            Self::NotAPolygon { error_span, length } => {
+                DiagnosticData::new(&format!("a polygon needs at least 3 vertices, got {length}"))
+                    .add_span(error_span)
+            }
             Self::ImplicitConversionDoesNotExist {
                 error_span,
                 from,
@@ -571,6 +743,44 @@ impl Error {
                 DiagnosticData::new(&"expected a number value")
                     .add_span(error_span)
             }
+            Self::PositiveNumberExpected { error_span } => {
+                DiagnosticData::new(&"expected a positive number value")
+                    .add_span(error_span)
+            }
+            Self::InvalidArcCount { error_span } => {
+                DiagnosticData::new(&"expected an arc count (1, 2 or 3)")
+                    .add_span(error_span)
+            }
+            Self::InvalidTickCount { error_span } => {
+                DiagnosticData::new(&"expected a tick count (1, 2 or 3)")
+                    .add_span(error_span)
+            }
+            Self::InvalidOpacity { error_span } => {
+                DiagnosticData::new(&"expected an opacity (a number between 0 and 1)")
+                    .add_span(error_span)
+            }
+            Self::InvalidLayer { error_span } => {
+                DiagnosticData::new(&"expected a layer (an integer)").add_span(error_span)
+            }
+            Self::PairExpected { error_span } => {
+                DiagnosticData::new(&"expected a pair of numbers, like (1, -0.5)")
+                    .add_span(error_span)
+            }
+            Self::NegativeWeight { error_span } => {
+                DiagnosticData::new(&"rule weights cannot be negative")
+                    .add_span(error_span)
+                    .add_annotation(error_span, AnnotationKind::Help, &"a weight of zero disables the rule")
+            }
+            Self::UnsatisfiableRule { error_span } => {
+                DiagnosticData::new(&"this rule can never be satisfied")
+                    .add_span(error_span)
+                    .add_annotation(error_span, AnnotationKind::Note, &"the rule is always false, regardless of the figure")
+            }
+            Self::CompoundAlternative { error_span } => {
+                DiagnosticData::new(&"an alternative must be a single rule")
+                    .add_span(error_span)
+                    .add_annotation(error_span, AnnotationKind::Help, &"each side of `or` must be exactly one rule, without chains or iterators")
+            }
             Self::InvalidIdentMathString { error_span } => {
                 DiagnosticData::new(&"invalid ident for a math string")
                     .add_span(error_span)
@@ -582,6 +792,11 @@ impl Error {
             } => DiagnosticData::new(&format!("redefined flag: `{flag_name}`"))
                 .add_span(error_span)
                 .add_annotation(first_defined, AnnotationKind::Note, "first defined here"),
+            Self::FlagForOtherEngine { error_span, flag_name, engine, engine_span } => {
+                DiagnosticData::new(&format!("flag `{flag_name}` does not apply to engine `{engine}`"))
+                    .add_span(error_span)
+                    .add_annotation(engine_span, AnnotationKind::Note, &"engine selected here")
+            }
             Self::EnumInvalidValue { error_span, available_values, received_value } => {
                 DiagnosticData::new(&format!("invalid value for an enum flag or property: `{received_value}`"))
                     .add_span(error_span)
@@ -589,6 +804,13 @@ impl Error {
                         |v| format!("`{v}`")
                     ).collect::<Vec<String>>().join(", ")))
             }
+            Self::InvalidColor { error_span, received_value } => {
+                DiagnosticData::new(&format!("invalid color: `{received_value}`"))
+                    .add_span(error_span)
+                    .add_annotation(error_span, AnnotationKind::Help, &format!("expected a `#rrggbb` hex code or one of: {}", Color::NAMED.iter().map(
+                        |(name, _)| format!("`{name}`")
+                    ).collect::<Vec<String>>().join(", ")))
+            }
             Self::RequiredFlagNotSet { flag_name, required_because, definition_span: flagdef_span, available_values } => {
                 DiagnosticData::new(&format!("you must set a value for flag `{flag_name}`."))
                     .add_annotation(required_because, AnnotationKind::Note, &"Required because of this line.")
@@ -657,10 +879,21 @@ impl Error {
                 DiagnosticData::new(&"unclosed special tag")
                     .add_span(error_span)
             }
+            Self::UnclosedComment { error_span } => {
+                DiagnosticData::new(&"unclosed block comment").add_span(error_span)
+            }
             Self::LabelIndexInsideIndex { error_span } => {
                 DiagnosticData::new(&"lower index cannot be used inside another lower index")
                     .add_span(error_span)
             }
+            Self::UnclosedLabelIndex { error_span } => {
+                DiagnosticData::new(&"there's a missing '}' somewhere here. Braces delimit lower indices.")
+                    .add_span(error_span)
+            }
+            Self::UnmatchedLabelBrace { error_span } => {
+                DiagnosticData::new(&"this brace doesn't delimit a lower index. To escape a brace, use \\{ or \\}.")
+                    .add_span(error_span)
+            }
             Self::UnexpectedDisplayOption { error_span, option, suggested } => {
                 let message = suggested.map(|v| format!("did you mean: `{v}`?"));
                 DiagnosticData::new(&format!("unexpected display option: `{option}`"))
@@ -684,6 +917,127 @@ impl Error {
                 DiagnosticData::new(&"expected function, found, value")
                     .add_span(error_span)
             }
+            Self::MixedArguments { error_span } => {
+                DiagnosticData::new(&"named and positional arguments cannot be mixed")
+                    .add_span(error_span)
+            }
+            Self::NonLiteralExponent { error_span } => {
+                DiagnosticData::new(&"exponent must be a literal").add_annotation(
+                    error_span,
+                    AnnotationKind::Help,
+                    "Only integers and fractions of integers, like `2` or `(1/2)`, are allowed.",
+                )
+            }
+            Self::IndexOutOfBounds {
+                error_span,
+                index,
+                length,
+            } => DiagnosticData::new(&format!(
+                "index {index} is out of bounds for a collection of {length} points"
+            ))
+            .add_annotation(
+                error_span,
+                AnnotationKind::Help,
+                "Indices start at 0.",
+            ),
+            Self::NotIndexable {
+                error_span,
+                on_type,
+            } => DiagnosticData::new(&format!("values of type {on_type} cannot be indexed"))
+                .add_annotation(
+                    error_span,
+                    AnnotationKind::Help,
+                    "Only point collections can be indexed.",
+                ),
+        }
+    }
+}
+
+/// A `GeoScript` warning. Warnings point at likely mistakes, but don't stop compilation.
+#[derive(Debug)]
+pub enum Warning {
+    /// A variable is never referenced and nothing it defines is drawn.
+    UnusedVariable {
+        /// The variable's definition
+        error_span: Span,
+        /// The variable name
+        variable_name: String,
+    },
+    /// A free point appears in no rule and isn't drawn.
+    UnconstrainedPoint {
+        /// Where the point is defined
+        error_span: Span,
+        /// The point's variable name, if it has one
+        point_name: Option<String>,
+    },
+    /// A free point appears in no rule, but is drawn.
+    UnconstrainedDrawnPoint {
+        /// Where the point is defined
+        error_span: Span,
+        /// The point's variable name, if it has one
+        point_name: Option<String>,
+    },
+    /// A rule is always satisfied and was removed.
+    TriviallyTrueRule {
+        /// The rule
+        error_span: Span,
+    },
+}
+
+impl Warning {
+    /// Convert the warning to a diagnostic
+    #[must_use]
+    pub fn diagnostic(self) -> DiagnosticData {
+        match self {
+            Self::UnusedVariable {
+                error_span,
+                variable_name,
+            } => DiagnosticData::new(&format!("unused variable: `{variable_name}`"))
+                .add_span(error_span)
+                .add_annotation(
+                    error_span,
+                    AnnotationKind::Help,
+                    "Use `@warnings: off;` to silence warnings.",
+                ),
+            Self::UnconstrainedPoint {
+                error_span,
+                point_name,
+            } => DiagnosticData::new(&format!(
+                "free point {}is not constrained by any rule",
+                point_name
+                    .map(|name| format!("`{name}` "))
+                    .unwrap_or_default()
+            ))
+            .add_span(error_span)
+            .add_annotation(
+                error_span,
+                AnnotationKind::Note,
+                "The point will be placed at random and has no effect on the figure.",
+            ),
+            Self::UnconstrainedDrawnPoint {
+                error_span,
+                point_name,
+            } => DiagnosticData::new(&format!(
+                "free point {}is unconstrained, but drawn",
+                point_name
+                    .map(|name| format!("`{name}` "))
+                    .unwrap_or_default()
+            ))
+            .add_span(error_span)
+            .add_annotation(
+                error_span,
+                AnnotationKind::Note,
+                "The point will be placed at random.",
+            ),
+            Self::TriviallyTrueRule { error_span } => {
+                DiagnosticData::new("rule is always satisfied")
+                    .add_span(error_span)
+                    .add_annotation(
+                        error_span,
+                        AnnotationKind::Note,
+                        "The rule has no effect on the figure and will be removed.",
+                    )
+            }
         }
     }
 }
@@ -792,9 +1146,7 @@ impl Display for ComplexUnit {
             if !self.0[i].is_zero() {
                 let name = match i {
                     0 => "Distance",
-                    1 => "Point",
-                    2 => "Angle",
-                    3 => "Line",
+                    1 => "Angle",
                     _ => unreachable!(),
                 };
 
@@ -5,11 +5,13 @@
 use crate::figure::Item;
 use crate::math::optimizations::ZeroLineDst;
 use crate::token::number::{CompExponent, ProcNum};
+use crate::token::Span;
+use crate::unit;
 use crate::unroll::figure::Node;
 use crate::unroll::flags::Flag;
 use derive_recursive::Recursive;
 use geo_aid_figure::{EntityIndex as EntityId, VarIndex};
-use num_traits::{FromPrimitive, One, Zero};
+use num_traits::{FromPrimitive, One, Signed, Zero};
 use serde::Serialize;
 use std::any::Any;
 use std::cell::OnceCell;
@@ -28,10 +30,11 @@ use super::unroll::GetData;
 use super::{
     figure::Figure,
     unroll::{
-        self, Circle as UnrolledCircle, Displayed, Expr as Unrolled, Line as UnrolledLine,
-        NumberData as UnrolledNumber, Point as UnrolledPoint, UnrolledRule, UnrolledRuleKind,
+        self, context::CompileContext, AnyExpr, Circle as UnrolledCircle, Displayed,
+        Expr as Unrolled, Line as UnrolledLine, NumberData as UnrolledNumber,
+        Point as UnrolledPoint, UnrolledRule, UnrolledRuleKind,
     },
-    ComplexUnit, Error, SimpleUnit,
+    ComplexUnit, Error, SimpleUnit, Warning,
 };
 
 mod optimizations;
@@ -41,6 +44,48 @@ mod optimizations;
 #[derive(Debug, Clone)]
 pub struct Optimizations {}
 
+/// The `figure` flag group. The figure size set in the script
+/// takes precedence over the one given by the caller.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FigureFlags {
+    /// The width of the figure, if set.
+    pub width: Option<f64>,
+    /// The height of the figure, if set.
+    pub height: Option<f64>,
+}
+
+/// An engine selected with the `engine` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineKind {
+    /// Gradient-Led Iterative Descent Engine
+    Glide,
+    /// Random Adjustment Generation Engine
+    Rage,
+    /// Simulated annealing
+    Sa,
+}
+
+/// The `glide` flag group.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlideFlags {
+    /// How strictly the rules are applied, if set.
+    pub strictness: Option<f64>,
+}
+
+/// The `rage` flag group.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RageFlags {
+    /// The maximal adjustment of an adjustable, if set.
+    pub max_adjustment: Option<f64>,
+}
+
+/// The `sa` flag group.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SaFlags {
+    /// The maximal adjustment of an adjustable, if set.
+    pub max_adjustment: Option<f64>,
+}
+
 /// Compiler flags.
 #[derive(Debug, Clone)]
 pub struct Flags {
@@ -48,6 +93,22 @@ pub struct Flags {
     pub optimizations: Optimizations,
     /// Whether to include point inequalitiy rules.
     pub point_inequalities: bool,
+    /// Whether to report warnings.
+    pub warnings: bool,
+    /// The `figure` flag group.
+    pub figure: FigureFlags,
+    /// The seed for random sampling, if set. Takes precedence over the one given by the caller.
+    pub seed: Option<u64>,
+    /// The quality at which Glide stops sampling early, if set.
+    pub quality_threshold: Option<f64>,
+    /// The engine preferred by the script, if set. The caller may override it.
+    pub engine: Option<EngineKind>,
+    /// The `glide` flag group.
+    pub glide: GlideFlags,
+    /// The `rage` flag group.
+    pub rage: RageFlags,
+    /// The `sa` flag group.
+    pub sa: SaFlags,
 }
 
 impl Default for Flags {
@@ -55,6 +116,14 @@ impl Default for Flags {
         Self {
             optimizations: Optimizations {},
             point_inequalities: false,
+            warnings: true,
+            figure: FigureFlags::default(),
+            seed: None,
+            quality_threshold: None,
+            engine: None,
+            glide: GlideFlags::default(),
+            rage: RageFlags::default(),
+            sa: SaFlags::default(),
         }
     }
 }
@@ -104,6 +173,18 @@ impl DeepClone for ProcNum {
     }
 }
 
+impl DeepClone for u8 {
+    fn deep_clone(&self, _math: &mut Math) -> Self {
+        *self
+    }
+}
+
+impl DeepClone for bool {
+    fn deep_clone(&self, _math: &mut Math) -> Self {
+        *self
+    }
+}
+
 impl DeepClone for CompExponent {
     fn deep_clone(&self, _math: &mut Math) -> Self {
         *self
@@ -145,6 +226,18 @@ impl ContainsEntity for CompExponent {
     }
 }
 
+impl ContainsEntity for u8 {
+    fn contains_entity(&self, _entity: EntityId, _math: &Math) -> bool {
+        false
+    }
+}
+
+impl ContainsEntity for bool {
+    fn contains_entity(&self, _entity: EntityId, _math: &Math) -> bool {
+        false
+    }
+}
+
 impl ContainsEntity for ProcNum {
     fn contains_entity(&self, _entity: EntityId, _math: &Math) -> bool {
         false
@@ -180,6 +273,10 @@ pub struct ReconstructCtx<'r> {
     old_vars: &'r [Expr<()>],
     /// New, reconstructed expressions.
     new_vars: Vec<Expr<()>>,
+    /// Reconstructed expressions mapped to their indices, so that no expression is stored twice.
+    new_lookup: HashMap<Expr<()>, VarIndex>,
+    /// Old expressions that have already been reconstructed.
+    reconstructed: Vec<Option<VarIndex>>,
     /// Old entities.
     old_entities: &'r [EntityKind],
     /// New, reconstructed entities.
@@ -198,6 +295,8 @@ impl<'r> ReconstructCtx<'r> {
             entity_replacement,
             old_vars,
             new_vars: Vec::new(),
+            new_lookup: HashMap::new(),
+            reconstructed: vec![None; old_vars.len()],
             old_entities,
             new_entities: vec![None; old_entities.len()],
         };
@@ -246,6 +345,18 @@ impl Reconstruct for ProcNum {
     }
 }
 
+impl Reconstruct for u8 {
+    fn reconstruct(self, _ctx: &mut ReconstructCtx) -> Self {
+        self
+    }
+}
+
+impl Reconstruct for bool {
+    fn reconstruct(self, _ctx: &mut ReconstructCtx) -> Self {
+        self
+    }
+}
+
 impl Reconstruct for CompExponent {
     fn reconstruct(self, _ctx: &mut ReconstructCtx) -> Self {
         self
@@ -320,10 +431,24 @@ trait Normalize {
 
 impl Reconstruct for VarIndex {
     fn reconstruct(self, ctx: &mut ReconstructCtx) -> Self {
+        if let Some(index) = ctx.reconstructed[self.0].clone() {
+            return index;
+        }
+
         let expr = ctx.old_vars[self.0].clone();
         let kind = expr.kind.reconstruct(ctx);
-        ctx.new_vars.push(Expr::new(kind, expr.ty));
-        VarIndex(ctx.new_vars.len() - 1)
+        let new_vars = &mut ctx.new_vars;
+        let index = ctx
+            .new_lookup
+            .entry(Expr::new(kind, expr.ty))
+            .or_insert_with_key(|expr| {
+                new_vars.push(expr.clone());
+                VarIndex(new_vars.len() - 1)
+            })
+            .clone();
+
+        ctx.reconstructed[self.0] = Some(index.clone());
+        index
     }
 }
 
@@ -404,6 +529,21 @@ pub enum ExprKind {
     // POINT
     /// An intersection of two lines: `k` and `l`
     LineLineIntersection { k: VarIndex, l: VarIndex },
+    /// One of the intersections of a line and a circle. The two points (`index` 0 and 1)
+    /// are ordered along the line's direction.
+    LineCircleIntersection {
+        line: VarIndex,
+        circle: VarIndex,
+        index: u8,
+    },
+    /// One of the intersections of two circles. The two points (`index` 0 and 1) are ordered
+    /// along the direction of `c1 -> c2` rotated counterclockwise by a right angle,
+    /// same as for the intersection of the radical axis with either circle.
+    CircleCircleIntersection {
+        c1: VarIndex,
+        c2: VarIndex,
+        index: u8,
+    },
     /// The arithmetic mean of given point expressions
     AveragePoint { items: Vec<VarIndex> },
     /// Center of a circle.
@@ -461,6 +601,12 @@ pub enum ExprKind {
         /// Angle's second arm.
         r: VarIndex,
     },
+    /// The signed area of a triangle, positive for counterclockwise orientation.
+    ThreePointArea {
+        p: VarIndex,
+        q: VarIndex,
+        r: VarIndex,
+    },
     /// The angle described by two lines.
     TwoLineAngle { k: VarIndex, l: VarIndex },
     /// The real part of a point.
@@ -473,10 +619,18 @@ pub enum ExprKind {
     Real { number: VarIndex },
     /// Imaginary part of a number
     Imaginary { number: VarIndex },
+    /// Absolute value of a real number
+    Abs { value: VarIndex },
+    /// The smaller of two real numbers
+    Min { a: VarIndex, b: VarIndex },
+    /// The larger of two real numbers
+    Max { a: VarIndex, b: VarIndex },
     /// Natural logarithm
     Log { number: VarIndex },
     /// Exponential function (e^this)
     Exp { number: VarIndex },
+    /// Radius of a circle.
+    CircleRadius { circle: VarIndex },
     /// Sine of an angle
     Sin { angle: VarIndex },
     /// Cosine of an angle
@@ -495,12 +649,21 @@ pub enum ExprKind {
         q: VarIndex,
         r: VarIndex,
     },
+    /// A bisector of the acute angle between two lines. If `external` is set, the obtuse
+    /// angle is bisected instead. Parallel lines give the line halfway between them.
+    LineLineBisector {
+        k: VarIndex,
+        l: VarIndex,
+        external: bool,
+    },
     /// A line parallel to another `line` going through a `point`
     ParallelThrough { point: VarIndex, line: VarIndex },
     /// A line perpendicular to another `line` going through a `point`
     PerpendicularThrough { point: VarIndex, line: VarIndex },
     /// A line made from a point and a direction vector
     PointVector { point: VarIndex, vector: VarIndex },
+    /// The radical axis of two circles.
+    RadicalAxis { c1: VarIndex, c2: VarIndex },
 
     // Circle
     /// A circle constructed from its center and radius.
@@ -543,6 +706,15 @@ impl ExprKind {
             Self::Exp { .. } => 28,
             Self::DirectionVector { .. } => 29,
             Self::PointVector { .. } => 30,
+            Self::ThreePointArea { .. } => 31,
+            Self::RadicalAxis { .. } => 32,
+            Self::CircleRadius { .. } => 33,
+            Self::Abs { .. } => 34,
+            Self::Min { .. } => 35,
+            Self::Max { .. } => 36,
+            Self::LineCircleIntersection { .. } => 37,
+            Self::CircleCircleIntersection { .. } => 38,
+            Self::LineLineBisector { .. } => 39,
         }
     }
 
@@ -566,6 +738,26 @@ impl ExprKind {
                         l: other_b,
                     },
                 )
+                | (
+                    Self::Min {
+                        a: self_a,
+                        b: self_b,
+                    },
+                    Self::Min {
+                        a: other_a,
+                        b: other_b,
+                    },
+                )
+                | (
+                    Self::Max {
+                        a: self_a,
+                        b: self_b,
+                    },
+                    Self::Max {
+                        a: other_a,
+                        b: other_b,
+                    },
+                )
                 | (
                     Self::PointPointDistance {
                         p: self_a,
@@ -635,16 +827,70 @@ impl ExprKind {
                         center: other_a,
                         radius: other_b,
                     },
+                )
+                | (
+                    Self::RadicalAxis {
+                        c1: self_a,
+                        c2: self_b,
+                    },
+                    Self::RadicalAxis {
+                        c1: other_a,
+                        c2: other_b,
+                    },
                 ) => self_a
                     .compare(other_a, math)
                     .then_with(|| self_b.compare(other_b, math)),
+                (
+                    Self::LineCircleIntersection {
+                        line: self_a,
+                        circle: self_b,
+                        index: self_i,
+                    },
+                    Self::LineCircleIntersection {
+                        line: other_a,
+                        circle: other_b,
+                        index: other_i,
+                    },
+                )
+                | (
+                    Self::CircleCircleIntersection {
+                        c1: self_a,
+                        c2: self_b,
+                        index: self_i,
+                    },
+                    Self::CircleCircleIntersection {
+                        c1: other_a,
+                        c2: other_b,
+                        index: other_i,
+                    },
+                ) => self_a
+                    .compare(other_a, math)
+                    .then_with(|| self_b.compare(other_b, math))
+                    .then_with(|| self_i.cmp(other_i)),
+                (
+                    Self::LineLineBisector {
+                        k: self_a,
+                        l: self_b,
+                        external: self_e,
+                    },
+                    Self::LineLineBisector {
+                        k: other_a,
+                        l: other_b,
+                        external: other_e,
+                    },
+                ) => self_a
+                    .compare(other_a, math)
+                    .then_with(|| self_b.compare(other_b, math))
+                    .then_with(|| self_e.cmp(other_e)),
                 (
                     Self::AveragePoint { items: self_items },
                     Self::AveragePoint { items: other_items },
                 ) => self_items.compare(other_items, math),
                 (Self::CircleCenter { circle: self_x }, Self::CircleCenter { circle: other_x })
+                | (Self::CircleRadius { circle: self_x }, Self::CircleRadius { circle: other_x })
                 | (Self::PointX { point: self_x }, Self::PointX { point: other_x })
-                | (Self::PointY { point: self_x }, Self::PointY { point: other_x }) => {
+                | (Self::PointY { point: self_x }, Self::PointY { point: other_x })
+                | (Self::Abs { value: self_x }, Self::Abs { value: other_x }) => {
                     self_x.compare(other_x, math)
                 }
                 (
@@ -708,6 +954,18 @@ impl ExprKind {
                         r: other_r,
                     },
                 )
+                | (
+                    Self::ThreePointArea {
+                        p: self_p,
+                        q: self_q,
+                        r: self_r,
+                    },
+                    Self::ThreePointArea {
+                        p: other_p,
+                        q: other_q,
+                        r: other_r,
+                    },
+                )
                 | (
                     Self::AngleBisector {
                         p: self_p,
@@ -733,6 +991,8 @@ impl ExprKind {
         match self {
             Self::Entity { id } => entities[id.0].get_type(expressions, entities),
             Self::LineLineIntersection { .. }
+            | Self::LineCircleIntersection { .. }
+            | Self::CircleCircleIntersection { .. }
             | Self::AveragePoint { .. }
             | Self::CircleCenter { .. }
             | Self::ComplexToPoint { .. } => ExprType::Point,
@@ -744,6 +1004,7 @@ impl ExprKind {
             | Self::PointLineDistance { .. }
             | Self::ThreePointAngle { .. }
             | Self::ThreePointAngleDir { .. }
+            | Self::ThreePointArea { .. }
             | Self::TwoLineAngle { .. }
             | Self::Sin { .. }
             | Self::Cos { .. }
@@ -752,15 +1013,21 @@ impl ExprKind {
             | Self::PointY { .. }
             | Self::Real { .. }
             | Self::Imaginary { .. }
+            | Self::Abs { .. }
+            | Self::Min { .. }
+            | Self::Max { .. }
             | Self::Log { .. }
             | Self::Exp { .. }
             | Self::DirectionVector { .. }
+            | Self::CircleRadius { .. }
             | Self::PointToComplex { .. } => ExprType::Number,
             Self::PointPoint { .. }
             | Self::AngleBisector { .. }
+            | Self::LineLineBisector { .. }
             | Self::ParallelThrough { .. }
             | Self::PerpendicularThrough { .. }
-            | Self::PointVector { .. } => ExprType::Line,
+            | Self::PointVector { .. }
+            | Self::RadicalAxis { .. } => ExprType::Line,
             Self::ConstructCircle { .. } => ExprType::Circle,
         }
     }
@@ -771,6 +1038,18 @@ impl From<ExprKind> for geo_aid_figure::ExpressionKind {
         match value {
             ExprKind::Entity { id } => Self::Entity { id },
             ExprKind::LineLineIntersection { k, l } => Self::LineLineIntersection { k, l },
+            ExprKind::LineCircleIntersection {
+                line,
+                circle,
+                index,
+            } => Self::LineCircleIntersection {
+                line,
+                circle,
+                index,
+            },
+            ExprKind::CircleCircleIntersection { c1, c2, index } => {
+                Self::CircleCircleIntersection { c1, c2, index }
+            }
             ExprKind::AveragePoint { items } => Self::AveragePoint { items },
             ExprKind::CircleCenter { circle } => Self::CircleCenter { circle },
             ExprKind::ComplexToPoint { number } => Self::ComplexToPoint { number },
@@ -789,6 +1068,7 @@ impl From<ExprKind> for geo_aid_figure::ExpressionKind {
             ExprKind::ThreePointAngleDir { p, q, r } => {
                 Self::ThreePointAngleDir { a: p, b: q, c: r }
             }
+            ExprKind::ThreePointArea { p, q, r } => Self::ThreePointArea { a: p, b: q, c: r },
             ExprKind::TwoLineAngle { k, l } => Self::TwoLineAngle { k, l },
             ExprKind::Sin { angle } => Self::Sin { angle },
             ExprKind::Cos { angle } => Self::Cos { angle },
@@ -799,15 +1079,23 @@ impl From<ExprKind> for geo_aid_figure::ExpressionKind {
             ExprKind::PointToComplex { point } => Self::PointToComplex { point },
             ExprKind::Real { number } => Self::Real { number },
             ExprKind::Imaginary { number } => Self::Imaginary { number },
+            ExprKind::Abs { value } => Self::Abs { value },
+            ExprKind::Min { a, b } => Self::Min { a, b },
+            ExprKind::Max { a, b } => Self::Max { a, b },
             ExprKind::Log { number } => Self::Log { number },
             ExprKind::Exp { number } => Self::Exp { number },
+            ExprKind::CircleRadius { circle } => Self::CircleRadius { circle },
             ExprKind::PointPoint { p, q } => Self::PointPointLine { p, q },
             ExprKind::PointVector { point, vector } => Self::PointVectorLine { point, vector },
             ExprKind::AngleBisector { p, q, r } => Self::AngleBisector { p, q, r },
+            ExprKind::LineLineBisector { k, l, external } => {
+                Self::LineLineBisector { k, l, external }
+            }
             ExprKind::ParallelThrough { point, line } => Self::ParallelThrough { point, line },
             ExprKind::PerpendicularThrough { point, line } => {
                 Self::PerpendicularThrough { point, line }
             }
+            ExprKind::RadicalAxis { c1, c2 } => Self::RadicalAxis { c1, c2 },
             ExprKind::ConstructCircle { center, radius } => {
                 Self::ConstructCircle { center, radius }
             }
@@ -832,6 +1120,7 @@ impl FindEntities for ExprKind {
                 set.extend(items.iter().flat_map(|x| previous[x.0].iter().copied()));
             }
             Self::CircleCenter { circle: x }
+            | Self::CircleRadius { circle: x }
             | Self::PointX { point: x }
             | Self::PointY { point: x }
             | Self::Sin { angle: x }
@@ -839,6 +1128,7 @@ impl FindEntities for ExprKind {
             | Self::Exponentiation { value: x, .. }
             | Self::PointToComplex { point: x }
             | Self::ComplexToPoint { number: x }
+            | Self::Abs { value: x }
             | Self::Log { number: x }
             | Self::Exp { number: x }
             | Self::DirectionVector { line: x }
@@ -856,8 +1146,15 @@ impl FindEntities for ExprKind {
             }
             Self::PointPointDistance { p: a, q: b }
             | Self::PointLineDistance { point: a, line: b }
+            | Self::Min { a, b }
+            | Self::Max { a, b }
             | Self::TwoLineAngle { k: a, l: b }
             | Self::LineLineIntersection { k: a, l: b }
+            | Self::LineLineBisector { k: a, l: b, .. }
+            | Self::LineCircleIntersection {
+                line: a, circle: b, ..
+            }
+            | Self::CircleCircleIntersection { c1: a, c2: b, .. }
             | Self::ParallelThrough { point: a, line: b }
             | Self::PerpendicularThrough { point: a, line: b }
             | Self::PointPoint { p: a, q: b }
@@ -866,6 +1163,7 @@ impl FindEntities for ExprKind {
                 point: a,
                 vector: b,
             }
+            | Self::RadicalAxis { c1: a, c2: b }
             | Self::ConstructCircle {
                 center: a,
                 radius: b,
@@ -875,6 +1173,7 @@ impl FindEntities for ExprKind {
             }
             Self::ThreePointAngle { p, q, r }
             | Self::ThreePointAngleDir { p, q, r }
+            | Self::ThreePointArea { p, q, r }
             | Self::AngleBisector { p, q, r } => {
                 set.extend(previous[p.0].iter().copied());
                 set.extend(previous[q.0].iter().copied());
@@ -900,16 +1199,41 @@ impl FromUnrolled<UnrolledPoint> for ExprKind {
                 k: math.load(a),
                 l: math.load(b),
             },
+            UnrolledPoint::LineCircleIntersection(line, circle, index) => {
+                ExprKind::LineCircleIntersection {
+                    line: math.load(line),
+                    circle: math.load(circle),
+                    index: *index,
+                }
+            }
+            UnrolledPoint::CircleCircleIntersection(c1, c2, index) => {
+                ExprKind::CircleCircleIntersection {
+                    c1: math.load(c1),
+                    c2: math.load(c2),
+                    index: *index,
+                }
+            }
             UnrolledPoint::Average(exprs) => ExprKind::AveragePoint {
                 items: exprs.iter().map(|x| math.load(x)).collect(),
             },
             UnrolledPoint::CircleCenter(circle) => match circle.get_data() {
                 UnrolledCircle::Circle(center, _) => return math.load_no_store(center),
+                UnrolledCircle::Free => ExprKind::CircleCenter {
+                    circle: math.load(circle),
+                },
                 UnrolledCircle::Generic(_) => unreachable!(),
             },
             UnrolledPoint::Free => ExprKind::Entity {
                 id: math.add_point(),
             },
+            UnrolledPoint::OnSegment(p, q) => {
+                let p = math.load(p);
+                let q = math.load(q);
+
+                ExprKind::Entity {
+                    id: math.add_entity(EntityKind::PointOnSegment { p, q }),
+                }
+            }
             UnrolledPoint::FromComplex(number) => ExprKind::ComplexToPoint {
                 number: math.load(number),
             },
@@ -958,6 +1282,9 @@ impl FromUnrolled<unroll::Number> for ExprKind {
             }
             UnrolledNumber::CircleRadius(circle) => match circle.get_data() {
                 UnrolledCircle::Circle(_, radius) => math.load_no_store(radius),
+                UnrolledCircle::Free => ExprKind::CircleRadius {
+                    circle: math.load(circle),
+                },
                 UnrolledCircle::Generic(_) => unreachable!(),
             },
             UnrolledNumber::Free => ExprKind::Entity {
@@ -992,6 +1319,11 @@ impl FromUnrolled<unroll::Number> for ExprKind {
                 q: math.load(q),
                 r: math.load(r),
             },
+            UnrolledNumber::ThreePointArea(p, q, r) => ExprKind::ThreePointArea {
+                p: math.load(p),
+                q: math.load(q),
+                r: math.load(r),
+            },
             UnrolledNumber::TwoLineAngle(k, l) => ExprKind::TwoLineAngle {
                 k: math.load(k),
                 l: math.load(l),
@@ -1028,6 +1360,17 @@ impl FromUnrolled<unroll::Number> for ExprKind {
             UnrolledNumber::Imaginary(number) => ExprKind::Imaginary {
                 number: math.load(number),
             },
+            UnrolledNumber::Abs(number) => ExprKind::Abs {
+                value: math.load(number),
+            },
+            UnrolledNumber::Min(a, b) => ExprKind::Min {
+                a: math.load(a),
+                b: math.load(b),
+            },
+            UnrolledNumber::Max(a, b) => ExprKind::Max {
+                a: math.load(a),
+                b: math.load(b),
+            },
             UnrolledNumber::Log(number) => ExprKind::Log {
                 number: math.load(number),
             },
@@ -1054,6 +1397,11 @@ impl FromUnrolled<UnrolledLine> for ExprKind {
                 q: math.load(b),
                 r: math.load(c),
             },
+            UnrolledLine::LineLineBisector(k, l, external) => Self::LineLineBisector {
+                k: math.load(k),
+                l: math.load(l),
+                external: *external,
+            },
             UnrolledLine::PerpendicularThrough(k, p) => {
                 // Remove unnecessary intermediates
                 match k.get_data() {
@@ -1092,6 +1440,10 @@ impl FromUnrolled<UnrolledLine> for ExprKind {
                 point: math.load(point),
                 vector: math.load(vector),
             },
+            UnrolledLine::RadicalAxis(c1, c2) => Self::RadicalAxis {
+                c1: math.load(c1),
+                c2: math.load(c2),
+            },
             UnrolledLine::Generic(_) => unreachable!(),
         };
 
@@ -1102,11 +1454,14 @@ impl FromUnrolled<UnrolledLine> for ExprKind {
 
 impl FromUnrolled<UnrolledCircle> for ExprKind {
     fn load(expr: &Unrolled<UnrolledCircle>, math: &mut Expand) -> Self {
-        let mut kind = match expr.data.as_ref() {
+        let mut kind = match expr.get_data() {
             UnrolledCircle::Circle(center, radius) => Self::ConstructCircle {
                 center: math.load(center),
                 radius: math.load(radius),
             },
+            UnrolledCircle::Free => Self::Entity {
+                id: math.add_circle(),
+            },
             UnrolledCircle::Generic(_) => unreachable!(),
         };
 
@@ -1127,6 +1482,7 @@ impl Normalize for ExprKind {
 
         match self {
             Self::CircleCenter { .. }
+            | Self::CircleRadius { .. }
             | Self::PointLineDistance { .. }
             | Self::PointX { .. }
             | Self::PointY { .. }
@@ -1134,21 +1490,34 @@ impl Normalize for ExprKind {
             | Self::Cos { .. }
             | Self::DirectionVector { .. }
             | Self::Atan2 { .. }
-            | Self::Exponentiation { .. }
             | Self::ConstructCircle { .. }
             | Self::Const { .. }
             | Self::ThreePointAngleDir { .. } // DO NOT NORMALIZE DIRECTED ANGLES
+            | Self::ThreePointArea { .. } // NOR SIGNED AREAS
             | Self::Entity { .. }
             | Self::ComplexToPoint { .. }
             | Self::PointToComplex { .. }
+            | Self::Abs { .. }
             | Self::Log { .. }
             | Self::Exp { .. }
             | Self::PointVector { .. }
+            | Self::LineCircleIntersection { .. }
             | Self::Real { .. }
             | Self::Imaginary { .. } => (),
+            Self::CircleCircleIntersection { c1, c2, index } => {
+                // Swapping the circles also swaps the intersection points.
+                if math.compare(c1, c2) == Ordering::Greater {
+                    mem::swap(c1, c2);
+                    *index = 1 - *index;
+                }
+            }
             Self::LineLineIntersection { k: a, l: b }
+            | Self::LineLineBisector { k: a, l: b, .. }
             | Self::PointPoint { p: a, q: b }
             | Self::TwoLineAngle { k: a, l: b }
+            | Self::Min { a, b }
+            | Self::Max { a, b }
+            | Self::RadicalAxis { c1: a, c2: b }
             | Self::AngleBisector { p: a, r: b, .. }
             | Self::ThreePointAngle { p: a, r: b, .. }
             | Self::PointPointDistance { p: a, q: b } => {
@@ -1157,6 +1526,23 @@ impl Normalize for ExprKind {
             Self::AveragePoint { items } => {
                 items.sort_by(&cmp);
             }
+            Self::Exponentiation { value, exponent } => {
+                // Constants raised to integer powers are folded.
+                if let ExprKind::Const { value } = &math.at(value).kind {
+                    let power = exponent
+                        .is_integer()
+                        .then(|| i32::try_from(exponent.to_integer()).ok())
+                        .flatten();
+
+                    if let Some(power) = power {
+                        if power >= 0 || !value.is_zero() {
+                            new_self = Some(Self::Const {
+                                value: ProcNum(value.0.powi(power)),
+                            });
+                        }
+                    }
+                }
+            }
             Self::Sum { plus, minus } => {
                 normalize_sum(plus, minus, math);
                 if plus.len() == 1 && minus.is_empty() {
@@ -1473,6 +1859,9 @@ pub enum RuleKind {
     PointEq(VarIndex, VarIndex),
     /// Equality of two numbers
     NumberEq(VarIndex, VarIndex),
+    /// Equality of two distances. Unlike [`RuleKind::NumberEq`], its error becomes relative
+    /// to the distances compared when they're small, so that shrinking the figure isn't rewarded.
+    DistanceEq(VarIndex, VarIndex),
     /// a > b
     Gt(VarIndex, VarIndex),
     /// At least one of the rules must be satisfied
@@ -1486,7 +1875,9 @@ pub enum RuleKind {
 impl Display for RuleKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            RuleKind::PointEq(a, b) | RuleKind::NumberEq(a, b) => write!(f, "{a} = {b}"),
+            RuleKind::PointEq(a, b) | RuleKind::NumberEq(a, b) | RuleKind::DistanceEq(a, b) => {
+                write!(f, "{a} = {b}")
+            }
             RuleKind::Gt(a, b) => write!(f, "{a} > {b}"),
             RuleKind::Alternative(v) => {
                 for kind in v {
@@ -1511,7 +1902,10 @@ impl FindEntities for RuleKind {
         let mut set = HashSet::new();
 
         match self {
-            Self::PointEq(a, b) | Self::NumberEq(a, b) | Self::Gt(a, b) => {
+            Self::PointEq(a, b)
+            | Self::NumberEq(a, b)
+            | Self::DistanceEq(a, b)
+            | Self::Gt(a, b) => {
                 set.extend(previous[a.0].iter().copied());
                 set.extend(previous[b.0].iter().copied());
             }
@@ -1567,7 +1961,10 @@ impl Reconstruct for Rule {
 impl Reindex for RuleKind {
     fn reindex(&mut self, map: &IndexMap) {
         match self {
-            Self::PointEq(a, b) | Self::NumberEq(a, b) | Self::Gt(a, b) => {
+            Self::PointEq(a, b)
+            | Self::NumberEq(a, b)
+            | Self::DistanceEq(a, b)
+            | Self::Gt(a, b) => {
                 a.reindex(map);
                 b.reindex(map);
             }
@@ -1594,7 +1991,22 @@ impl RuleKind {
     fn load(rule: &UnrolledRule, math: &mut Expand) -> Self {
         let mut mathed = match &rule.kind {
             UnrolledRuleKind::PointEq(a, b) => Self::PointEq(math.load(a), math.load(b)),
-            UnrolledRuleKind::NumberEq(a, b) => Self::NumberEq(math.load(a), math.load(b)),
+            UnrolledRuleKind::NumberEq(a, b) => {
+                let (a_loaded, b_loaded) = (math.load(a), math.load(b));
+                let is_zero = |x: &VarIndex| match &math.at(x).kind {
+                    ExprKind::Const { value } => value.is_zero(),
+                    _ => false,
+                };
+
+                // Distances compared to zero, like in `lies_on`, keep the absolute error,
+                // as the relative one would be constant for small distances.
+                if a.data.unit == Some(unit::DISTANCE) && !is_zero(&a_loaded) && !is_zero(&b_loaded)
+                {
+                    Self::DistanceEq(a_loaded, b_loaded)
+                } else {
+                    Self::NumberEq(a_loaded, b_loaded)
+                }
+            }
             UnrolledRuleKind::Gt(a, b) => Self::Gt(math.load(a), math.load(b)),
             UnrolledRuleKind::Alternative(rules) => {
                 Self::Alternative(rules.iter().map(|x| Self::load(x, math)).collect())
@@ -1626,10 +2038,199 @@ impl Rule {
     }
 }
 
+/// What is known about the sign of an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sign {
+    Zero,
+    Positive,
+    Negative,
+    NonNegative,
+    NonPositive,
+    Unknown,
+}
+
+impl Sign {
+    /// Whether the expression is certainly `>= 0`.
+    fn is_non_negative(self) -> bool {
+        matches!(self, Self::Zero | Self::Positive | Self::NonNegative)
+    }
+
+    /// Whether the expression is certainly `<= 0`.
+    fn is_non_positive(self) -> bool {
+        matches!(self, Self::Zero | Self::Negative | Self::NonPositive)
+    }
+
+    /// Whether the expression is certainly not zero.
+    fn is_strict(self) -> bool {
+        matches!(self, Self::Positive | Self::Negative)
+    }
+
+    /// Sign of `-x`.
+    fn neg(self) -> Self {
+        match self {
+            Self::Positive => Self::Negative,
+            Self::Negative => Self::Positive,
+            Self::NonNegative => Self::NonPositive,
+            Self::NonPositive => Self::NonNegative,
+            Self::Zero | Self::Unknown => self,
+        }
+    }
+
+    /// Sign of `x + y`.
+    fn add(self, other: Self) -> Self {
+        let strict = self.is_strict() || other.is_strict();
+
+        if self == Self::Zero {
+            other
+        } else if other == Self::Zero {
+            self
+        } else if self.is_non_negative() && other.is_non_negative() {
+            if strict {
+                Self::Positive
+            } else {
+                Self::NonNegative
+            }
+        } else if self.is_non_positive() && other.is_non_positive() {
+            if strict {
+                Self::Negative
+            } else {
+                Self::NonPositive
+            }
+        } else {
+            Self::Unknown
+        }
+    }
+
+    /// Sign of `x * y`.
+    fn mul(self, other: Self) -> Self {
+        if self == Self::Zero || other == Self::Zero {
+            return Self::Zero;
+        } else if self == Self::Unknown || other == Self::Unknown {
+            return Self::Unknown;
+        }
+
+        let negative = self.is_non_positive() != other.is_non_positive();
+        let strict = self.is_strict() && other.is_strict();
+
+        match (negative, strict) {
+            (false, true) => Self::Positive,
+            (false, false) => Self::NonNegative,
+            (true, true) => Self::Negative,
+            (true, false) => Self::NonPositive,
+        }
+    }
+}
+
+impl Math {
+    /// Find out what can be said about the sign of a number expression without generating the figure.
+    fn sign(&self, expr: &VarIndex) -> Sign {
+        match &self.at(expr).kind {
+            ExprKind::Const { value } => {
+                if !value.0.im.is_zero() {
+                    Sign::Unknown
+                } else if value.0.re.is_zero() {
+                    Sign::Zero
+                } else if value.0.re.is_positive() {
+                    Sign::Positive
+                } else {
+                    Sign::Negative
+                }
+            }
+            ExprKind::PointPointDistance { .. } | ExprKind::PointLineDistance { .. } => {
+                Sign::NonNegative
+            }
+            // The distance unit is always considered positive.
+            ExprKind::Entity { id } => match self.entities[id.0] {
+                EntityKind::DistanceUnit => Sign::Positive,
+                _ => Sign::Unknown,
+            },
+            ExprKind::Sum { plus, minus } => plus
+                .iter()
+                .map(|x| self.sign(x))
+                .chain(minus.iter().map(|x| self.sign(x).neg()))
+                .fold(Sign::Zero, Sign::add),
+            ExprKind::Product { times, by } => times
+                .iter()
+                .map(|x| self.sign(x))
+                // Division by zero tells us nothing.
+                .chain(by.iter().map(|x| match self.sign(x) {
+                    Sign::Zero => Sign::Unknown,
+                    sign => sign,
+                }))
+                .fold(Sign::Positive, Sign::mul),
+            ExprKind::Exponentiation { value, exponent } => {
+                let sign = self.sign(value);
+
+                if sign == Sign::Positive {
+                    Sign::Positive
+                } else if exponent.is_integer() && exponent.to_integer() % 2 == 0 {
+                    Sign::NonNegative
+                } else {
+                    Sign::Unknown
+                }
+            }
+            _ => Sign::Unknown,
+        }
+    }
+}
+
+impl RuleKind {
+    /// Check whether the rule's value is known without generating the figure.
+    /// This is the case if both its sides are constant or they have incompatible signs.
+    ///
+    /// # Returns
+    /// `Some(true)` if the rule is always satisfied, `Some(false)` if it never is
+    /// and `None` if it depends on the figure.
+    fn evaluate_constant(&self, math: &Math) -> Option<bool> {
+        match self {
+            Self::NumberEq(a, b) | Self::DistanceEq(a, b) => {
+                if let (ExprKind::Const { value: a }, ExprKind::Const { value: b }) =
+                    (&math.at(a).kind, &math.at(b).kind)
+                {
+                    return Some(a == b);
+                }
+
+                let (a, b) = (math.sign(a), math.sign(b));
+                let never = (a.is_strict() && a.is_non_negative() && b.is_non_positive())
+                    || (a.is_strict() && a.is_non_positive() && b.is_non_negative())
+                    || (b.is_strict() && b.is_non_negative() && a.is_non_positive())
+                    || (b.is_strict() && b.is_non_positive() && a.is_non_negative());
+
+                never.then_some(false)
+            }
+            Self::Gt(a, b) => {
+                if let (ExprKind::Const { value: a }, ExprKind::Const { value: b }) =
+                    (&math.at(a).kind, &math.at(b).kind)
+                {
+                    return Some(a.0.re > b.0.re);
+                }
+
+                (math.sign(a).is_non_positive() && math.sign(b).is_non_negative()).then_some(false)
+            }
+            Self::Alternative(rules) => {
+                let values: Vec<_> = rules
+                    .iter()
+                    .map(|rule| rule.evaluate_constant(math))
+                    .collect();
+
+                if values.contains(&Some(true)) {
+                    Some(true)
+                } else if values.iter().all(|v| *v == Some(false)) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            Self::Invert(rule) => rule.evaluate_constant(math).map(|v| !v),
+            Self::PointEq(_, _) | Self::Bias => None,
+        }
+    }
+}
+
 impl Normalize for RuleKind {
     fn normalize(&mut self, math: &mut Math) {
         match self {
-            Self::PointEq(a, b) | Self::NumberEq(a, b) => {
+            Self::PointEq(a, b) | Self::NumberEq(a, b) | Self::DistanceEq(a, b) => {
                 if math.compare(a, b) == Ordering::Greater {
                     mem::swap(a, b);
                 }
@@ -1657,6 +2258,22 @@ pub struct Adjusted {
     pub entities: Vec<EntityKind>,
 }
 
+impl Adjusted {
+    /// Find the entities affected by each rule and store them in the rules.
+    pub fn find_rule_entities(&mut self) {
+        let mut found_entities = Vec::new();
+        for expr in &self.variables {
+            let found = expr.find_entities(&found_entities, &self.entities);
+            found_entities.push(found);
+        }
+
+        for rule in &mut self.rules {
+            let entities = rule.kind.find_entities(&found_entities, &self.entities);
+            rule.entities = entities.into_iter().collect();
+        }
+    }
+}
+
 /// The full Math IR, the ultimate result of the entire compiler
 #[derive(Debug)]
 pub struct Intermediate {
@@ -1666,6 +2283,8 @@ pub struct Intermediate {
     pub adjusted: Adjusted,
     /// Compiler flags.
     pub flags: Flags,
+    /// Warnings collected during compilation.
+    pub warnings: Vec<Warning>,
 }
 
 /// An entity along with some metadata.
@@ -1682,8 +2301,10 @@ impl<M> Entity<M> {
         match &self.kind {
             EntityKind::FreePoint
             | EntityKind::PointOnLine { .. }
+            | EntityKind::PointOnSegment { .. }
             | EntityKind::PointOnCircle { .. } => ExprType::Point,
             EntityKind::FreeReal | EntityKind::DistanceUnit => ExprType::Number,
+            EntityKind::FreeCircle => ExprType::Circle,
             EntityKind::Bind(expr) => expressions[expr.0].get_type(expressions, entities),
         }
     }
@@ -1711,12 +2332,16 @@ pub enum EntityKind {
     FreePoint,
     /// A point bound to a specific line, only one relative degree of freedom.
     PointOnLine { line: VarIndex },
+    /// A point bound to the segment `pq`, only one relative degree of freedom.
+    PointOnSegment { p: VarIndex, q: VarIndex },
     /// A point bound to a specific circle, only one relative degree of freedom.
     PointOnCircle { circle: VarIndex },
     /// A free real with one degree of freedom
     FreeReal,
     /// A special distance unit entity, effectively a free real.
     DistanceUnit,
+    /// A free circle with three degrees of freedom (center and radius).
+    FreeCircle,
     /// A bind. Never shows up past the compilation stage. It serves as a temporary
     /// value in-between compilation steps.
     Bind(VarIndex),
@@ -1732,7 +2357,10 @@ impl FindEntities for EntityKind {
             Self::PointOnLine { line: var } | Self::PointOnCircle { circle: var } => {
                 previous[var.0].clone()
             }
-            Self::FreePoint | Self::FreeReal | Self::DistanceUnit => HashSet::new(),
+            Self::PointOnSegment { p, q } => previous[p.0].union(&previous[q.0]).copied().collect(),
+            Self::FreePoint | Self::FreeReal | Self::DistanceUnit | Self::FreeCircle => {
+                HashSet::new()
+            }
             Self::Bind(_) => unreachable!(),
         }
     }
@@ -1743,9 +2371,11 @@ impl From<EntityKind> for geo_aid_figure::EntityKind {
         match value {
             EntityKind::FreePoint => Self::FreePoint,
             EntityKind::PointOnLine { line } => Self::PointOnLine { line },
+            EntityKind::PointOnSegment { p, q } => Self::PointOnSegment { p, q },
             EntityKind::PointOnCircle { circle } => Self::PointOnCircle { circle },
             EntityKind::FreeReal => Self::FreeReal,
             EntityKind::DistanceUnit => Self::DistanceUnit,
+            EntityKind::FreeCircle => Self::FreeCircle,
             EntityKind::Bind(_) => unreachable!(),
         }
     }
@@ -1754,8 +2384,12 @@ impl From<EntityKind> for geo_aid_figure::EntityKind {
 impl Reindex for EntityKind {
     fn reindex(&mut self, map: &IndexMap) {
         match self {
-            Self::FreePoint | Self::DistanceUnit | Self::FreeReal => {}
+            Self::FreePoint | Self::DistanceUnit | Self::FreeReal | Self::FreeCircle => {}
             Self::PointOnLine { line } => line.reindex(map),
+            Self::PointOnSegment { p, q } => {
+                p.reindex(map);
+                q.reindex(map);
+            }
             Self::PointOnCircle { circle } => circle.reindex(map),
             Self::Bind(_) => unreachable!("Should not appear"),
         }
@@ -1779,6 +2413,37 @@ impl ContainsEntity for EntityId {
     }
 }
 
+impl ContainsEntity for Item {
+    fn contains_entity(&self, entity: EntityId, math: &Math) -> bool {
+        match self {
+            Self::Point(v) => v.id.contains_entity(entity, math),
+            Self::Circle(v) => v.id.contains_entity(entity, math),
+            Self::Line(v) => v.id.contains_entity(entity, math),
+            Self::Annotation(v) => v.id.contains_entity(entity, math),
+            Self::Ray(v) => {
+                v.p_id.contains_entity(entity, math) || v.q_id.contains_entity(entity, math)
+            }
+            Self::Segment(v) => {
+                v.p_id.contains_entity(entity, math) || v.q_id.contains_entity(entity, math)
+            }
+            Self::Angle(v) => {
+                v.p_id.contains_entity(entity, math)
+                    || v.q_id.contains_entity(entity, math)
+                    || v.r_id.contains_entity(entity, math)
+            }
+            Self::Arc(v) => {
+                v.circle_id.contains_entity(entity, math)
+                    || v.start_id.contains_entity(entity, math)
+                    || v.end_id.contains_entity(entity, math)
+                    || v.through_id
+                        .as_ref()
+                        .is_some_and(|id| id.contains_entity(entity, math))
+            }
+            Self::Polygon(v) => v.ids.contains_entity(entity, math),
+        }
+    }
+}
+
 /// A context struct for loading unrolled data.
 #[derive(Debug, Clone, Default)]
 pub struct Expand {
@@ -1792,6 +2457,32 @@ pub struct Expand {
     /// It's an ugly solution, but it works. I'm most likely going to come back to this one with some
     /// new ideas for solving the issue.
     pub rc_keepalive: Vec<Rc<dyn Any>>,
+    /// Script spans of the loaded expressions.
+    pub spans: Vec<VarSpan>,
+}
+
+/// A script span an expression was loaded from.
+#[derive(Debug, Clone)]
+pub struct VarSpan {
+    /// The loaded expression
+    pub id: VarIndex,
+    /// The span of its unrolled counterpart
+    pub span: Span,
+}
+
+impl Reindex for VarSpan {
+    fn reindex(&mut self, map: &IndexMap) {
+        self.id.reindex(map);
+    }
+}
+
+impl Reconstruct for VarSpan {
+    fn reconstruct(self, ctx: &mut ReconstructCtx) -> Self {
+        Self {
+            id: self.id.reconstruct(ctx),
+            ..self
+        }
+    }
 }
 
 impl Deref for Expand {
@@ -1817,6 +2508,9 @@ pub struct Math {
     pub dst_var: OnceCell<EntityId>,
     /// Collected expressions in flattened layout.
     pub expr_record: Vec<Expr<()>>,
+    /// Stored expressions mapped to their indices. Structurally equal expressions
+    /// are only stored once.
+    expr_lookup: HashMap<Expr<()>, VarIndex>,
 }
 
 impl Expand {
@@ -1829,7 +2523,16 @@ impl Expand {
         ExprKind: FromUnrolled<T>,
     {
         let expr = self.load_no_store(unrolled);
-        self.store(expr, T::get_math_type())
+        let id = self.store(expr, T::get_math_type());
+
+        if !unrolled.span.is_empty() {
+            self.spans.push(VarSpan {
+                id: id.clone(),
+                span: unrolled.span,
+            });
+        }
+
+        id
     }
 
     /// Load an unrolled expression without storing it.
@@ -1860,11 +2563,34 @@ impl Expand {
 }
 
 impl Math {
-    /// Store an expression in the expression record.
+    /// Store an expression in the expression record. If a structurally equal expression
+    /// has already been stored, its index is returned instead.
     #[must_use]
     pub fn store(&mut self, expr: ExprKind, ty: ExprType) -> VarIndex {
-        self.expr_record.push(Expr::new(expr, ty));
-        VarIndex(self.expr_record.len() - 1)
+        let expr_record = &mut self.expr_record;
+        self.expr_lookup
+            .entry(Expr::new(expr, ty))
+            .or_insert_with_key(|expr| {
+                expr_record.push(expr.clone());
+                VarIndex(expr_record.len() - 1)
+            })
+            .clone()
+    }
+
+    /// Replace the expression record, updating the lookup used by [`Math::store`].
+    fn set_record(&mut self, expr_record: Vec<Expr<()>>) {
+        self.expr_record = expr_record;
+        self.update_lookup();
+    }
+
+    /// Rebuild the lookup used by [`Math::store`]. Must be called after expressions
+    /// in the record are modified.
+    fn update_lookup(&mut self) {
+        self.expr_lookup.clear();
+
+        for (i, expr) in self.expr_record.iter().enumerate() {
+            self.expr_lookup.entry(expr.clone()).or_insert(VarIndex(i));
+        }
     }
 
     /// Compare two expressions referenced by indices.
@@ -1913,6 +2639,11 @@ impl Math {
     pub fn add_real(&mut self) -> EntityId {
         self.add_entity(EntityKind::FreeReal)
     }
+
+    /// Add a free circle entity.
+    pub fn add_circle(&mut self) -> EntityId {
+        self.add_entity(EntityKind::FreeCircle)
+    }
 }
 
 /// Used explicitly for figure IR building.
@@ -1922,6 +2653,14 @@ pub struct Build {
     expand: Expand,
     /// Aggregated items to be drawn on the figure.
     items: Vec<Item>,
+    /// Spans of the nodes the items were built by.
+    item_spans: Vec<Option<Span>>,
+    /// Layers the items are drawn on.
+    item_layers: Vec<Option<i32>>,
+    /// Span of the node currently being built.
+    span: Option<Span>,
+    /// Layer of the node currently being built.
+    layer: Option<i32>,
 }
 
 impl Build {
@@ -1933,12 +2672,68 @@ impl Build {
     where
         ExprKind: FromUnrolled<T>,
     {
-        self.expand.load(expr)
+        let id = self.expand.load(expr);
+
+        // Expressions made up by nodes are attributed to the node.
+        if let Some(span) = self.span.filter(|_| expr.span.is_empty()) {
+            self.expand.spans.push(VarSpan {
+                id: id.clone(),
+                span,
+            });
+        }
+
+        id
     }
 
     pub fn add<I: Into<Item>>(&mut self, item: I) {
         let item = item.into();
         self.items.push(item);
+        self.item_spans.push(self.span);
+        self.item_layers.push(self.layer);
+    }
+
+    /// Build with everything loaded and added attributed to `span`.
+    /// If the span is empty, the current one is kept.
+    pub fn spanned(&mut self, span: Span, f: impl FnOnce(&mut Self)) {
+        let parent = self.span;
+
+        if !span.is_empty() {
+            self.span = Some(span);
+        }
+
+        f(self);
+        self.span = parent;
+    }
+
+    /// Build with everything added put on `layer`. If it's `None`, the current one is kept.
+    pub fn layered(&mut self, layer: Option<i32>, f: impl FnOnce(&mut Self)) {
+        let parent = self.layer;
+
+        if layer.is_some() {
+            self.layer = layer;
+        }
+
+        f(self);
+        self.layer = parent;
+    }
+
+    /// Order the items by where they were defined in the script, so that the order
+    /// doesn't depend on how the nodes are nested. Items without a span go last.
+    fn sort_items(&mut self) {
+        let mut entries: Vec<_> = mem::take(&mut self.items)
+            .into_iter()
+            .zip(mem::take(&mut self.item_spans))
+            .zip(mem::take(&mut self.item_layers))
+            .collect();
+
+        // The sort is stable, so items of the same node keep their order.
+        entries.sort_by_key(|((_, span), _)| (span.is_none(), span.map(|span| span.start)));
+
+        for ((item, span), layer) in entries {
+            self.items.push(item);
+            self.item_spans.push(span);
+            self.item_layers.push(layer);
+        }
     }
 }
 
@@ -1949,10 +2744,19 @@ impl Build {
 fn optimize_rules(rules: &mut Vec<Option<Rule>>, math: &mut Math) -> bool {
     let mut performed = false;
 
-    for rule in rules.iter_mut() {
-        let rule_performed = ZeroLineDst::process(rule, math)
-            | RightAngle::process(rule, math)
-            | EqPointDst::process(rule, math)
+    // Redefining entities makes a rule exact, as if its weight was infinite. A rule can only be
+    // made exact if no heavier rule constrains the same entities, so that a lighter rule never
+    // overrides a heavier one.
+    for i in 0..rules.len() {
+        let exact_allowed = rules[i]
+            .as_ref()
+            .is_some_and(|rule| !is_outweighed(rule, rules, math));
+
+        let rule = &mut rules[i];
+        let rule_performed = (exact_allowed
+            && (ZeroLineDst::process(rule, math)
+                | RightAngle::process(rule, math)
+                | EqPointDst::process(rule, math)))
             | EqExpressions::process(rule, math);
 
         performed |= rule_performed;
@@ -1965,6 +2769,30 @@ fn optimize_rules(rules: &mut Vec<Option<Rule>>, math: &mut Math) -> bool {
     performed
 }
 
+/// Checks whether a heavier rule constrains any of the points `rule` constrains.
+/// Only points get redefined, so other entities, like the distance unit, don't count.
+fn is_outweighed(rule: &Rule, rules: &[Option<Rule>], math: &Math) -> bool {
+    let weight = rule.weight.to_complex().real;
+    let entities: Vec<_> = (0..math.entities.len())
+        .map(EntityId)
+        .filter(|&id| {
+            matches!(
+                math.entities[id.0],
+                EntityKind::FreePoint
+                    | EntityKind::PointOnLine { .. }
+                    | EntityKind::PointOnSegment { .. }
+                    | EntityKind::PointOnCircle { .. }
+            ) && rule.contains_entity(id, math)
+        })
+        .collect();
+
+    rules
+        .iter()
+        .flatten()
+        .filter(|other| other.weight.to_complex().real > weight)
+        .any(|other| entities.iter().any(|&id| other.contains_entity(id, math)))
+}
+
 /// Constructs a map between two sets of numbers A -> B.
 #[derive(Debug, Clone, Default)]
 pub struct IndexMap {
@@ -2023,12 +2851,28 @@ impl Reindex for ProcNum {
     fn reindex(&mut self, _map: &IndexMap) {}
 }
 
+impl Reindex for u8 {
+    fn reindex(&mut self, _map: &IndexMap) {}
+}
+
+impl Reindex for bool {
+    fn reindex(&mut self, _map: &IndexMap) {}
+}
+
 impl<T: Reindex> Reindex for Box<T> {
     fn reindex(&mut self, map: &IndexMap) {
         self.as_mut().reindex(map);
     }
 }
 
+impl<T: Reindex> Reindex for Option<T> {
+    fn reindex(&mut self, map: &IndexMap) {
+        if let Some(v) = self {
+            v.reindex(map);
+        }
+    }
+}
+
 impl<T: Reindex> Reindex for Vec<T> {
     fn reindex(&mut self, map: &IndexMap) {
         for item in self {
@@ -2094,14 +2938,49 @@ fn fold(matrix: &mut Vec<Expr<()>>) -> IndexMap {
 }
 
 fn read_flags(flags: &HashMap<&'static str, Flag>) -> Flags {
+    let figure = flags["figure"].as_set().unwrap();
+    let glide = flags["glide"].as_set().unwrap();
+    let rage = flags["rage"].as_set().unwrap();
+    let sa = flags["sa"].as_set().unwrap();
+
     Flags {
         optimizations: Optimizations {},
         point_inequalities: flags["point_inequalities"].as_bool().unwrap(),
+        warnings: flags["warnings"].as_bool().unwrap(),
+        figure: FigureFlags {
+            width: figure["width"].as_number(),
+            height: figure["height"].as_number(),
+        },
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        seed: flags["seed"].as_number().map(|seed| seed as u64),
+        quality_threshold: flags["quality_threshold"].as_number(),
+        engine: flags["engine"]
+            .as_ident()
+            .map(|engine| match engine.as_str() {
+                "glide" => EngineKind::Glide,
+                "rage" => EngineKind::Rage,
+                "sa" => EngineKind::Sa,
+                _ => unreachable!(),
+            }),
+        glide: GlideFlags {
+            strictness: glide["strictness"].as_number(),
+        },
+        rage: RageFlags {
+            max_adjustment: rage["max_adjustment"].as_number(),
+        },
+        sa: SaFlags {
+            max_adjustment: sa["max_adjustment"].as_number(),
+        },
     }
 }
 
 /// Optimize, Normalize, Repeat
-fn optimize_cycle(rules: &mut Vec<Option<Rule>>, math: &mut Math, items: &mut Vec<Item>) {
+fn optimize_cycle(
+    rules: &mut Vec<Option<Rule>>,
+    math: &mut Math,
+    items: &mut Vec<Item>,
+    spans: &mut Vec<VarSpan>,
+) {
     let mut entity_map = Vec::new();
     loop {
         if !optimize_rules(rules, math) {
@@ -2123,8 +3002,12 @@ fn optimize_cycle(rules: &mut Vec<Option<Rule>>, math: &mut Math, items: &mut Ve
                 EntityKind::FreePoint
                 | EntityKind::FreeReal
                 | EntityKind::DistanceUnit
+                | EntityKind::FreeCircle
                 | EntityKind::PointOnCircle { .. }
-                | EntityKind::PointOnLine { .. } => EntityBehavior::MapEntity(EntityId(i - offset)),
+                | EntityKind::PointOnLine { .. }
+                | EntityKind::PointOnSegment { .. } => {
+                    EntityBehavior::MapEntity(EntityId(i - offset))
+                }
                 EntityKind::Bind(expr) => {
                     offset += 1;
                     EntityBehavior::MapVar(expr.clone()) // Technically moving
@@ -2145,7 +3028,9 @@ fn optimize_cycle(rules: &mut Vec<Option<Rule>>, math: &mut Math, items: &mut Ve
         *items = old_items.reconstruct(&mut ctx);
         let old_rules = mem::take(rules);
         *rules = old_rules.reconstruct(&mut ctx);
-        math.expr_record = ctx.new_vars;
+        let old_spans = mem::take(spans);
+        *spans = old_spans.reconstruct(&mut ctx);
+        math.set_record(ctx.new_vars);
         math.entities = ctx.new_entities.into_iter().map(Option::unwrap).collect();
 
         // After reconstruction, all forward referencing is gone.
@@ -2158,6 +3043,7 @@ fn optimize_cycle(rules: &mut Vec<Option<Rule>>, math: &mut Math, items: &mut Ve
             // Then, set each corresponding var.
             math.expr_record[i] = var;
         }
+        math.update_lookup();
 
         // Normalize all rules now
         for rule in rules.iter_mut().flatten() {
@@ -2166,6 +3052,112 @@ fn optimize_cycle(rules: &mut Vec<Option<Rule>>, math: &mut Math, items: &mut Ve
     }
 }
 
+/// Find unused variables and free points that no rule constrains.
+fn find_warnings(
+    unrolled: &CompileContext,
+    expand: &Expand,
+    figure_spans: &[VarSpan],
+    items: &[Item],
+    item_spans: &[Option<Span>],
+    rules: &[Option<Rule>],
+    trivial_rules: &[Span],
+) -> Vec<Warning> {
+    let math = &expand.math;
+    let mut warnings: Vec<_> = trivial_rules
+        .iter()
+        .map(|&error_span| Warning::TriviallyTrueRule { error_span })
+        .collect();
+
+    // A variable is unused if it's never referenced and nothing drawn stems from its definition.
+    let mut unused_spans = Vec::new();
+    for (name, var) in &unrolled.variables {
+        let Some(definition) = var.try_get_variable_span().filter(|span| !span.is_empty()) else {
+            continue;
+        };
+
+        let drawn = item_spans
+            .iter()
+            .flatten()
+            .any(|span| span.overlaps(definition));
+
+        if !unrolled.is_referenced(name) && !drawn {
+            unused_spans.push(definition);
+            warnings.push(Warning::UnusedVariable {
+                error_span: definition,
+                variable_name: name.clone(),
+            });
+        }
+    }
+
+    // Name the points defined directly by variables. Only already loaded expressions are looked up.
+    let point_names: HashMap<_, _> = unrolled
+        .variables
+        .iter()
+        .filter_map(|(name, var)| {
+            let AnyExpr::Point(point) = var else {
+                return None;
+            };
+
+            let key = std::ptr::from_ref(point.get_data()) as usize;
+            match expand.expr_map.get(&key)?.kind {
+                ExprKind::Entity { id } => Some((id, name.clone())),
+                _ => None,
+            }
+        })
+        .collect();
+
+    for (i, entity) in math.entities.iter().enumerate() {
+        if !matches!(entity, EntityKind::FreePoint) {
+            continue;
+        }
+
+        let id = EntityId(i);
+
+        if rules
+            .iter()
+            .flatten()
+            .any(|rule| rule.kind.contains_entity(id, math))
+        {
+            continue;
+        }
+
+        // Points with no place in the script to point to are made up by the compiler.
+        // Those in unused variables have already been reported.
+        let Some(span) = figure_spans
+            .iter()
+            .chain(&expand.spans)
+            .find(|var_span| matches!(math.at(&var_span.id).kind, ExprKind::Entity { id: ent } if ent == id))
+            .map(|var_span| var_span.span)
+            .filter(|span| !unused_spans.iter().any(|unused| unused.overlaps(*span)))
+        else {
+            continue;
+        };
+
+        let point_name = point_names.get(&id).cloned();
+
+        if items.iter().any(|item| item.contains_entity(id, math)) {
+            warnings.push(Warning::UnconstrainedDrawnPoint {
+                error_span: span,
+                point_name,
+            });
+        } else {
+            warnings.push(Warning::UnconstrainedPoint {
+                error_span: span,
+                point_name,
+            });
+        }
+    }
+
+    // Report in script order.
+    warnings.sort_by_key(|warning| match warning {
+        Warning::UnusedVariable { error_span, .. }
+        | Warning::UnconstrainedPoint { error_span, .. }
+        | Warning::UnconstrainedDrawnPoint { error_span, .. }
+        | Warning::TriviallyTrueRule { error_span } => error_span.start,
+    });
+    warnings
+}
+
 /// Loads a `GeoScript` script and compiles it into Math IR. Encapsulates the entire compiler's
 /// work.
 ///
@@ -2195,9 +3187,11 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
     // Expand & normalize figure
     let mut build = Build::default();
     Box::new(nodes).build(&mut build);
+    build.sort_items();
 
-    // Move expand base
+    // Move expand base. Only spans of the figure's expressions are kept.
     let mut expand = build.expand;
+    let mut spans = mem::take(&mut expand.spans);
 
     // for (i, v) in expand.expr_record.iter().enumerate() {
     //     println!("[{i}] = {:?}", v.kind);
@@ -2208,10 +3202,45 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
     // Expand & normalize rules
     let mut rules = Vec::new();
 
+    let mut errors = Vec::new();
+    let mut trivial_rules = Vec::new();
+
+    // Rules with zero weight have no effect and must not take part in optimizations.
     for rule in unrolled.take_rules() {
-        rules.push(Some(Rule::load(&rule, &mut expand)));
+        if rule.weight.is_zero() {
+            continue;
+        }
+
+        let loaded = Rule::load(&rule, &mut expand);
+
+        // Rules that are always satisfied are dropped, and those that never are, reported.
+        match loaded.kind.evaluate_constant(&expand) {
+            Some(true) => trivial_rules.push(rule.get_span()),
+            Some(false) => errors.push(Error::UnsatisfiableRule {
+                error_span: rule.get_span(),
+            }),
+            None => rules.push(Some(loaded)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
     }
 
+    let warnings = if unrolled.flags["warnings"].as_bool().unwrap() {
+        find_warnings(
+            &unrolled,
+            &expand,
+            &spans,
+            &build.items,
+            &build.item_spans,
+            &rules,
+            &trivial_rules,
+        )
+    } else {
+        Vec::new()
+    };
+
     // for (i, ent) in expand.entities.iter().enumerate() {
     //     println!("[{i}] = {ent:?}");
     // }
@@ -2219,7 +3248,7 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
     // Get the math out of the `Expand`.
     let mut math = expand.math;
 
-    optimize_cycle(&mut rules, &mut math, &mut build.items);
+    optimize_cycle(&mut rules, &mut math, &mut build.items, &mut spans);
 
     // Now everything that could be normalized is normalized.
     // Unfortunately, normalization can introduce forward referencing, which is not what we want.
@@ -2234,7 +3263,8 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
     let mut ctx = ReconstructCtx::new(&entity_map, &old_vars, &old_entities);
     build.items = build.items.reconstruct(&mut ctx);
     rules = rules.reconstruct(&mut ctx);
-    math.expr_record = ctx.new_vars;
+    spans = spans.reconstruct(&mut ctx);
+    math.set_record(ctx.new_vars);
     let new_entities: Vec<_> = ctx.new_entities.into_iter().map(Option::unwrap).collect();
 
     // for (i, ent) in new_entities.iter().enumerate() {
@@ -2259,6 +3289,7 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
                 matches!(
                     ent.1,
                     EntityKind::PointOnLine { .. }
+                        | EntityKind::PointOnSegment { .. }
                         | EntityKind::FreePoint
                         | EntityKind::PointOnCircle { .. }
                 )
@@ -2273,6 +3304,7 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
                     matches!(
                         ent.1,
                         EntityKind::PointOnLine { .. }
+                            | EntityKind::PointOnSegment { .. }
                             | EntityKind::FreePoint
                             | EntityKind::PointOnCircle { .. }
                     )
@@ -2310,17 +3342,12 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
     entities.reindex(&index_map);
     rules.reindex(&index_map);
 
-    // Find entities affected by specific rules
-    let mut found_entities = Vec::new();
-    for expr in &variables {
-        let found = expr.find_entities(&found_entities, &entities);
-        found_entities.push(found);
-    }
-
-    for rule in &mut rules {
-        let entities = rule.kind.find_entities(&found_entities, &entities);
-        rule.entities = entities.into_iter().collect();
-    }
+    let mut adjusted = Adjusted {
+        variables,
+        rules,
+        entities,
+    };
+    adjusted.find_rule_entities();
 
     // Fold figure variables
     // println!("PRE-FOLD");
@@ -2342,6 +3369,21 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
     let mut items = build.items;
     items.reindex(&index_map);
     fig_entities.reindex(&index_map);
+    spans.reindex(&index_map);
+
+    // The first span loaded for an expression is the one of its definition.
+    let mut variable_spans = vec![None; fig_variables.len()];
+    for var_span in spans {
+        variable_spans[var_span.id.0].get_or_insert(var_span.span);
+    }
+
+    // Entities are defined where the expressions referencing them directly are.
+    let mut entity_spans = vec![None; fig_entities.len()];
+    for (var, span) in fig_variables.iter().zip(&variable_spans) {
+        if let (ExprKind::Entity { id }, Some(span)) = (&var.kind, span) {
+            entity_spans[id.0].get_or_insert(*span);
+        }
+    }
 
     // for (i, v) in fig_variables.iter().enumerate() {
     //     println!("[{i}] = {:?}", v.kind);
@@ -2352,16 +3394,376 @@ pub fn load_script(input: &str) -> Result<Intermediate, Vec<Error>> {
     // }
 
     Ok(Intermediate {
-        adjusted: Adjusted {
-            variables,
-            rules,
-            entities,
-        },
+        adjusted,
         figure: Figure {
             entities: fig_entities,
             variables: fig_variables,
             items,
+            item_spans: build.item_spans,
+            item_layers: build.item_layers,
+            entity_spans,
+            variable_spans,
         },
         flags,
+        warnings,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{load_script, unroll, Build, EntityKind, Intermediate, Node, Rule, RuleKind};
+    use crate::{Error, Warning};
+
+    const FOUR_WAY_ITERATOR: &str = "
+        let A, B, C, D, X = Point();
+        let P, Q, R, S = intersection(perpendicular_through(X, (AB, BC, CD, DA)), (AB, BC, CD, DA));
+        PQ = RS;
+    ";
+
+    #[test]
+    fn structurally_equal_expressions_are_stored_once() {
+        let (mut unrolled, nodes) = unroll::unroll(FOUR_WAY_ITERATOR).unwrap();
+
+        let mut build = Build::default();
+        Box::new(nodes).build(&mut build);
+        let mut expand = build.expand;
+
+        for rule in unrolled.take_rules() {
+            let _ = Rule::load(&rule, &mut expand);
+        }
+
+        let unique: HashSet<_> = expand.expr_record.iter().collect();
+        assert_eq!(unique.len(), expand.expr_record.len());
+
+        // 5 points, 4 lines, 4 perpendiculars, 4 intersections and 2 distances.
+        // Without deduplication, every line would be loaded once per use.
+        assert_eq!(expand.expr_record.len(), 19);
+    }
+
+    #[test]
+    fn rule_entities_stay_valid_after_deduplication() {
+        let intermediate = load_script(FOUR_WAY_ITERATOR).unwrap();
+        let adjusted = &intermediate.adjusted;
+
+        assert_eq!(adjusted.variables.len(), 19);
+        assert_eq!(intermediate.figure.variables.len(), 19);
+
+        for rule in &adjusted.rules {
+            assert!(!rule.entities.is_empty());
+            assert!(rule
+                .entities
+                .iter()
+                .all(|entity| entity.0 < adjusted.entities.len()));
+        }
+    }
+
+    #[test]
+    fn equal_expressions_are_stored_once() {
+        let intermediate = load_script(
+            "
+            let A, B, C = Point();
+            let M = mid(A, B);
+            dst(mid(A, B), C) = 2;
+            dst(C, mid(A, B)) = dst(A, B);
+        ",
+        )
+        .unwrap();
+
+        for variables in [
+            &intermediate.adjusted.variables,
+            &intermediate.figure.variables,
+        ] {
+            let unique: HashSet<_> = variables.iter().collect();
+            assert_eq!(unique.len(), variables.len());
+        }
+    }
+
+    const UNUSED: &str = "
+        let A, B, C = Point();
+        let r = 5;
+        let X = Point();
+        let Y = Point() [display = false];
+        AC = BC;
+    ";
+
+    #[test]
+    fn unused_variables_and_free_points_are_reported() {
+        let intermediate = load_script(UNUSED).unwrap();
+        let warnings = &intermediate.warnings;
+
+        // `Y` is reported as unused only, not as an unconstrained point.
+        assert_eq!(warnings.len(), 3);
+        assert!(matches!(
+            &warnings[0],
+            Warning::UnusedVariable { variable_name, .. } if variable_name == "r"
+        ));
+        assert!(matches!(
+            &warnings[1],
+            Warning::UnconstrainedDrawnPoint { point_name: Some(name), .. } if name == "X"
+        ));
+        assert!(matches!(
+            &warnings[2],
+            Warning::UnusedVariable { variable_name, .. } if variable_name == "Y"
+        ));
+    }
+
+    #[test]
+    fn warnings_can_be_turned_off() {
+        let intermediate = load_script(&format!("@warnings: off;\n{UNUSED}")).unwrap();
+        assert!(intermediate.warnings.is_empty());
+    }
+
+    #[test]
+    fn script_functions_expand_like_their_bodies() {
+        let defined = load_script(
+            "
+            fn drop_foot(X, YZ) = intersection(perpendicular_through(X, YZ), YZ);
+            fn altitude_foot(X, Y, Z) = drop_foot(X, YZ);
+            let A, B, C = Point();
+            let D = drop_foot(A, BC);
+            let E = altitude_foot(B, C, A) [label = F];
+            AD = BE;
+        ",
+        )
+        .unwrap();
+        let inline = load_script(
+            "
+            let A, B, C = Point();
+            let D = intersection(perpendicular_through(A, BC), BC);
+            let E = intersection(perpendicular_through(B, CA), CA) [label = F];
+            AD = BE;
+        ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            defined.adjusted.variables.len(),
+            inline.adjusted.variables.len()
+        );
+        assert_eq!(defined.adjusted.rules.len(), inline.adjusted.rules.len());
+    }
+
+    #[test]
+    fn script_functions_check_arity_and_recursion() {
+        let errors = load_script("fn f(X, Y) = mid(X, Y); let A = f(Point());").unwrap_err();
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            Error::OverloadNotFound { function_name, .. } if function_name == "f"
+        )));
+
+        let errors = load_script("fn f(X) = f(X); let A = f(Point());").unwrap_err();
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            Error::RecursiveFunction { function_name, .. } if function_name == "f"
+        )));
+    }
+
+    #[test]
+    fn script_functions_only_see_earlier_variables() {
+        let errors =
+            load_script("fn f(X) = mid(X, A); let A, B = Point(); let C = f(B);").unwrap_err();
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            Error::UndefinedVariable { variable_name, .. } if variable_name == "A"
+        )));
+
+        let defined = load_script(
+            "let A, B = Point(); fn f(B) = mid(A, B); let C = Point(); let D = f(C); AD = BD;",
+        )
+        .unwrap();
+        let inline =
+            load_script("let A, B = Point(); let C = Point(); let D = mid(A, C); AD = BD;")
+                .unwrap();
+        assert_eq!(defined.adjusted.rules.len(), inline.adjusted.rules.len());
+    }
+
+    #[test]
+    fn mixing_units_is_reported_at_the_operands() {
+        let errors = load_script("let A, B, C = Point();\nangle(ABC) + AB = 3;").unwrap_err();
+        let [Error::InconsistentUnits {
+            lhs,
+            rhs,
+            error_span,
+        }] = errors.as_slice()
+        else {
+            panic!("expected a single unit error, got {errors:?}");
+        };
+
+        assert_eq!(lhs.0, crate::ty::ANGLE);
+        assert_eq!(rhs.0, crate::ty::DISTANCE);
+        assert_eq!((lhs.1.start.line, lhs.1.start.column), (2, 1));
+        assert_eq!((rhs.1.start.line, rhs.1.start.column), (2, 14));
+        assert_eq!((rhs.1.end.line, rhs.1.end.column), (2, 16));
+        assert_eq!((error_span.start.column, error_span.end.column), (1, 16));
+
+        let errors = load_script("let A, B, C = Point(); AB > angle(ABC);").unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::InconsistentUnits { lhs, rhs, .. }]
+                if lhs.0 == crate::ty::DISTANCE && rhs.0 == crate::ty::ANGLE
+        ));
+    }
+
+    #[test]
+    fn products_of_distances_compare_with_areas() {
+        assert!(load_script("let A, B, C = Point(); AB * BC = area(ABC);").is_ok());
+        assert!(load_script("let A, B, C = Point(); AB = area(ABC)^(1/2);").is_ok());
+
+        let errors = load_script("let A, B, C = Point(); AB = area(ABC);").unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::InconsistentUnits { .. }]
+        ));
+    }
+
+    #[test]
+    fn neq_is_inverted_equality() {
+        let rules = |script| {
+            load_script(script)
+                .unwrap()
+                .adjusted
+                .rules
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let neq = rules("let A, B, C = Point(); AB != 3; A != C;");
+        assert_eq!(neq, rules("let A, B, C = Point(); AB ! = 3; A ! = C;"));
+        assert!(neq.iter().filter(|rule| rule.starts_with("not")).count() >= 2);
+
+        // A point named by a letter is compared with a computed one like any other point.
+        let point = rules("let A, B, E = Point(); E != mid(A, B);");
+        assert_eq!(point, rules("let A, B, E = Point(); E ! = mid(A, B);"));
+        assert!(point.iter().any(|rule| rule.starts_with("not")));
+        assert!(load_script("let A, B, E = Point(); E = mid(A, B);").is_ok());
+
+        // Split operators aren't joined: `< =` is not `<=`.
+        assert!(load_script("let A, B = Point(); AB < = 3;").is_err());
+    }
+
+    #[test]
+    fn clamp_requires_one_unit() {
+        assert!(load_script("let A, B, C = Point(); clamp(AB, 1, BC) = 2;").is_ok());
+
+        let errors =
+            load_script("let A, B, C = Point(); clamp(angle(ABC), 1, BC) = 2;").unwrap_err();
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            Error::OverloadNotFound { function_name, .. } if function_name == "clamp"
+        )));
+    }
+
+    #[test]
+    fn convex_statement_needs_a_polygon() {
+        let rule_count = |script: &str| load_script(script).unwrap().adjusted.rules.len();
+
+        let points = "let A, B, C, D, E, F = Point();";
+        assert!(rule_count(&format!("{points} convex ABCDEF;")) > rule_count(points));
+
+        let errors = load_script("let A, B = Point(); convex AB;").unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::NotAPolygon { length: 2, .. }]
+        ));
+    }
+
+    #[test]
+    fn chained_rules_see_the_defined_variables() {
+        let rules = |script| {
+            load_script(script)
+                .unwrap()
+                .adjusted
+                .rules
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            rules("let A, B = Point(); let X = Point() lies_on Circle(mid(A, B), AX);"),
+            rules("let A, B = Point(); let X = Point(); X lies_on Circle(mid(A, B), AX);")
+        );
+
+        // With many variables, the rule is applied to each of them, with matching variants.
+        assert_eq!(
+            rules("let A, B = Point(); let P, Q = Point() lies_on Circle(A, AQ), Circle(B, BP);"),
+            rules(
+                "let A, B = Point(); let P, Q = Point();
+                P lies_on Circle(A, AQ); Q lies_on Circle(B, BP);"
+            )
+        );
+    }
+
+    #[test]
+    fn definitions_cannot_see_later_variables() {
+        assert!(load_script("let A = Point(); let P, Q = Point(), mid(A, P);").is_ok());
+
+        let errors = load_script("let A = Point(); let P, Q = mid(A, Q), Point();").unwrap_err();
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            Error::VariableDefinedLater { variable_name, .. } if variable_name == "Q"
+        )));
+    }
+
+    #[test]
+    fn rules_of_unrelated_entities_are_made_exact() {
+        let circles = |intermediate: &Intermediate| {
+            intermediate
+                .adjusted
+                .entities
+                .iter()
+                .filter(|ent| matches!(ent, EntityKind::PointOnCircle { .. }))
+                .count()
+        };
+        let constraints = |intermediate: &Intermediate| -> Vec<f64> {
+            intermediate
+                .adjusted
+                .rules
+                .iter()
+                .filter(|rule| !matches!(rule.kind, RuleKind::Invert(_)))
+                .map(|rule| rule.weight.to_complex().real)
+                .collect()
+        };
+
+        // B's rule is the heaviest one constraining B, so a heavier rule elsewhere
+        // doesn't stop it from being made exact.
+        let intermediate =
+            load_script("let A, B, C, D = Point(); AB = 3; CD = 4 [weight = 2];").unwrap();
+        assert_eq!(circles(&intermediate), 2);
+        assert!(constraints(&intermediate).is_empty());
+
+        let intermediate = load_script("let A, B = Point(); AB = 3; AB = 4 [weight = 2];").unwrap();
+        assert_eq!(circles(&intermediate), 1);
+        assert_eq!(constraints(&intermediate), [1.0]);
+    }
+
+    #[test]
+    fn unsatisfiable_rules_are_errors() {
+        for rule in ["dst(AB) = -1", "degrees(30) = degrees(60)"] {
+            let errors = load_script(&format!("let A, B = Point(); {rule};")).unwrap_err();
+            assert!(
+                matches!(errors[..], [Error::UnsatisfiableRule { .. }]),
+                "{rule}: {errors:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn trivially_true_rules_are_dropped() {
+        let constrained = load_script("let A, B = Point(); AB = 2;").unwrap();
+        let intermediate =
+            load_script("let A, B = Point(); AB = 2; degrees(30) * 2 = degrees(60);").unwrap();
+
+        assert_eq!(
+            intermediate.adjusted.rules.len(),
+            constrained.adjusted.rules.len()
+        );
+        assert!(matches!(
+            intermediate.warnings[..],
+            [Warning::TriviallyTrueRule { .. }]
+        ));
+    }
+}
@@ -79,8 +79,12 @@ impl ZeroLineDst {
                 );
                 math.entities[a.0] = EntityKind::Bind(expr);
             }
-            EntityKind::DistanceUnit | EntityKind::FreeReal => unreachable!(),
-            EntityKind::PointOnCircle { .. } | EntityKind::Bind(_) => return false,
+            EntityKind::DistanceUnit | EntityKind::FreeReal | EntityKind::FreeCircle => {
+                unreachable!()
+            }
+            EntityKind::PointOnCircle { .. }
+            | EntityKind::PointOnSegment { .. }
+            | EntityKind::Bind(_) => return false,
         }
 
         *rule = None;
@@ -94,7 +98,7 @@ pub struct EqExpressions;
 impl EqExpressions {
     pub fn process(rule: &mut Option<Rule>, math: &Math) -> bool {
         let Some(Rule {
-            kind: RuleKind::NumberEq(a, b) | RuleKind::PointEq(a, b),
+            kind: RuleKind::NumberEq(a, b) | RuleKind::DistanceEq(a, b) | RuleKind::PointEq(a, b),
             ..
         }) = rule
         else {
@@ -162,7 +166,7 @@ impl EqPointDst {
 
     pub fn process(rule: &mut Option<Rule>, math: &mut Math) -> bool {
         let Some(Rule {
-            kind: RuleKind::NumberEq(a, b),
+            kind: RuleKind::NumberEq(a, b) | RuleKind::DistanceEq(a, b),
             ..
         }) = rule
         else {
@@ -216,7 +220,7 @@ impl RightAngle {
         let p = p_cloned;
 
         if let Some(rule) = rule {
-            rule.kind = RuleKind::NumberEq(
+            rule.kind = RuleKind::DistanceEq(
                 math.store(ExprKind::PointPointDistance { p: mid, q }, ExprType::Number),
                 math.store(
                     ExprKind::PointPointDistance { p, q: mid_cloned },
@@ -5,12 +5,12 @@ use geo_aid_derive::Parse;
 use num_traits::Zero;
 
 use crate::token::number::ProcNum;
-use crate::unroll::library;
+use crate::unroll::{library, Properties};
 use std::fmt::Formatter;
 use std::ops::Deref;
 use std::{
     fmt::{Debug, Display},
-    iter::Peekable,
+    iter::{self, Peekable},
     marker::PhantomData,
 };
 
@@ -19,9 +19,9 @@ use crate::span;
 use super::{
     token::{
         number::CompExponent, Ampersant, Asterisk, At, Caret, Colon, Comma, Dollar, Dot, Eq,
-        Exclamation, Gt, Gteq, Ident, LBrace, LParen, LSquare, Let, Lt, Lteq, Minus, NamedIdent,
-        NumberLit, Plus, Question, RBrace, RParen, RSquare, Semi, Slash, Span, StrLit, TokInteger,
-        Token,
+        Exclamation, Fn, Gt, Gteq, Ident, LBrace, LParen, LSquare, Let, Lt, Lteq, Minus,
+        NamedIdent, Neq, NumberLit, Plus, Question, RBrace, RParen, RSquare, Semi, Slash, Span,
+        StrLit, TokInteger, Token,
     },
     unit, ComplexUnit, Error,
 };
@@ -403,6 +403,16 @@ pub enum Expression<const ITER: bool> {
     Binop(ExprBinop<ITER>),
 }
 
+impl<const ITER: bool> Expression<ITER> {
+    /// Get the rightmost simple expression.
+    pub fn last_simple_mut(&mut self) -> &mut SimpleExpression {
+        match self {
+            Self::ImplicitIterator(it) => it.exprs.last_mut(),
+            Self::Binop(e) => e.rhs.last_simple_mut(),
+        }
+    }
+}
+
 impl<const ITER: bool> Parse for Expression<ITER> {
     type FirstToken = <SimpleExpression as Parse>::FirstToken;
 
@@ -471,13 +481,15 @@ impl Parse for RationalExponent {
 pub enum Exponent {
     Simple(TokInteger),
     Parenthesized(RationalExponent),
+    /// Any other expression. Never valid, only parsed for better error reporting.
+    Invalid(Box<SimpleExpressionKind>),
 }
 
 impl Exponent {
     /// Converts this exponent into a rational number.
     ///
     /// # Errors
-    /// Returns an error if the numbers can't fit.
+    /// Returns an error if the numbers can't fit or if the exponent is not a literal.
     pub fn as_comp(&self) -> Result<CompExponent, Error> {
         match self {
             Self::Simple(i) => Ok(CompExponent::new(
@@ -497,6 +509,9 @@ impl Exponent {
                         error_span: exp.denum.span,
                     })?,
             )),
+            Self::Invalid(expr) => Err(Error::NonLiteralExponent {
+                error_span: expr.get_span(),
+            }),
         }
     }
 }
@@ -523,6 +538,26 @@ pub struct FieldIndex {
     pub field: Ident,
 }
 
+/// An index into a point collection: `[index]`.
+#[derive(Debug, Parse)]
+pub struct Index {
+    /// The left bracket
+    pub lsquare: LSquare,
+    /// The index, starting at 0.
+    pub index: TokInteger,
+    /// The right bracket
+    pub rsquare: RSquare,
+}
+
+/// An indexed point collection `value[index]`
+#[derive(Debug, Parse)]
+pub struct ExprIndex {
+    /// The indexed collection
+    pub name: Box<Name>,
+    /// The index
+    pub index: Index,
+}
+
 /// A name (field, method or variable).
 #[derive(Debug)]
 pub enum Name {
@@ -530,6 +565,7 @@ pub enum Name {
     /// A function call.
     Call(ExprCall),
     FieldIndex(FieldIndex),
+    Index(ExprIndex),
     Ident(Ident),
     Expression(ExprParenthesised),
 }
@@ -563,6 +599,12 @@ impl Parse for Name {
                     params: input.parse()?,
                     rparen: input.parse()?,
                 })
+            } else if let Some(index) = input.parse::<Option<Index>>()? {
+                // Display properties also start with a bracket, but never with an integer.
+                Self::Index(ExprIndex {
+                    name: Box::new(name),
+                    index,
+                })
             } else {
                 break Ok(name);
             };
@@ -573,6 +615,7 @@ impl Parse for Name {
         match self {
             Self::Call(v) => v.get_span(),
             Self::FieldIndex(v) => v.get_span(),
+            Self::Index(v) => v.get_span(),
             Self::Expression(v) => v.get_span(),
             Self::Ident(v) => v.get_span(),
         }
@@ -613,11 +656,37 @@ pub struct ExprCall {
     /// The `(` token.
     pub lparen: LParen,
     /// Punctuated params. `None` if no params are given.
-    pub params: Option<Punctuated<Expression<false>, Comma>>,
+    pub params: Option<Punctuated<CallArgument, Comma>>,
     /// The `)` token.
     pub rparen: RParen,
 }
 
+/// A parsed function call argument, either positional or named.
+#[derive(Debug, Parse)]
+pub struct CallArgument {
+    /// The argument's name, if it is given by name.
+    pub name: Option<ArgumentName>,
+    /// The argument's value.
+    pub value: Expression<false>,
+}
+
+impl CallArgument {
+    /// Whether the argument is given by name.
+    #[must_use]
+    pub fn is_named(&self) -> bool {
+        self.name.is_some()
+    }
+}
+
+/// `<name> =` - the name of a named argument.
+#[derive(Debug, Parse)]
+pub struct ArgumentName {
+    /// The parameter's name.
+    pub ident: NamedIdent,
+    /// The `=` token.
+    pub eq: Eq,
+}
+
 /// A parsed parenthesised expression
 #[derive(Debug, Parse)]
 pub struct ExprParenthesised {
@@ -694,6 +763,8 @@ pub enum PredefinedRuleOperator {
     Lteq(Lteq),
     /// Greater than or equal
     Gteq(Gteq),
+    /// Not equal, the same as `!` followed by `=`
+    Neq(Neq),
 }
 
 /// A rule operator.
@@ -756,14 +827,46 @@ pub struct VariableDefinition {
     pub display_properties: Option<DisplayProperties>,
 }
 
+/// `(A, B, ...)`. Names the elements of a point collection.
+#[derive(Debug, Parse)]
+#[parse(first_token = LParen)]
+pub struct Destructuring {
+    /// The `(` token.
+    pub lparen: LParen,
+    /// The names of consecutive elements.
+    pub names: Punctuated<VariableDefinition, Comma>,
+    /// The `)` token.
+    pub rparen: RParen,
+}
+
+/// The left hand side of a `let` statement.
+#[derive(Debug, Parse)]
+pub enum LetLhs {
+    /// `(A, B, ...)`. Each variable is bound to an element of the rhs.
+    Destructured(Destructuring),
+    /// `A, B, ...`. Each variable is bound to the rhs (or its next iteration).
+    Variables(Punctuated<VariableDefinition, Comma>),
+}
+
+impl LetLhs {
+    /// Get the defined variables.
+    #[must_use]
+    pub fn definitions(&self) -> &Punctuated<VariableDefinition, Comma> {
+        match self {
+            Self::Destructured(destructuring) => &destructuring.names,
+            Self::Variables(variables) => variables,
+        }
+    }
+}
+
 /// `let <something> = <something else>`.
 /// Defines variables and possibly adds rules to them.
 #[derive(Debug, Parse)]
 pub struct LetStatement {
     /// The `let` token.
     pub let_token: Let,
-    /// The lhs ident iterator.
-    pub ident: Punctuated<VariableDefinition, Comma>,
+    /// The lhs ident iterator or destructuring.
+    pub ident: LetLhs,
     /// The `=` token.
     pub eq: Eq,
     /// The rhs expression.
@@ -774,18 +877,101 @@ pub struct LetStatement {
     pub semi: Semi,
 }
 
-/// `lhs ruleop rhs`.
+/// `lhs ruleop rhs [props]`.
 /// Defines a rule.
-#[derive(Debug, Parse)]
+#[derive(Debug)]
 pub struct RuleStatement {
     /// Left hand side
     pub first: Expression<true>,
     /// The rules themselves.
     pub rules: AtLeastOne<(RuleOperator, Expression<true>)>,
+    /// Alternative rule chains, each preceded by an `or`.
+    pub alternatives: Vec<RuleAlternative>,
+    /// Rule properties given after the last rhs.
+    pub display_properties: Option<DisplayProperties>,
     /// The ending semicolon.
     pub semi: Semi,
 }
 
+/// `or lhs ruleop rhs`. An alternative to the previous rules of a rule statement.
+#[derive(Debug)]
+pub struct RuleAlternative {
+    /// The `or` identifier.
+    pub or: NamedIdent,
+    /// Left hand side
+    pub first: Expression<true>,
+    /// The rules themselves.
+    pub rules: AtLeastOne<(RuleOperator, Expression<true>)>,
+}
+
+impl RuleStatement {
+    /// Check whether the next token is an `or` separating alternatives.
+    fn is_or<'t, I: Iterator<Item = &'t Token> + Clone>(input: &mut InputStream<'t, I>) -> bool {
+        matches!(input.it.peek(), Some(Token::Ident(Ident::Named(name))) if name.ident == "or")
+    }
+
+    /// Parse a rule chain, stopping at an `or`.
+    fn parse_chain<'t, I: Iterator<Item = &'t Token> + Clone>(
+        input: &mut InputStream<'t, I>,
+    ) -> AtLeastOne<(RuleOperator, Expression<true>)> {
+        let mut parsed = Vec::new();
+
+        while !Self::is_or(input) {
+            match input.parse() {
+                Ok(Some(v)) => parsed.push(v),
+                _ => break,
+            }
+        }
+
+        AtLeastOne(parsed)
+    }
+}
+
+impl Parse for RuleStatement {
+    type FirstToken = <Expression<true> as Parse>::FirstToken;
+
+    fn parse<'t, I: Iterator<Item = &'t Token> + Clone>(
+        input: &mut InputStream<'t, I>,
+    ) -> Result<Self, Error> {
+        let first = input.parse()?;
+        let mut rules = Self::parse_chain(input);
+        let mut alternatives = Vec::new();
+
+        while Self::is_or(input) {
+            alternatives.push(RuleAlternative {
+                or: input.parse()?,
+                first: input.parse()?,
+                rules: Self::parse_chain(input),
+            });
+        }
+
+        let last_rules = alternatives
+            .last_mut()
+            .map_or(&mut rules, |alt| &mut alt.rules);
+
+        // Properties after the last rhs get parsed as that expression's display properties.
+        // The rule's `weight` has no meaning for an expression, so it's moved to the rule.
+        let display_properties = last_rules.0.last_mut().and_then(|(_, rhs)| {
+            let last = rhs.last_simple_mut();
+            let (weight, rest) = last.display.take()?.split_off("weight");
+            last.display = rest;
+            weight
+        });
+
+        Ok(Self {
+            first,
+            rules,
+            alternatives,
+            display_properties,
+            semi: input.parse()?,
+        })
+    }
+
+    fn get_span(&self) -> Span {
+        self.first.get_span().join(self.semi.span)
+    }
+}
+
 /// `?expr`. Used for displaying and biases.
 #[derive(Debug, Parse)]
 pub struct RefStatement {
@@ -797,7 +983,53 @@ pub struct RefStatement {
     pub semi: Semi,
 }
 
-/// A general statement. A rule, `let` or a ref
+/// `fn <name>(<params>) = <body>;`.
+/// Defines a function that can be called later in the script.
+#[derive(Debug, Parse)]
+pub struct FnStatement {
+    /// The `fn` token.
+    pub fn_token: Fn,
+    /// The function's name.
+    pub name: NamedIdent,
+    /// The `(` token.
+    pub lparen: LParen,
+    /// Punctuated parameter names. `None` if the function takes no parameters.
+    pub params: Option<Punctuated<Ident, Comma>>,
+    /// The `)` token.
+    pub rparen: RParen,
+    /// The `=` token.
+    pub eq: Eq,
+    /// The function's body.
+    pub body: Expression<false>,
+    /// The ending semicolon.
+    pub semi: Semi,
+}
+
+/// `concurrent <line>, <line>, <line>`.
+/// Makes the given lines concurrent.
+#[derive(Debug, Parse)]
+pub struct ConcurrentStatement {
+    /// The `concurrent` identifier.
+    pub concurrent: NamedIdent,
+    /// The lines.
+    pub lines: Punctuated<Expression<false>, Comma>,
+    /// The ending semicolon.
+    pub semi: Semi,
+}
+
+/// `convex <points>`.
+/// Makes the polygon with the given vertices convex.
+#[derive(Debug, Parse)]
+pub struct ConvexStatement {
+    /// The `convex` identifier.
+    pub convex: NamedIdent,
+    /// The polygon's vertices.
+    pub polygon: Expression<false>,
+    /// The ending semicolon.
+    pub semi: Semi,
+}
+
+/// A general statement. A rule, `let`, `fn` or a ref
 #[derive(Debug)]
 pub enum Statement {
     /// No operation
@@ -808,8 +1040,14 @@ pub enum Statement {
     Flag(FlagStatement),
     /// Reference
     Ref(Displayed<RefStatement>),
+    /// Function definition
+    Fn(FnStatement),
     /// rule
     Rule(Displayed<RuleStatement>),
+    /// `concurrent`
+    Concurrent(Displayed<ConcurrentStatement>),
+    /// `convex`
+    Convex(Displayed<ConvexStatement>),
 }
 
 impl Parse for Statement {
@@ -817,7 +1055,13 @@ impl Parse for Statement {
         LSquare,
         TokenOr<
             Semi,
-            TokenOr<At, TokenOr<Question, TokenOr<Let, <SimpleExpression as Parse>::FirstToken>>>,
+            TokenOr<
+                At,
+                TokenOr<
+                    Question,
+                    TokenOr<Let, TokenOr<Fn, <SimpleExpression as Parse>::FirstToken>>,
+                >,
+            >,
         >,
     >;
 
@@ -855,10 +1099,59 @@ impl Parse for Statement {
                     Ok(Self::Flag(input.parse()?))
                 }
             }
+            Token::Fn(_) => {
+                if props.is_some() {
+                    Err(Error::UnexpectedProperties {
+                        error_span: props.get_span(),
+                    })
+                } else {
+                    Ok(Self::Fn(input.parse()?))
+                }
+            }
             Token::Question(_) => Ok(Self::Ref(Displayed {
                 properties: props,
                 statement: input.parse()?,
             })),
+            Token::Ident(Ident::Named(name)) if name.ident == "concurrent" => {
+                // `concurrent` might as well be a variable on the left hand side of a rule.
+                let mut attempt = InputStream {
+                    it: input.it.clone(),
+                };
+
+                if let Ok(statement) = attempt.parse() {
+                    *input = attempt;
+
+                    Ok(Self::Concurrent(Displayed {
+                        properties: props,
+                        statement,
+                    }))
+                } else {
+                    Ok(Self::Rule(Displayed {
+                        properties: props,
+                        statement: input.parse()?,
+                    }))
+                }
+            }
+            Token::Ident(Ident::Named(name)) if name.ident == "convex" => {
+                // Same as with `concurrent`, `convex` might be a variable.
+                let mut attempt = InputStream {
+                    it: input.it.clone(),
+                };
+
+                if let Ok(statement) = attempt.parse() {
+                    *input = attempt;
+
+                    Ok(Self::Convex(Displayed {
+                        properties: props,
+                        statement,
+                    }))
+                } else {
+                    Ok(Self::Rule(Displayed {
+                        properties: props,
+                        statement: input.parse()?,
+                    }))
+                }
+            }
             _ => Ok(Self::Rule(Displayed {
                 properties: props,
                 statement: input.parse()?,
@@ -944,6 +1237,36 @@ impl<T: Parse, P: Parse> Punctuated<T, P> {
             _ => self.collection.get(index - 1).map(|x| &x.1),
         }
     }
+
+    /// Gets the last element.
+    pub fn last_mut(&mut self) -> &mut T {
+        match self.collection.last_mut() {
+            Some(x) => &mut x.1,
+            None => &mut self.first,
+        }
+    }
+
+    /// Splits the items into ones satisfying the predicate and the rest.
+    /// A part is `None` if it has no items.
+    pub fn partition(self, mut f: impl FnMut(&T) -> bool) -> (Option<Self>, Option<Self>) {
+        let mut matching: Option<Self> = None;
+        let mut rest: Option<Self> = None;
+
+        let items = iter::once((None, *self.first))
+            .chain(self.collection.into_iter().map(|(p, x)| (Some(p), x)));
+
+        for (punct, item) in items {
+            let part = if f(&item) { &mut matching } else { &mut rest };
+
+            match part {
+                // Only the first item has no punctuator and it always starts a part.
+                Some(part) => part.collection.push((punct.unwrap(), item)),
+                None => *part = Some(Self::new(item)),
+            }
+        }
+
+        (matching, rest)
+    }
 }
 
 impl Parse for TokInteger {
@@ -1085,6 +1408,8 @@ impl Type {
                     true
                 } else if let Type::PointCollection(count) = into {
                     library::get_derived_pc(name) == *count
+                } else if *into == Type::Line {
+                    library::is_derived_line(name)
                 } else {
                     false
                 }
@@ -1108,10 +1433,11 @@ pub struct Property {
 /// A property's value
 #[derive(Debug, Clone, Parse)]
 pub enum PropertyValue {
-    Number(NumberLit),
+    Number(SignedNumberLit),
     Ident(Ident),
     RawString(RawString),
     String(StrLit),
+    Pair(Box<NumberPair>),
 }
 
 impl Display for PropertyValue {
@@ -1121,7 +1447,49 @@ impl Display for PropertyValue {
             Self::Ident(i) => write!(f, "{i}"),
             Self::RawString(s) => write!(f, "!{}", s.lit),
             Self::String(s) => write!(f, "{s}"),
+            Self::Pair(p) => write!(f, "{p}"),
+        }
+    }
+}
+
+/// A pair of numbers, like `(1, -0.5)`.
+#[derive(Debug, Clone, Parse)]
+#[parse(first_token = LParen)]
+pub struct NumberPair {
+    /// '('
+    pub lparen: LParen,
+    /// The first number.
+    pub first: SignedNumberLit,
+    /// ','
+    pub comma: Comma,
+    /// The second number.
+    pub second: SignedNumberLit,
+    /// ')'
+    pub rparen: RParen,
+}
+
+impl Display for NumberPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.first, self.second)
+    }
+}
+
+/// A number literal with an optional minus, like `-1.5`.
+#[derive(Debug, Clone, Parse)]
+pub struct SignedNumberLit {
+    /// Possible minus for negation.
+    pub minus: Option<Minus>,
+    /// The number itself.
+    pub lit: NumberLit,
+}
+
+impl Display for SignedNumberLit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.minus.is_some() {
+            write!(f, "-")?;
         }
+
+        write!(f, "{}", self.lit)
     }
 }
 
@@ -1163,13 +1531,15 @@ impl FromProperty for bool {
                     error_span: ident.get_span(),
                 }),
             },
-            PropertyValue::Number(num) => match num {
-                NumberLit::Integer(i) => match i.parsed.parse::<u8>() {
+            PropertyValue::Number(num) => match num.lit {
+                NumberLit::Integer(i) if num.minus.is_none() => match i.parsed.parse::<u8>() {
                     Ok(0) => Ok(false),
                     Ok(1) => Ok(true),
                     _ => Err(Error::BooleanExpected { error_span: i.span }),
                 },
-                NumberLit::Float(f) => Err(Error::BooleanExpected { error_span: f.span }),
+                _ => Err(Error::BooleanExpected {
+                    error_span: num.get_span(),
+                }),
             },
             PropertyValue::String(s) => match s.content.as_str() {
                 "enabled" | "on" | "true" | "yes" => Ok(true),
@@ -1181,6 +1551,9 @@ impl FromProperty for bool {
             PropertyValue::RawString(s) => Err(Error::BooleanExpected {
                 error_span: s.get_span(),
             }),
+            PropertyValue::Pair(p) => Err(Error::BooleanExpected {
+                error_span: p.get_span(),
+            }),
         }
     }
 }
@@ -1194,6 +1567,9 @@ impl FromProperty for String {
             }),
             PropertyValue::RawString(s) => Ok(s.lit.content),
             PropertyValue::String(s) => Ok(s.content),
+            PropertyValue::Pair(p) => Err(Error::StringExpected {
+                error_span: p.get_span(),
+            }),
         }
     }
 }
@@ -1201,7 +1577,11 @@ impl FromProperty for String {
 impl FromProperty for ProcNum {
     fn from_property(property: PropertyValue) -> Result<Self, Error> {
         match property {
-            PropertyValue::Number(num) => Ok(ProcNum::from(&num)),
+            PropertyValue::Number(num) => {
+                let value = ProcNum::from(&num.lit);
+
+                Ok(if num.minus.is_some() { -value } else { value })
+            }
             PropertyValue::RawString(s) => Err(Error::NumberExpected {
                 error_span: s.get_span(),
             }),
@@ -1211,6 +1591,9 @@ impl FromProperty for ProcNum {
             PropertyValue::Ident(ident) => Err(Error::NumberExpected {
                 error_span: ident.get_span(),
             }),
+            PropertyValue::Pair(p) => Err(Error::NumberExpected {
+                error_span: p.get_span(),
+            }),
         }
     }
 }
@@ -1225,3 +1608,23 @@ pub struct DisplayProperties {
     /// ']'
     pub rsquare: RSquare,
 }
+
+impl DisplayProperties {
+    /// Splits off the property with the given name.
+    /// Returns the properties with that name and the rest, if any.
+    #[must_use]
+    pub fn split_off(self, name: &str) -> (Option<Self>, Option<Self>) {
+        let (lsquare, rsquare) = (self.lsquare, self.rsquare);
+        let (matching, rest) = self
+            .properties
+            .partition(|prop| Properties::normalize(&prop.name.ident) == name);
+
+        let wrap = |properties| Self {
+            lsquare,
+            properties,
+            rsquare,
+        };
+
+        (matching.map(wrap), rest.map(wrap))
+    }
+}
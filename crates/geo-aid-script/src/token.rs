@@ -214,6 +214,13 @@ pub struct Let {
     pub span: Span,
 }
 
+/// A 'fn' token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Parse)]
+#[parse(token)]
+pub struct Fn {
+    pub span: Span,
+}
+
 /// A '+' token.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Parse)]
 #[parse(token)]
@@ -291,6 +298,13 @@ pub struct Exclamation {
     pub span: Span,
 }
 
+/// A '!=' token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Parse)]
+#[parse(token)]
+pub struct Neq {
+    pub span: Span,
+}
+
 /// A string, delimited by quotation marks.
 #[derive(Debug, Clone, PartialEq, Eq, Parse)]
 #[parse(token)]
@@ -327,6 +341,7 @@ pub enum Token {
     Comma(Comma),
     Caret(Caret),
     Let(Let),
+    Fn(Fn),
     Plus(Plus),
     Minus(Minus),
     Asterisk(Asterisk),
@@ -339,6 +354,7 @@ pub enum Token {
     Lteq(Lteq),
     Gteq(Gteq),
     Exclamation(Exclamation),
+    Neq(Neq),
     Ident(Ident),
     NumberLit(NumberLit),
     Dollar(Dollar),
@@ -362,6 +378,7 @@ impl Display for Token {
             Self::Comma(_) => write!(f, ","),
             Self::Dot(_) => write!(f, "."),
             Self::Let(_) => write!(f, "let"),
+            Self::Fn(_) => write!(f, "fn"),
             Self::Plus(_) => write!(f, "+"),
             Self::Minus(_) => write!(f, "-"),
             Self::Asterisk(_) => write!(f, "*"),
@@ -374,6 +391,7 @@ impl Display for Token {
             Self::Lteq(_) => write!(f, "<="),
             Self::Gteq(_) => write!(f, ">="),
             Self::Exclamation(_) => write!(f, "!"),
+            Self::Neq(_) => write!(f, "!="),
             Self::Dollar(_) => write!(f, "$"),
             Self::Ampersant(_) => write!(f, "&"),
             Self::Question(_) => write!(f, "?"),
@@ -411,6 +429,7 @@ impl Token {
             Self::Comma(v) => v.span,
             Self::Caret(v) => v.span,
             Self::Let(v) => v.span,
+            Self::Fn(v) => v.span,
             Self::Plus(v) => v.span,
             Self::Minus(v) => v.span,
             Self::Asterisk(v) => v.span,
@@ -423,6 +442,7 @@ impl Token {
             Self::Lteq(v) => v.span,
             Self::Gteq(v) => v.span,
             Self::Exclamation(v) => v.span,
+            Self::Neq(v) => v.span,
             Self::Ident(v) => v.get_span(),
             Self::NumberLit(v) => v.get_span(),
             Self::Dollar(v) => v.span,
@@ -939,6 +959,8 @@ fn tokenize_special<I: Iterator<Item = char>>(
                             sp.start.column + 1
                         ),
                     });
+                } else {
+                    tokens.push(Token::Eq(Eq { span: sp }));
                 }
             }
             Some(Token::Gt(Gt { span })) => {
@@ -958,6 +980,29 @@ fn tokenize_special<I: Iterator<Item = char>>(
                             sp.start.column + 1
                         ),
                     });
+                } else {
+                    tokens.push(Token::Eq(Eq { span: sp }));
+                }
+            }
+            Some(Token::Exclamation(Exclamation { span })) => {
+                if span
+                    == span!(
+                        sp.start.line,
+                        sp.start.column - 1,
+                        sp.start.line,
+                        sp.start.column
+                    )
+                {
+                    *tokens.last_mut().unwrap() = Token::Neq(Neq {
+                        span: span!(
+                            sp.start.line,
+                            sp.start.column - 1,
+                            sp.start.line,
+                            sp.start.column + 1
+                        ),
+                    });
+                } else {
+                    tokens.push(Token::Eq(Eq { span: sp }));
                 }
             }
             _ => tokens.push(Token::Eq(Eq { span: sp })),
@@ -1002,6 +1047,64 @@ fn tokenize_special<I: Iterator<Item = char>>(
     Ok(())
 }
 
+/// Skips a `//` line comment or a `/* */` block comment, keeping track of the position.
+///
+/// # Errors
+/// Returns an error if a block comment is never closed.
+fn skip_comment<I: Iterator<Item = char>>(
+    it: &mut Peekable<I>,
+    position: &mut Position,
+) -> Result<(), Error> {
+    let begin_pos = *position;
+
+    // Assume the first char to be correct.
+    it.next();
+    position.column += 1;
+
+    if it.next() == Some('/') {
+        position.column += 1;
+
+        for c in it.by_ref() {
+            if c == '\n' {
+                position.line += 1;
+                position.column = 1;
+                return Ok(());
+            }
+
+            position.column += 1;
+        }
+
+        return Ok(());
+    }
+
+    position.column += 1;
+    let mut last = None;
+
+    for c in it.by_ref() {
+        if c == '\n' {
+            position.line += 1;
+            position.column = 1;
+        } else {
+            position.column += 1;
+
+            if last == Some('*') && c == '/' {
+                return Ok(());
+            }
+        }
+
+        last = Some(c);
+    }
+
+    Err(Error::UnclosedComment {
+        error_span: span!(
+            begin_pos.line,
+            begin_pos.column,
+            begin_pos.line,
+            begin_pos.column + 2
+        ),
+    })
+}
+
 /// Tokenizes the given script (turns it into a series of tokens).
 ///
 /// # Errors
@@ -1028,6 +1131,7 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
 
                     tokens.push(match ident.as_str() {
                         "let" => Token::Let(Let { span: sp }),
+                        "fn" => Token::Fn(Fn { span: sp }),
                         _ => Token::Ident(dispatch_ident(sp, ident)),
                     });
                 } else if c.is_ascii_digit() {
@@ -1040,6 +1144,8 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
                             break;
                         }
                     }
+                } else if c == '/' && matches!(it.clone().nth(1), Some('/' | '*')) {
+                    skip_comment(&mut it, &mut position)?;
                 } else if c == '"' {
                     let s = read_string(&mut it, &mut position)?;
 
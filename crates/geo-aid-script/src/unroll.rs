@@ -8,12 +8,13 @@
 use crate::{parser, span};
 use flags::FlagSetConstructor;
 use geo_aid_derive::CloneWithNode;
-use geo_aid_figure::Style;
+use geo_aid_figure::{Color, Style};
 use num_traits::{One, Zero};
 use std::any::Any;
 use std::fmt::Formatter;
 use std::mem;
 use std::{
+    cmp::Reverse,
     collections::{hash_map::Entry, HashMap},
     fmt::{Debug, Display},
     hash::Hash,
@@ -28,21 +29,23 @@ use library::macros::index;
 
 use self::context::CompileContext;
 use self::figure::{
-    AnyExprNode, CircleNode, CollectionNode, EmptyNode, FromExpr, HierarchyNode, LineNode,
-    LineType, MaybeUnset, Node, NumberNode, PCNode, PointNode,
+    AnyExprNode, CircleNode, CollectionNode, EmptyNode, FromExpr, HierarchyNode, Layer, LineNode,
+    LineType, MaybeUnset, Node, NumberNode, Opacity, PCNode, PointNode,
 };
-use self::library::Library;
+use self::library::{arrange_params, Library, ScriptFunction};
 
 use super::parser::{
-    ExprBinop, ExprCall, FromProperty, InputStream, Name, PointCollectionConstructor, RefStatement,
+    ExprBinop, ExprCall, ExprIndex, FieldIndex, FromProperty, InputStream, Name,
+    PointCollectionConstructor, RefStatement,
 };
 use super::token::number::{CompExponent, ProcNum};
 use super::token::NumberLit;
 use super::{
     parser::{
-        BinaryOperator, DisplayProperties, ExplicitIterator, Expression, ImplicitIterator,
-        LetStatement, Parse, PredefinedRuleOperator, PropertyValue, Punctuated, RuleOperator,
-        RuleStatement, SimpleExpression, SimpleExpressionKind, Statement, Type,
+        BinaryOperator, ConcurrentStatement, ConvexStatement, Destructuring, DisplayProperties,
+        ExplicitIterator, Expression, ImplicitIterator, LetLhs, LetStatement, Parse,
+        PredefinedRuleOperator, PropertyValue, Punctuated, RuleOperator, RuleStatement,
+        SimpleExpression, SimpleExpressionKind, Statement, Type,
     },
     token::{self, Ident, NamedIdent, PointCollection as PCToken, Span},
     unit, ComplexUnit, Error,
@@ -142,18 +145,18 @@ impl From<&Name> for IterNode {
         match value {
             Name::Ident(_) => IterNode::new(Vec::new()),
             Name::FieldIndex(f) => f.name.as_ref().into(),
-            Name::Call(expr) => IterNode::new(
-                IterNode::from(expr.name.as_ref())
-                    .0
-                    .into_iter()
-                    .chain(
-                        expr.params
-                            .as_ref()
-                            .into_iter()
-                            .flat_map(|params| params.iter().flat_map(|v| IterNode::from(v).0)),
-                    )
-                    .collect(),
-            ),
+            Name::Index(i) => i.name.as_ref().into(),
+            Name::Call(expr) => {
+                IterNode::new(
+                    IterNode::from(expr.name.as_ref())
+                        .0
+                        .into_iter()
+                        .chain(expr.params.as_ref().into_iter().flat_map(|params| {
+                            params.iter().flat_map(|v| IterNode::from(&v.value).0)
+                        }))
+                        .collect(),
+                )
+            }
             Name::Expression(expr) => expr.content.as_ref().into(),
         }
     }
@@ -349,7 +352,7 @@ impl<'r> IterTreeIterator<'r> {
 
     /// Add a iterator tree node.
     fn add_node(&mut self, node: &'r IterNode) {
-        if node.len() > 0 {
+        if !node.is_empty() {
             let mut visited = Vec::new();
             let mut lengths = Vec::new();
 
@@ -687,10 +690,16 @@ pub enum Point {
     Average(ClonedVec<Expr<Point>>),
     /// Intersection of two lines.
     LineLineIntersection(Expr<Line>, Expr<Line>),
+    /// One of the intersections of a line and a circle (index 0 or 1).
+    LineCircleIntersection(Expr<Line>, Expr<Circle>, u8),
+    /// One of the intersections of two circles (index 0 or 1).
+    CircleCircleIntersection(Expr<Circle>, Expr<Circle>, u8),
     /// Center of a circle.
     CircleCenter(Expr<Circle>),
     /// A free point.
     Free,
+    /// A point free to move along the segment between two points.
+    OnSegment(Expr<Point>, Expr<Point>),
     /// A point made from a complex number.
     FromComplex(Expr<Number>),
 }
@@ -733,7 +742,7 @@ impl Expr<Point> {
     /// Box this expression with a given span.
     #[must_use]
     pub fn boxed(mut self, span: Span) -> Self {
-        let node = self.node.take();
+        let node = self.take_node_at(span);
 
         Self {
             span,
@@ -805,10 +814,17 @@ impl Display for Point {
             Self::LineLineIntersection(l1, l2) => {
                 write!(f, "intersection({l1}, {l2})")
             }
+            Self::LineCircleIntersection(line, circle, index) => {
+                write!(f, "intersection({line}, {circle})[{index}]")
+            }
+            Self::CircleCircleIntersection(c1, c2, index) => {
+                write!(f, "intersection({c1}, {c2})[{index}]")
+            }
             Self::CircleCenter(circle) => {
                 write!(f, "{circle}.center")
             }
             Self::Free => write!(f, "Free point"),
+            Self::OnSegment(p, q) => write!(f, "point-on-segment({p}, {q})"),
             Self::FromComplex(number) => write!(f, "to_point({number})"),
         }
     }
@@ -868,6 +884,8 @@ pub enum Circle {
     Generic(Generic<Self>),
     /// A circle constructed from its center and radius.
     Circle(Expr<Point>, Expr<Number>),
+    /// A free circle - an adjusted entity
+    Free,
 }
 
 impl_x_from_x! {Circle}
@@ -911,7 +929,7 @@ impl GetData for Circle {
                 Generic::VariableAccess(v) => v.definition.get_data(),
                 Generic::Dummy => self,
             },
-            Circle::Circle(..) => self,
+            Circle::Circle(..) | Circle::Free => self,
         }
     }
 }
@@ -920,7 +938,7 @@ impl Expr<Circle> {
     /// Box this expression with a given span.
     #[must_use]
     pub fn boxed(mut self, span: Span) -> Self {
-        let node = self.node.take();
+        let node = self.take_node_at(span);
 
         Self {
             span,
@@ -972,6 +990,7 @@ impl Display for Circle {
             Self::Circle(center, radius) => {
                 write!(f, "circle({center}, {radius})")
             }
+            Self::Free => write!(f, "Free circle"),
         }
     }
 }
@@ -992,12 +1011,16 @@ pub enum Line {
     LineFromPoints(Expr<Point>, Expr<Point>),
     /// A bisector line of an angle defined by three points.
     AngleBisector(Expr<Point>, Expr<Point>, Expr<Point>),
+    /// A bisector of the acute angle between two lines. The flag selects the obtuse one.
+    LineLineBisector(Expr<Line>, Expr<Line>, bool),
     /// A line perpendicular to another one, going through a specific point
     PerpendicularThrough(Expr<Line>, Expr<Point>),
     /// A line perpendicular to another one, going through a specific point
     ParallelThrough(Expr<Line>, Expr<Point>),
     /// A line made from a point and a direction vector
     PointVector(Expr<Point>, Expr<Number>),
+    /// The radical axis of two circles.
+    RadicalAxis(Expr<Circle>, Expr<Circle>),
 }
 
 impl Line {
@@ -1038,7 +1061,7 @@ impl Expr<Line> {
     /// Box the expression with a span.
     #[must_use]
     pub fn boxed(mut self, span: Span) -> Self {
-        let node = self.node.take();
+        let node = self.take_node_at(span);
 
         Self {
             span,
@@ -1051,13 +1074,40 @@ impl Expr<Line> {
 impl_x_from_x! {Line}
 impl_from_any! {Line}
 impl_from_unknown! {Line}
-impl_convert_err! {Derived -> Line}
 impl_convert_err! {Circle -> Line}
 impl_convert_err! {Point -> Line}
 impl_convert_err! {Number -> Line}
 
 impl_make_variable! {Line}
 
+impl ConvertFrom<Expr<Derived>> for Line {
+    fn convert_from(mut value: Expr<Derived>, context: &CompileContext) -> Expr<Self> {
+        let points = match &value.get_data().data {
+            DerivedData::Data(data) => data.line_points(),
+            DerivedData::Generic(_) => None,
+        };
+
+        if let Some((a, b)) = points {
+            let mut expr = context.line(a, b);
+
+            // Only the derived value is displayed, not the whole line.
+            expr.node = value.node.take().map(|node| {
+                let mut ln_node = HierarchyNode::new(LineNode::dummy());
+                ln_node.push_child(node);
+                ln_node
+            });
+
+            expr
+        } else {
+            convert_err!(Derived(value) -> Line with context)
+        }
+    }
+
+    fn can_convert_from(value: &Expr<Derived>) -> bool {
+        matches!(&value.get_data().data, DerivedData::Data(data) if data.line_points().is_some())
+    }
+}
+
 impl ConvertFrom<Expr<PointCollection>> for Line {
     fn convert_from(mut value: Expr<PointCollection>, context: &CompileContext) -> Expr<Self> {
         if value.data.length == 2 {
@@ -1076,6 +1126,8 @@ impl ConvertFrom<Expr<PointCollection>> for Line {
                             .get("default-label")
                             .get_or(SpannedMathString::new(span!(0, 0, 0, 0)));
                         ln_node.root.style = props.get("style").maybe_unset(Style::default());
+                        ln_node.root.color = props.get("color").maybe_unset(Color::default());
+                        ln_node.root.opacity = props.get("opacity").maybe_unset(Opacity::default());
                         ln_node.root.line_type = props.get("type").maybe_unset(LineType::default());
                     }
 
@@ -1108,6 +1160,13 @@ impl Display for Line {
             Self::AngleBisector(e1, e2, e3) => {
                 write!(f, "angle-bisector({e1}, {e2}, {e3})")
             }
+            Self::LineLineBisector(k, l, external) => {
+                if *external {
+                    write!(f, "external-bisector({k}, {l})")
+                } else {
+                    write!(f, "bisector({k}, {l})")
+                }
+            }
             Self::PerpendicularThrough(l, p) => {
                 write!(f, "perpendicular-through({l}, {p})")
             }
@@ -1115,6 +1174,7 @@ impl Display for Line {
                 write!(f, "parallel-through({l}, {p})")
             }
             Self::PointVector(p, v) => write!(f, "point-vector({p}, {v})"),
+            Self::RadicalAxis(c1, c2) => write!(f, "radical-axis({c1}, {c2})"),
         }
     }
 }
@@ -1156,6 +1216,8 @@ pub enum NumberData {
     ThreePointAngle(Expr<Point>, Expr<Point>, Expr<Point>),
     /// Directed angle defined by three points.
     ThreePointAngleDir(Expr<Point>, Expr<Point>, Expr<Point>),
+    /// Signed area of a triangle defined by three points.
+    ThreePointArea(Expr<Point>, Expr<Point>, Expr<Point>),
     /// Angle between two lines.
     TwoLineAngle(Expr<Line>, Expr<Line>),
     /// The arithmetic mean of numbers.
@@ -1176,6 +1238,12 @@ pub enum NumberData {
     Real(Expr<Number>),
     /// Imaginary part of a number
     Imaginary(Expr<Number>),
+    /// Absolute value of a number
+    Abs(Expr<Number>),
+    /// The smaller of two numbers
+    Min(Expr<Number>, Expr<Number>),
+    /// The larger of two numbers
+    Max(Expr<Number>, Expr<Number>),
     /// Natural logarithm (base e)
     Log(Expr<Number>),
     /// Exp function (e^this)
@@ -1221,6 +1289,9 @@ impl Display for NumberData {
             Self::ThreePointAngleDir(e1, e2, e3) => {
                 write!(f, "dir_angle({e1}, {e2}, {e3})")
             }
+            Self::ThreePointArea(e1, e2, e3) => {
+                write!(f, "signed_area({e1}, {e2}, {e3})")
+            }
             Self::TwoLineAngle(e1, e2) => write!(f, "angle({e1}, {e2})"),
             Self::CircleRadius(circle) => {
                 write!(f, "{circle}.radius")
@@ -1232,6 +1303,9 @@ impl Display for NumberData {
             Self::FromPoint(point) => write!(f, "to_complex({point})"),
             Self::Real(number) => write!(f, "Re({number})"),
             Self::Imaginary(number) => write!(f, "Im({number})"),
+            Self::Abs(number) => write!(f, "abs({number})"),
+            Self::Min(a, b) => write!(f, "min({a}, {b})"),
+            Self::Max(a, b) => write!(f, "max({a}, {b})"),
             Self::Log(number) => write!(f, "log({number})"),
             Self::Exp(number) => write!(f, "exp({number})"),
             Self::Sin(v) => write!(f, "sin({v})"),
@@ -1299,9 +1373,12 @@ impl ConvertFrom<Expr<PointCollection>> for Number {
                 if let Some(sc_node) = &mut expr.node {
                     sc_node.children = pc_node.children;
                     sc_node.root.display = pc_node.root.display;
+                    sc_node.span = value.span;
                 }
             }
 
+            // The distance spans the whole collection it was made of.
+            expr.0.span = value.span;
             expr.0
         } else {
             convert_err!(PointCollection(value) -> Number with context)
@@ -1337,7 +1414,7 @@ impl Expr<Number> {
     /// Box the expression with a span.
     #[must_use]
     pub fn boxed(mut self, span: Span) -> Self {
-        let node = self.node.take();
+        let node = self.take_node_at(span);
 
         Self {
             span,
@@ -1384,7 +1461,17 @@ impl Expr<Number> {
                     unit,
                     data: match &self.data.data {
                         NumberData::Generic(generic) => match generic {
-                            Generic::VariableAccess(_) => unreachable!(), // Always concrete
+                            // Variables with no unit (defined by literals) get converted as a whole.
+                            Generic::VariableAccess(var) => {
+                                NumberData::Generic(Generic::VariableAccess(Rc::new(Variable {
+                                    name: var.name.clone(),
+                                    definition: var
+                                        .definition
+                                        .clone_without_node()
+                                        .convert_unit(unit, context),
+                                    definition_span: var.definition_span,
+                                })))
+                            }
                             Generic::Boxed(v) => NumberData::Generic(Generic::Boxed(
                                 v.clone_without_node().convert_unit(unit, context),
                             )),
@@ -1399,6 +1486,7 @@ impl Expr<Number> {
                         | NumberData::PointLineDistance(_, _)
                         | NumberData::ThreePointAngle(_, _, _)
                         | NumberData::ThreePointAngleDir(_, _, _)
+                        | NumberData::ThreePointArea(_, _, _)
                         | NumberData::TwoLineAngle(_, _)
                         | NumberData::CircleRadius(_)
                         | NumberData::PointX(_)
@@ -1465,6 +1553,23 @@ impl Expr<Number> {
                         NumberData::Imaginary(number) => NumberData::Imaginary(
                             number.clone_without_node().convert_unit(unit, context),
                         ),
+                        NumberData::Abs(number) => {
+                            NumberData::Abs(number.clone_without_node().convert_unit(unit, context))
+                        }
+                        NumberData::Min(a, b) => {
+                            // Both operands are guaranteed to be unit-less here.
+                            NumberData::Min(
+                                a.clone_without_node().convert_unit(unit, context),
+                                b.clone_without_node().convert_unit(unit, context),
+                            )
+                        }
+                        NumberData::Max(a, b) => {
+                            // Both operands are guaranteed to be unit-less here.
+                            NumberData::Max(
+                                a.clone_without_node().convert_unit(unit, context),
+                                b.clone_without_node().convert_unit(unit, context),
+                            )
+                        }
                     },
                 }),
                 ..self
@@ -1668,7 +1773,7 @@ impl Expr<PointCollection> {
     /// Box the expression with the given span.
     #[must_use]
     pub fn boxed(mut self, span: Span) -> Self {
-        let node = self.node.take();
+        let node = self.take_node_at(span);
 
         Self {
             span,
@@ -1759,6 +1864,11 @@ impl Display for DerivedData {
 pub trait DerivedType: Debug + Display + 'static {
     /// Cast this into an `Any` trait object
     fn as_any(&self) -> &dyn Any;
+
+    /// The two points defining the line this value lies on, if it can be used as a line.
+    fn line_points(&self) -> Option<(Expr<Point>, Expr<Point>)> {
+        None
+    }
 }
 
 /// A special derived type expression. Used to encapsulate
@@ -1812,7 +1922,7 @@ impl Expr<Derived> {
     /// Box the expression with the given span.
     #[must_use]
     pub fn boxed(mut self, span: Span) -> Self {
-        let node = self.node.take();
+        let node = self.take_node_at(span);
 
         Self {
             span,
@@ -1914,7 +2024,7 @@ impl Expr<Unknown> {
     /// Box the expression with the given span.
     #[must_use]
     pub fn boxed(mut self, span: Span) -> Self {
-        let node = self.take_node();
+        let node = self.take_node_at(span);
 
         Self {
             data: Rc::new(Unknown::Generic(Generic::Boxed(self))),
@@ -2003,6 +2113,20 @@ impl AnyExpr {
         })
     }
 
+    /// Get a mutable reference to the node without taking it.
+    #[must_use]
+    pub fn get_node_mut(&mut self) -> Option<&mut dyn Node> {
+        match self {
+            Self::Point(x) => x.node.as_mut().map(|c| c as &mut dyn Node),
+            Self::Line(x) => x.node.as_mut().map(|c| c as &mut dyn Node),
+            Self::Number(x) => x.node.as_mut().map(|c| c as &mut dyn Node),
+            Self::Circle(x) => x.node.as_mut().map(|c| c as &mut dyn Node),
+            Self::PointCollection(x) => x.node.as_mut().map(|c| c as &mut dyn Node),
+            Self::Derived(x) => x.node.as_mut().map(|c| c as &mut dyn Node),
+            Self::Unknown(x) => x.node.as_mut().map(|c| c as &mut dyn Node),
+        }
+    }
+
     /// Get the node without taking it (reference).
     #[must_use]
     pub fn get_node(&self) -> Option<&dyn Node> {
@@ -2139,34 +2263,42 @@ impl AnyExpr {
     /// Panics if not a variable.
     #[must_use]
     pub fn get_variable_span(&self) -> Span {
+        self.try_get_variable_span().expect("not a variable")
+    }
+
+    /// Get the underlying variable's defining span, if this is a variable.
+    #[must_use]
+    pub fn try_get_variable_span(&self) -> Option<Span> {
         match self {
             Self::Point(v) => match v.data.as_ref() {
-                Point::Generic(Generic::VariableAccess(var)) => var.definition_span,
-                _ => panic!("not a variable"),
+                Point::Generic(Generic::VariableAccess(var)) => Some(var.definition_span),
+                _ => None,
             },
             Self::Line(v) => match v.data.as_ref() {
-                Line::Generic(Generic::VariableAccess(var)) => var.definition_span,
-                _ => panic!("not a variable"),
+                Line::Generic(Generic::VariableAccess(var)) => Some(var.definition_span),
+                _ => None,
             },
             Self::Number(v) => match &v.data.data {
-                NumberData::Generic(Generic::VariableAccess(var)) => var.definition_span,
-                _ => panic!("not a variable"),
+                NumberData::Generic(Generic::VariableAccess(var)) => Some(var.definition_span),
+                _ => None,
             },
             Self::Circle(v) => match v.data.as_ref() {
-                Circle::Generic(Generic::VariableAccess(var)) => var.definition_span,
-                _ => panic!("not a variable"),
+                Circle::Generic(Generic::VariableAccess(var)) => Some(var.definition_span),
+                _ => None,
             },
             Self::PointCollection(v) => match &v.data.data {
-                PointCollectionData::Generic(Generic::VariableAccess(var)) => var.definition_span,
-                _ => panic!("not a variable"),
+                PointCollectionData::Generic(Generic::VariableAccess(var)) => {
+                    Some(var.definition_span)
+                }
+                _ => None,
             },
             Self::Derived(v) => match &v.data.data {
-                DerivedData::Generic(Generic::VariableAccess(var)) => var.definition_span,
-                _ => panic!("not a variable"),
+                DerivedData::Generic(Generic::VariableAccess(var)) => Some(var.definition_span),
+                _ => None,
             },
             Self::Unknown(v) => match v.data.as_ref() {
-                Unknown::Generic(Generic::VariableAccess(var)) => var.definition_span,
-                Unknown::Generic(_) => panic!("not a variable"),
+                Unknown::Generic(Generic::VariableAccess(var)) => Some(var.definition_span),
+                Unknown::Generic(_) => None,
             },
         }
     }
@@ -2194,6 +2326,7 @@ impl AnyExpr {
         }
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn try_into_derived(self) -> Result<Expr<Derived>, Self> {
         if let Self::Derived(v) = self {
             Ok(v)
@@ -2442,6 +2575,17 @@ impl<T: Displayed> Expr<T> {
     pub fn take_node(&mut self) -> Option<HierarchyNode<T::Node>> {
         self.node.take()
     }
+
+    /// Take the expression's display node, making it display an expression at `span`.
+    fn take_node_at(&mut self, span: Span) -> Option<HierarchyNode<T::Node>> {
+        let mut node = self.node.take();
+
+        if let Some(node) = &mut node {
+            node.span = span;
+        }
+
+        node
+    }
 }
 
 impl<T: Displayed + Dummy> Dummy for Expr<T> {
@@ -2490,6 +2634,21 @@ impl Display for UnrolledRule {
     }
 }
 
+impl UnrolledRule {
+    /// Get the span of this rule, spanning all of its operands.
+    #[must_use]
+    pub fn get_span(&self) -> Span {
+        match &self.kind {
+            UnrolledRuleKind::PointEq(a, b) => a.span.join(b.span),
+            UnrolledRuleKind::NumberEq(a, b) | UnrolledRuleKind::Gt(a, b) => a.span.join(b.span),
+            UnrolledRuleKind::Alternative(v) => v
+                .iter()
+                .fold(Span::empty(), |span, rule| span.join(rule.get_span())),
+            UnrolledRuleKind::Bias(v) => v.get_span(),
+        }
+    }
+}
+
 // /// A point id is a 64-bit integer. First 32 bits are for the letter codepoint, next 8 for the amount of primes, next 16 for the point index.
 // fn construct_point_id(letter: char, primes: u8) -> u64 {
 //     ((letter as u64) << 8) | u64::from(primes)
@@ -2501,10 +2660,29 @@ pub fn construct_point_name(letter: char, primes: u8) -> String {
     String::from(letter) + &"'".repeat(primes as usize)
 }
 
-/// Fetch the variable's defining expression by its name.
-fn fetch_variable(context: &CompileContext, name: &str, variable_span: Span) -> AnyExpr {
+/// Fetch the variable's defining expression by its name. If no such variable exists,
+/// a library constant of that name is used.
+fn fetch_variable(
+    context: &CompileContext,
+    library: &Library,
+    name: &str,
+    variable_span: Span,
+) -> AnyExpr {
     let mut var = if let Some(var) = context.variables.get(name) {
+        context.mark_referenced(name);
         var.clone_without_node()
+    } else if let Some(defined_at) = context.pending_variables.get(name) {
+        context.push_error(Error::VariableDefinedLater {
+            error_span: variable_span,
+            variable_name: name.to_string(),
+            defined_at: *defined_at,
+        });
+
+        Expr::new_spanless(Unknown::dummy())
+            .make_variable(name.to_string())
+            .into()
+    } else if let Some(constant) = library.get_constant(name) {
+        AnyExpr::Number(constant.get())
     } else {
         let suggested = most_similar(context.variables.keys(), name);
 
@@ -2532,16 +2710,21 @@ impl Unroll for SimpleExpression {
         it_index: &HashMap<u8, usize>,
         display: Properties,
     ) -> AnyExpr {
-        let display = Properties::from(self.display.clone()).merge_with(display);
+        let mut display = Properties::from(self.display.clone()).merge_with(display);
+        let layer = display.get::<Layer>("layer").get();
+
+        let mut unrolled = self.kind.unroll(context, library, it_index, display);
+        if let (Some(layer), Some(node)) = (layer, unrolled.get_node_mut()) {
+            node.set_layer(layer.0);
+        }
 
-        let unrolled = self.kind.unroll(context, library, it_index, display);
         let unrolled = if let Some(exponent) = &self.exponent {
             let mut unrolled: Expr<Number> = unrolled.convert(context);
             let node = unrolled.node.take();
 
             let exponent = match exponent.exponent.as_comp() {
                 Ok(v) => {
-                    if self.minus.is_some() {
+                    if exponent.minus.is_some() {
                         -v
                     } else {
                         v
@@ -2597,7 +2780,7 @@ impl Unroll for Ident {
     fn unroll(
         &self,
         context: &mut CompileContext,
-        _library: &Library,
+        library: &Library,
         _it_index: &HashMap<u8, usize>,
         display: Properties,
     ) -> AnyExpr {
@@ -2606,7 +2789,7 @@ impl Unroll for Ident {
                 // No options are expected, as var refs don't generate nodes.
                 display.finish(context);
 
-                fetch_variable(context, &named.ident, named.span)
+                fetch_variable(context, library, &named.ident, named.span)
             }
             Ident::Collection(col) => {
                 let mut display = display;
@@ -2623,7 +2806,7 @@ impl Unroll for Ident {
                             col.collection
                                 .iter()
                                 .map(|item| {
-                                    fetch_variable(context, &format!("{item}"), col.span)
+                                    fetch_variable(context, library, &format!("{item}"), col.span)
                                         .convert::<Point>(context)
                                 })
                                 .collect::<Vec<_>>()
@@ -2672,9 +2855,32 @@ impl Unroll for ExprCall {
         let mut params = Vec::new();
         params.extend(self_param);
 
+        // Names of the named arguments. They always come after the positional ones.
+        let mut names = Vec::new();
+
         if let Some(parsed) = &self.params {
+            if parsed
+                .iter()
+                .any(|p| p.is_named() != parsed.first.is_named())
+            {
+                context.push_error(Error::MixedArguments {
+                    error_span: parsed.get_span(),
+                });
+            }
+
             for p in parsed.iter() {
-                params.push(p.unroll(context, library, it_index, Properties::default()));
+                params.push(
+                    p.value
+                        .unroll(context, library, it_index, Properties::default()),
+                );
+                names.extend(p.name.as_ref().map(|name| name.ident.ident.clone()));
+            }
+        }
+
+        // Functions defined in the script take precedence over builtin ones.
+        if self_type.is_none() {
+            if let Some(func) = library.get_script_function(&func_name) {
+                return unroll_script_call(self, func, params, &names, context, library, display);
             }
         }
 
@@ -2685,7 +2891,13 @@ impl Unroll for ExprCall {
 
         let res = match func {
             Ok(func) => {
-                if let Some(overload) = func.get_overload(&params) {
+                let overload = if names.is_empty() {
+                    func.get_overload(&params)
+                } else {
+                    func.get_named_overload(&mut params, &names)
+                };
+
+                if let Some(overload) = overload {
                     let ret = overload.unroll(params, context, display);
 
                     return ret.boxed(self.get_span());
@@ -2739,6 +2951,148 @@ impl Unroll for ExprCall {
     }
 }
 
+/// Unroll a call to a function defined in the script. The function's body is unrolled
+/// in place of the call, in a scope holding only its parameters and the variables
+/// defined before the function.
+fn unroll_script_call(
+    call: &ExprCall,
+    func: &ScriptFunction,
+    mut params: Vec<AnyExpr>,
+    arg_names: &[String],
+    context: &mut CompileContext,
+    library: &Library,
+    mut display: Properties,
+) -> AnyExpr {
+    let definition = &func.definition;
+    let names: Vec<&Ident> = definition
+        .params
+        .iter()
+        .flat_map(Punctuated::iter)
+        .collect();
+
+    let mut matches = names.len() == params.len();
+
+    if matches && !arg_names.is_empty() {
+        let param_names: Vec<_> = names.iter().map(ToString::to_string).collect();
+
+        if let Some(order) = arrange_params(&param_names, params.len() - arg_names.len(), arg_names)
+        {
+            let mut taken: Vec<_> = params.into_iter().map(Some).collect();
+            params = order.iter().map(|&i| taken[i].take().unwrap()).collect();
+        } else {
+            matches = false;
+        }
+    }
+
+    let matches = matches
+        && names.iter().zip(&params).all(|(name, param)| match name {
+            Ident::Named(_) => true,
+            Ident::Collection(col) => {
+                let ty = param.get_type();
+                ty == Type::Unknown || ty.can_cast(&Type::PointCollection(col.len()))
+            }
+        });
+
+    if !matches || func.unrolling.get() {
+        if matches {
+            context.push_error(Error::RecursiveFunction {
+                error_span: call.get_span(),
+                function_name: definition.name.ident.clone(),
+            });
+        } else {
+            context.push_error(Error::OverloadNotFound {
+                error_span: call.get_span(),
+                function_name: definition.name.ident.clone(),
+                params: params.iter().map(AnyExpr::get_type).collect(),
+            });
+        }
+
+        for mut param in params {
+            if let Some(AnyExprNode::PointCollection(mut pc)) = param.replace_node(None) {
+                if let Some(props) = pc.root.props.take() {
+                    props.finish(context);
+                }
+            }
+        }
+
+        display.ignore_all();
+        display.finish(context);
+
+        return Expr::new_spanless(Unknown::dummy()).into();
+    }
+
+    // The body only sees the variables defined before the function and its parameters.
+    let mut outer = mem::take(&mut context.variables);
+    let pending = mem::take(&mut context.pending_variables);
+
+    for name in &func.visible {
+        if let Some(var) = outer.remove(name) {
+            context.variables.insert(name.clone(), var);
+        }
+    }
+
+    // Parameters shadow the visible variables of the same name.
+    let mut shadowed = Vec::new();
+    let mut param_nodes = Vec::new();
+
+    for (name, mut param) in names.into_iter().zip(params) {
+        match param.replace_node(None) {
+            Some(AnyExprNode::PointCollection(mut pc)) => {
+                if let Some(props) = pc.root.props.take() {
+                    props.finish(context);
+                }
+            }
+            node => param_nodes.extend(node.map(AnyExprNode::to_dyn)),
+        }
+
+        match name {
+            Ident::Named(named) => {
+                let var = param.make_variable(named.ident.clone());
+                if let Some(previous) = context.variables.insert(named.ident.clone(), var) {
+                    shadowed.push((named.ident.clone(), previous));
+                }
+            }
+            Ident::Collection(col) => {
+                let param = param.convert::<PointCollection>(context);
+
+                for (i, pt) in col.collection.iter().enumerate() {
+                    let id = format!("{pt}");
+                    let var = AnyExpr::Point(param.index_without_node(i).make_variable(id.clone()));
+                    if let Some(previous) = context.variables.insert(id.clone(), var) {
+                        shadowed.push((id, previous));
+                    }
+                }
+            }
+        }
+    }
+
+    func.unrolling.set(true);
+    let mut ret = definition
+        .body
+        .unroll(context, library, &HashMap::new(), display);
+    func.unrolling.set(false);
+
+    let mut scope = mem::replace(&mut context.variables, outer);
+    context.pending_variables = pending;
+
+    for (name, previous) in shadowed {
+        scope.insert(name, previous);
+    }
+
+    for name in &func.visible {
+        if let Some(var) = scope.remove(name) {
+            context.variables.insert(name.clone(), var);
+        }
+    }
+
+    if let Some(mut node) = ret.replace_node(None) {
+        node.extend_boxed(param_nodes);
+        ret.replace_node(Some(node));
+    }
+
+    ret.boxed(call.get_span())
+}
+
 impl Unroll for Name {
     fn unroll(
         &self,
@@ -2751,14 +3105,134 @@ impl Unroll for Name {
             Self::Ident(i) => i.unroll(context, library, it_index, display),
             Self::Call(expr) => expr.unroll(context, library, it_index, display),
             Self::Expression(v) => v.content.unroll(context, library, it_index, display),
-            Self::FieldIndex(_) => {
-                context.push_error(Error::FieldAccess {
-                    error_span: self.get_span(),
+            Self::FieldIndex(f) => f.unroll(context, library, it_index, display),
+            Self::Index(i) => i.unroll(context, library, it_index, display),
+        }
+    }
+}
+
+impl Unroll for ExprIndex {
+    fn unroll(
+        &self,
+        context: &mut CompileContext,
+        library: &Library,
+        it_index: &HashMap<u8, usize>,
+        display: Properties,
+    ) -> AnyExpr {
+        // No options are expected, as indexing doesn't generate nodes.
+        display.finish(context);
+
+        let value = self
+            .name
+            .unroll(context, library, it_index, Properties::default());
+        let index_span = self.index.index.span;
+
+        match value {
+            AnyExpr::PointCollection(mut pc) => {
+                let length = pc.data.length;
+                let index = self
+                    .index
+                    .index
+                    .parsed
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&i| i < length);
+
+                if let Some(index) = index {
+                    let mut point = pc.index_without_node(index);
+                    point.span = self.get_span();
+
+                    // The collection's node still has to be built.
+                    if let Some(mut pc_node) = pc.node.take() {
+                        if let Some(props) = pc_node.root.props.take() {
+                            props.finish(context);
+                        }
+
+                        let mut node = HierarchyNode::new(PointNode::dummy());
+                        node.push_child(pc_node);
+                        point.node = Some(node);
+                    }
+
+                    return AnyExpr::Point(point);
+                }
+
+                context.push_error(Error::IndexOutOfBounds {
+                    error_span: index_span,
+                    index: self.index.index.to_string(),
+                    length,
                 });
+            }
+            AnyExpr::Unknown(_) => (),
+            value => context.push_error(Error::NotIndexable {
+                error_span: self.name.get_span(),
+                on_type: value.get_type(),
+            }),
+        }
 
-                Expr::new_spanless(Unknown::dummy()).into()
+        Expr::new_spanless(Unknown::dummy()).into()
+    }
+}
+
+/// Get the fields available on values of the given type.
+fn get_fields(ty: Type) -> &'static [&'static str] {
+    match ty {
+        Type::Point | Type::PointCollection(1) => &["x", "y"],
+        Type::Circle => &["center", "radius"],
+        _ => &[],
+    }
+}
+
+impl Unroll for FieldIndex {
+    fn unroll(
+        &self,
+        context: &mut CompileContext,
+        library: &Library,
+        it_index: &HashMap<u8, usize>,
+        mut display: Properties,
+    ) -> AnyExpr {
+        let mut value: AnyExpr =
+            self.name
+                .unroll(context, library, it_index, Properties::default());
+        let ty = value.get_type();
+        let field_name = self.field.to_string();
+
+        if ty != Type::Unknown {
+            let fields = get_fields(ty);
+
+            if fields.contains(&field_name.as_str()) {
+                // Fields are backed by the type's methods of the same name.
+                let params = vec![value];
+                let overload = library
+                    .get_method(ty, &field_name)
+                    .ok()
+                    .and_then(|func| func.get_overload(&params))
+                    .unwrap();
+
+                return overload
+                    .unroll(params, context, display)
+                    .boxed(self.get_span());
+            }
+
+            let suggested = most_similar(fields.iter().copied(), &field_name);
+
+            context.push_error(Error::UndefinedField {
+                error_span: self.field.get_span(),
+                field_name,
+                on_type: ty,
+                suggested,
+            });
+        }
+
+        if let Some(AnyExprNode::PointCollection(mut pc)) = value.replace_node(None) {
+            if let Some(props) = pc.root.props.take() {
+                props.finish(context);
             }
         }
+
+        display.ignore_all();
+        display.finish(context);
+
+        Expr::new_spanless(Unknown::dummy()).into()
     }
 }
 
@@ -2811,8 +3285,10 @@ impl Unroll for NumberLit {
         context: &mut CompileContext,
         _library: &Library,
         _it_index: &HashMap<u8, usize>,
-        display: Properties,
+        mut display: Properties,
     ) -> AnyExpr {
+        // Literals generate no nodes, so there's nothing to label.
+        display.ignore("default-label");
         display.finish(context);
 
         AnyExpr::Number(Expr {
@@ -2978,7 +3454,25 @@ impl<const ITER: bool> Unroll for ExprBinop<ITER> {
                     lhs
                 };
 
-                let rhs = rhs.convert_unit(lhs.data.unit, context);
+                let rhs = if rhs.data.unit.is_some() && rhs.data.unit != lhs.data.unit {
+                    context.push_error(Error::InconsistentUnits {
+                        lhs: (lhs.get_value_type(), Box::new(lhs.span)),
+                        rhs: (rhs.get_value_type(), Box::new(rhs.span)),
+                        error_span: Box::new(self.get_span()),
+                    });
+
+                    // Pretend the units match.
+                    Expr {
+                        span: rhs.span,
+                        data: Rc::new(Number {
+                            unit: lhs.data.unit,
+                            data: rhs.data.data.clone_without_node(),
+                        }),
+                        node: None,
+                    }
+                } else {
+                    rhs.convert_unit(lhs.data.unit, context)
+                };
                 let mut expr = Expr {
                     span: self.get_span(),
                     data: Rc::new(Number {
@@ -3001,13 +3495,25 @@ impl<const ITER: bool> Unroll for ExprBinop<ITER> {
                 AnyExpr::Number(expr)
             }
             BinaryOperator::Mul(_) | BinaryOperator::Div(_) => {
-                let lhs = lhs.specify_unit(context);
+                // If both operands have no unit (e.g. literals or constants), the result doesn't either.
+                let (lhs, rhs) = if lhs.data.unit.is_none() && rhs.data.unit.is_none() {
+                    (lhs, rhs)
+                } else {
+                    (lhs.specify_unit(context), rhs.specify_unit(context))
+                };
+
+                let unit = lhs.data.unit.zip(rhs.data.unit).map(|(l, r)| {
+                    if matches!(self.operator, BinaryOperator::Div(_)) {
+                        l / &r
+                    } else {
+                        l * &r
+                    }
+                });
 
-                let rhs = rhs.specify_unit(context);
                 let mut expr = Expr {
                     span: self.get_span(),
                     data: Rc::new(Number {
-                        unit: Some(lhs.data.unit.unwrap() * rhs.data.unit.as_ref().unwrap()),
+                        unit,
                         data: match &self.operator {
                             BinaryOperator::Mul(_) => NumberData::Multiply(lhs, rhs),
                             BinaryOperator::Div(_) => NumberData::Divide(lhs, rhs),
@@ -3105,6 +3611,31 @@ pub fn most_similar<'r, I: IntoIterator<Item = &'r T>, T: AsRef<str> + ?Sized +
         .map(|v| v.0)
 }
 
+/// A generic helper function for finding up to `count` strings similar to the given one
+/// from a list of strings, the most similar first. Uses the edit distance, so that
+/// only actual typos are suggested.
+pub fn most_similar_n<'r, I: IntoIterator<Item = &'r T>, T: AsRef<str> + ?Sized + 'r>(
+    expected: I,
+    received: &str,
+    count: usize,
+) -> Vec<&'r T> {
+    #[allow(clippy::cast_possible_truncation)]
+    let mut similar: Vec<_> = expected
+        .into_iter()
+        .map(|v| {
+            (
+                v,
+                (strsim::normalized_damerau_levenshtein(v.as_ref(), received) * 1000.0).floor()
+                    as i64,
+            )
+        })
+        .filter(|v| v.1 >= 500)
+        .collect();
+
+    similar.sort_by_key(|v| Reverse(v.1));
+    similar.into_iter().take(count).map(|v| v.0).collect()
+}
+
 /// Properties, commonly used for display options.
 #[derive(Debug)]
 pub struct Properties {
@@ -3156,7 +3687,7 @@ impl Default for Properties {
 }
 
 impl Properties {
-    fn normalize(property: &str) -> String {
+    pub(crate) fn normalize(property: &str) -> String {
         property
             .chars()
             .filter(|c| *c != '_')
@@ -3211,6 +3742,25 @@ impl Properties {
         }
     }
 
+    /// Get the rule weight or a default if it is not present.
+    /// Negative weights are reported and replaced with the default.
+    #[must_use]
+    pub fn get_weight(&mut self, default: ProcNum) -> ProcNum {
+        let weight = self.get::<ProcNum>("weight");
+        let span = weight.get_span();
+
+        match weight.get() {
+            Some(v) if v < ProcNum::zero() => {
+                self.errors.push(Error::NegativeWeight {
+                    error_span: span.unwrap(),
+                });
+                default
+            }
+            Some(v) => v,
+            None => default,
+        }
+    }
+
     /// Ignore the given property.
     pub fn ignore(&mut self, property: &'static str) {
         self.props.remove(&Self::normalize(property));
@@ -3338,6 +3888,7 @@ fn create_variable_named(
         if pc.data.length == 1 {
             true
         } else {
+            // Point collections aren't labeled.
             if let Some(node) = &mut pc.node {
                 if let Some(mut props) = node.root.props.take() {
                     let _ = props.get::<Result<SpannedMathString, Error>>("default-label");
@@ -3345,11 +3896,6 @@ fn create_variable_named(
                 }
             }
 
-            // Point collection variables are ambiguous and therefore cause compilation errors.
-            // They do not, however prevent the program from running properly.
-            context.push_error(Error::InvalidPC {
-                error_span: stat.get_span(),
-            });
             false
         }
     } else {
@@ -3453,6 +3999,105 @@ fn create_variable_collection(
     Ok(())
 }
 
+/// Create variables from a destructuring `let` statement, like `let (A, B) = ...`.
+/// Each variable is bound to the respective element of the unpacked `rhs`.
+fn create_variables_destructured(
+    stat: &LetStatement,
+    context: &mut CompileContext,
+    library: &Library,
+    destructuring: &Destructuring,
+    external: Option<DisplayProperties>,
+    variable_nodes: &mut Vec<Box<dyn Node>>,
+) -> Result<(), Error> {
+    let rhs_unrolled = stat.expr.unroll(
+        context,
+        library,
+        &HashMap::new(),
+        Properties::from(external),
+    );
+
+    let maybe_error = Error::CannotUnpack {
+        error_span: rhs_unrolled.get_span(),
+        ty: rhs_unrolled.get_type(),
+        length: destructuring.names.len(),
+    };
+
+    let mut rhs = rhs_unrolled.convert::<PointCollection>(context);
+
+    if rhs.data.length != destructuring.names.len()
+        && !matches!(rhs.data.data, PointCollectionData::Generic(Generic::Dummy))
+    {
+        return Err(maybe_error);
+    }
+
+    for (i, def) in destructuring.names.iter().enumerate() {
+        let mut display = Properties::from(def.display_properties.clone());
+
+        if def.name.is_named() {
+            display.add_if_not_present(
+                "default-label",
+                (def.name.get_span(), PropertyValue::Ident(def.name.clone())),
+            );
+        }
+
+        // Each element is an `IndexCollection` of the rhs.
+        let mut var = rhs.index_with_node(i);
+        let mut pt_node = match var.take_node() {
+            Some(mut node) => {
+                // Keep whatever the rhs built for the element, only use the new properties.
+                node.root = Box::new(PointNode::from_expr(&var, display, context));
+                node
+            }
+            None => HierarchyNode::new(PointNode::from_expr(&var, display, context)),
+        };
+
+        let name = match &def.name {
+            Ident::Named(named) => named.ident.clone(),
+            Ident::Collection(col) if col.len() == 1 => {
+                pt_node.root.default_label = SpannedMathString::from(col.collection[0].clone());
+                format!("{col}")
+            }
+            // A single point cannot be unpacked further.
+            Ident::Collection(col) => {
+                return Err(Error::CannotUnpack {
+                    error_span: col.span,
+                    ty: ty::POINT,
+                    length: col.len(),
+                })
+            }
+        };
+        variable_nodes.push(Box::new(pt_node));
+
+        match context.variables.entry(name) {
+            // If the variable already exists, it's a redefinition error.
+            Entry::Occupied(entry) => {
+                return Err(Error::RedefinedVariable {
+                    defined_at: entry.get().get_variable_span(),
+                    error_span: stat.get_span(),
+                    variable_name: entry.key().clone(),
+                })
+            }
+            // Otherwise, create a new variable
+            Entry::Vacant(entry) => {
+                let var = var.make_variable(entry.key().clone());
+                entry.insert(AnyExpr::Point(var));
+            }
+        }
+    }
+
+    variable_nodes.extend(rhs.take_node().map(|n| Box::new(n) as Box<dyn Node>));
+
+    Ok(())
+}
+
+/// Get the names of variables defined by an identifier.
+fn defined_names(ident: &Ident) -> Vec<String> {
+    match ident {
+        Ident::Named(named) => vec![named.ident.clone()],
+        Ident::Collection(col) => col.collection.iter().map(|pt| format!("{pt}")).collect(),
+    }
+}
+
 /// Create variables from a `let` statement.
 fn create_variables(
     stat: &LetStatement,
@@ -3464,10 +4109,32 @@ fn create_variables(
 
     let tree = IterNode::from(&stat.expr);
 
+    let definitions = match &stat.ident {
+        LetLhs::Destructured(destructuring) => {
+            if let Some(iter) = tree.first() {
+                return Err(Error::LetStatUnexpectedIterator {
+                    var_span: destructuring.get_span(),
+                    error_span: iter.span,
+                });
+            }
+
+            create_variables_destructured(
+                stat,
+                context,
+                library,
+                destructuring,
+                external,
+                &mut variable_nodes,
+            )?;
+            return Ok(variable_nodes);
+        }
+        LetLhs::Variables(definitions) => definitions,
+    };
+
     let ind = if let Some(iter) = tree.first() {
-        if stat.ident.len() == 1 {
+        if definitions.len() == 1 {
             context.push_error(Error::LetStatUnexpectedIterator {
-                var_span: stat.ident.get_span(),
+                var_span: definitions.get_span(),
                 error_span: iter.span,
             });
 
@@ -3502,10 +4169,10 @@ fn create_variables(
         tree.get_iter_lengths(&mut lengths, stat.expr.get_span())?;
         let entry = lengths.get(&iter.id).unwrap();
 
-        if entry.0 != stat.ident.len() {
+        if entry.0 != definitions.len() {
             return Err(Error::InconsistentIterators {
-                first_span: stat.ident.get_span(),
-                first_length: stat.ident.len(),
+                first_span: definitions.get_span(),
+                first_length: definitions.len(),
                 occurred_span: entry.1,
                 occurred_length: entry.0,
                 error_span: stat.get_span(),
@@ -3519,8 +4186,18 @@ fn create_variables(
 
     let mut it_index = IterTreeIterator::new(&tree);
 
+    // Variables of this statement can only be used after they're defined.
+    context.pending_variables = definitions
+        .iter()
+        .flat_map(|def| {
+            defined_names(&def.name)
+                .into_iter()
+                .map(|name| (name, def.name.get_span()))
+        })
+        .collect();
+
     // Iterate over each identifier.
-    for def in stat.ident.iter() {
+    for def in definitions.iter() {
         let mut external = Properties::from(external.clone());
 
         if def.name.is_named() {
@@ -3543,13 +4220,22 @@ fn create_variables(
 
         // println!("let {} = {rhs_unrolled}", def.name);
 
-        match &def.name {
+        for name in defined_names(&def.name) {
+            context.pending_variables.remove(&name);
+        }
+
+        let created = match &def.name {
             Ident::Named(named) => {
-                create_variable_named(stat, context, named, rhs_unrolled, &mut variable_nodes)?;
+                create_variable_named(stat, context, named, rhs_unrolled, &mut variable_nodes)
             }
             Ident::Collection(col) => {
-                create_variable_collection(stat, context, col, rhs_unrolled, &mut variable_nodes)?;
+                create_variable_collection(stat, context, col, rhs_unrolled, &mut variable_nodes)
             }
+        };
+
+        if let Err(err) = created {
+            context.pending_variables.clear();
+            return Err(err);
         }
     }
 
@@ -3573,7 +4259,7 @@ fn unroll_ref(
 
     while let Some(it_index) = index.get_currents() {
         let mut display = Properties::from(stat.properties.clone());
-        let weight = display.get("weight").get_or(ProcNum::zero());
+        let weight = display.get_weight(ProcNum::zero());
 
         let mut expr = stat
             .statement
@@ -3610,6 +4296,90 @@ fn unroll_ref(
     Ok(nodes)
 }
 
+/// Unroll a `concurrent` statement.
+fn unroll_concurrent(
+    stat: &parser::Displayed<ConcurrentStatement>,
+    context: &mut CompileContext,
+    library: &Library,
+) -> Result<Vec<Box<dyn Node>>, Error> {
+    let lines: Vec<_> = stat.statement.lines.iter().collect();
+    let &[k, l, m] = lines.as_slice() else {
+        return Err(Error::InvalidArgumentCount {
+            error_span: stat.get_span(),
+            expected: &[3],
+            got: u8::try_from(lines.len()).unwrap_or(u8::MAX),
+        });
+    };
+
+    let mut tree = IterNode::from2(k, l);
+    tree.extend(IterNode::from(m).0);
+    tree.get_iter_lengths(&mut HashMap::new(), stat.get_span())?;
+
+    let mut nodes: Vec<Box<dyn Node>> = Vec::new();
+    let mut index = IterTreeIterator::new(&tree);
+
+    while let Some(it_index) = index.get_currents() {
+        let mut display = Properties::from(stat.properties.clone());
+        let weight = display.get_weight(ProcNum::one());
+
+        let [k, l, m] = [k, l, m].map(|line| {
+            line.unroll(context, library, it_index, Properties::default())
+                .convert(context)
+        });
+
+        nodes.push(Box::new(library::concurrent::lines(
+            k, l, m, context, display, false, weight,
+        )));
+
+        index.next();
+    }
+
+    Ok(nodes)
+}
+
+/// Unroll a `convex` statement.
+fn unroll_convex(
+    stat: &parser::Displayed<ConvexStatement>,
+    context: &mut CompileContext,
+    library: &Library,
+) -> Result<Vec<Box<dyn Node>>, Error> {
+    let polygon = &stat.statement.polygon;
+
+    let tree = IterNode::from(polygon);
+    tree.get_iter_lengths(&mut HashMap::new(), stat.get_span())?;
+
+    let mut nodes: Vec<Box<dyn Node>> = Vec::new();
+    let mut index = IterTreeIterator::new(&tree);
+
+    while let Some(it_index) = index.get_currents() {
+        let mut display = Properties::from(stat.properties.clone());
+        let weight = display.get_weight(ProcNum::one());
+
+        let mut pc = polygon
+            .unroll(context, library, it_index, Properties::default())
+            .convert::<PointCollection>(context);
+
+        if !pc.is_dummy() {
+            if pc.data.length < 3 {
+                return Err(Error::NotAPolygon {
+                    error_span: polygon.get_span(),
+                    length: pc.data.length,
+                });
+            }
+
+            let mut node = CollectionNode::from_display(display, context);
+            node.extend(pc.take_node());
+            library::polygon::convex_rules(&pc, context, &weight);
+
+            nodes.push(Box::new(node));
+        }
+
+        index.next();
+    }
+
+    Ok(nodes)
+}
+
 /// Unroll a `let` statement.
 fn unroll_let(
     stat: parser::Displayed<LetStatement>,
@@ -3622,16 +4392,16 @@ fn unroll_let(
     } = stat;
 
     // First, we construct an iterator out of lhs
+    let definitions = stat.ident.definitions();
     let lhs: Expression<true> = Expression::ImplicitIterator(ImplicitIterator {
         exprs: Punctuated {
             first: Box::new(SimpleExpression {
                 minus: None,
-                kind: SimpleExpressionKind::Name(Name::Ident(stat.ident.first.name.clone())),
+                kind: SimpleExpressionKind::Name(Name::Ident(definitions.first.name.clone())),
                 exponent: None,
                 display: None,
             }),
-            collection: stat
-                .ident
+            collection: definitions
                 .collection
                 .iter()
                 .map(|(p, i)| {
@@ -3721,13 +4491,27 @@ fn unroll_eq(
         } else if lhs.can_convert_to(rhs_type) {
             (lhs.convert_to(rhs_type, context), rhs, rhs_type)
         } else {
-            context.push_error(Error::InconsistentTypes {
-                expected: (lhs_type, Box::new(lhs.get_span())),
-                got: (rhs_type, Box::new(rhs.get_span())),
-                error_span: Box::new(full_span),
-            });
+            // Two scalars (or a scalar and a distance written as `AB`) only differ in units.
+            context.push_error(
+                if let (Some(lhs_unit), Some(rhs_unit)) = (
+                    lhs.can_convert_to_scalar(None),
+                    rhs.can_convert_to_scalar(None),
+                ) {
+                    Error::InconsistentUnits {
+                        lhs: (Type::Number(lhs_unit), Box::new(lhs.get_span())),
+                        rhs: (Type::Number(rhs_unit), Box::new(rhs.get_span())),
+                        error_span: Box::new(full_span),
+                    }
+                } else {
+                    Error::InconsistentTypes {
+                        expected: (lhs_type, Box::new(lhs.get_span())),
+                        got: (rhs_type, Box::new(rhs.get_span())),
+                        error_span: Box::new(full_span),
+                    }
+                },
+            );
 
-            // "Cast" rhs to lhs.
+            // "Cast" rhs to lhs. The comparison itself is skipped, as the error is reported already.
             (
                 lhs,
                 AnyExpr::Unknown(Expr {
@@ -3736,12 +4520,13 @@ fn unroll_eq(
                     node: None,
                 })
                 .convert_to(lhs_type, context),
-                lhs_type,
+                Type::Unknown,
             )
         };
 
         match new_type {
-            Type::Point => {
+            // A single point collection is compared like the point it contains.
+            Type::Point | Type::PointCollection(1) => {
                 let lhs = lhs.convert(context);
                 let rhs = rhs.convert(context);
 
@@ -3786,9 +4571,9 @@ fn unroll_gt(
         let rhs = if rhs.can_convert_unit(lhs.data.unit) {
             rhs.convert_unit(lhs.data.unit, context)
         } else {
-            context.push_error(Error::InconsistentTypes {
-                expected: (lhs.get_value_type(), Box::new(lhs.span)),
-                got: (rhs.get_value_type(), Box::new(rhs.span)),
+            context.push_error(Error::InconsistentUnits {
+                lhs: (lhs.get_value_type(), Box::new(lhs.span)),
+                rhs: (rhs.get_value_type(), Box::new(rhs.span)),
                 error_span: Box::new(full_span),
             });
 
@@ -3825,6 +4610,9 @@ fn unroll_rule(
             PredefinedRuleOperator::Eq(_) => {
                 unroll_eq(lhs, rhs, context, full_span, inverted, display)
             }
+            PredefinedRuleOperator::Neq(_) => {
+                unroll_eq(lhs, rhs, context, full_span, !inverted, display)
+            }
             PredefinedRuleOperator::Lt(_) => unroll_gt(
                 rhs.convert(context),
                 lhs.convert(context),
@@ -3859,7 +4647,7 @@ fn unroll_rule(
             ),
         },
         RuleOperator::Defined(op) => {
-            let weight = display.get("weight").get_or(ProcNum::one());
+            let weight = display.get_weight(ProcNum::one());
 
             let overload = match library.get_rule(op.ident.as_str()) {
                 Ok(func) => {
@@ -3915,12 +4703,96 @@ fn unroll_rule_statement(
         statement: rule,
     } = rule;
 
-    let firsts = Some(&rule.first)
+    // Properties given after the rule cannot repeat the ones given before it.
+    for prop in rule
+        .display_properties
+        .iter()
+        .flat_map(|v| v.properties.iter())
+    {
+        let name = Properties::normalize(&prop.name.ident);
+
+        if let Some(first) = properties
+            .iter()
+            .flat_map(|v| v.properties.iter())
+            .find(|v| Properties::normalize(&v.name.ident) == name)
+        {
+            context.push_error(Error::RepeatedDisplayOption {
+                error_span: prop.name.span,
+                first_span: first.name.span,
+                option: prop.name.ident.clone(),
+            });
+        }
+    }
+
+    if rule.alternatives.is_empty() {
+        nodes.extend(unroll_rule_chain(
+            &rule.first,
+            &rule.rules,
+            context,
+            library,
+            (properties, &rule.display_properties),
+        )?);
+
+        return Ok(nodes);
+    }
+
+    // Each alternative is unrolled on its own and the rules it produces are then
+    // gathered into a single alternative rule.
+    let chains = Some((&rule.first, &rule.rules))
         .into_iter()
-        .chain(rule.rules.iter().map(|v| &v.1));
+        .chain(rule.alternatives.iter().map(|alt| (&alt.first, &alt.rules)));
+
+    let mut alternatives = Vec::new();
+
+    for (first, rules) in chains {
+        let rule_count = context.rules.borrow().len();
+        nodes.extend(unroll_rule_chain(
+            first,
+            rules,
+            context,
+            library,
+            (properties, &rule.display_properties),
+        )?);
+        let mut unrolled = context.rules.borrow_mut().split_off(rule_count);
+
+        if unrolled.len() > 1 || rules.is_empty() {
+            let last = rules.last().map_or(first, |(_, rhs)| rhs);
+
+            return Err(Error::CompoundAlternative {
+                error_span: first.get_span().join(last.get_span()),
+            });
+        }
+
+        alternatives.extend(unrolled.pop());
+    }
+
+    // If any of the alternatives failed to unroll, an error has already been reported.
+    if alternatives.len() == rule.alternatives.len() + 1 {
+        let weight = alternatives[0].weight.clone();
+
+        context.push_rule(UnrolledRule {
+            kind: UnrolledRuleKind::Alternative(alternatives),
+            inverted: false,
+            weight,
+        });
+    }
+
+    Ok(nodes)
+}
+
+/// Unroll a chain of rules: `lhs ruleop rhs ruleop rhs...`.
+fn unroll_rule_chain(
+    first: &Expression<true>,
+    rules: &[(RuleOperator, Expression<true>)],
+    context: &mut CompileContext,
+    library: &Library,
+    (properties, rule_properties): (&Option<DisplayProperties>, &Option<DisplayProperties>),
+) -> Result<Vec<Box<dyn Node>>, Error> {
+    let mut nodes = Vec::new();
+    let firsts = Some(first).into_iter().chain(rules.iter().map(|v| &v.1));
 
     for (lhs, op, rhs) in firsts
-        .zip(rule.rules.iter())
+        .zip(rules.iter())
         .map(|(lhs, (op, rhs))| (lhs, op, rhs))
     {
         let tree = IterNode::from2(lhs, rhs);
@@ -3940,7 +4812,8 @@ fn unroll_rule_statement(
                 library,
                 full_span,
                 false,
-                Properties::from(properties.clone()),
+                Properties::from(properties.clone())
+                    .merge_with(Properties::from(rule_properties.clone())),
             ));
 
             it_index.next();
@@ -3957,7 +4830,7 @@ fn unroll_rule_statement(
 pub fn unroll(input: &str) -> Result<(CompileContext, CollectionNode), Vec<Error>> {
     // Unfortunately, due to how context-dependent geoscript is, the code must be compiled immediately after parsing.
     let mut context = CompileContext::new();
-    let library = Library::new();
+    let mut library = Library::new();
 
     let mut figure = CollectionNode::new();
 
@@ -3978,17 +4851,35 @@ pub fn unroll(input: &str) -> Result<(CompileContext, CollectionNode), Vec<Error
 
     let mut flags = FlagSetConstructor::new()
         .add_set("optimizations", FlagSetConstructor::new())
+        .add_set(
+            "figure",
+            FlagSetConstructor::new()
+                .add_number("width")
+                .add_number("height"),
+        )
         .add_set(
             "language",
             FlagSetConstructor::new().add_bool_def("complex_numbers", false),
         )
         .add_bool_def("point_inequalities", true)
+        .add_bool_def("warnings", true)
+        .add_number("seed")
+        .add_number("quality_threshold")
+        .add_enum("engine", flags::ENGINES)
+        .add_set("glide", FlagSetConstructor::new().add_number("strictness"))
+        .add_set(
+            "rage",
+            FlagSetConstructor::new().add_number("max_adjustment"),
+        )
+        .add_set("sa", FlagSetConstructor::new().add_number("max_adjustment"))
         .finish();
 
     for flag in statements.iter().filter_map(Statement::as_flag) {
         flags::set_flag(&mut flags, flag, &context);
     }
 
+    flags::check_engine_flags(&flags, &context);
+
     context.flags = flags;
 
     // If complex numbers are enabled, create the complex unit variable.
@@ -4015,6 +4906,13 @@ pub fn unroll(input: &str) -> Result<(CompileContext, CollectionNode), Vec<Error
         // Unroll the statement
         match stat {
             Statement::Noop(_) | Statement::Flag(_) => (),
+            Statement::Fn(stat) => {
+                let visible = context.variables.keys().cloned().collect();
+
+                if let Err(err) = library.add_script_function(stat, visible) {
+                    context.push_error(err);
+                }
+            }
             Statement::Let(stat) => match unroll_let(stat, &mut context, &library) {
                 Ok(nodes) => {
                     for node in nodes {
@@ -4031,6 +4929,22 @@ pub fn unroll(input: &str) -> Result<(CompileContext, CollectionNode), Vec<Error
                 }
                 Err(err) => context.push_error(err),
             },
+            Statement::Concurrent(stat) => match unroll_concurrent(&stat, &mut context, &library) {
+                Ok(nodes) => {
+                    for node in nodes {
+                        figure.push_boxed(node);
+                    }
+                }
+                Err(err) => context.push_error(err),
+            },
+            Statement::Convex(stat) => match unroll_convex(&stat, &mut context, &library) {
+                Ok(nodes) => {
+                    for node in nodes {
+                        figure.push_boxed(node);
+                    }
+                }
+                Err(err) => context.push_error(err),
+            },
             Statement::Ref(stat) => match unroll_ref(&stat, &mut context, &library) {
                 Ok(nodes) => {
                     for node in nodes {
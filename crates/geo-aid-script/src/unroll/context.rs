@@ -6,11 +6,15 @@ use paste::paste;
 use std::cell::RefCell;
 use std::mem;
 use std::rc::Rc;
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
 
 use super::library::macros::number;
 use crate::span;
 use crate::token::number::ProcNum;
+use crate::token::Span;
 use crate::unroll::{AnyExpr, CloneWithNode};
 use crate::{unit, ComplexUnit, Error};
 
@@ -27,12 +31,17 @@ use super::{
 pub struct CompileContext {
     /// Variables. `AnyExpr` is expected to be the variable variant.
     pub variables: HashMap<String, AnyExpr>, // We have to store variables in this form to prevent type errors.
+    /// Variables of the `let` statement being unrolled that aren't defined yet,
+    /// along with the spans of their definitions.
+    pub pending_variables: HashMap<String, Span>,
     /// Flags
     pub flags: FlagSet,
     /// Unrolled rules
     pub rules: RefCell<Vec<UnrolledRule>>,
     /// Errors collected.
     errors: RefCell<Vec<Error>>,
+    /// Names of the variables referenced at least once.
+    referenced: RefCell<HashSet<String>>,
 }
 
 impl Default for CompileContext {
@@ -47,9 +56,11 @@ impl CompileContext {
     pub fn new() -> Self {
         Self {
             variables: HashMap::new(),
+            pending_variables: HashMap::new(),
             flags: FlagSet::default(),
             rules: RefCell::new(Vec::new()),
             errors: RefCell::new(Vec::new()),
+            referenced: RefCell::new(HashSet::new()),
         }
     }
 
@@ -93,6 +104,17 @@ impl CompileContext {
     pub fn take_rules(&mut self) -> Vec<UnrolledRule> {
         mem::take(&mut self.rules.borrow_mut())
     }
+
+    /// Mark a variable as referenced.
+    pub fn mark_referenced(&self, name: &str) {
+        self.referenced.borrow_mut().insert(name.to_string());
+    }
+
+    /// Whether the variable was referenced anywhere in the script.
+    #[must_use]
+    pub fn is_referenced(&self, name: &str) -> bool {
+        self.referenced.borrow().contains(name)
+    }
 }
 
 /// Everything related to circles.
@@ -299,6 +321,16 @@ impl CompileContext {
         self.free_point_display(Properties::default())
     }
 
+    /// Free circle expression with properties.
+    pub fn free_circle_display(&self, display: Properties) -> Expr<Circle> {
+        self.expr_with(Circle::Free, display, Vec::new())
+    }
+
+    /// Free circle expression with no properties.
+    pub fn free_circle(&self) -> Expr<Circle> {
+        self.free_circle_display(Properties::default())
+    }
+
     /// Free scalar expression with properties.
     pub fn free_scalar_display(&self, display: Properties) -> Expr<Number> {
         self.expr_with(
@@ -317,6 +349,7 @@ impl CompileContext {
     }
 
     generic_expr! {intersection(k: Line, l: Line) -> Point::LineLineIntersection}
+    generic_expr! {point_on_segment(p: Point, q: Point) -> Point::OnSegment}
     generic_expr! {distance_pp(p: Point, q: Point) -> Number[unit::DISTANCE]::PointPointDistance}
     generic_expr! {distance_pl(p: Point, k: Line) -> Number[unit::DISTANCE]::PointLineDistance}
     generic_expr! {circle_center(c: Circle) -> Point::CircleCenter}
@@ -324,6 +357,7 @@ impl CompileContext {
     generic_expr! {line(a: Point, b: Point) -> Line::LineFromPoints}
     generic_expr! {angle_ppp(a: Point, b: Point, c: Point) -> Number[unit::ANGLE]::ThreePointAngle}
     generic_expr! {angle_dir(a: Point, b: Point, c: Point) -> Number[unit::ANGLE]::ThreePointAngleDir}
+    generic_expr! {area_ppp(a: Point, b: Point, c: Point) -> Number[unit::DISTANCE * &unit::DISTANCE]::ThreePointArea}
     generic_expr! {angle_ll(k: Line, l: Line) -> Number[unit::ANGLE]::TwoLineAngle}
     generic_expr! {bisector_ppp(a: Point, b: Point, c: Point) -> Line::AngleBisector}
     generic_expr! {perpendicular_through(line: Line, point: Point) -> Line::PerpendicularThrough}
@@ -333,6 +367,9 @@ impl CompileContext {
     generic_expr! {sub(a: Number, b: Number) -> Number[inferred]::Subtract}
     generic_expr! {real(v: Number) -> Number[inferred]::Real}
     generic_expr! {imaginary(v: Number) -> Number[inferred]::Imaginary}
+    generic_expr! {abs(v: Number) -> Number[inferred]::Abs}
+    generic_expr! {min(a: Number, b: Number) -> Number[inferred]::Min}
+    generic_expr! {max(a: Number, b: Number) -> Number[inferred]::Max}
     generic_expr! {to_complex(p: Point) -> Number[unit::DISTANCE]::FromPoint}
     generic_expr! {to_point(v: Number) -> Point::FromComplex}
     generic_expr! {sin(v: Number) -> Number[unit::SCALAR]::Sin}
@@ -341,6 +378,7 @@ impl CompileContext {
     generic_expr! {direction(k: Line) -> Number[unit::SCALAR]::Direction}
     generic_expr! {neg(v: Number) -> Number[inferred]::Negate}
     generic_expr! {point_vector(p: Point, v: Number) -> Line::PointVector}
+    generic_expr! {radical_axis(c1: Circle, c2: Circle) -> Line::RadicalAxis}
     generic_expr! {point_x(p: Point) -> Number[unit::DISTANCE]::PointX}
     generic_expr! {point_y(p: Point) -> Number[unit::DISTANCE]::PointY}
 
@@ -386,6 +424,21 @@ impl CompileContext {
         self.pow_display(v, exponent, Properties::default())
     }
 
+    pub fn bisector_ll_display(
+        &self,
+        mut k: Expr<Line>,
+        mut l: Expr<Line>,
+        external: bool,
+        display: Properties,
+    ) -> Expr<Line> {
+        let nodes = take_nodes!(k, l);
+        self.expr_with(Line::LineLineBisector(k, l, external), display, nodes)
+    }
+
+    pub fn bisector_ll(&self, k: Expr<Line>, l: Expr<Line>, external: bool) -> Expr<Line> {
+        self.bisector_ll_display(k, l, external, Properties::default())
+    }
+
     pub fn div_display(
         &self,
         mut a: Expr<Number>,
@@ -448,7 +501,7 @@ impl CompileContext {
         mut display: Properties,
         def_weight: ProcNum,
     ) -> Box<dyn Node> {
-        let weight = display.get("weight").get_or(def_weight);
+        let weight = display.get_weight(def_weight);
         let mut node = CollectionNode::from_display(display, self);
 
         node.extend(lhs);
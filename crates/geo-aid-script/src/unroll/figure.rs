@@ -1,13 +1,16 @@
 //! Everything related to unrolled figures.
 
-use crate::figure::{CircleItem, LineItem, PointItem, RayItem, SegmentItem};
+use crate::figure::{CircleItem, LabelPosition, LineItem, PointItem, RayItem, SegmentItem};
 use crate::math::Build;
 use crate::{
     figure::SpannedMathString as MathString,
-    parser::{FromProperty, Parse, PropertyValue},
-    span, Error,
+    geometry::Complex,
+    parser::{FromProperty, Parse, PropertyValue, SignedNumberLit},
+    span,
+    token::{number::ProcNum, NumberLit, Span},
+    Error,
 };
-use geo_aid_figure::Style;
+use geo_aid_figure::{Color, Style};
 use std::{collections::HashMap, fmt::Debug, ops::Deref};
 
 use super::{
@@ -26,6 +29,14 @@ pub trait Node: Debug {
     /// Build this node.
     fn build(self: Box<Self>, build: &mut Build);
 
+    /// Get the span of the expression this node displays. Empty if not known.
+    fn get_span(&self) -> Span {
+        Span::empty()
+    }
+
+    /// Put everything this node draws on the given layer. Ignored by default.
+    fn set_layer(&mut self, _layer: i32) {}
+
     /// Build this node, with an unboxed `self` type.
     fn build_unboxed(self, compiler: &mut Build)
     where
@@ -47,6 +58,14 @@ impl<T: Node + ?Sized> Node for Box<T> {
     fn build(self: Box<Self>, build: &mut Build) {
         <T as Node>::build(*self, build);
     }
+
+    fn get_span(&self) -> Span {
+        self.as_ref().get_span()
+    }
+
+    fn set_layer(&mut self, layer: i32) {
+        self.as_mut().set_layer(layer);
+    }
 }
 
 /// Helper trait for building nodes out of unrolled expressions.
@@ -296,8 +315,16 @@ pub enum AssociatedData {
     Bool(MaybeUnset<bool>),
     /// A style (brush) value
     Style(MaybeUnset<Style>),
+    /// A color value
+    Color(MaybeUnset<Color>),
     /// A line type - line, ray or segment.
     LineType(MaybeUnset<LineType>),
+    /// The number of arcs marking an angle.
+    ArcCount(MaybeUnset<ArcCount>),
+    /// The number of ticks marking a segment.
+    TickCount(MaybeUnset<TickCount>),
+    /// An item's opacity.
+    Opacity(MaybeUnset<Opacity>),
 }
 
 impl AssociatedData {
@@ -317,6 +344,14 @@ impl AssociatedData {
         }
     }
 
+    #[must_use]
+    pub fn as_color(&self) -> Option<MaybeUnset<Color>> {
+        match self {
+            Self::Color(v) => Some(v.copied()),
+            _ => None,
+        }
+    }
+
     #[must_use]
     pub fn as_line_type(&self) -> Option<MaybeUnset<LineType>> {
         match self {
@@ -324,6 +359,30 @@ impl AssociatedData {
             _ => None,
         }
     }
+
+    #[must_use]
+    pub fn as_arc_count(&self) -> Option<MaybeUnset<ArcCount>> {
+        match self {
+            Self::ArcCount(v) => Some(v.copied()),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_tick_count(&self) -> Option<MaybeUnset<TickCount>> {
+        match self {
+            Self::TickCount(v) => Some(v.copied()),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_opacity(&self) -> Option<MaybeUnset<Opacity>> {
+        match self {
+            Self::Opacity(v) => Some(v.copied()),
+            _ => None,
+        }
+    }
 }
 
 impl From<MaybeUnset<bool>> for AssociatedData {
@@ -338,12 +397,36 @@ impl From<MaybeUnset<Style>> for AssociatedData {
     }
 }
 
+impl From<MaybeUnset<Color>> for AssociatedData {
+    fn from(value: MaybeUnset<Color>) -> Self {
+        Self::Color(value)
+    }
+}
+
 impl From<MaybeUnset<LineType>> for AssociatedData {
     fn from(value: MaybeUnset<LineType>) -> Self {
         Self::LineType(value)
     }
 }
 
+impl From<MaybeUnset<ArcCount>> for AssociatedData {
+    fn from(value: MaybeUnset<ArcCount>) -> Self {
+        Self::ArcCount(value)
+    }
+}
+
+impl From<MaybeUnset<TickCount>> for AssociatedData {
+    fn from(value: MaybeUnset<TickCount>) -> Self {
+        Self::TickCount(value)
+    }
+}
+
+impl From<MaybeUnset<Opacity>> for AssociatedData {
+    fn from(value: MaybeUnset<Opacity>) -> Self {
+        Self::Opacity(value)
+    }
+}
+
 /// Contains a root node, apart from its children. Simulates a hierarchy.
 #[derive(Debug)]
 pub struct HierarchyNode<T: Node + ?Sized> {
@@ -355,6 +438,10 @@ pub struct HierarchyNode<T: Node + ?Sized> {
     pub associated: Option<Box<dyn BuildAssociated<T>>>,
     /// Associated data for associated node construction.
     pub associated_data: HashMap<&'static str, AssociatedData>,
+    /// Span of the displayed expression. Empty if not known.
+    pub span: Span,
+    /// The layer everything built by this node is drawn on, if set.
+    pub layer: Option<i32>,
 }
 
 impl<T: Node + ?Sized> Node for HierarchyNode<T> {
@@ -366,17 +453,29 @@ impl<T: Node + ?Sized> Node for HierarchyNode<T> {
         self.root.get_display()
     }
 
+    fn get_span(&self) -> Span {
+        self.span
+    }
+
+    fn set_layer(&mut self, layer: i32) {
+        self.layer = Some(layer);
+    }
+
     fn build(mut self: Box<Self>, build: &mut Build) {
         if self.root.get_display() {
-            if let Some(associated) = self.associated.take() {
-                associated.build_associated(build, &mut self);
-            }
+            build.spanned(self.span, |build| {
+                build.layered(self.layer, |build| {
+                    if let Some(associated) = self.associated.take() {
+                        associated.build_associated(build, &mut self);
+                    }
 
-            self.root.build(build);
+                    self.root.build(build);
 
-            for child in self.children {
-                child.build(build);
-            }
+                    for child in self.children {
+                        child.build(build);
+                    }
+                });
+            });
         }
     }
 }
@@ -388,6 +487,8 @@ impl<U: Displayed, T: FromExpr<U>> FromExpr<U> for HierarchyNode<T> {
             children: Vec::new(),
             associated: None,
             associated_data: HashMap::new(),
+            span: expr.span,
+            layer: None,
         }
     }
 }
@@ -397,10 +498,12 @@ impl<T: Node> HierarchyNode<T> {
     #[must_use]
     pub fn new(root: T) -> Self {
         Self {
+            span: root.get_span(),
             root: Box::new(root),
             children: Vec::new(),
             associated: None,
             associated_data: HashMap::new(),
+            layer: None,
         }
     }
 }
@@ -410,10 +513,12 @@ impl HierarchyNode<dyn Node> {
     #[must_use]
     pub fn new_dyn(root: impl Node + 'static) -> Self {
         Self {
+            span: root.get_span(),
             root: Box::new(root),
             children: Vec::new(),
             associated: None,
             associated_data: HashMap::new(),
+            layer: None,
         }
     }
 }
@@ -513,6 +618,10 @@ impl Node for PCNode {
         self.display.get_copied()
     }
 
+    fn get_span(&self) -> Span {
+        self.expr.span
+    }
+
     fn build(self: Box<Self>, build: &mut Build) {
         if self.display.unwrap() {
             for child in self.children.into_iter().flatten() {
@@ -567,6 +676,19 @@ impl AnyExprNode {
         }
     }
 
+    /// Extend boxed children.
+    pub fn extend_boxed<Iter: IntoIterator<Item = Box<dyn Node>>>(&mut self, nodes: Iter) {
+        match self {
+            Self::Point(v) => v.extend_boxed(nodes),
+            Self::Line(v) => v.extend_boxed(nodes),
+            Self::Circle(v) => v.extend_boxed(nodes),
+            Self::Number(v) => v.extend_boxed(nodes),
+            Self::PointCollection(v) => v.extend_boxed(nodes),
+            Self::Derived(v) => v.extend_boxed(nodes),
+            Self::Unknown(v) => v.extend_boxed(nodes),
+        }
+    }
+
     /// # Panics
     /// If the node is not a point node.
     #[must_use]
@@ -718,6 +840,14 @@ pub struct PointNode {
     pub display_label: MaybeUnset<bool>,
     /// Whether to display the point's dot.
     pub display_dot: MaybeUnset<bool>,
+    /// The point's color
+    pub color: MaybeUnset<Color>,
+    /// The point's opacity
+    pub opacity: MaybeUnset<Opacity>,
+    /// Forced direction of the label.
+    pub label_position: MaybeUnset<LabelPosition>,
+    /// Manual offset of the label.
+    pub label_offset: MaybeUnset<LabelOffset>,
     /// Default label to use if `label` is empty.
     pub default_label: MathString,
     /// Defining expression
@@ -731,6 +861,10 @@ impl Dummy for PointNode {
             label: MaybeUnset::new(MathString::new(span!(0, 0, 0, 0))),
             display_label: MaybeUnset::new(true),
             display_dot: MaybeUnset::new(true),
+            color: MaybeUnset::new(Color::default()),
+            opacity: MaybeUnset::new(Opacity::default()),
+            label_position: MaybeUnset::new(LabelPosition::BelowRight),
+            label_offset: MaybeUnset::new(LabelOffset::default()),
             default_label: MathString::new(span!(0, 0, 0, 0)),
             expr: Expr::dummy(),
         }
@@ -750,6 +884,10 @@ impl Node for PointNode {
         self.display.get_copied()
     }
 
+    fn get_span(&self) -> Span {
+        self.expr.span
+    }
+
     fn build(self: Box<Self>, build: &mut Build) {
         if self.display.unwrap() && !self.is_dummy() {
             let id = build.load(&self.expr);
@@ -767,6 +905,10 @@ impl Node for PointNode {
                 }
                 .string,
                 display_dot: self.display_dot.unwrap(),
+                color: self.color.unwrap(),
+                opacity: self.opacity.try_get().map(|o| o.0),
+                label_position: self.label_position.try_get().copied(),
+                label_offset: self.label_offset.try_get().map(|offset| offset.0),
             });
         }
     }
@@ -781,6 +923,14 @@ impl FromExpr<Point> for PointNode {
                 .maybe_unset(MathString::new(span!(0, 0, 0, 0))),
             display_label: props.get("display_label").maybe_unset(true),
             display_dot: props.get("display_dot").maybe_unset(true),
+            color: props.get("color").maybe_unset(Color::default()),
+            opacity: props.get("opacity").maybe_unset(Opacity::default()),
+            label_position: props
+                .get("label_position")
+                .maybe_unset(LabelPosition::BelowRight),
+            label_offset: props
+                .get("label_offset")
+                .maybe_unset(LabelOffset::default()),
             default_label: props
                 .get("default-label")
                 .ok_or(MathString::new(span!(0, 0, 0, 0))),
@@ -806,6 +956,10 @@ pub struct CircleNode {
     pub default_label: MathString,
     /// How to draw the circle (brush)
     pub style: MaybeUnset<Style>,
+    /// The circle's color
+    pub color: MaybeUnset<Color>,
+    /// The circle's opacity
+    pub opacity: MaybeUnset<Opacity>,
     /// The defining expression
     pub expr: Expr<Circle>,
 }
@@ -818,6 +972,8 @@ impl Dummy for CircleNode {
             display_label: MaybeUnset::new(true),
             default_label: MathString::new(span!(0, 0, 0, 0)),
             style: MaybeUnset::new(Style::default()),
+            color: MaybeUnset::new(Color::default()),
+            opacity: MaybeUnset::new(Opacity::default()),
             expr: Expr::dummy(),
         }
     }
@@ -836,6 +992,10 @@ impl Node for CircleNode {
         self.display.get_copied()
     }
 
+    fn get_span(&self) -> Span {
+        self.expr.span
+    }
+
     fn build(self: Box<Self>, build: &mut Build) {
         if self.display.unwrap() && !self.is_dummy() {
             let id = build.load(&self.expr);
@@ -853,6 +1013,8 @@ impl Node for CircleNode {
                 }
                 .string,
                 style: self.style.unwrap(),
+                color: self.color.unwrap(),
+                opacity: self.opacity.try_get().map(|o| o.0),
             });
         }
     }
@@ -870,6 +1032,8 @@ impl FromExpr<Circle> for CircleNode {
                 .get("default-label")
                 .ok_or(MathString::new(span!(0, 0, 0, 0))),
             style: props.get("style").maybe_unset(Style::default()),
+            color: props.get("color").maybe_unset(Color::default()),
+            opacity: props.get("opacity").maybe_unset(Opacity::default()),
             expr: expr.clone_without_node(),
         };
 
@@ -906,6 +1070,9 @@ macro_rules! property_enum_impl {
                     PropertyValue::RawString(s) => Err(Error::NonRawStringOrIdentExpected {
                         error_span: s.get_span(),
                     }),
+                    PropertyValue::Pair(p) => Err(Error::StringOrIdentExpected {
+                        error_span: p.get_span(),
+                    }),
                 }
             }
         }
@@ -913,10 +1080,11 @@ macro_rules! property_enum_impl {
 }
 
 macro_rules! property_enum {
-    ($name:ident { $($variant:ident: $key:literal),* $(,)? }) => {
+    ($(#[$attr:meta])* $name:ident { $($(#[$variant_attr:meta])* $variant:ident: $key:literal),* $(,)? }) => {
         #[derive(Debug, Clone, Copy)]
+        $(#[$attr])*
         pub enum $name {
-            $($variant),*
+            $($(#[$variant_attr])* $variant),*
         }
 
         property_enum_impl! {$name { $($variant: $key),* }}
@@ -924,16 +1092,25 @@ macro_rules! property_enum {
 }
 
 property_enum! {
+    #[derive(Default)]
     LineType {
+        #[default]
         Line: "line",
         Ray: "ray",
         Segment: "segment"
     }
 }
 
-impl Default for LineType {
-    fn default() -> Self {
-        Self::Line
+property_enum_impl! {
+    LabelPosition {
+        Above: "above",
+        Below: "below",
+        Left: "left",
+        Right: "right",
+        AboveLeft: "above_left",
+        AboveRight: "above_right",
+        BelowLeft: "below_left",
+        BelowRight: "below_right"
     }
 }
 
@@ -946,6 +1123,224 @@ property_enum_impl! {
     }
 }
 
+/// The number of arcs marking an angle - 1, 2 or 3.
+#[derive(Debug, Clone, Copy)]
+pub struct ArcCount(pub u8);
+
+impl FromProperty for ArcCount {
+    fn from_property(property: PropertyValue) -> Result<Self, Error> {
+        match property {
+            PropertyValue::Number(num) => match &num.lit {
+                NumberLit::Integer(i) if num.minus.is_none() => match i.parsed.parse::<u8>() {
+                    Ok(v @ 1..=3) => Ok(Self(v)),
+                    _ => Err(Error::InvalidArcCount {
+                        error_span: num.get_span(),
+                    }),
+                },
+                _ => Err(Error::InvalidArcCount {
+                    error_span: num.get_span(),
+                }),
+            },
+            PropertyValue::Ident(i) => Err(Error::InvalidArcCount {
+                error_span: i.get_span(),
+            }),
+            PropertyValue::String(s) => Err(Error::InvalidArcCount {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::RawString(s) => Err(Error::InvalidArcCount {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::Pair(p) => Err(Error::InvalidArcCount {
+                error_span: p.get_span(),
+            }),
+        }
+    }
+}
+
+/// The number of ticks marking a segment - 1, 2 or 3. Unmarked by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TickCount(pub u8);
+
+impl FromProperty for TickCount {
+    fn from_property(property: PropertyValue) -> Result<Self, Error> {
+        match property {
+            PropertyValue::Number(num) => match &num.lit {
+                NumberLit::Integer(i) if num.minus.is_none() => match i.parsed.parse::<u8>() {
+                    Ok(v @ 1..=3) => Ok(Self(v)),
+                    _ => Err(Error::InvalidTickCount {
+                        error_span: num.get_span(),
+                    }),
+                },
+                _ => Err(Error::InvalidTickCount {
+                    error_span: num.get_span(),
+                }),
+            },
+            PropertyValue::Ident(i) => Err(Error::InvalidTickCount {
+                error_span: i.get_span(),
+            }),
+            PropertyValue::String(s) => Err(Error::InvalidTickCount {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::RawString(s) => Err(Error::InvalidTickCount {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::Pair(p) => Err(Error::InvalidTickCount {
+                error_span: p.get_span(),
+            }),
+        }
+    }
+}
+
+/// The layer an item is drawn on. Items on higher layers are drawn over the lower ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Layer(pub i32);
+
+impl FromProperty for Layer {
+    fn from_property(property: PropertyValue) -> Result<Self, Error> {
+        match property {
+            PropertyValue::Number(num) => match &num.lit {
+                NumberLit::Integer(i) => match i.parsed.parse::<i32>() {
+                    Ok(v) => Ok(Self(if num.minus.is_some() { -v } else { v })),
+                    Err(_) => Err(Error::InvalidLayer {
+                        error_span: num.get_span(),
+                    }),
+                },
+                _ => Err(Error::InvalidLayer {
+                    error_span: num.get_span(),
+                }),
+            },
+            PropertyValue::Ident(i) => Err(Error::InvalidLayer {
+                error_span: i.get_span(),
+            }),
+            PropertyValue::String(s) => Err(Error::InvalidLayer {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::RawString(s) => Err(Error::InvalidLayer {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::Pair(p) => Err(Error::InvalidLayer {
+                error_span: p.get_span(),
+            }),
+        }
+    }
+}
+
+/// An item's opacity - from 0 (fully transparent) to 1 (fully opaque).
+#[derive(Debug, Clone, Copy)]
+pub struct Opacity(pub f64);
+
+impl Default for Opacity {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl FromProperty for Opacity {
+    fn from_property(property: PropertyValue) -> Result<Self, Error> {
+        match property {
+            PropertyValue::Number(num) => {
+                let value = ProcNum::from(&num.lit).to_complex();
+
+                if num.minus.is_none()
+                    && value.imaginary == 0.0
+                    && (0.0..=1.0).contains(&value.real)
+                {
+                    Ok(Self(value.real))
+                } else {
+                    Err(Error::InvalidOpacity {
+                        error_span: num.get_span(),
+                    })
+                }
+            }
+            PropertyValue::Ident(i) => Err(Error::InvalidOpacity {
+                error_span: i.get_span(),
+            }),
+            PropertyValue::String(s) => Err(Error::InvalidOpacity {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::RawString(s) => Err(Error::InvalidOpacity {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::Pair(p) => Err(Error::InvalidOpacity {
+                error_span: p.get_span(),
+            }),
+        }
+    }
+}
+
+/// A manual offset of a label from its automatically chosen position, in figure coordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct LabelOffset(pub Complex);
+
+impl Default for LabelOffset {
+    fn default() -> Self {
+        Self(Complex::zero())
+    }
+}
+
+impl FromProperty for LabelOffset {
+    fn from_property(property: PropertyValue) -> Result<Self, Error> {
+        match property {
+            PropertyValue::Pair(pair) => {
+                let component = |num: &SignedNumberLit| {
+                    let value = ProcNum::from(&num.lit).to_complex().real;
+
+                    if num.minus.is_some() {
+                        -value
+                    } else {
+                        value
+                    }
+                };
+
+                Ok(Self(Complex::new(
+                    component(&pair.first),
+                    component(&pair.second),
+                )))
+            }
+            PropertyValue::Number(n) => Err(Error::PairExpected {
+                error_span: n.get_span(),
+            }),
+            PropertyValue::Ident(i) => Err(Error::PairExpected {
+                error_span: i.get_span(),
+            }),
+            PropertyValue::String(s) => Err(Error::PairExpected {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::RawString(s) => Err(Error::PairExpected {
+                error_span: s.get_span(),
+            }),
+        }
+    }
+}
+
+impl FromProperty for Color {
+    fn from_property(property: PropertyValue) -> Result<Self, Error> {
+        match property {
+            PropertyValue::Number(n) => Err(Error::StringOrIdentExpected {
+                error_span: n.get_span(),
+            }),
+            PropertyValue::Ident(i) => {
+                Color::parse(&i.to_string()).ok_or_else(|| Error::InvalidColor {
+                    error_span: i.get_span(),
+                    received_value: i.to_string(),
+                })
+            }
+            PropertyValue::String(s) => {
+                Color::parse(&s.content).ok_or_else(|| Error::InvalidColor {
+                    error_span: s.get_span(),
+                    received_value: s.content,
+                })
+            }
+            PropertyValue::RawString(s) => Err(Error::NonRawStringOrIdentExpected {
+                error_span: s.get_span(),
+            }),
+            PropertyValue::Pair(p) => Err(Error::StringOrIdentExpected {
+                error_span: p.get_span(),
+            }),
+        }
+    }
+}
+
 /// Node for a line
 #[derive(Debug)]
 pub struct LineNode {
@@ -961,6 +1356,10 @@ pub struct LineNode {
     pub line_type: MaybeUnset<LineType>,
     /// How to draw the line (brush)
     pub style: MaybeUnset<Style>,
+    /// The line's color
+    pub color: MaybeUnset<Color>,
+    /// The line's opacity
+    pub opacity: MaybeUnset<Opacity>,
     /// Defining expression
     pub expr: Expr<Line>,
 }
@@ -974,6 +1373,8 @@ impl Dummy for LineNode {
             default_label: MathString::new(span!(0, 0, 0, 0)),
             line_type: MaybeUnset::new(LineType::Line),
             style: MaybeUnset::new(Style::default()),
+            color: MaybeUnset::new(Color::default()),
+            opacity: MaybeUnset::new(Opacity::default()),
             expr: Expr::dummy(),
         }
     }
@@ -992,6 +1393,10 @@ impl Node for LineNode {
         self.display.get_copied()
     }
 
+    fn get_span(&self) -> Span {
+        self.expr.span
+    }
+
     fn build(self: Box<Self>, build: &mut Build) {
         if self.display.unwrap() && !self.is_dummy() {
             let label = if self.display_label.unwrap() {
@@ -1006,6 +1411,8 @@ impl Node for LineNode {
                 MathString::new(span!(0, 0, 0, 0))
             };
             let style = self.style.unwrap();
+            let color = self.color.unwrap();
+            let opacity = self.opacity.try_get().map(|o| o.0);
 
             match self.line_type.unwrap() {
                 LineType::Line => {
@@ -1014,6 +1421,8 @@ impl Node for LineNode {
                         id,
                         label: label.string,
                         style,
+                        color,
+                        opacity,
                     });
                 }
                 LineType::Ray => match &self.expr.data.as_ref() {
@@ -1025,6 +1434,8 @@ impl Node for LineNode {
                             q_id,
                             label: label.string,
                             style,
+                            color,
+                            opacity,
                         });
                     }
                     Line::AngleBisector(a, b, c) => {
@@ -1043,6 +1454,8 @@ impl Node for LineNode {
                             q_id,
                             label: label.string,
                             style,
+                            color,
+                            opacity,
                         });
                     }
                     _ => unreachable!(),
@@ -1056,6 +1469,9 @@ impl Node for LineNode {
                             q_id,
                             label: label.string,
                             style,
+                            color,
+                            opacity,
+                            ticks: 0,
                         });
                     }
                     _ => unreachable!(),
@@ -1078,6 +1494,8 @@ impl FromExpr<Line> for LineNode {
                 .ok_or(MathString::new(span!(0, 0, 0, 0))),
             line_type: MaybeUnset::new(LineType::Line),
             style: props.get("style").maybe_unset(Style::default()),
+            color: props.get("color").maybe_unset(Color::default()),
+            opacity: props.get("opacity").maybe_unset(Opacity::default()),
             expr: expr.clone_without_node(),
         };
 
@@ -1118,6 +1536,10 @@ impl Node for NumberNode {
         self.display.get_copied()
     }
 
+    fn get_span(&self) -> Span {
+        self.expr.span
+    }
+
     fn build(self: Box<Self>, _build: &mut Build) {}
 }
 
@@ -46,6 +46,14 @@ impl Flag {
         }
     }
 
+    #[must_use]
+    pub fn as_number(&self) -> Option<f64> {
+        match &self.kind {
+            FlagKind::Setting(setting) => setting.get_value().and_then(FlagValue::as_number),
+            FlagKind::Set(_) => None,
+        }
+    }
+
     /// Get the span of where the flag is set. Only works with non-flagset flags.
     #[must_use]
     pub fn get_span(&self) -> Option<Span> {
@@ -64,6 +72,10 @@ pub enum FlagType {
     /// True or false
     Boolean,
     String,
+    /// One of the given identifiers
+    Enum(&'static [&'static str]),
+    /// A positive number
+    Number,
 }
 
 /// The kind of a flag.
@@ -131,6 +143,7 @@ impl FlagSetting {
 pub enum FlagValue {
     String(String),
     Bool(bool),
+    Number(f64),
 }
 
 impl FlagValue {
@@ -151,8 +164,21 @@ impl FlagValue {
             None
         }
     }
+
+    #[must_use]
+    pub fn as_number(&self) -> Option<f64> {
+        if let Self::Number(v) = self {
+            Some(*v)
+        } else {
+            None
+        }
+    }
 }
 
+/// Engines that can be selected with the `engine` flag. Each one has a flag group
+/// of the same name with engine-specific settings.
+pub const ENGINES: &[&str] = &["glide", "rage", "sa"];
+
 /// A set of flags, also referenced to as a flag group.
 pub type FlagSet = HashMap<&'static str, Flag>;
 
@@ -200,6 +226,21 @@ impl FlagSetConstructor {
         self
     }
 
+    /// Add a flag accepting one of the given identifiers.
+    #[must_use]
+    pub fn add_enum(mut self, name: &'static str, values: &'static [&'static str]) -> Self {
+        self.flags.push((
+            name,
+            Flag {
+                name,
+                kind: FlagKind::Setting(FlagSetting::Unset),
+                ty: FlagType::Enum(values),
+            },
+        ));
+
+        self
+    }
+
     /// Add a boolean flag
     #[must_use]
     pub fn add_bool(mut self, name: &'static str) -> Self {
@@ -230,6 +271,21 @@ impl FlagSetConstructor {
         self
     }
 
+    /// Add a positive number flag
+    #[must_use]
+    pub fn add_number(mut self, name: &'static str) -> Self {
+        self.flags.push((
+            name,
+            Flag {
+                name,
+                kind: FlagKind::Setting(FlagSetting::Unset),
+                ty: FlagType::Number,
+            },
+        ));
+
+        self
+    }
+
     /// Add a nested flag set
     #[must_use]
     pub fn add_set(mut self, name: &'static str, set: FlagSetConstructor) -> Self {
@@ -258,6 +314,33 @@ impl Default for FlagSetConstructor {
     }
 }
 
+/// Check that no engine-specific flags are set for an engine other than the selected one.
+pub fn check_engine_flags(set: &FlagSet, context: &CompileContext) {
+    let engine_flag = &set["engine"];
+    let (Some(engine), Some(engine_span)) = (engine_flag.as_ident(), engine_flag.get_span()) else {
+        return;
+    };
+
+    for other in ENGINES.iter().filter(|name| *name != engine) {
+        let mut set_flags: Vec<_> = set[other]
+            .as_set()
+            .unwrap()
+            .values()
+            .filter_map(|flag| flag.get_span().map(|span| (flag.name, span)))
+            .collect();
+        set_flags.sort_by_key(|(_, span)| span.start);
+
+        for (name, error_span) in set_flags {
+            context.push_error(Error::FlagForOtherEngine {
+                error_span,
+                flag_name: format!("{other}.{name}"),
+                engine: engine.clone(),
+                engine_span,
+            });
+        }
+    }
+}
+
 /// Set a flag's value.
 pub fn set_flag(set: &mut FlagSet, flag: &FlagStatement, context: &CompileContext) {
     set_flag_recursive(set, flag, context, 0);
@@ -306,6 +389,12 @@ fn set_flag_recursive(
             FlagType::Boolean => {
                 context.ok(set_flag_bool(flag_ref, flag));
             }
+            FlagType::Number => {
+                context.ok(set_flag_number(flag_ref, flag));
+            }
+            FlagType::Enum(values) => {
+                context.ok(set_flag_enum(flag_ref, values, flag));
+            }
             FlagType::String => match &flag.value {
                 crate::parser::FlagValue::Number(_) | crate::parser::FlagValue::Set(_) => {
                     context.push_error(Error::StringExpected {
@@ -412,3 +501,75 @@ fn set_flag_bool(flag: &mut Flag, stmt: &FlagStatement) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Set an enum flag.
+fn set_flag_enum(
+    flag: &mut Flag,
+    values: &'static [&'static str],
+    stmt: &FlagStatement,
+) -> Result<(), Error> {
+    let crate::parser::FlagValue::Ident(ident) = &stmt.value else {
+        return Err(Error::StringExpected {
+            error_span: stmt.get_span(),
+        });
+    };
+
+    if !values.contains(&ident.ident.as_str()) {
+        return Err(Error::EnumInvalidValue {
+            error_span: ident.span,
+            available_values: values,
+            received_value: ident.ident.clone(),
+        });
+    }
+
+    match &mut flag.kind {
+        FlagKind::Setting(s) => match s {
+            FlagSetting::Default(_) | FlagSetting::Unset => {
+                *s = FlagSetting::Set(FlagValue::String(ident.ident.clone()), stmt.get_span());
+            }
+            FlagSetting::Set(_, sp) => {
+                return Err(Error::RedefinedFlag {
+                    error_span: stmt.get_span(),
+                    first_defined: *sp,
+                    flag_name: flag.name,
+                })
+            }
+        },
+        FlagKind::Set(_) => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Set a positive number flag.
+fn set_flag_number(flag: &mut Flag, stmt: &FlagStatement) -> Result<(), Error> {
+    let crate::parser::FlagValue::Number(num) = &stmt.value else {
+        return Err(Error::NumberExpected {
+            error_span: stmt.get_span(),
+        });
+    };
+
+    if num.is_zero() {
+        return Err(Error::PositiveNumberExpected {
+            error_span: stmt.get_span(),
+        });
+    }
+
+    match &mut flag.kind {
+        FlagKind::Setting(s) => match s {
+            FlagSetting::Default(_) | FlagSetting::Unset => {
+                *s = FlagSetting::Set(FlagValue::Number(num.to_float()), stmt.get_span());
+            }
+            FlagSetting::Set(_, sp) => {
+                return Err(Error::RedefinedFlag {
+                    error_span: stmt.get_span(),
+                    first_defined: *sp,
+                    flag_name: flag.name,
+                })
+            }
+        },
+        FlagKind::Set(_) => unreachable!(),
+    }
+
+    Ok(())
+}
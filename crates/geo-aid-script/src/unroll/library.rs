@@ -1,7 +1,8 @@
 //! `GeoScript`'s builtin functions and types
 
 use std::{
-    collections::HashMap,
+    cell::Cell,
+    collections::{hash_map::Entry, HashMap, HashSet},
     fmt::Display,
     marker::PhantomData,
     mem,
@@ -11,35 +12,52 @@ use std::{
 use num_rational::Ratio;
 
 use crate::{
-    parser::Type,
+    parser::{FnStatement, Type},
     token::number::ProcNum,
-    unit,
+    ty, unit,
     unroll::{AnyExpr, Expr, GeoType, Number, PointCollection},
-    ComplexUnit,
+    ComplexUnit, Error,
 };
 
 use super::{
-    context::CompileContext, figure::Node, most_similar, Convert, ConvertFrom, Dummy, Generic,
-    NumberData, Properties,
+    context::CompileContext, figure::Node, most_similar_n, CloneWithNode, Convert, ConvertFrom,
+    Dummy, Generic, NumberData, Properties,
 };
 
+pub mod abs;
 pub mod angle;
+pub mod annotation;
+pub mod arc;
+pub mod arc_mid;
 pub mod area;
 pub mod bisector;
+pub mod centroid;
 pub mod circle;
+pub mod collinear;
 pub mod complex;
+pub mod concurrent;
+pub mod constants;
+pub mod cross_ratio;
 pub mod degrees;
 pub mod dst;
+pub mod external_bisector;
+pub mod foot;
+pub mod harmonic_conjugate;
 pub mod intersection;
 pub mod lies_on;
 pub mod line;
 pub mod mid;
+pub mod min_max;
 pub mod parallel;
 pub mod perpendicular;
 pub mod point;
 pub mod polygon;
+pub mod power;
 pub mod radians;
+pub mod radical_axis;
 pub mod segment;
+pub mod sqrt;
+pub mod tangent;
 pub mod transform;
 pub mod triangle;
 pub mod trigonometry;
@@ -51,15 +69,17 @@ pub mod prelude {
         unroll::{
             context::CompileContext,
             figure::{
-                BuildAssociated, CollectionNode, HierarchyNode, LineNode, LineType, NumberNode,
-                PointNode,
+                ArcCount, BuildAssociated, CollectionNode, HierarchyNode, LineNode, LineType,
+                NumberNode, PointNode, TickCount,
+            },
+            library::{
+                macros::*, Angle, Area, Constant, Distance, Function, Library, Pc, Rule, Unitless,
             },
-            library::{macros::*, Angle, Area, Distance, Function, Library, Pc, Rule, Unitless},
             Circle, CloneWithNode, Derived, DerivedType, Expr, GeoType, Line, NumberData, Point,
             Properties, UnrolledRule, UnrolledRuleKind,
         },
     };
-    pub(crate) use geo_aid_figure::Style;
+    pub(crate) use geo_aid_figure::{Color, Style};
     pub(crate) use std::rc::Rc;
 }
 /// A `GeoScript` function.
@@ -129,6 +149,20 @@ impl Function {
         self
     }
 
+    /// Add a new overload with named parameters to this function.
+    #[must_use]
+    pub fn overload_named<Marker>(
+        mut self,
+        names: &'static [&'static str],
+        f: impl IntoOverload<Marker>,
+    ) -> Self {
+        self.overloads.push(Box::new(NamedOverload {
+            names,
+            overload: f.into_overload(),
+        }));
+        self
+    }
+
     /// Tries to find an overload for the given param types.
     #[must_use]
     pub fn get_overload(&self, params: &[AnyExpr]) -> Option<&dyn Overload> {
@@ -137,6 +171,61 @@ impl Function {
             .map(AsRef::as_ref)
             .find(|x| x.get_returned_type(params).is_some())
     }
+
+    /// Tries to find an overload for the given params, the last of which are given by `names`.
+    /// If found, the params are reordered to match the overload's parameters.
+    #[must_use]
+    pub fn get_named_overload(
+        &self,
+        params: &mut Vec<AnyExpr>,
+        names: &[String],
+    ) -> Option<&dyn Overload> {
+        let positional = params.len() - names.len();
+
+        for overload in &self.overloads {
+            let Some(order) = overload
+                .param_names()
+                .and_then(|param_names| arrange_params(param_names, positional, names))
+            else {
+                continue;
+            };
+
+            let arranged: Vec<_> = order
+                .iter()
+                .map(|&i| params[i].clone_without_node())
+                .collect();
+
+            if overload.get_returned_type(&arranged).is_some() {
+                let mut taken: Vec<_> = mem::take(params).into_iter().map(Some).collect();
+                *params = order.iter().map(|&i| taken[i].take().unwrap()).collect();
+
+                return Some(overload.as_ref());
+            }
+        }
+
+        None
+    }
+}
+
+/// Match named params against the names of an overload's parameters. The first `positional`
+/// parameters are matched by position. Returns the index of the param given for each parameter.
+#[must_use]
+pub fn arrange_params<S: AsRef<str>>(
+    param_names: &[S],
+    positional: usize,
+    names: &[String],
+) -> Option<Vec<usize>> {
+    if param_names.len() != positional + names.len() {
+        return None;
+    }
+
+    let mut order: Vec<_> = (0..positional).collect();
+
+    for param in &param_names[positional..] {
+        order.push(positional + names.iter().position(|name| name == param.as_ref())?);
+    }
+
+    Some(order)
 }
 
 /// Trait for function overloads
@@ -155,6 +244,40 @@ pub trait Overload {
         context: &mut CompileContext,
         props: Properties,
     ) -> AnyExpr;
+
+    /// Names of the parameters, if the overload can be called with named arguments.
+    #[must_use]
+    fn param_names(&self) -> Option<&'static [&'static str]> {
+        None
+    }
+}
+
+/// An overload with named parameters.
+#[derive(Debug)]
+pub struct NamedOverload<O> {
+    /// Names of the parameters.
+    names: &'static [&'static str],
+    /// The actual overload.
+    overload: O,
+}
+
+impl<O: Overload> Overload for NamedOverload<O> {
+    fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
+        self.overload.get_returned_type(params)
+    }
+
+    fn unroll(
+        &self,
+        params: Vec<AnyExpr>,
+        context: &mut CompileContext,
+        props: Properties,
+    ) -> AnyExpr {
+        self.overload.unroll(params, context, props)
+    }
+
+    fn param_names(&self) -> Option<&'static [&'static str]> {
+        Some(self.names)
+    }
 }
 
 /// An overload of a function in `GeoScript`.
@@ -196,7 +319,7 @@ macro_rules! impl_overload_function {
                 #[allow(unused_mut, unused_variables)]
                 let mut param = params.into_iter();
                 (self.0)(
-                    $($arg::Target::convert_from(param.next().unwrap(), context).into(),)*
+                    $($arg::Target::convert_from(param.next().unwrap().convert_to($arg::get_type(), context), context).into(),)*
                     context, props
                 ).into()
             }
@@ -431,6 +554,59 @@ impl<
     }
 }
 
+/// A `GeoScript` constant. Constants are numbers with no unit set, resolved when
+/// no variable of the same name exists.
+pub struct Constant {
+    /// Name of this constant.
+    pub name: &'static str,
+    /// The constant's value.
+    pub value: ProcNum,
+    /// Aliases of this constant.
+    pub aliases: Vec<&'static str>,
+}
+
+impl Constant {
+    /// Create a new constant with the given name. The name MUST be ascii and lowercase
+    #[must_use]
+    pub fn new(name: &'static str, value: ProcNum) -> Self {
+        if name
+            .chars()
+            .any(|c| !c.is_ascii() || !c.is_lowercase() && c.is_alphabetic() || c == '_')
+        {
+            panic!("Constant name must be ASCII, lowercase and not contain underscores. Received name: {name}");
+        }
+
+        Self {
+            name,
+            value,
+            aliases: Vec::new(),
+        }
+    }
+
+    /// Create an alias for this constant
+    #[must_use]
+    pub fn alias(mut self, name: &'static str) -> Self {
+        if name
+            .chars()
+            .any(|c| !c.is_ascii() || !c.is_lowercase() && c.is_alphabetic() || c == '_')
+        {
+            panic!("Constant name must be ASCII, lowercase and not contain underscores. Received name: {name}");
+        }
+
+        self.aliases.push(name);
+        self
+    }
+
+    /// Get the constant as an expression.
+    #[must_use]
+    pub fn get(&self) -> Expr<Number> {
+        Expr::new_spanless(Number {
+            unit: None,
+            data: NumberData::Number(self.value.clone()),
+        })
+    }
+}
+
 /// A direct definition or an alias
 pub enum Definition<T> {
     /// A direct function definition
@@ -438,6 +614,9 @@ pub enum Definition<T> {
     Alias(&'static str),
 }
 
+/// How many similar names are suggested at most for an undefined function, method or rule.
+const SUGGESTIONS: usize = 3;
+
 /// The library of all rules and functions available in geoscript.
 #[derive(Default)]
 pub struct Library {
@@ -447,6 +626,22 @@ pub struct Library {
     methods: HashMap<Type, HashMap<&'static str, Definition<Function>>>,
     /// The rule operators.
     rule_ops: HashMap<&'static str, Definition<Rule>>,
+    /// Constants
+    constants: HashMap<&'static str, Definition<Constant>>,
+    /// Functions defined in the script itself.
+    script_functions: HashMap<String, ScriptFunction>,
+}
+
+/// A function defined in the script with a `fn` statement.
+#[derive(Debug)]
+pub struct ScriptFunction {
+    /// The function's definition.
+    pub definition: FnStatement,
+    /// Names of the variables defined before the function. Only these and the parameters
+    /// are visible inside its body.
+    pub visible: HashSet<String>,
+    /// Whether the function is currently being unrolled. Used for detecting recursion.
+    pub unrolling: Cell<bool>,
 }
 
 impl Library {
@@ -457,9 +652,15 @@ impl Library {
             functions: HashMap::new(),
             methods: HashMap::new(),
             rule_ops: HashMap::new(),
+            constants: HashMap::new(),
+            script_functions: HashMap::new(),
         };
 
+        constants::register(&mut library); // pi, tau
         complex::register(&mut library);
+        abs::register(&mut library); // abs()
+        sqrt::register(&mut library); // sqrt()
+        min_max::register(&mut library); // min(), max()
         trigonometry::register(&mut library);
         transform::register(&mut library);
         point::register(&mut library); // Point()
@@ -468,18 +669,31 @@ impl Library {
         degrees::register(&mut library); // degrees()
         radians::register(&mut library); // radians()
         mid::register(&mut library); // mid()
+        centroid::register(&mut library); // centroid()
         perpendicular::register(&mut library); // perpendicular_through()
+        foot::register(&mut library); // foot()
         parallel::register(&mut library); // parallel_through()
         intersection::register(&mut library); // intersection()
         bisector::register(&mut library); // bisector()
+        external_bisector::register(&mut library); // external_bisector()
+        radical_axis::register(&mut library); // radical_axis()
+        power::register(&mut library); // power()
+        cross_ratio::register(&mut library); // cross_ratio()
+        harmonic_conjugate::register(&mut library); // harmonic_conjugate()
         circle::register(&mut library); // Circle()
+        tangent::register(&mut library); // tangent()
         segment::register(&mut library); // Segment()
-        line::register(&mut library); // Line()
+        arc::register(&mut library); // arc()
+        arc_mid::register(&mut library); // arc_mid()
+        annotation::register(&mut library); // annotate()
+        line::register(&mut library); // Line(), ray()
         area::register(&mut library);
         polygon::register(&mut library);
         triangle::register(&mut library);
 
         lies_on::register(&mut library); // lies_on
+        collinear::register(&mut library); // collinear
+        concurrent::register(&mut library); // concurrent
 
         library
     }
@@ -490,14 +704,22 @@ impl Library {
         self
     }
 
+    /// Get the names of all functions, including aliases. The names are normalized:
+    /// lowercase and without underscores.
+    pub fn function_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.functions.keys().copied()
+    }
+
     /// Get the function by its name. If the function doesn't exist,
-    /// return the most similar name if one exists. The search is case-insensitive and ignores underscores.
-    pub fn get_function(&self, name: &str) -> Result<&Function, Option<&'static str>> {
+    /// return the most similar names. The search is case-insensitive and ignores underscores.
+    pub fn get_function(&self, name: &str) -> Result<&Function, Vec<&'static str>> {
         let mut name = name.to_lowercase();
         name.retain(|c| c != '_');
         self.functions
             .get(name.as_str())
-            .ok_or_else(|| most_similar(self.functions.keys().copied(), name.as_str()))
+            .ok_or_else(|| {
+                most_similar_n(self.functions.keys().copied(), name.as_str(), SUGGESTIONS)
+            })
             .and_then(|f| match f {
                 Definition::Direct(f) => Ok(f),
                 Definition::Alias(n) => self.get_function(n),
@@ -505,18 +727,18 @@ impl Library {
     }
 
     /// Get the method by its name and self type. If the method doesn't exist,
-    /// return the most similar name if one exists. The search is case-insensitive and ignores underscores.
+    /// return the most similar names. The search is case-insensitive and ignores underscores.
     /// Self type is expected to be concrete. Internal use only.
     fn get_method_concrete(
         &self,
         self_type: Type,
         name: &str,
-    ) -> Result<&Function, Option<&'static str>> {
-        let methods = self.methods.get(&self_type).ok_or(None)?;
+    ) -> Result<&Function, Vec<&'static str>> {
+        let methods = self.methods.get(&self_type).ok_or_else(Vec::new)?;
 
         methods
-            .get(name.to_lowercase().as_str())
-            .ok_or_else(|| most_similar(methods.keys().copied(), name))
+            .get(name)
+            .ok_or_else(|| most_similar_n(methods.keys().copied(), name, SUGGESTIONS))
             .and_then(|f| match f {
                 Definition::Direct(f) => Ok(f),
                 Definition::Alias(n) => self.get_function(n),
@@ -524,13 +746,12 @@ impl Library {
     }
 
     /// Get the method by its name and self type. If the method doesn't exist,
-    /// return the most similar name if one exists. The search is case-insensitive and ignores underscores.
-    pub fn get_method(
-        &self,
-        self_type: Type,
-        name: &str,
-    ) -> Result<&Function, Option<&'static str>> {
-        let suggested = match self.get_method_concrete(self_type, name) {
+    /// return the most similar names. The search is case-insensitive and ignores underscores.
+    pub fn get_method(&self, self_type: Type, name: &str) -> Result<&Function, Vec<&'static str>> {
+        let mut name = name.to_lowercase();
+        name.retain(|c| c != '_');
+
+        let suggested = match self.get_method_concrete(self_type, &name) {
             Ok(method) => return Ok(method),
             Err(suggested) => suggested,
         };
@@ -538,27 +759,80 @@ impl Library {
         self.get_method_concrete(
             match self_type {
                 Type::PointCollection(_) => Type::PointCollection(0),
+                // Numbers of unknown unit (like literals) can be used as unitless ones.
+                Type::Number(None) => ty::SCALAR,
                 Type::Number(_) => Type::Number(None),
                 _ => return Err(suggested),
             },
-            name,
+            &name,
         )
-        .map_err(|err| most_similar(suggested.into_iter().chain(err), name))
+        .map_err(|err| most_similar_n(suggested.into_iter().chain(err), &name, SUGGESTIONS))
     }
 
     /// Get the rule operator by its name. If the rule doesn't exist,
-    /// return the most similar name if one exists. The search is case-insensitive and ignores underscores.
-    pub fn get_rule(&self, name: &str) -> Result<&Rule, Option<&'static str>> {
+    /// return the most similar names. The search is case-insensitive and ignores underscores.
+    pub fn get_rule(&self, name: &str) -> Result<&Rule, Vec<&'static str>> {
         let mut name = name.to_lowercase();
         name.retain(|c| c != '_');
         self.rule_ops
             .get(name.as_str())
-            .ok_or_else(|| most_similar(self.rule_ops.keys().copied(), name.as_str()))
+            .ok_or_else(|| {
+                most_similar_n(self.rule_ops.keys().copied(), name.as_str(), SUGGESTIONS)
+            })
             .and_then(|f| match f {
                 Definition::Direct(f) => Ok(f),
                 Definition::Alias(n) => self.get_rule(n),
             })
     }
+
+    /// Get the constant by its name. The search is case-insensitive and ignores underscores.
+    #[must_use]
+    pub fn get_constant(&self, name: &str) -> Option<&Constant> {
+        let mut name = name.to_lowercase();
+        name.retain(|c| c != '_');
+        self.constants.get(name.as_str()).and_then(|c| match c {
+            Definition::Direct(c) => Some(c),
+            Definition::Alias(n) => self.get_constant(n),
+        })
+    }
+
+    /// Get a function defined in the script by its name. The search is case-insensitive and ignores underscores.
+    #[must_use]
+    pub fn get_script_function(&self, name: &str) -> Option<&ScriptFunction> {
+        let mut name = name.to_lowercase();
+        name.retain(|c| c != '_');
+        self.script_functions.get(&name)
+    }
+
+    /// Add a function defined in the script. Script functions take precedence over builtin ones.
+    /// `visible` holds the names of the variables its body can refer to.
+    ///
+    /// # Errors
+    /// Returns an error if a script function with the same name already exists.
+    pub fn add_script_function(
+        &mut self,
+        definition: FnStatement,
+        visible: HashSet<String>,
+    ) -> Result<(), Error> {
+        let mut name = definition.name.ident.to_lowercase();
+        name.retain(|c| c != '_');
+
+        match self.script_functions.entry(name) {
+            Entry::Occupied(entry) => Err(Error::RedefinedFunction {
+                defined_at: entry.get().definition.name.span,
+                error_span: definition.name.span,
+                function_name: definition.name.ident,
+            }),
+            Entry::Vacant(entry) => {
+                entry.insert(ScriptFunction {
+                    definition,
+                    visible,
+                    unrolling: Cell::new(false),
+                });
+                Ok(())
+            }
+        }
+    }
 }
 
 /// Trait for adding a definition to the library.
@@ -598,6 +872,20 @@ impl Addable for Rule {
     }
 }
 
+impl Addable for Constant {
+    fn add_to(mut self, library: &mut Library) {
+        for alias in mem::take(&mut self.aliases) {
+            library
+                .constants
+                .insert(alias, Definition::Alias(self.name));
+        }
+
+        library
+            .constants
+            .insert(self.name, Definition::Direct(self));
+    }
+}
+
 /// Point collection with a specific size
 pub struct Pc<const N: usize>(pub Expr<PointCollection>);
 
@@ -734,6 +1022,12 @@ pub const fn get_derived_pc(_name: &'static str) -> usize {
     0
 }
 
+/// Returns whether the given derived type can be cast onto a line.
+#[must_use]
+pub fn is_derived_line(name: &str) -> bool {
+    matches!(name, "Segment")
+}
+
 /// Helper macros
 pub mod macros {
     /// Get the expression at given index in a point collection.
@@ -857,3 +1151,25 @@ pub mod macros {
 
     pub(crate) use {impl_derived, index, number};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Library;
+
+    #[test]
+    fn similar_function_names_are_suggested() {
+        let library = Library::new();
+
+        let suggested = library.get_function("Cirlce").err().unwrap();
+        assert_eq!(suggested.first(), Some(&"circle"));
+        assert!(suggested.len() <= 3);
+    }
+
+    #[test]
+    fn dissimilar_function_names_are_not_suggested() {
+        let library = Library::new();
+
+        let suggested = library.get_function("qwxzkj").err().unwrap();
+        assert!(suggested.is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+//! The `abs` function
+
+use crate::{
+    parser::Type,
+    unroll::{AnyExpr, Number},
+};
+
+use super::{prelude::*, Overload};
+
+/// Absolute value of a real number. The unit is preserved.
+#[must_use]
+pub fn abs(num: Expr<Number>, context: &CompileContext, props: Properties) -> Expr<Number> {
+    context.abs_display(num, props)
+}
+
+#[derive(Debug)]
+struct Abs;
+
+impl Overload for Abs {
+    fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
+        if params.len() != 1 {
+            return None;
+        }
+
+        params[0].can_convert_to_scalar(None).map(Type::Number)
+    }
+
+    fn unroll(
+        &self,
+        mut params: Vec<AnyExpr>,
+        context: &mut CompileContext,
+        props: Properties,
+    ) -> AnyExpr {
+        let ty = self.get_returned_type(&params).unwrap();
+        let num = params
+            .swap_remove(0)
+            .convert_to(ty, context)
+            .to_scalar()
+            .unwrap();
+
+        abs(num, context, props).into()
+    }
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("abs")
+            .alias_method(Type::Number(None), "abs")
+            .overload(Abs),
+    );
+}
@@ -1,6 +1,6 @@
 /// The `angle` function
 use crate::{
-    figure::{LineItem, RayItem, SegmentItem},
+    figure::{AngleItem, LineItem, RayItem, SegmentItem},
     math::Build,
 };
 
@@ -17,14 +17,22 @@ fn angle_function_ppp(
 ) -> Angle {
     let display_arms = display.get("display_arms").maybe_unset(true);
     let arms_style = display.get("arms_style").maybe_unset(Style::default());
+    let arms_color = display.get("arms_color").maybe_unset(Color::default());
     let arms_type = display.get("arms_type").maybe_unset(LineType::Segment);
+    let arcs = display.get("arcs").maybe_unset(ArcCount(1));
+    let arcs_style = display.get("arcs_style").maybe_unset(Style::default());
+    let arcs_color = display.get("arcs_color").maybe_unset(Color::default());
 
     let mut expr = context.angle_ppp_display(a, b, c, display);
 
     if let Some(node) = &mut expr.node {
         node.insert_data("display_arms", display_arms);
         node.insert_data("arms_style", arms_style);
+        node.insert_data("arms_color", arms_color);
         node.insert_data("arms_type", arms_type);
+        node.insert_data("arcs", arcs);
+        node.insert_data("arcs_style", arcs_style);
+        node.insert_data("arcs_color", arcs_color);
 
         node.set_associated(Associated);
     }
@@ -32,12 +40,16 @@ fn angle_function_ppp(
 }
 
 /// ```
-/// # use geo_aid_figure::Style;
-/// # use geo_aid_script::unroll::figure::LineType;
+/// # use geo_aid_figure::{Color, Style};
+/// # use geo_aid_script::unroll::figure::{ArcCount, LineType};
 /// struct Associated {
 ///     display_arms: bool,
 ///     arms_style: Style,
-///     amrs_type: LineType
+///     arms_color: Color,
+///     amrs_type: LineType,
+///     arcs: ArcCount,
+///     arcs_style: Style,
+///     arcs_color: Color,
 /// }
 /// ```
 #[derive(Debug)]
@@ -51,6 +63,7 @@ pub fn display_angle_arms(
     c_expr: &Expr<Point>,
     arms_type: LineType,
     arms_style: Style,
+    arms_color: Color,
 ) {
     let a = build.load(a_expr);
     let b = build.load(b_expr);
@@ -72,12 +85,16 @@ pub fn display_angle_arms(
                 id,
                 label: MathString::new(),
                 style: arms_style,
+                color: arms_color,
+                opacity: None,
             });
             let id = build.load(&line_c);
             build.add(LineItem {
                 id,
                 label: MathString::new(),
                 style: arms_style,
+                color: arms_color,
+                opacity: None,
             });
         }
         LineType::Ray => {
@@ -86,12 +103,16 @@ pub fn display_angle_arms(
                 q_id: a,
                 label: MathString::new(),
                 style: arms_style,
+                color: arms_color,
+                opacity: None,
             });
             build.add(RayItem {
                 p_id: b,
                 q_id: c,
                 label: MathString::new(),
                 style: arms_style,
+                color: arms_color,
+                opacity: None,
             });
         }
         LineType::Segment => {
@@ -100,12 +121,18 @@ pub fn display_angle_arms(
                 q_id: a,
                 label: MathString::new(),
                 style: arms_style,
+                color: arms_color,
+                opacity: None,
+                ticks: 0,
             });
             build.add(SegmentItem {
                 p_id: b,
                 q_id: c,
                 label: MathString::new(),
                 style: arms_style,
+                color: arms_color,
+                opacity: None,
+                ticks: 0,
             });
         }
     }
@@ -131,6 +158,13 @@ impl BuildAssociated<NumberNode> for Associated {
             .unwrap()
             .unwrap();
 
+        let arms_color = associated
+            .get_data("arms_color")
+            .unwrap()
+            .as_color()
+            .unwrap()
+            .unwrap();
+
         let arms_type = associated
             .get_data("arms_type")
             .unwrap()
@@ -138,14 +172,48 @@ impl BuildAssociated<NumberNode> for Associated {
             .unwrap()
             .unwrap();
 
-        if display_arms {
-            match &associated.root.expr.data.data {
-                NumberData::ThreePointAngle(a_expr, b_expr, c_expr)
-                | NumberData::ThreePointAngleDir(a_expr, b_expr, c_expr) => {
-                    display_angle_arms(build, a_expr, b_expr, c_expr, arms_type, arms_style);
+        let arcs = associated.get_data("arcs").unwrap().as_arc_count().unwrap();
+
+        let arcs_style = associated
+            .get_data("arcs_style")
+            .unwrap()
+            .as_style()
+            .unwrap()
+            .unwrap();
+
+        let arcs_color = associated
+            .get_data("arcs_color")
+            .unwrap()
+            .as_color()
+            .unwrap()
+            .unwrap();
+
+        match &associated.root.expr.data.data {
+            NumberData::ThreePointAngle(a_expr, b_expr, c_expr)
+            | NumberData::ThreePointAngleDir(a_expr, b_expr, c_expr) => {
+                if display_arms {
+                    display_angle_arms(
+                        build, a_expr, b_expr, c_expr, arms_type, arms_style, arms_color,
+                    );
+                }
+
+                // The angle is only marked if the arc count was explicitly given.
+                if arcs.is_set() {
+                    let p_id = build.load(a_expr);
+                    let q_id = build.load(b_expr);
+                    let r_id = build.load(c_expr);
+
+                    build.add(AngleItem {
+                        p_id,
+                        q_id,
+                        r_id,
+                        arcs: arcs.unwrap().0,
+                        style: arcs_style,
+                        color: arcs_color,
+                    });
                 }
-                _ => unreachable!(),
             }
+            _ => unreachable!(),
         }
     }
 }
@@ -0,0 +1,103 @@
+//! The `Annotation` type and function
+
+use std::fmt::Display;
+
+use crate::{
+    figure::{AnnotationItem, SpannedMathString as MathString},
+    math::Build,
+    span,
+    unroll::figure::{MaybeUnset, Node, Opacity},
+};
+
+use super::prelude::*;
+
+/// A free-standing text annotation.
+#[derive(Debug)]
+pub struct Annotation {
+    /// The point the text is drawn at
+    pub point: Expr<Point>,
+}
+
+impl DerivedType for Annotation {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Annotation({})", self.point)
+    }
+}
+
+impl_derived! {Annotation}
+
+#[derive(Debug)]
+struct AnnotationNode {
+    /// Whether the node should be displayed
+    display: MaybeUnset<bool>,
+    /// The annotation's text
+    label: MaybeUnset<MathString>,
+    /// The text's color
+    color: MaybeUnset<Color>,
+    /// The text's opacity
+    opacity: MaybeUnset<Opacity>,
+    /// The point the text is drawn at
+    point: Expr<Point>,
+}
+
+impl Node for AnnotationNode {
+    fn set_display(&mut self, display: bool) {
+        self.display.set(display);
+    }
+
+    fn get_display(&self) -> bool {
+        self.display.get_copied()
+    }
+
+    fn build(self: Box<Self>, build: &mut Build) {
+        // Empty annotations would be invisible anyway.
+        if self.display.unwrap() && !self.label.get().is_empty() {
+            let id = build.load(&self.point);
+
+            build.add(AnnotationItem {
+                id,
+                content: self.label.unwrap().string,
+                color: self.color.unwrap(),
+                opacity: self.opacity.try_get().map(|o| o.0),
+            });
+        }
+    }
+}
+
+/// `annotate(point)` - draws the `label` text centered on the point. Adds no rules.
+fn annotate(
+    mut point: Expr<Point>,
+    context: &CompileContext,
+    mut display: Properties,
+) -> AnnotationExpr {
+    let default_label = display
+        .get("default-label")
+        .ok_or(MathString::new(span!(0, 0, 0, 0)));
+
+    let node = AnnotationNode {
+        display: display.get("display").maybe_unset(true),
+        label: display.get("label").maybe_unset(default_label),
+        color: display.get("color").maybe_unset(Color::default()),
+        opacity: display.get("opacity").maybe_unset(Opacity::default()),
+        point: point.clone_without_node(),
+    };
+
+    display.finish(context);
+
+    let point_node = point.take_node();
+    let mut node = HierarchyNode::new_dyn(node);
+    node.extend_children(point_node);
+
+    AnnotationExpr::new(Annotation { point }, node)
+}
+
+/// Register the type and the function
+pub fn register(library: &mut Library) {
+    library.add(Function::new("annotate").overload(annotate));
+}
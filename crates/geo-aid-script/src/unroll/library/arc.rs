@@ -0,0 +1,241 @@
+//! The `Arc` type and function
+
+use std::fmt::Display;
+
+use crate::{
+    figure::{ArcItem, SpannedMathString as MathString},
+    math::Build,
+    span,
+    unroll::{
+        figure::{LabelOffset, MaybeUnset, Node},
+        Dummy,
+    },
+};
+
+use super::{circle, prelude::*};
+
+/// An arc of a circle.
+#[derive(Debug)]
+pub struct Arc {
+    /// The circle the arc lies on
+    pub circle: Expr<Circle>,
+    /// The point at the arc's start
+    pub start: Expr<Point>,
+    /// The point at the arc's end
+    pub end: Expr<Point>,
+    /// A point the arc goes through. If not given, the arc goes counterclockwise.
+    pub through: Option<Expr<Point>>,
+}
+
+impl DerivedType for Arc {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Display for Arc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(through) = &self.through {
+            write!(f, "Arc({}, {}, {})", self.start, through, self.end)
+        } else {
+            write!(f, "Arc({}, {}, {})", self.circle, self.start, self.end)
+        }
+    }
+}
+
+impl_derived! {Arc}
+
+#[derive(Debug)]
+struct ArcNode {
+    /// Whether the node should be displayed
+    display: MaybeUnset<bool>,
+    /// The arc's label
+    label: MaybeUnset<MathString>,
+    /// Whether to display the label
+    display_label: MaybeUnset<bool>,
+    /// How the arc should be drawn.
+    style: MaybeUnset<Style>,
+    /// The arc's color.
+    color: MaybeUnset<Color>,
+    /// Manual offset of the label.
+    label_offset: MaybeUnset<LabelOffset>,
+    /// The circle the arc lies on
+    circle: Expr<Circle>,
+    /// The point at the arc's start
+    start: Expr<Point>,
+    /// The point at the arc's end
+    end: Expr<Point>,
+    /// A point the arc goes through
+    through: Option<Expr<Point>>,
+}
+
+impl Dummy for ArcNode {
+    fn dummy() -> Self {
+        Self {
+            display: MaybeUnset::new(true),
+            label: MaybeUnset::new(MathString::new(span!(0, 0, 0, 0))),
+            display_label: MaybeUnset::new(true),
+            style: MaybeUnset::new(Style::Solid),
+            color: MaybeUnset::new(Color::default()),
+            label_offset: MaybeUnset::new(LabelOffset::default()),
+            circle: Expr::dummy(),
+            start: Expr::dummy(),
+            end: Expr::dummy(),
+            through: None,
+        }
+    }
+
+    fn is_dummy(&self) -> bool {
+        self.circle.is_dummy()
+            || self.start.is_dummy()
+            || self.end.is_dummy()
+            || self.through.as_ref().is_some_and(Expr::is_dummy)
+    }
+}
+
+impl Node for ArcNode {
+    fn set_display(&mut self, display: bool) {
+        self.display.set(display);
+    }
+
+    fn get_display(&self) -> bool {
+        self.display.get_copied()
+    }
+
+    fn build(self: Box<Self>, build: &mut Build) {
+        if self.display.unwrap() && !self.is_dummy() {
+            let circle_id = build.load(&self.circle);
+            let start_id = build.load(&self.start);
+            let end_id = build.load(&self.end);
+            let through_id = self.through.as_ref().map(|through| build.load(through));
+
+            build.add(ArcItem {
+                circle_id,
+                start_id,
+                end_id,
+                through_id,
+                label: if self.display_label.unwrap() {
+                    self.label.unwrap()
+                } else {
+                    MathString::new(span!(0, 0, 0, 0))
+                }
+                .string,
+                style: self.style.unwrap(),
+                color: self.color.unwrap(),
+                label_offset: self.label_offset.try_get().map(|offset| offset.0),
+            });
+        }
+    }
+}
+
+/// Creates the arc expression along with its node.
+fn arc(
+    circle: Expr<Circle>,
+    start: Expr<Point>,
+    end: Expr<Point>,
+    through: Option<Expr<Point>>,
+    context: &CompileContext,
+    mut display: Properties,
+) -> ArcExpr {
+    let node = ArcNode {
+        display: display.get("display").maybe_unset(true),
+        label: display
+            .get("label")
+            .maybe_unset(MathString::new(span!(0, 0, 0, 0))),
+        display_label: display.get("display_label").maybe_unset(true),
+        style: display.get("style").maybe_unset(Style::default()),
+        color: display.get("color").maybe_unset(Color::default()),
+        label_offset: display
+            .get("label_offset")
+            .maybe_unset(LabelOffset::default()),
+        circle: circle.clone_without_node(),
+        start: start.clone_without_node(),
+        end: end.clone_without_node(),
+        through: through.as_ref().map(CloneWithNode::clone_without_node),
+    };
+
+    display.ignore("default-label");
+    display.finish(context);
+
+    ArcExpr::new(
+        Arc {
+            circle,
+            start,
+            end,
+            through,
+        },
+        HierarchyNode::new_dyn(node),
+    )
+}
+
+/// `arc(point, point, point)` - the arc going from the first point, through the second one,
+/// to the third one.
+fn arc_function_ppp(
+    mut a: Expr<Point>,
+    mut b: Expr<Point>,
+    mut c: Expr<Point>,
+    context: &CompileContext,
+    display: Properties,
+) -> ArcExpr {
+    let nodes = [a.take_node(), b.take_node(), c.take_node()];
+
+    // Only the arc is displayed, not the whole circle.
+    let mut circle = circle::circumcircle(
+        a.clone_without_node(),
+        b.clone_without_node(),
+        c.clone_without_node(),
+        context,
+        Properties::default(),
+    );
+    circle.take_node();
+
+    let mut expr = arc(circle, a, c, Some(b), context, display);
+
+    if let Some(node) = expr.0.node.as_mut() {
+        node.extend_children(nodes.into_iter().flatten());
+    }
+
+    expr
+}
+
+/// `arc(circle, point, point)` - the arc of the circle going counterclockwise from
+/// the first point to the second one.
+fn arc_function_cpp(
+    mut circle: Expr<Circle>,
+    mut start: Expr<Point>,
+    mut end: Expr<Point>,
+    context: &CompileContext,
+    display: Properties,
+) -> ArcExpr {
+    let circle_node = circle.take_node();
+    let start_node = start.take_node();
+    let end_node = end.take_node();
+
+    let mut expr = arc(circle, start, end, None, context, display);
+
+    if let Some(node) = expr.0.node.as_mut() {
+        node.extend_children(circle_node);
+        node.extend_children(start_node);
+        node.extend_children(end_node);
+    }
+
+    expr
+}
+
+/// Register the type and the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("arc")
+            .overload(|mut col: Pc<3>, context: &CompileContext, display| {
+                arc_function_ppp(
+                    index!(node col, 0),
+                    index!(node col, 1),
+                    index!(node col, 2),
+                    context,
+                    display,
+                )
+            })
+            .overload(arc_function_ppp)
+            .overload(arc_function_cpp),
+    );
+}
@@ -0,0 +1,106 @@
+//! The `arc_mid` function
+
+use crate::token::number::ProcNum;
+
+use super::prelude::*;
+
+/// `arc_mid(A, B, circle[, P])` - the midpoint of an arc `AB` of the circle.
+///
+/// With `O` being the center and `r` the radius, the midpoint of the arc going counterclockwise
+/// from `A` to `B` is `O - i * r * (B - A) / |AB|`. Using the chord's direction instead of `A + B`
+/// keeps it stable when `A` and `B` are (nearly) diametrically opposite. If `P` is given,
+/// the arc not containing it is chosen by flipping the result when `P` lies on the same side
+/// of `AB` as the counterclockwise arc.
+fn arc_mid(
+    mut a: Expr<Point>,
+    mut b: Expr<Point>,
+    mut circle: Expr<Circle>,
+    opposite: Option<Expr<Point>>,
+    context: &CompileContext,
+    display: Properties,
+) -> Expr<Point> {
+    let point_nodes = [a.take_node(), b.take_node()];
+    let circle_node = circle.take_node();
+
+    let center = context.to_complex(context.circle_center(circle.clone_without_node()));
+    let radius = context.circle_radius(circle);
+    let start = context.to_complex(a.clone_without_node());
+
+    // The unit normal of AB pointing towards the counterclockwise arc.
+    let normal = context.div(
+        context.mult(
+            context.sub(
+                context.to_complex(b.clone_without_node()),
+                start.clone_without_node(),
+            ),
+            number!(-ProcNum::i()),
+        ),
+        context.distance_pp(a, b),
+    );
+
+    let offset = match opposite {
+        Some(mut point) => {
+            let point_node = point.take_node();
+
+            // The signed distance of P from AB against the normal. It's positive when P is
+            // on the other side than the counterclockwise arc, which is then the one to take.
+            let side = context.real(context.div(
+                context.sub(start, context.to_complex(point)),
+                normal.clone_without_node(),
+            ));
+            let sign = context.div(context.abs(side.clone_without_node()), side);
+
+            let mut offset = context.mult(context.mult(normal, radius), sign);
+            if let Some(node) = offset.node.as_mut() {
+                node.extend_children(point_node);
+            }
+
+            offset
+        }
+        None => context.mult(normal, radius),
+    };
+
+    let mut expr = context.to_point_display(context.add(center, offset), display);
+
+    if let Some(node) = expr.node.as_mut() {
+        node.extend_children(point_nodes.into_iter().flatten());
+        node.extend_children(circle_node);
+    }
+
+    expr
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("arcmid")
+            .alias("arcmidpoint")
+            .overload(
+                |a: Expr<Point>,
+                 b: Expr<Point>,
+                 circle: Expr<Circle>,
+                 context: &CompileContext,
+                 display| { arc_mid(a, b, circle, None, context, display) },
+            )
+            .overload(
+                |a: Expr<Point>,
+                 b: Expr<Point>,
+                 circle: Expr<Circle>,
+                 point: Expr<Point>,
+                 context: &CompileContext,
+                 display| { arc_mid(a, b, circle, Some(point), context, display) },
+            )
+            .overload(
+                |mut col: Pc<2>, circle: Expr<Circle>, context: &CompileContext, display| {
+                    arc_mid(
+                        index!(node col, 0),
+                        index!(node col, 1),
+                        circle,
+                        None,
+                        context,
+                        display,
+                    )
+                },
+            ),
+    );
+}
@@ -2,16 +2,14 @@
 
 use std::mem;
 
-use num_traits::FromPrimitive;
-
 use crate::{
     parser::Type,
-    token::number::ProcNum,
     unroll::{AnyExpr, Convert, PointCollection},
 };
 
-use super::{complex::abs, prelude::*, Overload};
+use super::{abs::abs, prelude::*, Overload};
 
+/// `area(A, B, C)` - the (unsigned) area of a triangle.
 fn area(
     a: Expr<Point>,
     b: Expr<Point>,
@@ -19,26 +17,11 @@ fn area(
     context: &CompileContext,
     props: Properties,
 ) -> Area {
-    let sin_angle = context.sin(context.angle_ppp(
-        a.clone_without_node(),
-        b.clone_without_node(),
-        c.clone_without_node(),
-    ));
-    let ba = context.distance_pp(b.clone_without_node(), a);
-    let bc = context.distance_pp(b, c);
-
-    context
-        .mult_display(
-            context.mult(
-                sin_angle,
-                number!(SCALAR ProcNum::from_i64(1).unwrap() / &ProcNum::from_i64(2).unwrap()),
-            ),
-            context.mult(ba, bc),
-            props,
-        )
-        .into()
+    let signed = signed_area(a, b, c, context, Properties::default());
+    abs(signed.0, context, props).into()
 }
 
+/// `signed_area(A, B, C)` - the signed area of a triangle, positive for counterclockwise orientation.
 fn signed_area(
     a: Expr<Point>,
     b: Expr<Point>,
@@ -46,24 +29,7 @@ fn signed_area(
     context: &CompileContext,
     props: Properties,
 ) -> Area {
-    let sin_angle = context.sin(context.angle_dir(
-        a.clone_without_node(),
-        b.clone_without_node(),
-        c.clone_without_node(),
-    ));
-    let ba = context.distance_pp(b.clone_without_node(), a);
-    let bc = context.distance_pp(b, c);
-
-    context
-        .mult_display(
-            context.mult(
-                sin_angle,
-                number!(SCALAR ProcNum::from_i64(1).unwrap() / &ProcNum::from_i64(2).unwrap()),
-            ),
-            context.mult(ba, bc),
-            props,
-        )
-        .into()
+    context.area_ppp_display(a, b, c, props).into()
 }
 
 #[derive(Debug)]
@@ -112,7 +78,7 @@ fn variadic_signed_area(
     mut props: Properties,
 ) -> Area {
     let mut first = points[0].clone_with_node();
-    (2..points.len())
+    let area = (2..points.len())
         .map(|i| {
             (
                 signed_area(
@@ -137,8 +103,12 @@ fn variadic_signed_area(
             )
         })
         .unwrap()
-        .0
-        .into()
+        .0;
+
+    // Properties are only consumed for more than one triangle.
+    props.finish(context);
+
+    area.into()
 }
 
 #[derive(Debug)]
@@ -184,7 +154,7 @@ pub fn register(library: &mut Library) {
         .add(
             Function::new("signedarea")
                 .alias_method(Type::PointCollection(0), "signedarea")
-                .overload(area)
+                .overload(signed_area)
                 .overload(|mut col: Pc<3>, context: &CompileContext, props| {
                     signed_area(
                         col.index_with_node(0),
@@ -1,6 +1,7 @@
 //! The `bisector` function
 
-use crate::math::Build;
+use crate::{figure::SegmentItem, math::Build};
+use geo_aid_figure::math_string::MathString;
 
 use super::{angle::display_angle_arms, prelude::*};
 
@@ -14,6 +15,7 @@ pub fn point_point_point(
 ) -> Expr<Line> {
     let display_arms = display.get("display_arms").maybe_unset(true);
     let arms_style = display.get("arms_style").maybe_unset(Style::default());
+    let arms_color = display.get("arms_color").maybe_unset(Color::default());
     let line_type = display.get("line_type").maybe_unset(LineType::Ray);
     let arms_type = display.get("arms_type").maybe_unset(LineType::Segment);
 
@@ -22,6 +24,7 @@ pub fn point_point_point(
     if let Some(node) = &mut expr.node {
         node.insert_data("display_arms", display_arms);
         node.insert_data("arms_style", arms_style);
+        node.insert_data("arms_color", arms_color);
         node.insert_data("line_type", line_type);
         node.insert_data("arms_type", arms_type);
 
@@ -31,10 +34,11 @@ pub fn point_point_point(
 }
 
 /// ```
-/// # use geo_aid_figure::Style;
+/// # use geo_aid_figure::{Color, Style};
 /// struct Associated {
 ///     display_arms: bool,
-///     arms_style: Style
+///     arms_style: Style,
+///     arms_color: Color
 /// }
 /// ```
 #[derive(Debug)]
@@ -60,6 +64,13 @@ impl BuildAssociated<LineNode> for Associated {
             .unwrap()
             .unwrap();
 
+        let arms_color = associated
+            .get_data("arms_color")
+            .unwrap()
+            .as_color()
+            .unwrap()
+            .unwrap();
+
         let mut line_type = associated
             .get_data("line_type")
             .unwrap()
@@ -80,7 +91,9 @@ impl BuildAssociated<LineNode> for Associated {
         if display_arms {
             match associated.root.expr.data.as_ref() {
                 Line::AngleBisector(a_expr, b_expr, c_expr) => {
-                    display_angle_arms(build, a_expr, b_expr, c_expr, arms_type, arms_style);
+                    display_angle_arms(
+                        build, a_expr, b_expr, c_expr, arms_type, arms_style, arms_color,
+                    );
                 }
                 _ => unreachable!(),
             }
@@ -93,16 +106,112 @@ pub fn point_point(
     a: Expr<Point>,
     b: Expr<Point>,
     context: &CompileContext,
-    display: Properties,
+    mut display: Properties,
 ) -> Expr<Line> {
     use super::perpendicular::line_point;
 
-    line_point(
+    let display_ticks = display.get("display_ticks").maybe_unset(false);
+    let tick_style = display.get("tick_style").maybe_unset(Style::default());
+
+    let mut expr = line_point(
         context.line(a.clone_without_node(), b.clone_without_node()),
         context.average_p(vec![a, b]),
         context,
         display,
-    )
+    );
+
+    if let Some(node) = &mut expr.node {
+        node.insert_data("display_ticks", display_ticks);
+        node.insert_data("tick_style", tick_style);
+
+        node.set_associated(SegmentAssociated);
+    }
+
+    expr
+}
+
+/// ```
+/// # use geo_aid_figure::Style;
+/// struct SegmentAssociated {
+///     display_ticks: bool,
+///     tick_style: Style
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SegmentAssociated;
+
+impl SegmentAssociated {
+    /// Add the segment `pq` with a tick crossing it in its middle.
+    fn add_tick(
+        build: &mut Build,
+        p: &Expr<Point>,
+        q: &Expr<Point>,
+        style: Style,
+        color: Color,
+        opacity: Option<f64>,
+    ) {
+        let p_id = build.load(p);
+        let q_id = build.load(q);
+        build.add(SegmentItem {
+            p_id,
+            q_id,
+            label: MathString::new(),
+            style,
+            color,
+            opacity,
+            ticks: 1,
+        });
+    }
+}
+
+impl BuildAssociated<LineNode> for SegmentAssociated {
+    fn build_associated(
+        self: Box<Self>,
+        build: &mut Build,
+        associated: &mut HierarchyNode<LineNode>,
+    ) {
+        let display_ticks = associated
+            .get_data("display_ticks")
+            .unwrap()
+            .as_bool()
+            .unwrap()
+            .unwrap();
+
+        let tick_style = associated
+            .get_data("tick_style")
+            .unwrap()
+            .as_style()
+            .unwrap()
+            .unwrap();
+
+        if display_ticks {
+            let Line::PerpendicularThrough(k, _) = associated.root.expr.data.as_ref() else {
+                unreachable!()
+            };
+            let Line::LineFromPoints(a, b) = k.data.as_ref() else {
+                unreachable!()
+            };
+
+            let mid = Expr::new_spanless(Point::Average(
+                vec![a.clone_without_node(), b.clone_without_node()].into(),
+            ));
+            let color = associated.root.color.get_copied();
+            let opacity = associated.root.opacity.try_get().map(|o| o.0);
+
+            Self::add_tick(build, a, &mid, tick_style, color, opacity);
+            Self::add_tick(build, &mid, b, tick_style, color, opacity);
+        }
+    }
+}
+
+/// bisector(line, line) - a bisector of the angle between two lines.
+pub fn line_line(
+    k: Expr<Line>,
+    l: Expr<Line>,
+    context: &CompileContext,
+    display: Properties,
+) -> Expr<Line> {
+    context.bisector_ll_display(k, l, false, display)
 }
 
 /// Register the function
@@ -122,6 +231,16 @@ pub fn register(library: &mut Library) {
             .overload(|mut col: Pc<2>, context: &mut CompileContext, display| {
                 point_point(index!(node col,0), index!(node col,1), context, display)
             })
+            .overload(point_point)
+            .overload(line_line),
+    );
+
+    library.add(
+        Function::new("perpendicularbisector")
+            .alias("perpbisector")
+            .overload(|mut col: Pc<2>, context: &mut CompileContext, display| {
+                point_point(index!(node col,0), index!(node col,1), context, display)
+            })
             .overload(point_point),
     );
 }
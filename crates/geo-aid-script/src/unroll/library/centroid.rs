@@ -0,0 +1,75 @@
+//! The `centroid` function
+
+use crate::{
+    parser::{PropertyValue, Type},
+    token::{Span, StrLit},
+    ty,
+    unroll::{AnyExpr, Convert},
+};
+
+use super::{prelude::*, Overload};
+
+/// The centroid of the given points. A triangle centroid is labeled `G` by default.
+fn centroid(
+    points: Vec<Expr<Point>>,
+    context: &CompileContext,
+    mut props: Properties,
+) -> Expr<Point> {
+    if points.len() == 3 {
+        props.add_if_not_present(
+            "default-label",
+            (
+                Span::empty(),
+                PropertyValue::String(StrLit {
+                    span: Span::empty(),
+                    content: String::from("G"),
+                }),
+            ),
+        );
+    }
+
+    context.average_p_display(points, props)
+}
+
+/// `centroid(P1, P2, ...)` - the centroid of any number of points.
+struct CentroidPoints;
+
+impl Overload for CentroidPoints {
+    fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
+        (!params.is_empty() && params.iter().all(|p| p.can_convert_to(Type::Point)))
+            .then_some(Type::Point)
+    }
+
+    fn unroll(
+        &self,
+        params: Vec<AnyExpr>,
+        context: &mut CompileContext,
+        props: Properties,
+    ) -> AnyExpr {
+        let points = params.into_iter().map(|x| x.convert(context)).collect();
+        centroid(points, context, props).into()
+    }
+}
+
+/// `centroid(col)` - the centroid of the points of a collection.
+fn centroid_pc(mut col: Pc<0>, context: &CompileContext, props: Properties) -> Expr<Point> {
+    let points = (0..col.0.data.length)
+        .map(|i| index!(node col, i))
+        .collect();
+    centroid(points, context, props)
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library
+        .add(
+            Function::new("centroid")
+                .overload(centroid_pc)
+                .overload(CentroidPoints),
+        )
+        .add(
+            Function::new("[pc]::centroid")
+                .alias_method(ty::collection(0), "centroid")
+                .overload(centroid_pc),
+        );
+}
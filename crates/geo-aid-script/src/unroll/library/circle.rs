@@ -2,7 +2,7 @@
 
 use crate::unroll::Number;
 
-use super::{bisector, prelude::*, NumberUnit};
+use super::{bisector, prelude::*};
 
 /// Circle constructor. Creates a circle based off of its center and radius.
 fn circle_function(
@@ -34,7 +34,7 @@ fn simple_circle(
 }
 
 /// A circle circumscribed on three points.
-fn circumcircle(
+pub fn circumcircle(
     mut a: Expr<Point>,
     mut b: Expr<Point>,
     mut c: Expr<Point>,
@@ -123,26 +123,13 @@ pub fn register(library: &mut Library) {
     library
         .add(
             Function::new("circle")
-                .overload(circle_function)
+                .overload_named(&["center", "radius"], circle_function)
                 .overload(
                     |radius: Distance, center: Expr<Point>, context: &CompileContext, display| {
                         circle_function(center, radius, context, display)
                     },
                 )
-                .overload(|context: &mut CompileContext, display| {
-                    let mut center = context.free_point();
-                    let mut radius = context.free_scalar();
-
-                    center.take_node();
-                    radius.take_node();
-
-                    circle_function(
-                        center,
-                        NumberUnit::from(context.set_unit(radius, unit::DISTANCE)),
-                        context,
-                        display,
-                    )
-                }),
+                .overload(|context: &CompileContext, display| context.free_circle_display(display)),
         )
         .add(
             Function::new("radius")
@@ -0,0 +1,84 @@
+//! The `collinear` rule
+
+use super::prelude::*;
+use crate::token::number::ProcNum;
+
+/// `point collinear pair` - a point is collinear with two other points.
+fn pt_collinear_pc(
+    mut lhs: Expr<Point>,
+    mut rhs: Pc<2>,
+    context: &mut CompileContext,
+    display: Properties,
+    inverted: bool,
+    weight: ProcNum,
+) -> CollectionNode {
+    let mut node = CollectionNode::from_display(display, context);
+    node.extend(lhs.node.take());
+    node.extend(rhs.node.take());
+
+    let point = lhs;
+    let a = index!(no-node rhs, 0);
+    let b = index!(no-node rhs, 1);
+
+    /*
+     * The points are collinear exactly when the cross product of PA and PB is zero.
+     * With PA = (ax, ay) and PB = (bx, by), this means ax * by = ay * bx.
+     */
+    let ax = context.sub(
+        context.point_x(a.clone_without_node()),
+        context.point_x(point.clone_without_node()),
+    );
+    let ay = context.sub(
+        context.point_y(a),
+        context.point_y(point.clone_without_node()),
+    );
+    let bx = context.sub(
+        context.point_x(b.clone_without_node()),
+        context.point_x(point.clone_without_node()),
+    );
+    let by = context.sub(context.point_y(b), context.point_y(point));
+
+    context.push_rule(UnrolledRule {
+        kind: UnrolledRuleKind::NumberEq(context.mult(ax, by), context.mult(ay, bx)),
+        inverted,
+        weight,
+    });
+
+    node
+}
+
+/// `pc collinear pair` - all points of a collection are collinear with two other points.
+fn col_collinear_pc(
+    mut lhs: Pc<0>,
+    mut rhs: Pc<2>,
+    context: &mut CompileContext,
+    display: Properties,
+    inverted: bool,
+    weight: ProcNum,
+) -> CollectionNode {
+    let len = lhs.data.length;
+
+    let mut node = CollectionNode::from_display(display, context);
+
+    for i in 0..len {
+        node.push(pt_collinear_pc(
+            index!(node lhs, i),
+            Pc(rhs.clone_with_node()),
+            context,
+            Properties::default(),
+            inverted,
+            weight.clone(),
+        ));
+    }
+
+    node
+}
+
+/// Register the rule
+pub fn register(library: &mut Library) {
+    library.add(
+        Rule::new("collinear")
+            .overload(pt_collinear_pc)
+            .overload(col_collinear_pc),
+    );
+}
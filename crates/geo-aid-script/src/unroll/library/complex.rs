@@ -125,9 +125,9 @@ fn vector(
         .into()
 }
 
-/// Absolute value of a number, real or complex.
+/// The module (absolute value) of a complex number.
 #[must_use]
-pub fn abs(num: Expr<Number>, context: &CompileContext, props: Properties) -> Expr<Number> {
+pub fn module(num: Expr<Number>, context: &CompileContext, props: Properties) -> Expr<Number> {
     let re2 = context.mult(
         context.real(num.clone_without_node()),
         context.real(num.clone_without_node()),
@@ -141,9 +141,9 @@ pub fn abs(num: Expr<Number>, context: &CompileContext, props: Properties) -> Ex
 }
 
 #[derive(Debug)]
-struct Abs;
+struct Module;
 
-impl Overload for Abs {
+impl Overload for Module {
     fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
         if params.len() != 1 {
             return None;
@@ -164,7 +164,7 @@ impl Overload for Abs {
             .to_scalar()
             .unwrap();
 
-        abs(num, context, props).into()
+        module(num, context, props).into()
     }
 }
 
@@ -216,10 +216,8 @@ pub fn register(library: &mut Library) {
                 }),
         )
         .add(
-            Function::new("abs")
-                .alias("module")
-                .alias_method(Type::Number(None), "abs")
+            Function::new("module")
                 .alias_method(Type::Number(None), "module")
-                .overload(Abs),
+                .overload(Module),
         );
 }
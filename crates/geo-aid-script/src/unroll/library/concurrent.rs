@@ -0,0 +1,84 @@
+//! The `concurrent` rule
+
+use super::prelude::*;
+use crate::token::number::ProcNum;
+use crate::unroll::Convert;
+
+/// Makes three lines concurrent. Used by both the rule and the `concurrent` statement.
+pub fn lines(
+    k: Expr<Line>,
+    l: Expr<Line>,
+    m: Expr<Line>,
+    context: &mut CompileContext,
+    display: Properties,
+    inverted: bool,
+    weight: ProcNum,
+) -> CollectionNode {
+    let mut k = k;
+    let mut l = l;
+    let mut m = m;
+
+    let mut node = CollectionNode::from_display(display, context);
+    node.extend(k.node.take());
+    node.extend(l.node.take());
+    node.extend(m.node.take());
+
+    // The lines are concurrent exactly when `k` and `m` meet where `l` crosses them both.
+    let kl = context.intersection(k, l.clone_without_node());
+    let lm = context.intersection(l, m);
+
+    context.push_rule(UnrolledRule {
+        kind: UnrolledRuleKind::PointEq(kl, lm),
+        inverted,
+        weight,
+    });
+
+    node
+}
+
+/// `line concurrent (line, line)` - a line is concurrent with two other lines given by four points.
+fn line_concurrent_pc(
+    lhs: Expr<Line>,
+    mut rhs: Pc<4>,
+    context: &mut CompileContext,
+    display: Properties,
+    inverted: bool,
+    weight: ProcNum,
+) -> CollectionNode {
+    let rhs_node = rhs.node.take();
+
+    let l = context.line(index!(no-node rhs, 0), index!(no-node rhs, 1));
+    let m = context.line(index!(no-node rhs, 2), index!(no-node rhs, 3));
+
+    let mut node = lines(lhs, l, m, context, display, inverted, weight);
+    node.extend(rhs_node);
+    node
+}
+
+/// `pair concurrent (line, line)` - a line given by two points is concurrent with two other lines given by four points.
+fn pc_concurrent_pc(
+    lhs: Pc<2>,
+    rhs: Pc<4>,
+    context: &mut CompileContext,
+    display: Properties,
+    inverted: bool,
+    weight: ProcNum,
+) -> CollectionNode {
+    line_concurrent_pc(
+        lhs.0.convert(context),
+        rhs,
+        context,
+        display,
+        inverted,
+        weight,
+    )
+}
+
+/// Register the rule
+pub fn register(library: &mut Library) {
+    library.add(
+        Rule::new("concurrent")
+            .overload(pc_concurrent_pc)
+            .overload(line_concurrent_pc),
+    );
+}
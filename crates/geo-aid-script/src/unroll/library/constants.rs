@@ -0,0 +1,17 @@
+//! Builtin numeric constants.
+
+use num_traits::FromPrimitive;
+
+use crate::token::number::ProcNum;
+
+use super::prelude::*;
+
+/// Register the constants
+pub fn register(library: &mut Library) {
+    library
+        .add(Constant::new("pi", ProcNum::pi()))
+        .add(Constant::new(
+            "tau",
+            ProcNum::pi() * &ProcNum::from_i8(2).unwrap(),
+        ));
+}
@@ -0,0 +1,77 @@
+//! The `cross_ratio` function
+
+use crate::unroll::Number;
+
+use super::prelude::*;
+
+/// `cross_ratio(A, B, C, D)` - the cross-ratio `(AC / BC) / (AD / BD)` of four points.
+///
+/// The ratios are signed, so the points are treated as complex numbers:
+/// `((C - A) / (C - B)) / ((D - A) / (D - B))`. It's real for collinear points,
+/// so only the real part is returned.
+fn cross_ratio_function_pppp(
+    a: Expr<Point>,
+    b: Expr<Point>,
+    c: Expr<Point>,
+    d: Expr<Point>,
+    context: &CompileContext,
+    display: Properties,
+) -> Unitless {
+    let [mut a, mut b, mut c, mut d] = [a, b, c, d].map(|point| context.to_complex(point));
+
+    let ac_bc = context.div(
+        context.sub(c.clone_with_node(), a.clone_with_node()),
+        context.sub(c, b.clone_with_node()),
+    );
+    let ad_bd = context.div(context.sub(d.clone_with_node(), a), context.sub(d, b));
+
+    context
+        .real_display(context.div(ac_bc, ad_bd), display)
+        .into()
+}
+
+/// `cross_ratio(k, l, m, n)` - the cross-ratio of four lines.
+///
+/// With `sin(x, y)` being the sine of the directed angle between lines `x` and `y`,
+/// it's equal to `(sin(k, m) * sin(l, n)) / (sin(l, m) * sin(k, n))`. For concurrent lines,
+/// it's the cross-ratio of the points where they cross any other line.
+fn cross_ratio_function_llll(
+    k: Expr<Line>,
+    l: Expr<Line>,
+    m: Expr<Line>,
+    n: Expr<Line>,
+    context: &CompileContext,
+    display: Properties,
+) -> Unitless {
+    let [mut k, mut l, mut m, mut n] = [k, l, m, n].map(|line| context.direction(line));
+
+    // Directions are unit vectors, so this is the sine of the angle from `x` to `y`.
+    let sin = |x: Expr<Number>, y: Expr<Number>| context.imaginary(context.div(y, x));
+
+    let numerator = context.mult(
+        sin(k.clone_with_node(), m.clone_with_node()),
+        sin(l.clone_with_node(), n.clone_with_node()),
+    );
+    let denominator = context.mult(sin(l, m), sin(k, n));
+
+    context.div_display(numerator, denominator, display).into()
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("crossratio")
+            .overload(cross_ratio_function_pppp)
+            .overload(|mut col: Pc<4>, context: &CompileContext, display| {
+                cross_ratio_function_pppp(
+                    index!(node col, 0),
+                    index!(node col, 1),
+                    index!(node col, 2),
+                    index!(node col, 3),
+                    context,
+                    display,
+                )
+            })
+            .overload(cross_ratio_function_llll),
+    );
+}
@@ -13,6 +13,8 @@ pub fn distance_function_pp(
 ) -> Distance {
     let display_segment = display.get("display_segment").maybe_unset(true);
     let style = display.get("style").maybe_unset(Style::Solid);
+    let color = display.get("color").maybe_unset(Color::default());
+    let ticks = display.get("ticks").maybe_unset(TickCount::default());
 
     let mut expr = context.distance_pp_display(a, b, display);
 
@@ -20,6 +22,8 @@ pub fn distance_function_pp(
         node.set_associated(Associated);
         node.insert_data("display_segment", display_segment);
         node.insert_data("style", style);
+        node.insert_data("color", color);
+        node.insert_data("ticks", ticks);
     }
 
     expr.into()
@@ -34,6 +38,8 @@ fn distance_function_pl(
 ) -> Distance {
     let display_segment = display.get("display_segment").maybe_unset(true);
     let style = display.get("style").maybe_unset(Style::Dashed);
+    let color = display.get("color").maybe_unset(Color::default());
+    let ticks = display.get("ticks").maybe_unset(TickCount::default());
 
     let mut expr = context.distance_pl_display(a, k, display);
 
@@ -41,6 +47,8 @@ fn distance_function_pl(
         node.set_associated(Associated);
         node.insert_data("display_segment", display_segment);
         node.insert_data("style", style);
+        node.insert_data("color", color);
+        node.insert_data("ticks", ticks);
     }
 
     expr.into()
@@ -62,10 +70,13 @@ fn distance_convert_pc(mut pc: Pc<2>, context: &CompileContext, display: Propert
 }
 
 /// ```
-/// # use geo_aid_figure::Style;
+/// # use geo_aid_figure::{Color, Style};
+/// # use geo_aid_script::unroll::figure::TickCount;
 /// struct Associated {
 ///     display_segment: bool,
-///     style: Style
+///     style: Style,
+///     color: Color,
+///     ticks: TickCount
 /// }
 #[derive(Debug)]
 pub struct Associated;
@@ -82,6 +93,12 @@ impl BuildAssociated<NumberNode> for Associated {
             .as_bool()
             .unwrap();
         let style = associated.get_data("style").unwrap().as_style().unwrap();
+        let color = associated.get_data("color").unwrap().as_color().unwrap();
+        let ticks = associated
+            .get_data("ticks")
+            .unwrap()
+            .as_tick_count()
+            .unwrap();
 
         if display_segment.unwrap() {
             match &associated.root.expr.data.data {
@@ -93,6 +110,9 @@ impl BuildAssociated<NumberNode> for Associated {
                         q_id,
                         label: MathString::new(),
                         style: style.unwrap(),
+                        color: color.unwrap(),
+                        opacity: None,
+                        ticks: ticks.get_copied().0,
                     });
                 }
                 NumberData::PointLineDistance(a, k) => {
@@ -112,6 +132,9 @@ impl BuildAssociated<NumberNode> for Associated {
                         q_id,
                         label: MathString::new(),
                         style: style.unwrap(),
+                        color: color.unwrap(),
+                        opacity: None,
+                        ticks: ticks.get_copied().0,
                     });
                 }
                 _ => unreachable!(),
@@ -137,8 +160,8 @@ pub fn register(library: &mut Library) {
             .overload(|v: Unitless, context: &CompileContext, display| {
                 Distance::from(context.set_unit_display(v.0, unit::DISTANCE, display))
             })
-            .overload(distance_function_pp)
-            .overload(distance_function_pl)
+            .overload_named(&["a", "b"], distance_function_pp)
+            .overload_named(&["point", "line"], distance_function_pl)
             .overload(
                 |line: Expr<Line>, point: Expr<Point>, context: &CompileContext, display| {
                     distance_function_pl(point, line, context, display)
@@ -0,0 +1,12 @@
+//! The `external_bisector` function
+
+use super::prelude::*;
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(Function::new("externalbisector").overload(
+        |k: Expr<Line>, l: Expr<Line>, context: &CompileContext, display| {
+            context.bisector_ll_display(k, l, true, display)
+        },
+    ));
+}
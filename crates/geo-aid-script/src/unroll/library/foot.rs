@@ -0,0 +1,149 @@
+//! The `projection` (alias `foot`) function
+
+use crate::{
+    figure::SegmentItem,
+    math::Build,
+    parser::PropertyValue,
+    token::StrLit,
+    unroll::{figure::Opacity, Convert},
+};
+use geo_aid_figure::math_string::MathString;
+
+use super::prelude::*;
+use crate::token::Span;
+
+/// `projection(point, line)` - the foot of the perpendicular dropped from a point onto a line.
+fn foot_function_pl(
+    mut point: Expr<Point>,
+    mut line: Expr<Line>,
+    context: &CompileContext,
+    mut display: Properties,
+) -> Expr<Point> {
+    let point_node = point.take_node();
+    let line_node = line.take_node();
+
+    display.add_if_not_present(
+        "display_label",
+        (
+            Span::empty(),
+            PropertyValue::String(StrLit {
+                span: Span::empty(),
+                content: String::from("false"),
+            }),
+        ),
+    );
+
+    let display_segment = display.get("display_segment").maybe_unset(false);
+    let style = display.get("style").maybe_unset(Style::Dashed);
+    let color = display.get("color").maybe_unset(Color::default());
+    let opacity = display.get("opacity").maybe_unset(Opacity::default());
+    let ticks = display.get("ticks").maybe_unset(TickCount::default());
+
+    // The perpendicular itself is not displayed.
+    let mut perpendicular =
+        context.perpendicular_through(line.clone_without_node(), point.clone_without_node());
+    perpendicular.take_node();
+
+    let mut expr = context.intersection_display(perpendicular, line, display);
+
+    if let Some(node) = expr.node.as_mut() {
+        node.extend_children(point_node);
+        node.extend_children(line_node);
+
+        // The color and opacity apply to the foot itself as well.
+        node.root.color = color;
+        node.root.opacity = opacity;
+
+        node.set_associated(Associated { point });
+        node.insert_data("display_segment", display_segment);
+        node.insert_data("style", style);
+        node.insert_data("color", color);
+        node.insert_data("opacity", opacity);
+        node.insert_data("ticks", ticks);
+    }
+
+    expr
+}
+
+/// The projected point, used for drawing the segment between it and its projection.
+///
+/// ```
+/// # use geo_aid_figure::{Color, Style};
+/// # use geo_aid_script::unroll::figure::{Opacity, TickCount};
+/// struct Associated {
+///     display_segment: bool,
+///     style: Style,
+///     color: Color,
+///     opacity: Opacity,
+///     ticks: TickCount,
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Associated {
+    point: Expr<Point>,
+}
+
+impl BuildAssociated<PointNode> for Associated {
+    fn build_associated(
+        self: Box<Self>,
+        build: &mut Build,
+        associated: &mut HierarchyNode<PointNode>,
+    ) {
+        let display_segment = associated
+            .get_data("display_segment")
+            .unwrap()
+            .as_bool()
+            .unwrap();
+        let style = associated.get_data("style").unwrap().as_style().unwrap();
+        let color = associated.get_data("color").unwrap().as_color().unwrap();
+        let opacity = associated
+            .get_data("opacity")
+            .unwrap()
+            .as_opacity()
+            .unwrap();
+        let ticks = associated
+            .get_data("ticks")
+            .unwrap()
+            .as_tick_count()
+            .unwrap();
+
+        if display_segment.unwrap() {
+            let p_id = build.load(&self.point);
+            let q_id = build.load(&associated.root.expr);
+            build.add(SegmentItem {
+                p_id,
+                q_id,
+                label: MathString::new(),
+                style: style.unwrap(),
+                color: color.unwrap(),
+                opacity: opacity.try_get().map(|o| o.0),
+                ticks: ticks.get_copied().0,
+            });
+        }
+    }
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("projection")
+            .alias("foot")
+            // A collection of two points is explicitly turned into a line going through them.
+            .overload(
+                |point: Expr<Point>, line: Pc<2>, context: &CompileContext, display| {
+                    foot_function_pl(point, line.0.convert(context), context, display)
+                },
+            )
+            .overload(
+                |line: Pc<2>, point: Expr<Point>, context: &CompileContext, display| {
+                    foot_function_pl(point, line.0.convert(context), context, display)
+                },
+            )
+            .overload(foot_function_pl)
+            .overload(
+                |line: Expr<Line>, point: Expr<Point>, context: &CompileContext, display| {
+                    foot_function_pl(point, line, context, display)
+                },
+            ),
+    );
+}
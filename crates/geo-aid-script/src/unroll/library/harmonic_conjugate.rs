@@ -0,0 +1,55 @@
+//! The `harmonic_conjugate` function
+
+use num_traits::One;
+
+use crate::token::number::ProcNum;
+
+use super::prelude::*;
+
+/// `harmonic_conjugate(A, B, C)` - the point `D` on line `AB` such that `(A, B; C, D) = -1`.
+///
+/// With `t` being the signed position of `C` on `AB` (`A` at 0 and `B` at 1),
+/// `D` lies at `t / (2t - 1)`. If `C` is not on `AB`, its projection is used instead.
+fn harmonic_conjugate_function(
+    a: Expr<Point>,
+    b: Expr<Point>,
+    c: Expr<Point>,
+    context: &CompileContext,
+    display: Properties,
+) -> Expr<Point> {
+    let [mut a, b, c] = [a, b, c].map(|point| context.to_complex(point));
+
+    let ab = context.sub(b, a.clone_without_node());
+    let t = context.real(context.div(
+        context.sub(c, a.clone_without_node()),
+        ab.clone_without_node(),
+    ));
+
+    let two = number!(ProcNum::one() + &ProcNum::one());
+    let s = context.div(
+        t.clone_without_node(),
+        context.sub(context.mult(two, t), number!(ProcNum::one())),
+    );
+
+    context.to_point_display(
+        context.add(a.clone_with_node(), context.mult(ab, s)),
+        display,
+    )
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("harmonicconjugate")
+            .overload(harmonic_conjugate_function)
+            .overload(|mut col: Pc<3>, context: &CompileContext, display| {
+                harmonic_conjugate_function(
+                    index!(node col, 0),
+                    index!(node col, 1),
+                    index!(node col, 2),
+                    context,
+                    display,
+                )
+            }),
+    );
+}
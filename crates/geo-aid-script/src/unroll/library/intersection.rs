@@ -1,8 +1,10 @@
 //! The `intersection` function
 
-use num_traits::One;
-
-use crate::{math::Build, token::number::ProcNum};
+use crate::{
+    math::Build,
+    token::Span,
+    unroll::{figure::PCNode, PointCollection, PointCollectionData},
+};
 
 use super::prelude::*;
 
@@ -23,42 +25,124 @@ fn intersection_function_ll(
     expr
 }
 
-/// `intesection(line, circle)` - intersection of a line and a circle.
+/// Collect both intersection points into a point collection.
+fn collect_points(mut points: [Expr<Point>; 2], mut display: Properties) -> Pc<2> {
+    for point in &mut points {
+        if let Some(node) = &mut point.node {
+            node.set_associated(Associated);
+        }
+    }
+
+    let mut expr = Expr {
+        data: Rc::new(PointCollection {
+            length: 2,
+            data: PointCollectionData::PointCollection(
+                points
+                    .iter()
+                    .map(Expr::clone_without_node)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        }),
+        span: Span::empty(),
+        node: None,
+    };
+
+    let node = PCNode {
+        display: display.get("display").maybe_unset(true),
+        children: points.into_iter().map(|mut pt| pt.take_node()).collect(),
+        props: Some(display),
+        expr: expr.clone_without_node(),
+    };
+    expr.node = Some(HierarchyNode::new(node));
+
+    expr.into()
+}
+
+/// `intesection(line, circle)` - both intersections of a line and a circle,
+/// ordered along the line's direction.
 fn intersection_function_lc(
-    k: Expr<Line>,
-    omega: Expr<Circle>,
+    mut k: Expr<Line>,
+    mut omega: Expr<Circle>,
     context: &mut CompileContext,
     display: Properties,
-) -> Expr<Point> {
-    let mut point = context.free_point_display(display);
-
-    context.point_on_line(&point, &k, ProcNum::one());
-    context.point_on_circle(&point, &omega, ProcNum::one());
+) -> Pc<2> {
+    let line_node = k.take_node();
+    let circle_node = omega.take_node();
+
+    // The line must cross the circle.
+    context.gt(
+        context.circle_radius(omega.clone_without_node()),
+        context.distance_pl(
+            context.circle_center(omega.clone_without_node()),
+            k.clone_without_node(),
+        ),
+        false,
+    );
 
-    if let Some(node) = &mut point.node {
-        node.set_associated(Associated);
+    let mut points = [0, 1].map(|index| {
+        context.expr_with(
+            Point::LineCircleIntersection(
+                k.clone_without_node(),
+                omega.clone_without_node(),
+                index,
+            ),
+            Properties::default(),
+            Vec::new(),
+        )
+    });
+
+    if let Some(node) = points[0].node.as_mut() {
+        node.extend_children(line_node);
+        node.extend_children(circle_node);
     }
 
-    point
+    collect_points(points, display)
 }
 
-/// `intesection(circle, circle)` - intersection of two circle.
+/// `intesection(circle, circle)` - both intersections of two circles, ordered along
+/// the line connecting their centers rotated counterclockwise by a right angle.
 fn intersection_function_cc(
-    o1: Expr<Circle>,
-    o2: Expr<Circle>,
+    mut o1: Expr<Circle>,
+    mut o2: Expr<Circle>,
     context: &mut CompileContext,
     display: Properties,
-) -> Expr<Point> {
-    let mut point = context.free_point_display(display);
-
-    context.point_on_circle(&point, &o1, ProcNum::one());
-    context.point_on_circle(&point, &o2, ProcNum::one());
-
-    if let Some(node) = &mut point.node {
-        node.set_associated(Associated);
+) -> Pc<2> {
+    let o1_node = o1.take_node();
+    let o2_node = o2.take_node();
+
+    // The circles must cross: |r1 - r2| < |O1O2| < r1 + r2
+    let r1 = context.circle_radius(o1.clone_without_node());
+    let r2 = context.circle_radius(o2.clone_without_node());
+    let dst = context.distance_pp(
+        context.circle_center(o1.clone_without_node()),
+        context.circle_center(o2.clone_without_node()),
+    );
+    context.gt(
+        context.add(r1.clone_without_node(), r2.clone_without_node()),
+        dst.clone_without_node(),
+        false,
+    );
+    context.gt(dst, context.abs(context.sub(r1, r2)), false);
+
+    let mut points = [0, 1].map(|index| {
+        context.expr_with(
+            Point::CircleCircleIntersection(
+                o1.clone_without_node(),
+                o2.clone_without_node(),
+                index,
+            ),
+            Properties::default(),
+            Vec::new(),
+        )
+    });
+
+    if let Some(node) = points[0].node.as_mut() {
+        node.extend_children(o1_node);
+        node.extend_children(o2_node);
     }
 
-    point
+    collect_points(points, display)
 }
 
 /// The associated data. No properties.
@@ -81,6 +165,11 @@ pub fn register(library: &mut Library) {
         Function::new("intersection")
             .overload(intersection_function_ll)
             .overload(intersection_function_lc)
+            .overload(
+                |omega: Expr<Circle>, k: Expr<Line>, context: &mut CompileContext, display| {
+                    intersection_function_lc(k, omega, context, display)
+                },
+            )
             .overload(intersection_function_cc),
     );
 }
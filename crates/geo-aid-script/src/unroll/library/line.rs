@@ -1,4 +1,9 @@
-//! The `Line` function
+//! The `line` and `ray` functions
+
+use crate::{
+    parser::PropertyValue,
+    token::{Span, StrLit},
+};
 
 use super::prelude::*;
 
@@ -19,7 +24,28 @@ pub fn function_pp(
     ln
 }
 
-/// Register the function
+/// `ray(point, point)` - a ray starting at the first point and going through the second one.
+fn ray_function_pp(
+    a: Expr<Point>,
+    b: Expr<Point>,
+    context: &CompileContext,
+    mut display: Properties,
+) -> Expr<Line> {
+    display.add_if_not_present(
+        "type",
+        (
+            Span::empty(),
+            PropertyValue::String(StrLit {
+                span: Span::empty(),
+                content: String::from("ray"),
+            }),
+        ),
+    );
+
+    function_pp(a, b, context, display)
+}
+
+/// Register the functions
 pub fn register(library: &mut Library) {
     library.add(
         Function::new("line")
@@ -28,4 +54,12 @@ pub fn register(library: &mut Library) {
             })
             .overload(function_pp),
     );
+
+    library.add(
+        Function::new("ray")
+            .overload(|mut col: Pc<2>, context: &CompileContext, display| {
+                ray_function_pp(index!(node col,0), index!(node col,1), context, display)
+            })
+            .overload(ray_function_pp),
+    );
 }
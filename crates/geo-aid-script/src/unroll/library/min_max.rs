@@ -0,0 +1,113 @@
+//! The `min`, `max` and `clamp` functions
+
+use crate::{parser::Type, unroll::AnyExpr};
+
+use super::{prelude::*, Overload};
+
+/// Get the type of numbers all `params` can be converted to, if there is one.
+fn common_number_type(params: &[AnyExpr]) -> Option<Type> {
+    let mut unit = None;
+
+    for param in params {
+        if let Some(u) = param.can_convert_to_scalar(unit) {
+            unit = u;
+        } else {
+            return None;
+        }
+    }
+
+    Some(Type::Number(unit))
+}
+
+/// `min(v1, v2, ...)` or `max(v1, v2, ...)` - the smallest or the largest of the given numbers.
+/// All of them must have the same unit.
+struct Extremum {
+    /// Whether the largest number should be chosen.
+    max: bool,
+}
+
+impl Overload for Extremum {
+    fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
+        // At least two numbers of the same unit are required, like in `mid`.
+        if params.len() < 2 {
+            return None;
+        }
+
+        common_number_type(params)
+    }
+
+    fn unroll(
+        &self,
+        params: Vec<AnyExpr>,
+        context: &mut CompileContext,
+        props: Properties,
+    ) -> AnyExpr {
+        let ty = self.get_returned_type(&params).unwrap();
+        let mut values: Vec<_> = params
+            .into_iter()
+            .map(|x| x.convert_to(ty, context).to_scalar().unwrap())
+            .collect();
+
+        // Fold all values but the last one, the last step gets the properties.
+        let last = values.pop().unwrap();
+        let folded = values
+            .into_iter()
+            .reduce(|a, b| {
+                if self.max {
+                    context.max(a, b)
+                } else {
+                    context.min(a, b)
+                }
+            })
+            .unwrap();
+
+        if self.max {
+            context.max_display(folded, last, props)
+        } else {
+            context.min_display(folded, last, props)
+        }
+        .into()
+    }
+}
+
+/// `clamp(value, min, max)` - `value` limited to the range from `min` to `max`.
+/// All three must have the same unit. If `min > max`, the result is `min`.
+struct Clamp;
+
+impl Overload for Clamp {
+    fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
+        if params.len() != 3 {
+            return None;
+        }
+
+        common_number_type(params)
+    }
+
+    fn unroll(
+        &self,
+        params: Vec<AnyExpr>,
+        context: &mut CompileContext,
+        props: Properties,
+    ) -> AnyExpr {
+        let ty = self.get_returned_type(&params).unwrap();
+        let mut values = params
+            .into_iter()
+            .map(|x| x.convert_to(ty, context).to_scalar().unwrap());
+        let (value, min, max) = (
+            values.next().unwrap(),
+            values.next().unwrap(),
+            values.next().unwrap(),
+        );
+
+        let below_max = context.min(value, max);
+        context.max_display(below_max, min, props).into()
+    }
+}
+
+/// Register the functions
+pub fn register(library: &mut Library) {
+    library
+        .add(Function::new("min").overload(Extremum { max: false }))
+        .add(Function::new("max").overload(Extremum { max: true }))
+        .add(Function::new("clamp").overload(Clamp));
+}
@@ -1,5 +1,7 @@
 //! The `perpendicular_through` function
 
+use crate::unroll::Convert;
+
 use super::prelude::*;
 
 /// `perpendicular_through(line, point)` - returns a line perpendicular to the 1st argument going through point at 2nd argument.
@@ -17,6 +19,17 @@ pub fn register(library: &mut Library) {
     library.add(
         Function::new("perpendicularthrough")
             .alias("pependicular")
+            // A collection of two points is explicitly turned into a line going through them.
+            .overload(
+                |line: Pc<2>, point: Expr<Point>, context: &CompileContext, props| {
+                    line_point(line.0.convert(context), point, context, props)
+                },
+            )
+            .overload(
+                |point: Expr<Point>, line: Pc<2>, context: &CompileContext, props| {
+                    line_point(line.0.convert(context), point, context, props)
+                },
+            )
             .overload(line_point)
             .overload(
                 |point: Expr<Point>, line: Expr<Line>, context: &CompileContext, props| {
@@ -12,6 +12,21 @@ pub fn register(library: &mut Library) {
                 context.expr_with(Point::Free, props, Vec::new())
             }),
         )
+        .add(
+            Function::new("pointonsegment")
+                .overload(|mut col: Pc<2>, context: &CompileContext, props| {
+                    context.point_on_segment_display(
+                        index!(node col, 0),
+                        index!(node col, 1),
+                        props,
+                    )
+                })
+                .overload(
+                    |p: Expr<Point>, q: Expr<Point>, context: &CompileContext, props| {
+                        context.point_on_segment_display(p, q, props)
+                    },
+                ),
+        )
         .add(
             Function::new("x")
                 .alias_method(ty::POINT, "x")
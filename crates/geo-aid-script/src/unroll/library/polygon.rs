@@ -1,17 +1,20 @@
 //! Polygon related functions.
 
 use crate::{
-    figure::SegmentItem,
+    figure::{PolygonItem, SegmentItem},
     math::Build,
     parser::Type,
     token::{number::ProcNum, Span},
-    unroll::{figure::PCNode, AnyExpr, PointCollection, PointCollectionData},
+    unroll::{
+        figure::{MaybeUnset, Node, Opacity, PCNode},
+        AnyExpr, Convert, Number, PointCollection, PointCollectionData,
+    },
 };
 
 use super::{prelude::*, Overload};
 
 use geo_aid_figure::math_string::MathString;
-use num_traits::{ToPrimitive, Zero};
+use num_traits::{One, ToPrimitive, Zero};
 
 fn poly(
     params: &[AnyExpr],
@@ -47,6 +50,7 @@ fn poly(
         props.get("displaysegments").maybe_unset(true),
     );
     node.insert_data("style", props.get("style").maybe_unset(Style::Solid));
+    node.insert_data("color", props.get("color").maybe_unset(Color::default()));
     node.root.props = Some(props);
     expr.node = Some(node);
 
@@ -104,6 +108,7 @@ impl BuildAssociated<PCNode> for Associated {
             .as_bool()
             .unwrap();
         let style = associated.get_data("style").unwrap().as_style().unwrap();
+        let color = associated.get_data("color").unwrap().as_color().unwrap();
 
         if display_segments.unwrap() {
             let PointCollectionData::PointCollection(points) =
@@ -120,12 +125,71 @@ impl BuildAssociated<PCNode> for Associated {
                     q_id: pts[i - 1].clone(),
                     label: MathString::new(),
                     style: style.unwrap(),
+                    color: color.unwrap(),
+                    opacity: None,
+                    ticks: 0,
                 })
             }
         }
     }
 }
 
+/// Add rules making the polygon with the given vertices convex. Used by both `convex(n)`
+/// and the `convex` statement.
+pub fn convex_rules(pc: &Expr<PointCollection>, context: &CompileContext, weight: &ProcNum) {
+    let len = pc.data.length;
+
+    if len <= 3 {
+        // Every triangle is convex.
+        return;
+    }
+
+    let positive = |product: Expr<Number>| {
+        // Technically the unit for 0 is invalid, but it's not checked later on anyway.
+        context.push_rule(UnrolledRule {
+            kind: UnrolledRuleKind::Gt(product, number!(ANGLE ProcNum::zero())),
+            inverted: false,
+            weight: weight.clone(),
+        });
+    };
+
+    // Every angle with origin in point 0 and arms defined by every pair of consecutive points should
+    // have the same direction. Makes sure it's not self-intersecting.
+    for i in 3..len {
+        let alpha = context.angle_dir(
+            pc.index_without_node(i - 2),
+            pc.index_without_node(0),
+            pc.index_without_node(i - 1),
+        );
+        let beta = context.angle_dir(
+            pc.index_without_node(i - 1),
+            pc.index_without_node(0),
+            pc.index_without_node(i),
+        );
+        positive(context.mult(alpha, beta));
+    }
+
+    // A copy of the code from collection lies on circle. Makes sure it's convex
+    for i in 1..len {
+        let i_plus_1 = (i + 1) % len;
+        let i_plus_2 = (i + 2) % len;
+
+        positive(context.mult(
+            context.angle_dir(
+                index!(no-node pc, i),
+                index!(no-node pc, i-1),
+                index!(no-node pc, i_plus_1),
+            ),
+            context.angle_dir(
+                index!(no-node pc, i_plus_1),
+                index!(no-node pc, i),
+                index!(no-node pc, i_plus_2),
+            ),
+        ));
+    }
+}
+
+/// `convex(n)` - a new convex polygon with `n` vertices.
 struct Convex;
 
 impl Overload for Convex {
@@ -140,56 +204,137 @@ impl Overload for Convex {
         props: Properties,
     ) -> AnyExpr {
         let pc = poly(&params, context, props);
-        let len = pc.data.length;
-
-        if len > 3 {
-            // Every angle with origin in point 0 and arms defined by every pair of consecutive points should
-            // have the same direction. Makes sure it's not self-intersecting.
-            for i in 3..len {
-                let alpha = context.angle_dir(
-                    pc.index_without_node(i - 2),
-                    pc.index_without_node(0),
-                    pc.index_without_node(i - 1),
-                );
-                let beta = context.angle_dir(
-                    pc.index_without_node(i - 1),
-                    pc.index_without_node(0),
-                    pc.index_without_node(i),
-                );
-                let product = context.mult(alpha, beta);
-                // Technically the unit for 0 is invalid, but it's not checked later on anyway.
-                context.gt(product, number!(ANGLE ProcNum::zero()), false);
-            }
+        convex_rules(&pc, context, &ProcNum::one());
 
-            // A copy of the code from collection lies on circle. Makes sure it's convex
-            for i in 1..len {
-                let i_plus_1 = (i + 1) % len;
-                let i_plus_2 = (i + 2) % len;
-
-                let product = context.mult(
-                    context.angle_dir(
-                        index!(no-node pc, i),
-                        index!(no-node pc, i-1),
-                        index!(no-node pc, i_plus_1),
-                    ),
-                    context.angle_dir(
-                        index!(no-node pc, i_plus_1),
-                        index!(no-node pc, i),
-                        index!(no-node pc, i_plus_2),
-                    ),
-                );
-                context.gt(product, number!(ANGLE ProcNum::zero()), false);
-            }
+        pc.into()
+    }
+}
+
+/// Node drawing a polygon with the given vertices.
+#[derive(Debug)]
+struct PolygonNode {
+    /// Whether the polygon should be displayed
+    display: MaybeUnset<bool>,
+    /// How the outline should be drawn
+    style: MaybeUnset<Style>,
+    /// The polygon's color
+    color: MaybeUnset<Color>,
+    /// The polygon's opacity
+    opacity: MaybeUnset<Opacity>,
+    /// The color the polygon should be filled with
+    fill: MaybeUnset<Color>,
+    /// The vertices, in order
+    points: Vec<Expr<Point>>,
+}
+
+impl Node for PolygonNode {
+    fn set_display(&mut self, display: bool) {
+        self.display.set(display);
+    }
+
+    fn get_display(&self) -> bool {
+        self.display.get_copied()
+    }
+
+    fn build(self: Box<Self>, build: &mut Build) {
+        if self.display.unwrap() {
+            let ids = self.points.iter().map(|pt| build.load(pt)).collect();
+
+            build.add(PolygonItem {
+                ids,
+                style: self.style.unwrap(),
+                color: self.color.unwrap(),
+                opacity: self.opacity.try_get().map(|o| o.0),
+                fill: self.fill.try_get().copied(),
+            });
         }
+    }
+}
 
-        pc.into()
+/// `polygon(points...)` - draws a polygon with the given vertices. Adds no rules.
+#[derive(Debug)]
+struct Shape;
+
+impl Overload for Shape {
+    fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
+        let n = match params {
+            [AnyExpr::PointCollection(pc)] => pc.data.length,
+            _ if params.iter().all(|p| p.can_convert_to(Type::Point)) => params.len(),
+            _ => return None,
+        };
+
+        (n > 2).then_some(Type::PointCollection(n))
+    }
+
+    fn unroll(
+        &self,
+        params: Vec<AnyExpr>,
+        context: &mut CompileContext,
+        mut props: Properties,
+    ) -> AnyExpr {
+        let mut points: Vec<Expr<Point>> = if let [AnyExpr::PointCollection(_)] = params.as_slice()
+        {
+            let mut pc: Expr<PointCollection> = params.into_iter().next().unwrap().convert(context);
+            let points = (0..pc.data.length).map(|i| index!(node pc, i)).collect();
+
+            if let Some(props) = pc.node.and_then(|node| node.root.props) {
+                props.finish(context);
+            }
+
+            points
+        } else {
+            params.into_iter().map(|p| p.convert(context)).collect()
+        };
+
+        let polygon = PolygonNode {
+            display: props.get("display").maybe_unset(true),
+            style: props.get("style").maybe_unset(Style::Solid),
+            color: props.get("color").maybe_unset(Color::default()),
+            opacity: props.get("opacity").maybe_unset(Opacity::default()),
+            fill: props.get("fill").maybe_unset(Color::default()),
+            points: points
+                .iter()
+                .map(CloneWithNode::clone_without_node)
+                .collect(),
+        };
+
+        props.ignore("default-label");
+        props.finish(context);
+
+        let mut expr = Expr {
+            data: Rc::new(PointCollection {
+                length: points.len(),
+                data: PointCollectionData::PointCollection(
+                    polygon
+                        .points
+                        .iter()
+                        .map(CloneWithNode::clone_without_node)
+                        .collect::<Vec<_>>()
+                        .into(),
+                ),
+            }),
+            span: Span::empty(),
+            node: None,
+        };
+
+        let mut node = HierarchyNode::new(PCNode::new(expr.clone_without_node()));
+        node.root.extend(points.iter_mut().map(|pt| pt.node.take()));
+        node.push_child(polygon);
+        expr.node = Some(node);
+
+        expr.into()
     }
 }
 
 /// Register the functions
 pub fn register(library: &mut Library) {
     library
-        .add(Function::new("poly").alias("polygon").overload(Poly))
+        .add(
+            Function::new("poly")
+                .alias("polygon")
+                .overload(Poly)
+                .overload(Shape),
+        )
         .add(
             Function::new("convex")
                 .alias("convexpolygon")
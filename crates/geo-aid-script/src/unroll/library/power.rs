@@ -0,0 +1,43 @@
+//! The `power` function
+
+use num_rational::Ratio;
+
+use super::prelude::*;
+
+/// `power(point, circle)` - the power of a point with respect to a circle.
+///
+/// With `O` being the center and `r` the radius, it's equal to `|PO|^2 - r^2`.
+/// Negative for points inside the circle, zero on it and positive outside of it.
+pub fn power_function_pc(
+    point: Expr<Point>,
+    circle: Expr<Circle>,
+    context: &CompileContext,
+    display: Properties,
+) -> Area {
+    // The center is not displayed.
+    let mut center = context.circle_center(circle.clone_without_node());
+    center.take_node();
+    let radius = context.circle_radius(circle);
+
+    context
+        .sub_display(
+            context.pow(context.distance_pp(point, center), Ratio::from_integer(2)),
+            context.pow(radius, Ratio::from_integer(2)),
+            display,
+        )
+        .into()
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("power")
+            .alias("powerofpoint")
+            .overload(power_function_pc)
+            .overload(
+                |circle: Expr<Circle>, point: Expr<Point>, context: &CompileContext, display| {
+                    power_function_pc(point, circle, context, display)
+                },
+            ),
+    );
+}
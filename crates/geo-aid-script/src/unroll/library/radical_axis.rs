@@ -0,0 +1,12 @@
+//! The `radical_axis` function
+
+use super::prelude::*;
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(Function::new("radicalaxis").overload(
+        |c1: Expr<Circle>, c2: Expr<Circle>, context: &CompileContext, display| {
+            context.radical_axis_display(c1, c2, display)
+        },
+    ));
+}
@@ -3,13 +3,13 @@
 use std::fmt::Display;
 
 use crate::{
-    figure::SegmentItem,
+    figure::{SegmentItem, SpannedMathString},
     math::Build,
     parser::PropertyValue,
     span,
     token::StrLit,
     unroll::{
-        figure::{MaybeUnset, Node},
+        figure::{MaybeUnset, Node, Opacity},
         Dummy,
     },
 };
@@ -29,6 +29,10 @@ impl DerivedType for Segment {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    fn line_points(&self) -> Option<(Expr<Point>, Expr<Point>)> {
+        Some((self.a.clone_without_node(), self.b.clone_without_node()))
+    }
 }
 
 impl Display for Segment {
@@ -45,8 +49,18 @@ struct SegmentNode {
     display: MaybeUnset<bool>,
     /// Whether tge segment itself should be displayed
     display_segment: MaybeUnset<bool>,
+    /// The segment's label
+    label: MaybeUnset<SpannedMathString>,
+    /// Whether to display the label
+    display_label: MaybeUnset<bool>,
     /// How the segment should be drawn.
     style: MaybeUnset<Style>,
+    /// The segment's color.
+    color: MaybeUnset<Color>,
+    /// The segment's opacity.
+    opacity: MaybeUnset<Opacity>,
+    /// How many ticks mark the segment.
+    ticks: MaybeUnset<TickCount>,
     /// Expression of one endpoint
     a: Expr<Point>,
     /// Expression of the other endpoint
@@ -58,7 +72,12 @@ impl Dummy for SegmentNode {
         Self {
             display: MaybeUnset::new(true),
             display_segment: MaybeUnset::new(true),
+            label: MaybeUnset::new(SpannedMathString::new(span!(0, 0, 0, 0))),
+            display_label: MaybeUnset::new(true),
             style: MaybeUnset::new(Style::Solid),
+            color: MaybeUnset::new(Color::default()),
+            opacity: MaybeUnset::new(Opacity::default()),
+            ticks: MaybeUnset::new(TickCount::default()),
             a: Expr::dummy(),
             b: Expr::dummy(),
         }
@@ -86,8 +105,15 @@ impl Node for SegmentNode {
             build.add(SegmentItem {
                 p_id,
                 q_id,
-                label: MathString::new(),
+                label: if self.display_label.unwrap() {
+                    self.label.unwrap().string
+                } else {
+                    MathString::new()
+                },
                 style: self.style.get_copied(),
+                color: self.color.get_copied(),
+                opacity: self.opacity.try_get().map(|o| o.0),
+                ticks: self.ticks.get_copied().0,
             });
         }
     }
@@ -103,7 +129,14 @@ fn segment_function_point_point(
     let node = SegmentNode {
         display: display.get("display").maybe_unset(true),
         display_segment: display.get("display_segment").maybe_unset(true),
+        label: display
+            .get("label")
+            .maybe_unset(SpannedMathString::new(span!(0, 0, 0, 0))),
+        display_label: display.get("display_label").maybe_unset(true),
         style: display.get("style").maybe_unset(Style::default()),
+        color: display.get("color").maybe_unset(Color::default()),
+        opacity: display.get("opacity").maybe_unset(Opacity::default()),
+        ticks: display.get("ticks").maybe_unset(TickCount::default()),
         a: a.clone_without_node(),
         b: b.clone_without_node(),
     };
@@ -119,10 +152,11 @@ fn segment_function_point_point(
 }
 
 /// ```
-/// # use geo_aid_figure::Style;
+/// # use geo_aid_figure::{Color, Style};
 /// struct Associated {
 ///     display_segment: bool,
-///     style: Style
+///     style: Style,
+///     color: Color
 /// }
 /// ```
 #[derive(Debug)]
@@ -0,0 +1,72 @@
+//! The `sqrt` function
+
+use num_rational::Ratio;
+use num_traits::Zero;
+
+use crate::{
+    parser::Type,
+    token::number::ProcNum,
+    unroll::{AnyExpr, Number, NumberData},
+};
+
+use super::{prelude::*, Overload};
+
+/// Square root of a non-negative real number. The unit is square-rooted as well.
+/// Negative values are penalized by a `num > 0` rule, instead of yielding a complex result.
+#[must_use]
+pub fn sqrt(num: Expr<Number>, context: &mut CompileContext, props: Properties) -> Expr<Number> {
+    let zero = Expr {
+        span: num.span,
+        data: Rc::new(Number {
+            unit: num.data.unit,
+            data: NumberData::Number(ProcNum::zero()),
+        }),
+        node: None,
+    };
+    context.gt(num.clone_without_node(), zero, false);
+
+    context.pow_display(num, Ratio::new(1, 2), props)
+}
+
+#[derive(Debug)]
+struct Sqrt;
+
+impl Overload for Sqrt {
+    fn get_returned_type(&self, params: &[AnyExpr]) -> Option<Type> {
+        if params.len() != 1 {
+            return None;
+        }
+
+        params[0]
+            .can_convert_to_scalar(None)
+            .map(|unit| Type::Number(unit.map(|unit| unit.pow(Ratio::new(1, 2)))))
+    }
+
+    fn unroll(
+        &self,
+        mut params: Vec<AnyExpr>,
+        context: &mut CompileContext,
+        props: Properties,
+    ) -> AnyExpr {
+        let ty = params[0]
+            .can_convert_to_scalar(None)
+            .map(Type::Number)
+            .unwrap();
+        let num = params
+            .swap_remove(0)
+            .convert_to(ty, context)
+            .to_scalar()
+            .unwrap();
+
+        sqrt(num, context, props).into()
+    }
+}
+
+/// Register the function
+pub fn register(library: &mut Library) {
+    library.add(
+        Function::new("sqrt")
+            .alias_method(Type::Number(None), "sqrt")
+            .overload(Sqrt),
+    );
+}
@@ -0,0 +1,196 @@
+//! The `tangent`, `tangent_points` and `tangent_length` functions
+
+use num_rational::Ratio;
+use num_traits::One;
+
+use crate::{
+    token::{number::ProcNum, Span},
+    unroll::{figure::PCNode, PointCollection, PointCollectionData},
+};
+
+use super::prelude::*;
+
+/// `tangent(point, circle)` - a line tangent to the circle going through the point.
+///
+/// A point outside of the circle has two tangents, so the point of tangency is
+/// left to the engine and is only constrained to lie on the circle.
+fn tangent_function_pc(
+    mut point: Expr<Point>,
+    mut circle: Expr<Circle>,
+    context: &mut CompileContext,
+    display: Properties,
+) -> Expr<Line> {
+    let point_node = point.take_node();
+    let circle_node = circle.take_node();
+
+    // The point of tangency is not displayed.
+    let mut touch = context.free_point();
+    touch.take_node();
+    context.point_on_circle(&touch, &circle, ProcNum::one());
+
+    let mut radius = context.line(context.circle_center(circle), touch.clone_without_node());
+    radius.take_node();
+
+    let mut expr = context.perpendicular_through_display(radius, touch, display);
+    context.point_on_line(&point, &expr, ProcNum::one());
+
+    if let Some(node) = expr.node.as_mut() {
+        node.extend_children(point_node);
+        node.extend_children(circle_node);
+    }
+
+    expr
+}
+
+/// `tangent_points(point, circle)` - the points of tangency of both tangents
+/// to the circle going through the point.
+///
+/// With `v = P - O`, `d = |v|` and `r` being the radius, the points are given by
+/// `O + v * r * (r ± i * sqrt(d^2 - r^2)) / d^2`. The first one is `OP` rotated counterclockwise.
+fn tangent_points_function_pc(
+    mut point: Expr<Point>,
+    mut circle: Expr<Circle>,
+    context: &mut CompileContext,
+    mut display: Properties,
+) -> Pc<2> {
+    let point_node = point.take_node();
+    let circle_node = circle.take_node();
+
+    let center = context.circle_center(circle.clone_without_node());
+    let radius = context.circle_radius(circle);
+
+    // There are no tangents from a point inside the circle.
+    context.gt(
+        context.distance_pp(point.clone_without_node(), center.clone_without_node()),
+        radius.clone_without_node(),
+        false,
+    );
+
+    let origin = context.to_complex(center.clone_without_node());
+    let v = context.sub(
+        context.to_complex(point.clone_without_node()),
+        origin.clone_without_node(),
+    );
+    let d_squared = context.pow(context.distance_pp(point, center), Ratio::from_integer(2));
+    let offset = context.mult(
+        context.pow(
+            context.sub(
+                d_squared.clone_without_node(),
+                context.pow(radius.clone_without_node(), Ratio::from_integer(2)),
+            ),
+            Ratio::new(1, 2),
+        ),
+        number!(SCALAR ProcNum::i()),
+    );
+    let scaled = context.div(context.mult(v, radius.clone_without_node()), d_squared);
+
+    let mut points = [
+        context.add(radius.clone_without_node(), offset.clone_without_node()),
+        context.sub(radius, offset),
+    ]
+    .map(|rotation| {
+        context.to_point_display(
+            context.add(
+                origin.clone_without_node(),
+                context.mult(scaled.clone_without_node(), rotation),
+            ),
+            Properties::default(),
+        )
+    });
+
+    if let Some(node) = points[0].node.as_mut() {
+        node.extend_children(point_node);
+        node.extend_children(circle_node);
+    }
+
+    let mut expr = Expr {
+        data: Rc::new(PointCollection {
+            length: 2,
+            data: PointCollectionData::PointCollection(
+                points
+                    .iter()
+                    .map(Expr::clone_without_node)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        }),
+        span: Span::empty(),
+        node: None,
+    };
+
+    let node = PCNode {
+        display: display.get("display").maybe_unset(true),
+        children: points.into_iter().map(|mut pt| pt.take_node()).collect(),
+        props: Some(display),
+        expr: expr.clone_without_node(),
+    };
+    expr.node = Some(HierarchyNode::new(node));
+
+    expr.into()
+}
+
+/// `tangent_length(point, circle)` - the distance from the point to either point of tangency.
+///
+/// With `O` being the center and `r` the radius, it's equal to `sqrt(|PO|^2 - r^2)`.
+fn tangent_length_function_pc(
+    point: Expr<Point>,
+    circle: Expr<Circle>,
+    context: &mut CompileContext,
+    display: Properties,
+) -> Distance {
+    // There are no tangents from a point inside the circle.
+    let mut center = context.circle_center(circle.clone_without_node());
+    center.take_node();
+    context.gt(
+        context.distance_pp(point.clone_without_node(), center),
+        context.circle_radius(circle.clone_without_node()),
+        false,
+    );
+
+    let power = super::power::power_function_pc(point, circle, context, Properties::default());
+
+    context
+        .pow_display(power.0, Ratio::new(1, 2), display)
+        .into()
+}
+
+/// Register the functions
+pub fn register(library: &mut Library) {
+    library
+        .add(
+            Function::new("tangent")
+                .overload(tangent_function_pc)
+                .overload(
+                    |circle: Expr<Circle>,
+                     point: Expr<Point>,
+                     context: &mut CompileContext,
+                     display| {
+                        tangent_function_pc(point, circle, context, display)
+                    },
+                ),
+        )
+        .add(
+            Function::new("tangentpoints")
+                .overload(tangent_points_function_pc)
+                .overload(
+                    |circle: Expr<Circle>,
+                     point: Expr<Point>,
+                     context: &mut CompileContext,
+                     display| {
+                        tangent_points_function_pc(point, circle, context, display)
+                    },
+                ),
+        )
+        .add(
+            Function::new("tangentlength")
+                .overload(tangent_length_function_pc)
+                .overload(
+                    |circle: Expr<Circle>,
+                     point: Expr<Point>,
+                     context: &mut CompileContext,
+                     display| {
+                        tangent_length_function_pc(point, circle, context, display)
+                    },
+                ),
+        );
+}
@@ -312,6 +312,34 @@ fn spiral(
     )
 }
 
+/// `homothety(point, scale, origin)` - the image of a point under the homothety
+/// with the given origin and scale.
+fn homothety_point(
+    point: Expr<Point>,
+    mut scale: Unitless,
+    mut origin: Expr<Point>,
+    context: &mut CompileContext,
+    props: Properties,
+) -> Expr<Point> {
+    let origin_node = origin.take_node();
+    let scale_node = scale.0.take_node();
+
+    let homothety = Spiral {
+        origin: Distance::from(context.to_complex(origin)),
+        vector: Unitless::from(scale.0.clone_without_node()),
+        scale,
+    };
+
+    let mut expr = homothety.transform(point, context, props);
+
+    if let Some(node) = expr.node.as_mut() {
+        node.extend_children(origin_node);
+        node.extend_children(scale_node);
+    }
+
+    expr
+}
+
 #[derive(Debug)]
 pub struct Reflect {
     line: Expr<Line>,
@@ -525,7 +553,8 @@ pub fn register(library: &mut Library) {
                             props,
                         )
                     },
-                ),
+                )
+                .overload(homothety_point),
         )
         .add(
             Function::new("spiral")
@@ -3,7 +3,8 @@
 use num_traits::FromPrimitive;
 
 use crate::{
-    token::{number::ProcNum, Span},
+    parser::PropertyValue,
+    token::{number::ProcNum, Span, StrLit},
     unroll::{figure::PCNode, PointCollection, PointCollectionData},
 };
 
@@ -34,6 +35,7 @@ fn triangle(context: &CompileContext, mut props: Properties) -> Pc<3> {
         props.get("displaysegments").maybe_unset(true),
     );
     node.insert_data("style", props.get("style").maybe_unset(Style::Solid));
+    node.insert_data("color", props.get("color").maybe_unset(Color::default()));
     node.root.props = Some(props);
     expr.node = Some(node);
 
@@ -139,13 +141,25 @@ fn main_right_triangle(context: &mut CompileContext, props: Properties) -> Pc<3>
     pc
 }
 
+/// `orthocenter(A, B, C)` - the intersection of the triangle's altitudes, labeled `H` by default.
 fn orthocenter(
     mut a: Expr<Point>,
     mut b: Expr<Point>,
     mut c: Expr<Point>,
     context: &CompileContext,
-    props: Properties,
+    mut props: Properties,
 ) -> Expr<Point> {
+    props.add_if_not_present(
+        "default-label",
+        (
+            Span::empty(),
+            PropertyValue::String(StrLit {
+                span: Span::empty(),
+                content: String::from("H"),
+            }),
+        ),
+    );
+
     let a_node = a.take_node();
     let b_node = b.take_node();
     let c_node = c.take_node();
@@ -231,6 +245,63 @@ fn incenter(
     expr
 }
 
+/// The reflection of line `vertex-p` over the bisector of angle `u-vertex-v`.
+fn isogonal_line(
+    vertex: &Expr<Point>,
+    p: &Expr<Point>,
+    u: &Expr<Point>,
+    v: &Expr<Point>,
+    context: &CompileContext,
+) -> Expr<Line> {
+    let bisector = context.bisector_ppp(
+        u.clone_without_node(),
+        vertex.clone_without_node(),
+        v.clone_without_node(),
+    );
+    let proj = context.intersection(
+        context.perpendicular_through(bisector.clone_without_node(), p.clone_without_node()),
+        bisector,
+    );
+    let proj = context.to_complex(proj);
+    let img = context.add(
+        proj.clone_without_node(),
+        context.sub(proj, context.to_complex(p.clone_without_node())),
+    );
+
+    let mut line = context.line(vertex.clone_without_node(), context.to_point(img));
+    // Make sure the construction is not displayed.
+    line.take_node();
+    line
+}
+
+/// `isogonal_conjugate(P, A, B, C)` - the common point of the reflections of cevians
+/// `AP`, `BP` and `CP` over the respective angle bisectors.
+fn isogonal_conjugate(
+    mut p: Expr<Point>,
+    mut a: Expr<Point>,
+    mut b: Expr<Point>,
+    mut c: Expr<Point>,
+    context: &CompileContext,
+    props: Properties,
+) -> Expr<Point> {
+    let p_node = p.take_node();
+    let a_node = a.take_node();
+    let b_node = b.take_node();
+    let c_node = c.take_node();
+
+    // Two of the three reflected cevians are enough to find the common point.
+    let iso_a = isogonal_line(&a, &p, &b, &c, context);
+    let iso_b = isogonal_line(&b, &p, &a, &c, context);
+
+    let mut expr = context.intersection_display(iso_a, iso_b, props);
+
+    if let Some(node) = expr.node.as_mut() {
+        node.extend_children([p_node, a_node, b_node, c_node].into_iter().flatten());
+    }
+
+    expr
+}
+
 /// Register the functions
 pub fn register(library: &mut Library) {
     library
@@ -282,6 +353,22 @@ pub fn register(library: &mut Library) {
                     )
                 }),
         )
+        .add(
+            Function::new("isogonalconjugate")
+                .overload(isogonal_conjugate)
+                .overload(
+                    |p: Expr<Point>, mut col: Pc<3>, context: &CompileContext, props| {
+                        isogonal_conjugate(
+                            p,
+                            col.index_with_node(0),
+                            col.index_with_node(1),
+                            col.index_with_node(2),
+                            context,
+                            props,
+                        )
+                    },
+                ),
+        )
         .add(Function::new("triangle").overload(triangle))
         .add(Function::new("maintriangle").overload(main_triangle))
         .add(
@@ -124,12 +124,12 @@ pub fn register(library: &mut Library) {
         )
         .add(
             Function::new("asin")
-                .alias_method(ty::ANGLE, "asin")
+                .alias_method(ty::SCALAR, "asin")
                 .overload(asin),
         )
         .add(
             Function::new("acsc")
-                .alias_method(ty::ANGLE, "acsc")
+                .alias_method(ty::SCALAR, "acsc")
                 .overload(|value: Unitless, context: &CompileContext, props| {
                     asin(
                         Unitless::from(context.div(number!(SCALAR ProcNum::one()), value.0)),
@@ -140,12 +140,12 @@ pub fn register(library: &mut Library) {
         )
         .add(
             Function::new("acos")
-                .alias_method(ty::ANGLE, "acos")
+                .alias_method(ty::SCALAR, "acos")
                 .overload(acos),
         )
         .add(
             Function::new("asec")
-                .alias_method(ty::ANGLE, "asec")
+                .alias_method(ty::SCALAR, "asec")
                 .overload(|value: Unitless, context: &CompileContext, props| {
                     acos(
                         Unitless::from(context.div(number!(SCALAR ProcNum::one()), value.0)),
@@ -157,15 +157,15 @@ pub fn register(library: &mut Library) {
         .add(
             Function::new("atan")
                 .alias("atg")
-                .alias_method(ty::ANGLE, "atan")
-                .alias_method(ty::ANGLE, "atg")
+                .alias_method(ty::SCALAR, "atan")
+                .alias_method(ty::SCALAR, "atg")
                 .overload(atan),
         )
         .add(
             Function::new("acot")
                 .alias("actg")
-                .alias_method(ty::ANGLE, "acot")
-                .alias_method(ty::ANGLE, "actg")
+                .alias_method(ty::SCALAR, "acot")
+                .alias_method(ty::SCALAR, "actg")
                 .overload(|value: Unitless, context: &CompileContext, props| {
                     atan(
                         Unitless::from(context.div(number!(SCALAR ProcNum::one()), value.0)),
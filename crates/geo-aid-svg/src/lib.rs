@@ -1,33 +1,221 @@
 //! Geo-AID is capable of outputting figures as a simple svg file. This file may not be possible
 //! to display everywhere, but it should be suitable for most cases.
 
+use std::fmt::Write as _;
 use std::io::{self, Seek, Write};
+use std::mem;
 
+use geo_aid_figure::math_string::{MathChar, MathIndex, MathSpecial, MathString};
 use geo_aid_figure::{
-    CircleItem, Figure, Item, LineItem, PointItem, Position, Style, TwoPointItem,
+    AngleItem, AnnotationItem, ArcItem, CircleItem, Color, Figure, Item, Label, LineItem,
+    PointItem, PolygonItem, Position, Style, TwoPointItem,
 };
 
+/// Convert the given math string into SVG text content. Lower indices become subscript `tspan`s.
+pub fn math_to_svg(math: &MathString) -> String {
+    let mut s = String::new();
+
+    for c in math.iter().copied() {
+        match c {
+            MathChar::Ascii('<') => s += "&lt;",
+            MathChar::Ascii('>') => s += "&gt;",
+            MathChar::Ascii('&') => s += "&amp;",
+            MathChar::Ascii(c) => s.push(c),
+            MathChar::Special(MathSpecial::Quote) => s += "&quot;",
+            MathChar::Special(special) => {
+                // Specials are greek letters in alphabetical order, lowercase first.
+                // Unicode has a final sigma between rho and sigma, hence the skip.
+                let code = special as u32;
+                let letter = code / 2 + u32::from(code / 2 > 16);
+                let base = if code.is_multiple_of(2) { 0x3B1 } else { 0x391 };
+                let _ = write!(s, "&#x{:X};", base + letter);
+            }
+            MathChar::SetIndex(MathIndex::Lower) => {
+                s += r#"<tspan baseline-shift="sub" font-size="7px">"#;
+            }
+            MathChar::SetIndex(MathIndex::Normal) => s += "</tspan>",
+            MathChar::Prime => s += "&#x2032;",
+        }
+    }
+
+    s
+}
+
+/// The part of the plane that gets drawn: the canvas extended by a margin on every side.
+///
+/// Items outside of it can't be seen anyway, and far away points (e.g. intersections of nearly
+/// parallel lines) would otherwise end up in the file with coordinates that break some renderers.
+#[derive(Debug, Clone, Copy)]
+struct ClipRect {
+    /// The bottom left corner
+    min: Position,
+    /// The top right corner
+    max: Position,
+}
+
+impl ClipRect {
+    /// The margin added on each side, as a fraction of the canvas size.
+    const MARGIN: f64 = 0.1;
+
+    fn new(figure: &Figure) -> Self {
+        let (dx, dy) = (figure.width * Self::MARGIN, figure.height * Self::MARGIN);
+
+        Self {
+            min: Position { x: -dx, y: -dy },
+            max: Position {
+                x: figure.width + dx,
+                y: figure.height + dy,
+            },
+        }
+    }
+
+    /// Check if the point is inside. Points with non-finite coordinates never are.
+    fn contains(&self, p: Position) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x) && (self.min.y..=self.max.y).contains(&p.y)
+    }
+
+    /// Clip a segment using the Liang-Barsky algorithm. `None` if no part of it is inside.
+    fn clip_segment(&self, (p1, p2): (Position, Position)) -> Option<(Position, Position)> {
+        if ![p1.x, p1.y, p2.x, p2.y].iter().all(|v| v.is_finite()) {
+            return None;
+        }
+
+        let (dx, dy) = (p2.x - p1.x, p2.y - p1.y);
+        let mut low = 0.0_f64;
+        let mut high = 1.0_f64;
+
+        for (p, q) in [
+            (-dx, p1.x - self.min.x),
+            (dx, self.max.x - p1.x),
+            (-dy, p1.y - self.min.y),
+            (dy, self.max.y - p1.y),
+        ] {
+            if p == 0.0 {
+                // Parallel to the edge, so either entirely outside or not limited by it.
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                low = low.max(q / p);
+            } else {
+                high = high.min(q / p);
+            }
+        }
+
+        let at = |t: f64| Position {
+            x: p1.x + dx * t,
+            y: p1.y + dy * t,
+        };
+
+        (low <= high).then(|| (at(low), at(high)))
+    }
+
+    /// Check if any part of the circle's outline is inside.
+    fn shows_circle(&self, center: Position, radius: f64) -> bool {
+        let distance = |p: Position| (p.x - center.x).hypot(p.y - center.y);
+        let nearest = Position {
+            x: center.x.clamp(self.min.x, self.max.x),
+            y: center.y.clamp(self.min.y, self.max.y),
+        };
+        let farthest = Position {
+            x: if center.x < f64::midpoint(self.min.x, self.max.x) {
+                self.max.x
+            } else {
+                self.min.x
+            },
+            y: if center.y < f64::midpoint(self.min.y, self.max.y) {
+                self.max.y
+            } else {
+                self.min.y
+            },
+        };
+
+        // The outline is inside when the rectangle is neither entirely outside of the circle
+        // nor entirely inside of it.
+        distance(nearest) <= radius.abs() && distance(farthest) >= radius.abs()
+    }
+
+    /// Clip a polygon using the Sutherland-Hodgman algorithm.
+    fn clip_polygon(&self, points: &[Position]) -> Vec<Position> {
+        if !points.iter().all(|p| p.x.is_finite() && p.y.is_finite()) {
+            return Vec::new();
+        }
+
+        let mut polygon = points.to_vec();
+
+        // Each edge is given by the axis (x or y), the bound and whether the inside is above it.
+        for (horizontal, bound, above) in [
+            (true, self.min.x, true),
+            (true, self.max.x, false),
+            (false, self.min.y, true),
+            (false, self.max.y, false),
+        ] {
+            let coord = |p: Position| if horizontal { p.x } else { p.y };
+            let inside = |p: Position| {
+                if above {
+                    coord(p) >= bound
+                } else {
+                    coord(p) <= bound
+                }
+            };
+            let crossing = |a: Position, b: Position| {
+                let t = (bound - coord(a)) / (coord(b) - coord(a));
+                Position {
+                    x: a.x + (b.x - a.x) * t,
+                    y: a.y + (b.y - a.y) * t,
+                }
+            };
+
+            let input = mem::take(&mut polygon);
+            for (i, &current) in input.iter().enumerate() {
+                let previous = input[(i + input.len() - 1) % input.len()];
+
+                if inside(current) {
+                    if !inside(previous) {
+                        polygon.push(crossing(previous, current));
+                    }
+
+                    polygon.push(current);
+                } else if inside(previous) {
+                    polygon.push(crossing(previous, current));
+                }
+            }
+        }
+
+        polygon
+    }
+}
+
 /// The SVG format writer.
 #[derive(Debug)]
 pub struct Svg<W: Write + Seek> {
     /// Writer stream
     writer: W,
+    /// Everything is clipped to this rectangle.
+    clip: ClipRect,
 }
 
 impl<W: Write + Seek> Svg<W> {
     /// Get the figure in SVG format.
     pub fn draw(figure: &Figure, writer: W) -> io::Result<()> {
-        let mut svg = Self { writer };
+        let mut svg = Self {
+            writer,
+            clip: ClipRect::new(figure),
+        };
 
         svg.begin(figure)?;
 
-        for item in &figure.items {
+        for item in figure.items_in_drawing_order() {
             match item {
                 Item::Point(point) => svg.draw_point(point)?,
                 Item::Line(line) => svg.draw_line(line)?,
                 Item::Ray(ray) => svg.draw_ray(ray)?,
                 Item::Segment(segment) => svg.draw_segment(segment)?,
                 Item::Circle(circle) => svg.draw_circle(circle)?,
+                Item::Angle(angle) => svg.draw_angle(angle)?,
+                Item::Arc(arc) => svg.draw_arc(arc)?,
+                Item::Polygon(polygon) => svg.draw_polygon(polygon)?,
+                Item::Annotation(annotation) => svg.draw_annotation(annotation)?,
             }
         }
 
@@ -52,19 +240,34 @@ impl<W: Write + Seek> Svg<W> {
         }
     }
 
-    /// Draw a styled segment delimited by two points.
+    /// Get the opacity attribute of an item. Fully opaque items don't need one.
+    fn get_opacity(opacity: Option<f64>) -> String {
+        opacity
+            .map(|opacity| format!(r#" opacity="{opacity}""#))
+            .unwrap_or_default()
+    }
+
+    /// Draw a styled segment delimited by two points. Only the part inside the clip rectangle is drawn.
     fn draw_simple_segment(
         &mut self,
-        (p1, p2): (Position, Position),
+        points: (Position, Position),
         style: Style,
+        color: Color,
+        opacity: Option<f64>,
     ) -> io::Result<()> {
+        let Some((p1, p2)) = self.clip.clip_segment(points) else {
+            return Ok(());
+        };
+
         write!(
             &mut self.writer,
             r#"
-                <line stroke-width="{}" stroke-dasharray="{}" stroke="black" x1="{}" x2="{}" y1="{}" y2="{}"/>
+                <line stroke-width="{}" stroke-dasharray="{}" stroke="{}"{} x1="{}" x2="{}" y1="{}" y2="{}"/>
             "#,
             Self::get_style_width(style),
             Self::get_style_dashing(style),
+            color,
+            Self::get_opacity(opacity),
             p1.x,
             p2.x,
             p1.y,
@@ -84,80 +287,254 @@ impl<W: Write + Seek> Svg<W> {
                 <g transform="translate(0,{})">
                 <g transform="scale(1,-1)">
             "#,
-            figure.width, figure.height, figure.width,
+            figure.height, figure.width, figure.height,
         )
     }
 
-    fn draw_point(&mut self, point: &PointItem) -> io::Result<()> {
-        let pos = point.position;
-        write!(
-            &mut self.writer,
-            r#"<circle cx="{}" cy="{}" fill="black" r="1"/>"#,
-            pos.x, pos.y
-        )?;
-
-        if let Some(label) = &point.label {
+    /// Draw an item's label, if it has one and it's inside the clip rectangle.
+    fn draw_label(
+        &mut self,
+        label: Option<&Label>,
+        color: Color,
+        opacity: Option<f64>,
+    ) -> io::Result<()> {
+        if let Some(label) = label.filter(|label| self.clip.contains(label.position)) {
             write!(
                 &mut self.writer,
                 r#"
                 <text transform="scale(1,-1)"
                     text-anchor="middle" dominant-baseline="middle"
                     style="font-family: 'Computer Modern'" font-size="10px"
-                    stroke="black" stroke-width="0" x="{}" y="-{}">{}
+                    fill="{}"{} stroke-width="0" x="{}" y="-{}">{}
                 </text>
             "#,
-                label.position.x, label.position.y, label.content
+                color,
+                Self::get_opacity(opacity),
+                label.position.x,
+                label.position.y,
+                math_to_svg(&label.content)
             )?;
         }
 
         Ok(())
     }
 
+    fn draw_point(&mut self, point: &PointItem) -> io::Result<()> {
+        let pos = point.position;
+
+        // A point outside can't be seen, and neither should its label be, as it would hang
+        // next to nothing on the edge.
+        if !self.clip.contains(pos) {
+            return Ok(());
+        }
+
+        if point.display_dot {
+            write!(
+                &mut self.writer,
+                r#"<circle cx="{}" cy="{}" fill="{}"{} r="1"/>"#,
+                pos.x,
+                pos.y,
+                point.color,
+                Self::get_opacity(point.opacity)
+            )?;
+        }
+
+        self.draw_label(point.label.as_ref(), point.color, point.opacity)
+    }
+
     fn draw_line(&mut self, line: &LineItem) -> io::Result<()> {
-        self.draw_simple_segment(line.points, line.style)
+        self.draw_simple_segment(line.points, line.style, line.color, line.opacity)?;
+        self.draw_label(line.label.as_ref(), line.color, line.opacity)
     }
 
     fn draw_ray(&mut self, ray: &TwoPointItem) -> io::Result<()> {
-        self.draw_simple_segment(ray.points, ray.style)
+        self.draw_simple_segment(ray.points, ray.style, ray.color, ray.opacity)?;
+        self.draw_label(ray.label.as_ref(), ray.color, ray.opacity)
+    }
+
+    /// Draw the ticks marking the middle of a segment, perpendicular to it.
+    fn draw_ticks(&mut self, segment: &TwoPointItem) -> io::Result<()> {
+        let (p1, p2) = segment.points;
+        let (dx, dy) = (p2.x - p1.x, p2.y - p1.y);
+        let len = dx.hypot(dy);
+
+        // A zero-length segment has no direction to put the ticks across.
+        if len == 0.0 {
+            return Ok(());
+        }
+
+        let (dx, dy) = (dx / len, dy / len);
+        let middle = ((p1.x + p2.x) / 2.0, (p1.y + p2.y) / 2.0);
+
+        for i in 0..segment.ticks {
+            // Ticks are 3 units apart, centered on the middle.
+            let shift = 3.0 * (f64::from(i) - f64::from(segment.ticks - 1) / 2.0);
+            let center = (middle.0 + dx * shift, middle.1 + dy * shift);
+
+            self.draw_simple_segment(
+                (
+                    Position {
+                        x: center.0 - dy * 4.0,
+                        y: center.1 + dx * 4.0,
+                    },
+                    Position {
+                        x: center.0 + dy * 4.0,
+                        y: center.1 - dx * 4.0,
+                    },
+                ),
+                Style::Solid,
+                segment.color,
+                segment.opacity,
+            )?;
+        }
+
+        Ok(())
     }
 
     fn draw_segment(&mut self, segment: &TwoPointItem) -> io::Result<()> {
-        self.draw_simple_segment(segment.points, segment.style)
-    }
-
-    // fn draw_angle(&mut self, angle: &RenderedAngle) {
-    //     let x: u32 = 45;
-    //     self.content += &format!(
-    //         r#"
-    //             <g transform="translate({}, {}) rotate({}, 0, 0)" fill="transparent">
-    //                 <path stroke-dasharray="{}" d="M {}, 0 A 45, 45, 0, 0, 0, {}, {}" stroke="black" stroke-width="{}"/>
-    //             </g>
-    //         "#,
-    //         angle.points.1.real,
-    //         angle.points.1.imaginary,
-    //         geometry::get_line(angle.points.1, angle.points.0)
-    //             .direction
-    //             .arg()
-    //             .to_degrees(),
-    //         Self::get_style_dashing(angle.item),
-    //         x, // It should probably be a constant. For now, we will leave it like this.
-    //         angle.angle_value.cos() * 45.0,
-    //         -angle.angle_value.sin() * 45.0,
-    //         style_width(angle.style),
-    //     );
-    // }
+        self.draw_simple_segment(
+            segment.points,
+            segment.style,
+            segment.color,
+            segment.opacity,
+        )?;
+        self.draw_ticks(segment)?;
+        self.draw_label(segment.label.as_ref(), segment.color, segment.opacity)
+    }
+
+    fn draw_angle(&mut self, angle: &AngleItem) -> io::Result<()> {
+        let (arm1, origin, arm2) = angle.points;
+
+        if !self.clip.contains(origin) {
+            return Ok(());
+        }
+
+        let direction = |p: Position| {
+            let (x, y) = (p.x - origin.x, p.y - origin.y);
+            let len = x.hypot(y);
+            (x / len, y / len)
+        };
+
+        let (x1, y1) = direction(arm1);
+        let (x2, y2) = direction(arm2);
+
+        // Always mark the smaller angle.
+        let sweep = u8::from(x1 * y2 - y1 * x2 > 0.0);
+
+        for i in 0..angle.arcs {
+            let radius = 12.0 + 3.0 * f64::from(i);
+
+            write!(
+                &mut self.writer,
+                r#"
+                <path d="M {} {} A {radius} {radius} 0 0 {sweep} {} {}" stroke="{}" stroke-width="{}" stroke-dasharray="{}" fill="transparent"/>
+            "#,
+                origin.x + x1 * radius,
+                origin.y + y1 * radius,
+                origin.x + x2 * radius,
+                origin.y + y2 * radius,
+                angle.color,
+                Self::get_style_width(angle.style),
+                Self::get_style_dashing(angle.style),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_arc(&mut self, arc: &ArcItem) -> io::Result<()> {
+        if !self.clip.shows_circle(arc.center, arc.radius) {
+            return Ok(());
+        }
+
+        // An SVG arc can't be a full circle, so the arc is drawn in two halves.
+        // Neither is larger than a half-circle, so the large arc flag is never needed.
+        let mid_angle = f64::midpoint(arc.start_angle, arc.end_angle);
+        let start = arc.point_at(arc.start_angle);
+        let mid = arc.point_at(mid_angle);
+        let end = arc.point_at(arc.end_angle);
+
+        write!(
+            &mut self.writer,
+            r#"
+                <path d="M {} {} A {r} {r} 0 0 1 {} {} A {r} {r} 0 0 1 {} {}" stroke="{}" stroke-width="{}" stroke-dasharray="{}" fill="transparent"/>
+            "#,
+            start.x,
+            start.y,
+            mid.x,
+            mid.y,
+            end.x,
+            end.y,
+            arc.color,
+            Self::get_style_width(arc.style),
+            Self::get_style_dashing(arc.style),
+            r = arc.radius,
+        )?;
+
+        self.draw_label(arc.label.as_ref(), arc.color, None)
+    }
 
     fn draw_circle(&mut self, circle: &CircleItem) -> io::Result<()> {
+        if !self.clip.shows_circle(circle.center, circle.radius) {
+            return Ok(());
+        }
+
         write!(
             &mut self.writer,
             r#"
-                <circle cx="{}" cy="{}" r="{}" stroke="black" stroke-width="{}" stroke-dasharray="{}" fill="transparent"/>
+                <circle cx="{}" cy="{}" r="{}" stroke="{}"{} stroke-width="{}" stroke-dasharray="{}" fill="transparent"/>
             "#,
             circle.center.x,
             circle.center.y,
             circle.radius,
+            circle.color,
+            Self::get_opacity(circle.opacity),
             Self::get_style_width(circle.style),
             Self::get_style_dashing(circle.style),
+        )?;
+
+        self.draw_label(circle.label.as_ref(), circle.color, circle.opacity)
+    }
+
+    fn draw_polygon(&mut self, polygon: &PolygonItem) -> io::Result<()> {
+        let clipped = self.clip.clip_polygon(&polygon.points);
+
+        if clipped.len() < 3 {
+            return Ok(());
+        }
+
+        let points = clipped
+            .iter()
+            .map(|p| format!("{},{}", p.x, p.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let fill = polygon
+            .fill
+            .map_or_else(|| String::from("transparent"), |color| color.to_string());
+
+        // The even-odd rule makes self-intersecting polygons look as expected.
+        write!(
+            &mut self.writer,
+            r#"
+                <polygon points="{}" stroke="{}"{} stroke-width="{}" stroke-dasharray="{}" fill="{}" fill-rule="evenodd"/>
+            "#,
+            points,
+            polygon.color,
+            Self::get_opacity(polygon.opacity),
+            Self::get_style_width(polygon.style),
+            Self::get_style_dashing(polygon.style),
+            fill,
+        )
+    }
+
+    fn draw_annotation(&mut self, annotation: &AnnotationItem) -> io::Result<()> {
+        self.draw_label(
+            Some(&Label {
+                position: annotation.position,
+                content: annotation.content.clone(),
+            }),
+            annotation.style.color,
+            annotation.style.opacity,
         )
     }
 
@@ -165,3 +542,133 @@ impl<W: Write + Seek> Svg<W> {
         write!(&mut self.writer, "</g> </g> </svg>")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Svg;
+    use geo_aid_figure::math_string::MathString;
+    use geo_aid_figure::{
+        CircleItem, Color, Figure, Item, Label, LineItem, PointItem, Position, Style, TwoPointItem,
+        VarIndex, CURRENT_VERSION,
+    };
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    const SIZE: f64 = 500.0;
+
+    /// A figure with lines `k` and `l` crossing far away at `X`, like nearly parallel lines do.
+    fn nearly_parallel() -> Figure {
+        let a = Position { x: 100.0, y: 200.0 };
+        let x = Position { x: 1e9, y: 300.0 };
+
+        Figure {
+            version: CURRENT_VERSION,
+            width: SIZE,
+            height: SIZE,
+            expressions: Vec::new(),
+            entities: Vec::new(),
+            items: vec![
+                Item::Point(PointItem {
+                    position: x,
+                    id: VarIndex(0),
+                    display_dot: true,
+                    color: Color::default(),
+                    opacity: None,
+                    label: Some(Label {
+                        position: Position { x: 1e9, y: 310.0 },
+                        content: MathString::from_str("X").unwrap(),
+                    }),
+                    span: None,
+                    layer: None,
+                }),
+                Item::Segment(TwoPointItem {
+                    points: (a, x),
+                    p_id: VarIndex(1),
+                    q_id: VarIndex(0),
+                    style: Style::Solid,
+                    color: Color::default(),
+                    opacity: None,
+                    label: None,
+                    ticks: 1,
+                    span: None,
+                    layer: None,
+                }),
+                Item::Line(LineItem {
+                    points: (Position { x: -1e9, y: 0.0 }, x),
+                    id: VarIndex(2),
+                    style: Style::Dashed,
+                    color: Color::default(),
+                    opacity: None,
+                    label: None,
+                    span: None,
+                    layer: None,
+                }),
+                Item::Circle(CircleItem {
+                    center: x,
+                    radius: 10.0,
+                    id: VarIndex(3),
+                    style: Style::Solid,
+                    color: Color::default(),
+                    opacity: None,
+                    label: None,
+                    span: None,
+                    layer: None,
+                }),
+            ],
+        }
+    }
+
+    fn draw(figure: &Figure) -> String {
+        let mut output = Cursor::new(Vec::new());
+        Svg::draw(figure, &mut output).unwrap();
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    /// All numbers in the document, apart from the ones in the namespace URL.
+    fn numbers(svg: &str) -> Vec<f64> {
+        svg.replace("http://www.w3.org/2000/svg", "")
+            .split(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | 'e')))
+            .filter_map(|s| s.parse().ok())
+            .collect()
+    }
+
+    #[test]
+    fn far_away_points_are_clipped() {
+        let svg = draw(&nearly_parallel());
+        let bound = SIZE * 1.5;
+
+        assert!(numbers(&svg).iter().all(|v| v.abs() <= bound));
+
+        // Neither the point, its label nor the circle around it are drawn.
+        assert!(!svg.contains("<circle"));
+        assert!(!svg.contains("<text"));
+        // The segment and the line still are, ending at the margin.
+        assert_eq!(svg.matches("<line").count(), 2);
+        assert!(svg.contains(r#"x2="550""#));
+    }
+
+    #[test]
+    fn visible_items_are_left_intact() {
+        let mut figure = nearly_parallel();
+        let Item::Segment(segment) = &mut figure.items[1] else {
+            unreachable!()
+        };
+        segment.points.1 = Position { x: 400.0, y: 300.0 };
+
+        let svg = draw(&figure);
+        assert!(svg.contains(r#"x1="100" x2="400" y1="200" y2="300""#));
+    }
+
+    #[test]
+    fn zero_length_segments_have_no_ticks() {
+        let mut figure = nearly_parallel();
+        let Item::Segment(segment) = &mut figure.items[1] else {
+            unreachable!()
+        };
+        segment.points.1 = segment.points.0;
+
+        let svg = draw(&figure);
+        assert!(!svg.contains("NaN"));
+        assert_eq!(svg.matches("<line").count(), 2);
+    }
+}
@@ -180,7 +180,7 @@ pub fn derive_clone_with_node(input: TokenStream) -> TokenStream {
 enum ParseAttr {
     Token,
     Standard,
-    FirstToken(Type),
+    FirstToken(Box<Type>),
 }
 
 impl Parse for ParseAttr {
@@ -192,7 +192,7 @@ impl Parse for ParseAttr {
             "standard" => Self::Standard,
             "first_token" => {
                 let _: Token![=] = input.parse()?;
-                Self::FirstToken(input.parse()?)
+                Self::FirstToken(Box::new(input.parse()?))
             }
             &_ => panic!("invalid def"),
         })
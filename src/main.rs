@@ -3,27 +3,29 @@
 use clap::{Parser, ValueEnum};
 use crossterm::{cursor, terminal, ExecutableCommand, QueueableCommand};
 use geo_aid_geogebra::Geogebra;
+use geo_aid_html::Html;
+use geo_aid_internal::engine::annealing::{SaParams, SimulatedAnnealing};
 use geo_aid_internal::engine::glide::Glide;
 use geo_aid_internal::engine::rage::GenParams;
-use geo_aid_internal::engine::{glide, rage};
+use geo_aid_internal::engine::{glide, rage, Engine as _};
 use geo_aid_internal::projector;
 use geo_aid_internal::script::figure::Generated;
 use geo_aid_internal::{
     engine::rage::Rage,
-    script::cli::{Diagnostic, DiagnosticKind},
+    script::cli::{Diagnostic, DiagnosticData, DiagnosticKind},
     script::math,
 };
 use geo_aid_json::Json;
-use geo_aid_latex::Latex;
+use geo_aid_latex::{Latex, LatexOptions};
 use geo_aid_plaintext::Plaintext;
 use geo_aid_svg::Svg;
+use notify::{RecursiveMode, Watcher};
 use std::time::Duration;
 use std::{
     fs::{self, File},
     io::{self, Write},
-    path::PathBuf,
-    process,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
 };
 
 #[derive(Debug, Parser)]
@@ -37,9 +39,10 @@ struct Args {
     /// The output directory.
     #[arg(long, short)]
     output: Option<PathBuf>,
-    /// The engine to use for figure generation.
-    #[arg(long, short, default_value_t = Engine::Glide, value_enum)]
-    engine: Engine,
+    /// The engine to use for figure generation. Overrides the `engine` flag set in the script.
+    /// Defaults to `glide`.
+    #[arg(long, short, value_enum)]
+    engine: Option<Engine>,
     /// The maximum mean quality delta.
     #[arg(long, short, default_value_t = 0.0001)]
     delta_max_mean: f64,
@@ -49,18 +52,44 @@ struct Args {
     /// The count of last deltas to include in mean calculation.
     #[arg(long, short, default_value_t = 128)]
     mean_count: usize,
-    /// Maximal adjustment of a point during generation. Only works with `--engine rage`.
-    #[arg(long, short, default_value_t = 0.5)]
-    adjustment_max: f64,
+    /// Maximal adjustment of a point during generation. Only works with `--engine rage` and `--engine sa`.
+    /// Overrides the `rage.max_adjustment` and `sa.max_adjustment` flags set in the script. Defaults to 0.5.
+    #[arg(long, short)]
+    adjustment_max: Option<f64>,
     /// Amount of samples to test during generation.
     #[arg(long, default_value_t = 512)]
     samples: usize,
     /// How strict are the rules. Negative values make the engine less strict. Zero is not allowed.
-    #[arg(long, short, default_value_t = 2.0)]
-    strictness: f64,
+    /// Overrides the `glide.strictness` flag set in the script when using `--engine glide`. Defaults to 2.0.
+    #[arg(long, short)]
+    strictness: Option<f64>,
+    /// Seed for random sampling. With the same seed, the same script produces the same figure.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// The optimizer used to descend towards a local minimum. Only works with `--engine glide`.
+    #[arg(long, value_enum, default_value_t = Optimizer::GradientDescent)]
+    optimizer: Optimizer,
+    /// Minimal total quality. If it's not reached, generation is retried with new samples.
+    /// Only works with `--engine glide`.
+    #[arg(long)]
+    min_quality: Option<f64>,
+    /// How many times generation can be retried at most when `--min-quality` is not reached.
+    #[arg(long, default_value_t = 3)]
+    max_retries: usize,
+    /// Also write at most this many intermediate figures as numbered SVG files, showing
+    /// how the figure converges. Only works with `--engine glide`.
+    #[arg(long)]
+    snapshots: Option<usize>,
+    /// Also write a JSON report of the generation next to the figure: total quality, per-rule errors,
+    /// time, sample count and rules that couldn't be evaluated. Only works with `--engine glide`.
+    #[arg(long)]
+    report: bool,
     /// Target formats.
     #[arg(long, short, default_value = "svg")]
     format: Vec<Format>,
+    /// Scale of the LaTeX and tikz pictures. If not given, the canvas size decides it.
+    #[arg(long)]
+    latex_scale: Option<f64>,
     /// Canvas width
     #[arg(long)]
     width: Option<f64>,
@@ -70,6 +99,9 @@ struct Args {
     /// Where to put the log output
     #[arg(long, short)]
     log: Option<PathBuf>,
+    /// Keep watching the input script and render the figure again each time it changes.
+    #[arg(long)]
+    watch: bool,
     #[arg(long, hide = true)]
     markdown_help: Option<PathBuf>,
 }
@@ -80,12 +112,25 @@ enum Engine {
     Rage,
     /// Gradient-Led Iterative Descent Engine
     Glide,
+    /// Simulated annealing
+    #[value(alias = "simulated-annealing")]
+    Sa,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum Optimizer {
+    /// Gradient descent with speed decaying on oscillation
+    GradientDescent,
+    /// Adaptive Moment Estimation
+    Adam,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum Format {
     /// LaTeX + tikz + tikz-euclide.
     Latex,
+    /// Only the tikzpicture environment, to be `\input` into an existing LaTeX document.
+    Tikz,
     /// Simple SVG
     Svg,
     /// JSON (machine-readable) format according to the Schema included in the repository.
@@ -94,12 +139,18 @@ enum Format {
     Plaintext,
     /// Geogebra workspace format (*.ggb)
     Geogebra,
+    /// Interactive HTML page, with draggable points.
+    Html,
 }
 
 struct GenerationResult {
     generated: Generated,
     total_quality: f64,
     time: Duration,
+    /// Intermediate figures, oldest first. Empty unless requested.
+    history: Vec<Generated>,
+    /// Summary of the generation. Only produced by Glide.
+    report: Option<glide::GenerationReport>,
 }
 
 #[allow(clippy::too_many_lines)]
@@ -107,12 +158,12 @@ fn main() {
     let args = Args::parse();
 
     // Has to be tested.
-    if args.strictness == 0.0 {
+    if args.strictness == Some(0.0) {
         println!("Strictness must not be 0.");
         return;
     }
 
-    if let Some(path) = args.markdown_help {
+    if let Some(path) = &args.markdown_help {
         fs::write(path, clap_markdown::help_markdown::<Args>()).unwrap();
     }
 
@@ -127,6 +178,7 @@ fn main() {
 
     let target_path = args
         .output
+        .clone()
         .unwrap_or_else(|| args.input.parent().unwrap().to_path_buf());
 
     let target_path = if target_path.is_dir() {
@@ -140,14 +192,33 @@ fn main() {
         return;
     }
 
-    let target_name = args.input.file_name().unwrap();
+    let target = target_path.join(args.input.file_name().unwrap());
 
-    let intermediate = match math::load_script(&script) {
+    if let Some((total_quality, time)) = render(&args, &script, &target) {
+        // In watch mode, the output of each generation goes to stderr.
+        if args.watch {
+            eprintln!("{}", summary(total_quality, time));
+        } else {
+            println!("{}", summary(total_quality, time));
+        }
+    }
+
+    if args.watch {
+        watch(&args, &target);
+    }
+}
+
+/// Compiles the script, generates the figure and writes it in all requested formats
+/// to files named after `target`. Returns the total quality and the generation time,
+/// or `None` if the script has errors.
+#[allow(clippy::too_many_lines)]
+fn render(args: &Args, script: &str, target: &Path) -> Option<(f64, Duration)> {
+    let mut intermediate = match math::load_script(script) {
         Ok(v) => v,
         Err(errors) => {
             for err in errors {
                 let data = err.diagnostic();
-                let diagnostic = Diagnostic::new(DiagnosticKind::Error, data, &args.input, &script);
+                let diagnostic = Diagnostic::new(DiagnosticKind::Error, data, &args.input, script);
 
                 println!("{diagnostic}");
             }
@@ -160,23 +231,54 @@ fn main() {
                     .expect("Writing to log file failed.");
             }
 
-            process::exit(0);
+            return None;
         }
     };
 
+    for warning in intermediate.warnings.drain(..) {
+        let data = warning.diagnostic();
+        let diagnostic = Diagnostic::new(DiagnosticKind::Warning, data, &args.input, script);
+
+        println!("{diagnostic}");
+    }
+
     // println!("{intermediate:#?}");
 
+    // The seed set in the script takes precedence.
+    if intermediate.flags.seed.is_some() && args.seed.is_some() {
+        print_warning(
+            args,
+            script,
+            "seed set in the script overrides the one given in the command line",
+        );
+    }
+    let seed = intermediate.flags.seed.or(args.seed);
+
+    // The engine given in the command line takes precedence.
+    let engine = args
+        .engine
+        .or(intermediate.flags.engine.map(|engine| match engine {
+            math::EngineKind::Glide => Engine::Glide,
+            math::EngineKind::Rage => Engine::Rage,
+            math::EngineKind::Sa => Engine::Sa,
+        }))
+        .unwrap_or(Engine::Glide);
+    let strictness = args.strictness.unwrap_or(2.0);
+
     let GenerationResult {
         generated,
         total_quality,
         time,
-    } = match args.engine {
+        history,
+        report,
+    } = match engine {
         Engine::Rage => {
             let mut rage = Rage::new(
                 rage::Params {
-                    strictness: args.strictness,
+                    strictness,
                     samples: args.samples,
                     worker_count: args.worker_count,
+                    seed,
                 },
                 &intermediate,
             );
@@ -186,7 +288,10 @@ fn main() {
             stdout.execute(cursor::Hide).unwrap();
 
             let time = rage.generate_mean_delta(GenParams {
-                max_adjustment: args.adjustment_max,
+                max_adjustment: args
+                    .adjustment_max
+                    .or(intermediate.flags.rage.max_adjustment)
+                    .unwrap_or(0.5),
                 mean_count: args.mean_count,
                 delta_max_mean: args.delta_max_mean,
                 progress_update: Box::new(|quality| {
@@ -208,35 +313,64 @@ fn main() {
                 time,
                 total_quality: rage.gen().get_total_quality(),
                 generated: rage.get_figure(),
+                history: Vec::new(),
+                report: None,
             }
         }
         Engine::Glide => {
             let mut glide = Glide::new(
                 glide::Params {
-                    strictness: args.strictness,
+                    strictness: args
+                        .strictness
+                        .or(intermediate.flags.glide.strictness)
+                        .unwrap_or(2.0),
                     samples: args.samples,
                     worker_count: args.worker_count,
                     mean_count: args.mean_count,
                     max_mean_delta: args.delta_max_mean,
+                    optimizer: match args.optimizer {
+                        Optimizer::GradientDescent => glide::OptimizerKind::GradientDescent,
+                        Optimizer::Adam => glide::OptimizerKind::Adam,
+                    },
+                    adam: glide::AdamParams::default(),
+                    seed,
+                    min_quality: args.min_quality,
+                    max_retries: args.max_retries,
+                    quality_threshold: intermediate.flags.quality_threshold,
                 },
                 &intermediate,
             );
 
-            let mut samples = 0;
-            let time = glide.generate(|| {
-                samples += 1;
+            let progress_update = |sample: glide::SampleResult| {
                 let mut stdout = io::stdout();
                 stdout
                     .queue(terminal::Clear(terminal::ClearType::FromCursorDown))
                     .unwrap();
 
+                // Retries launch additional batches of samples.
+                let retry = sample.sample_index / args.samples;
+                let progress = format!(
+                    "{}/{} samples ",
+                    sample.sample_index % args.samples + 1,
+                    args.samples
+                );
+
                 stdout.queue(cursor::SavePosition).unwrap();
-                stdout
-                    .write_all(format!("{}/{} samples ", samples, args.samples).as_bytes())
-                    .unwrap();
+                if retry == 0 {
+                    stdout.write_all(progress.as_bytes()).unwrap();
+                } else {
+                    stdout
+                        .write_all(format!("{progress}(retry {retry}) ").as_bytes())
+                        .unwrap();
+                }
                 stdout.queue(cursor::RestorePosition).unwrap();
                 stdout.flush().unwrap();
-            });
+            };
+
+            let (time, history) = match args.snapshots {
+                Some(max_snapshots) => glide.generate_with_history(max_snapshots, progress_update),
+                None => (glide.generate(progress_update), Vec::new()),
+            };
 
             io::stdout().execute(cursor::Show).unwrap();
 
@@ -244,39 +378,102 @@ fn main() {
                 time,
                 generated: glide.get_figure(),
                 total_quality: glide.get_total_quality(),
+                history,
+                report: Some(glide.get_generation_report()),
+            }
+        }
+        Engine::Sa => {
+            let mut sa = SimulatedAnnealing::new(
+                SaParams {
+                    strictness,
+                    max_adjustment: args
+                        .adjustment_max
+                        .or(intermediate.flags.sa.max_adjustment)
+                        .unwrap_or(0.5),
+                    seed,
+                    ..SaParams::default()
+                },
+                &intermediate,
+            );
+
+            let mut cooling_steps = 0;
+            let time = sa.generate(|| {
+                cooling_steps += 1;
+
+                let mut stdout = io::stdout();
+                stdout
+                    .queue(terminal::Clear(terminal::ClearType::FromCursorDown))
+                    .unwrap();
+
+                stdout.queue(cursor::SavePosition).unwrap();
+                stdout
+                    .write_all(format!("{cooling_steps} cooling steps ").as_bytes())
+                    .unwrap();
+                stdout.queue(cursor::RestorePosition).unwrap();
+                stdout.flush().unwrap();
+            });
+
+            GenerationResult {
+                time,
+                generated: sa.get_figure(),
+                total_quality: sa.get_total_quality(),
+                history: Vec::new(),
+                report: None,
             }
         }
     };
 
     let flags = Arc::new(intermediate.flags);
 
+    // The size set in the script takes precedence.
+    let script_size = flags.figure;
+    if (script_size.width.is_some() && args.width.is_some())
+        || (script_size.height.is_some() && args.height.is_some())
+    {
+        print_warning(
+            args,
+            script,
+            "figure size set in the script overrides the one given in the command line",
+        );
+    }
+
     for format in args.format.iter().copied() {
-        let width = args.width.unwrap_or(match format {
+        let width = script_size.width.or(args.width).unwrap_or(match format {
             Format::Json => 1.0,
             Format::Geogebra | Format::Plaintext => 5.0,
-            Format::Latex => 10.0,
-            Format::Svg => 500.0,
+            Format::Latex | Format::Tikz => 10.0,
+            Format::Svg | Format::Html => 500.0,
         });
-        let height = args.height.unwrap_or(width);
+        let height = script_size.height.or(args.height).unwrap_or(width);
 
         let rendered = projector::project(generated.clone(), &flags, (width, height));
 
-        let final_path = target_path.join(target_name).with_extension(match format {
+        let final_path = target.with_extension(match format {
             Format::Latex => "tex",
+            Format::Tikz => "tikz",
             Format::Svg => "svg",
             Format::Json => "json",
             Format::Plaintext => "txt",
             Format::Geogebra => "ggb",
+            Format::Html => "html",
         });
 
         match File::create(&final_path) {
             Ok(file) => {
                 let res = match format {
-                    Format::Latex => Latex::draw(&rendered, file),
+                    Format::Latex | Format::Tikz => Latex::draw_with(
+                        &rendered,
+                        file,
+                        LatexOptions {
+                            fragment: format == Format::Tikz,
+                            scale: args.latex_scale,
+                        },
+                    ),
                     Format::Json => Json::draw(&rendered, file),
                     Format::Svg => Svg::draw(&rendered, file),
                     Format::Plaintext => Plaintext::draw(&rendered, file),
                     Format::Geogebra => Geogebra::draw(&rendered, file),
+                    Format::Html => Html::draw(&rendered, file),
                 };
 
                 if let Err(err) = res {
@@ -287,11 +484,18 @@ fn main() {
         }
     }
 
-    println!(
-        "Finished rendering with total quality {:.2}% in {:.2} seconds.",
-        total_quality * 100.0,
-        time.as_secs_f64()
-    );
+    if args.snapshots.is_some() && engine != Engine::Glide {
+        println!("Warning: snapshots are only recorded with `--engine glide`.");
+    }
+
+    write_snapshots(&history, &flags, target, (args.width, args.height));
+
+    if args.report {
+        match &report {
+            Some(report) => write_report(report, target),
+            None => println!("Warning: reports are only written with `--engine glide`."),
+        }
+    }
 
     if let Some(path) = &args.log {
         let mut log = File::create(path)
@@ -301,4 +505,110 @@ fn main() {
         log.write_all(full.as_bytes())
             .expect("Writing to log file failed.");
     }
+
+    Some((total_quality, time))
+}
+
+/// Watches the input script and renders the figure again each time it changes.
+/// Errors in the script are reported, but don't stop the watcher.
+fn watch(args: &Args, target: &Path) {
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            eprintln!("Failed to watch the script file: {err}");
+            return;
+        }
+    };
+
+    // Editors often replace the file instead of writing to it, so the whole directory is watched.
+    let directory = match args.input.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    if let Err(err) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+        eprintln!("Failed to watch the script file: {err}");
+        return;
+    }
+
+    eprintln!("Watching {} for changes...", args.input.display());
+
+    while let Ok(event) = receiver.recv() {
+        let changed = event.is_ok_and(|event| {
+            (event.kind.is_modify() || event.kind.is_create())
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == args.input.file_name())
+        });
+
+        if !changed {
+            continue;
+        }
+
+        // A single save usually causes a burst of events.
+        while receiver.recv_timeout(Duration::from_millis(100)).is_ok() {}
+
+        let Ok(script) = fs::read_to_string(&args.input) else {
+            eprintln!("Failed to read script file.");
+            continue;
+        };
+
+        if let Some((total_quality, time)) = render(args, &script, target) {
+            eprintln!("{}", summary(total_quality, time));
+        }
+    }
+}
+
+/// The message shown after a figure is rendered.
+fn summary(total_quality: f64, time: Duration) -> String {
+    format!(
+        "Finished rendering with total quality {:.2}% in {:.2} seconds.",
+        total_quality * 100.0,
+        time.as_secs_f64()
+    )
+}
+
+/// Writes the generation report as JSON to a file named after `base`, e.g. `figure.report.json`.
+fn write_report(report: &glide::GenerationReport, base: &Path) {
+    let res = File::create(base.with_extension("report.json"))
+        .and_then(|file| serde_json::to_writer_pretty(file, report).map_err(io::Error::from));
+
+    if let Err(err) = res {
+        println!("Failed to write a file: {err}");
+    }
+}
+
+/// Prints a spanless warning diagnostic about the script to stderr.
+fn print_warning(args: &Args, script: &str, message: &str) {
+    let data = DiagnosticData::new(message);
+    let diagnostic = Diagnostic::new(DiagnosticKind::Warning, data, &args.input, script);
+
+    eprintln!("{diagnostic}");
+}
+
+/// Writes each of the intermediate figures as an SVG file named after `base` with
+/// a snapshot number appended, e.g. `figure-003.svg`.
+fn write_snapshots(
+    history: &[Generated],
+    flags: &Arc<math::Flags>,
+    base: &Path,
+    (width, height): (Option<f64>, Option<f64>),
+) {
+    let width = flags.figure.width.or(width).unwrap_or(500.0);
+    let height = flags.figure.height.or(height).unwrap_or(width);
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let digits = history.len().to_string().len();
+
+    for (i, generated) in history.iter().enumerate() {
+        let rendered = projector::project(generated.clone(), flags, (width, height));
+        let path = base.with_file_name(format!("{stem}-{:0digits$}.svg", i + 1));
+
+        let res = File::create(&path).and_then(|file| Svg::draw(&rendered, file));
+        if let Err(err) = res {
+            println!("Failed to write a file: {err}");
+        }
+    }
 }